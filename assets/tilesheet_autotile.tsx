@@ -0,0 +1,26 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.8" tiledversion="1.8.2" name="autotile" tilewidth="32" tileheight="32" tilecount="84" columns="14">
+ <image source="tilesheet.png" width="448" height="192"/>
+ <wangsets>
+  <wangset name="Coast" type="corner" tile="-1">
+   <wangcolor name="Grass" color="#00ff00" tile="-1" probability="1"/>
+   <wangcolor name="Water" color="#0000ff" tile="-1" probability="1"/>
+   <wangtile tileid="0" wangid="0,1,0,1,0,1,0,1"/>
+   <wangtile tileid="1" wangid="0,2,0,1,0,1,0,1"/>
+   <wangtile tileid="2" wangid="0,1,0,2,0,1,0,1"/>
+   <wangtile tileid="3" wangid="0,2,0,2,0,1,0,1"/>
+   <wangtile tileid="4" wangid="0,1,0,1,0,2,0,1"/>
+   <wangtile tileid="5" wangid="0,2,0,1,0,2,0,1"/>
+   <wangtile tileid="6" wangid="0,1,0,2,0,2,0,1"/>
+   <wangtile tileid="7" wangid="0,2,0,2,0,2,0,1"/>
+   <wangtile tileid="8" wangid="0,1,0,1,0,1,0,2"/>
+   <wangtile tileid="9" wangid="0,2,0,1,0,1,0,2"/>
+   <wangtile tileid="10" wangid="0,1,0,2,0,1,0,2"/>
+   <wangtile tileid="11" wangid="0,2,0,2,0,1,0,2"/>
+   <wangtile tileid="12" wangid="0,1,0,1,0,2,0,2"/>
+   <wangtile tileid="13" wangid="0,2,0,1,0,2,0,2"/>
+   <wangtile tileid="14" wangid="0,1,0,2,0,2,0,2"/>
+   <wangtile tileid="15" wangid="0,2,0,2,0,2,0,2"/>
+  </wangset>
+ </wangsets>
+</tileset>
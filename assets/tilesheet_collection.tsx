@@ -0,0 +1,13 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.9" tiledversion="1.9.0" name="collection" tilewidth="64" tileheight="64" tilecount="3" columns="0">
+ <grid orientation="orthogonal" width="1" height="1"/>
+ <tile id="0" x="0" y="0" width="64" height="32">
+  <image width="448" height="192" source="tilesheet.png"/>
+ </tile>
+ <tile id="1" x="64" y="32">
+  <image width="448" height="192" source="tilesheet.png"/>
+ </tile>
+ <tile id="2">
+  <image width="448" height="192" source="tilesheet.png"/>
+ </tile>
+</tileset>
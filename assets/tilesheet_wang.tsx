@@ -0,0 +1,28 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.8" tiledversion="1.8.2" name="tilesheet" tilewidth="32" tileheight="32" tilecount="84" columns="14">
+ <image source="tilesheet.png" width="448" height="192"/>
+ <tile id="1">
+  <properties>
+   <property name="walkable" type="bool" value="false"/>
+  </properties>
+ </tile>
+ <wangsets>
+  <wangset name="Terrain" type="corner" tile="-1">
+   <wangcolor name="Grass" color="#00ff00" tile="-1" probability="1">
+    <properties>
+     <property name="terrain" value="grass"/>
+     <property name="walkable" type="bool" value="true"/>
+    </properties>
+   </wangcolor>
+   <wangcolor name="Water" color="#0000ff" tile="-1" probability="1">
+    <properties>
+     <property name="terrain" value="water"/>
+     <property name="walkable" type="bool" value="false"/>
+    </properties>
+   </wangcolor>
+   <wangtile tileid="0" wangid="0,1,0,1,0,1,0,1"/>
+   <wangtile tileid="1" wangid="0,1,0,2,0,1,0,1"/>
+   <wangtile tileid="2" wangid="0,2,0,2,0,2,0,1"/>
+  </wangset>
+ </wangsets>
+</tileset>
@@ -0,0 +1,33 @@
+//! Playing back the animations of tiles.
+
+use crate::Tile;
+
+impl Tile {
+    /// Returns the length of one loop of the tile's animation in
+    /// milliseconds, or 0 if the tile isn't animated.
+    pub fn animation_duration(&self) -> u64 {
+        self.animation
+            .iter()
+            .flatten()
+            .map(|frame| frame.duration as u64)
+            .sum()
+    }
+
+    /// Returns the local id of the tile to show `elapsed` milliseconds into
+    /// the tile's animation, which loops. Tiles which aren't animated, or
+    /// whose frames all last 0 milliseconds, show themselves.
+    pub fn frame_at(&self, elapsed: u64) -> u32 {
+        let total = self.animation_duration();
+        if total == 0 {
+            return self.id;
+        }
+        let mut time = elapsed % total;
+        for frame in self.animation.iter().flatten() {
+            if time < frame.duration as u64 {
+                return frame.tile_id;
+            }
+            time -= frame.duration as u64;
+        }
+        self.id
+    }
+}
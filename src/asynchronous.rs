@@ -0,0 +1,85 @@
+//! Loading maps without blocking, for async asset pipelines.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::{Loader, Map, TiledError};
+
+/// The future of a file read by a `ResourceReader`.
+pub type ResourceFuture<'a> = Pin<Box<dyn Future<Output = io::Result<Vec<u8>>> + Send + 'a>>;
+
+/// Reads the files of maps and their external tilesets asynchronously, such
+/// as from an asset server or with tokio's file system functions.
+pub trait ResourceReader {
+    fn read<'a>(&'a self, path: &'a Path) -> ResourceFuture<'a>;
+}
+
+/// Returns the sources of the external tilesets of a map document.
+fn external_tileset_sources(map: &[u8]) -> Result<Vec<String>, TiledError> {
+    let mut parser = EventReader::new(map);
+    let mut sources = Vec::new();
+    let mut depth = 0;
+    loop {
+        match parser.next().map_err(TiledError::XmlDecodingError)? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                depth += 1;
+                if depth == 2 && name.local_name == "tileset" {
+                    sources.extend(
+                        attributes
+                            .into_iter()
+                            .find(|a| a.name.local_name == "source")
+                            .map(|a| a.value),
+                    );
+                }
+            }
+            XmlEvent::EndElement { .. } => depth -= 1,
+            XmlEvent::EndDocument => return Ok(sources),
+            _ => {}
+        }
+    }
+}
+
+impl Loader {
+    /// Parses a map file read through `reader`, reading its external
+    /// tilesets relative to it the same way, so that loading doesn't block.
+    /// The parsing itself happens once every file has been read.
+    pub async fn parse_file_async<R: ResourceReader>(
+        &self,
+        path: impl AsRef<Path>,
+        reader: &R,
+    ) -> Result<Map, TiledError> {
+        let path = path.as_ref();
+        let map = reader
+            .read(path)
+            .await
+            .map_err(|e| TiledError::io(path, e))?;
+        let mut files: HashMap<PathBuf, Vec<u8>> = HashMap::new();
+        if !self.skip_external_tilesets && !self.defer_external_tilesets {
+            let document = match self.transform {
+                Some(ref transform) => transform(&map)?,
+                None => map.clone(),
+            };
+            for source in external_tileset_sources(&document)? {
+                let tileset_path = path.with_file_name(source);
+                if files.contains_key(&tileset_path) {
+                    continue;
+                }
+                let tileset = reader
+                    .read(&tileset_path)
+                    .await
+                    .map_err(|e| TiledError::io(&tileset_path, e))?;
+                files.insert(tileset_path, tileset);
+            }
+        }
+        self.clone()
+            .with_resources(files)
+            .parse_with_path(&map[..], path)
+    }
+}
@@ -0,0 +1,75 @@
+//! Locating tiles within the images of their tilesets, for rendering.
+
+use std::convert::TryFrom;
+use std::path::PathBuf;
+
+use crate::{Gid, Image, ImageRect, Map, Tileset};
+
+/// The image a tile is drawn from, along with the part of it showing the
+/// tile.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TileImage<'a> {
+    pub image: &'a Image,
+    /// The path of the image, resolved like `Map::resolve_tileset_path`, or
+    /// `None` if the image is embedded.
+    pub path: Option<PathBuf>,
+    /// The part of the image showing the tile, in pixels.
+    pub rect: ImageRect,
+}
+
+impl Tileset {
+    /// Returns the rectangle of the tile with the given local id within the
+    /// tileset image, in pixels, taking the margin around the tiles and the
+    /// spacing between them into account. Tiles of image collection
+    /// tilesets take the part of their own image given by
+    /// `Tile::source_rect`, or all of it. Returns `None` for ids outside of
+    /// the tileset.
+    pub fn tile_rect(&self, id: u32) -> Option<ImageRect> {
+        if self.images.is_empty() {
+            let tile = self.get_tile(id)?;
+            let image = tile.images.first()?;
+            return Some(tile.source_rect.unwrap_or(ImageRect {
+                x: 0,
+                y: 0,
+                width: image.width,
+                height: image.height,
+            }));
+        }
+        if self.columns == 0 || self.tilecount.is_some_and(|count| id >= count) {
+            return None;
+        }
+        let (column, row) = ((id % self.columns) as i64, (id / self.columns) as i64);
+        let step_x = self.tile_width as i64 + self.spacing as i64;
+        let step_y = self.tile_height as i64 + self.spacing as i64;
+        Some(ImageRect {
+            x: i32::try_from(self.margin as i64 + column * step_x).ok()?,
+            y: i32::try_from(self.margin as i64 + row * step_y).ok()?,
+            width: i32::try_from(self.tile_width).ok()?,
+            height: i32::try_from(self.tile_height).ok()?,
+        })
+    }
+}
+
+impl Map {
+    /// Returns the image and the rectangle within it to draw the tile with
+    /// the given global tile id from, for both tilesets sharing one image
+    /// and image collection tilesets. Flip flags are ignored, and are left
+    /// for the renderer to apply.
+    pub fn tile_image(&self, gid: impl Into<Gid>) -> Option<TileImage<'_>> {
+        let (tileset, id) = self.tileset_for_gid(gid)?;
+        let image = match tileset.images.first() {
+            Some(image) => image,
+            None => tileset.get_tile(id)?.images.first()?,
+        };
+        let path = if image.data.is_some() || image.source.as_os_str().is_empty() {
+            None
+        } else {
+            Some(self.resolve_tileset_path(tileset, &image.source))
+        };
+        Some(TileImage {
+            image,
+            path,
+            rect: tileset.tile_rect(id)?,
+        })
+    }
+}
@@ -0,0 +1,160 @@
+//! Audio zones authored as objects.
+
+use crate::{Layer, LayerType, Map, Object, ObjectShape, Properties, PropertyValue};
+
+/// The number of points used to approximate the outline of an ellipse.
+const ELLIPSE_SEGMENTS: usize = 32;
+
+/// An area playing a sound, read from a rectangle, ellipse or polygon object.
+/// The zone's settings come from the object's properties:
+///
+/// - `track`: the sound to play, as a string or file property.
+/// - `volume`: the volume within the zone, 1 by default.
+/// - `fade_distance`: how far outside of the zone the sound fades out, in
+///   pixels, 0 by default.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AudioZone {
+    pub name: String,
+    pub track: String,
+    pub volume: f32,
+    pub fade_distance: f32,
+    /// The outline of the zone in world space, in pixels. Ellipses are
+    /// approximated by a polygon.
+    pub polygon: Vec<(f32, f32)>,
+    pub properties: Properties,
+}
+
+impl AudioZone {
+    /// Reads an audio zone from an object, given the offset of its layer.
+    /// Returns `None` if the object has no `track` property or its shape
+    /// doesn't enclose an area.
+    pub fn from_object(object: &Object, offset: (f32, f32)) -> Option<AudioZone> {
+        let track = match object.properties.get("track") {
+            Some(PropertyValue::StringValue(track)) => track.clone(),
+            Some(PropertyValue::FileValue(track)) => track.to_string_lossy().into_owned(),
+            _ => return None,
+        };
+        let outline: Vec<(f32, f32)> = match object.shape {
+            ObjectShape::Rect { width, height } => {
+                vec![(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)]
+            }
+            ObjectShape::Ellipse { width, height } => (0..ELLIPSE_SEGMENTS)
+                .map(|i| {
+                    let angle = i as f32 / ELLIPSE_SEGMENTS as f32 * std::f32::consts::TAU;
+                    (
+                        width / 2.0 * (1.0 + angle.cos()),
+                        height / 2.0 * (1.0 + angle.sin()),
+                    )
+                })
+                .collect(),
+            ObjectShape::Polygon { ref points } => points.clone(),
+            _ => return None,
+        };
+        // Objects rotate clockwise around their position.
+        let (sin, cos) = object.rotation.to_radians().sin_cos();
+        let polygon = outline
+            .into_iter()
+            .map(|(x, y)| {
+                (
+                    offset.0 + object.x + x * cos - y * sin,
+                    offset.1 + object.y + x * sin + y * cos,
+                )
+            })
+            .collect();
+        Some(AudioZone {
+            name: object.name.clone(),
+            track,
+            volume: float_property(&object.properties, "volume").unwrap_or(1.0),
+            fade_distance: float_property(&object.properties, "fade_distance").unwrap_or(0.0),
+            polygon,
+            properties: object.properties.clone(),
+        })
+    }
+
+    /// Whether the given point, in pixels, lies within the zone.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        let mut inside = false;
+        let mut j = self.polygon.len().wrapping_sub(1);
+        for (i, &(xi, yi)) in self.polygon.iter().enumerate() {
+            let (xj, yj) = self.polygon[j];
+            if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+                inside = !inside;
+            }
+            j = i;
+        }
+        inside
+    }
+
+    /// Returns the volume heard at the given point: the zone's volume inside
+    /// of it, fading out linearly over `fade_distance` outside of it.
+    pub fn volume_at(&self, x: f32, y: f32) -> f32 {
+        if self.contains(x, y) {
+            return self.volume;
+        }
+        if self.fade_distance <= 0.0 {
+            return 0.0;
+        }
+        let distance = self.distance_to_outline(x, y);
+        (self.volume * (1.0 - distance / self.fade_distance)).max(0.0)
+    }
+
+    fn distance_to_outline(&self, x: f32, y: f32) -> f32 {
+        let mut closest = f32::INFINITY;
+        for (i, &a) in self.polygon.iter().enumerate() {
+            let b = self.polygon[(i + 1) % self.polygon.len()];
+            let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+            let length = dx * dx + dy * dy;
+            let t = if length > 0.0 {
+                (((x - a.0) * dx + (y - a.1) * dy) / length).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let (px, py) = (a.0 + dx * t, a.1 + dy * t);
+            closest = closest.min(((x - px).powi(2) + (y - py).powi(2)).sqrt());
+        }
+        closest
+    }
+}
+
+fn float_property(properties: &Properties, name: &str) -> Option<f32> {
+    match properties.get(name)? {
+        PropertyValue::FloatValue(v) => Some(*v),
+        PropertyValue::IntValue(v) => Some(*v as f32),
+        _ => None,
+    }
+}
+
+impl Layer {
+    /// Reads every object of an object layer as an audio zone, skipping the
+    /// ones which aren't valid zones.
+    pub fn audio_zones(&self) -> Vec<AudioZone> {
+        match self.layer_type {
+            LayerType::ObjectLayer(ref group) => group
+                .objects
+                .iter()
+                .filter_map(|o| AudioZone::from_object(o, (self.offset_x, self.offset_y)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Map {
+    /// Reads the objects of the given class (or type) across all object
+    /// layers, including those within groups, as audio zones.
+    pub fn audio_zones(&self, class: &str) -> Vec<AudioZone> {
+        let mut zones = Vec::new();
+        for flat in self.flat_layers() {
+            if let LayerType::ObjectLayer(ref group) = flat.layer.layer_type {
+                zones.extend(
+                    group
+                        .objects
+                        .iter()
+                        .filter(|o| o.obj_type == class)
+                        .filter_map(|o| AudioZone::from_object(o, (flat.offset_x, flat.offset_y))),
+                );
+            }
+        }
+        zones
+    }
+}
@@ -0,0 +1,103 @@
+//! Automatic selection of transition tiles using a wang set.
+
+use crate::{LayerTile, TileLayer, Tileset, WangSet};
+
+/// The wang id positions of the corners, along with the direction of the
+/// neighbouring cells sharing them.
+const CORNERS: [(usize, i32, i32); 4] = [(1, 1, -1), (3, 1, 1), (5, -1, 1), (7, -1, -1)];
+/// The wang id positions of the edges, along with the direction of the
+/// neighbouring cell sharing them.
+const EDGES: [(usize, i32, i32); 4] = [(0, 0, -1), (2, 1, 0), (4, 0, 1), (6, -1, 0)];
+
+impl TileLayer {
+    /// Replaces the tiles belonging to `wang_set` with the tiles which best
+    /// match their neighbours, so that areas of different wang colors are
+    /// joined by transition tiles. Each tile keeps the color it has the most
+    /// of; where differently colored tiles meet, the color listed first in
+    /// the wang set claims the shared corners and edges. Tiles which aren't
+    /// part of the wang set are left untouched.
+    pub fn autotile(&mut self, tileset: &Tileset, wang_set: &WangSet) {
+        let bounds = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let first_gid = tileset.first_gid.value();
+        let color_of = |tile: Option<LayerTile>| -> u8 {
+            let id = match tile.and_then(|tile| tile.gid.value().checked_sub(first_gid)) {
+                Some(id) => id,
+                None => return 0,
+            };
+            let color = match wang_set.dominant_color(id) {
+                Some(color) => color,
+                None => return 0,
+            };
+            wang_set
+                .colors
+                .iter()
+                .position(|c| std::ptr::eq(c, color))
+                .map_or(0, |i| i as u8 + 1)
+        };
+        let colors: Vec<u8> = (bounds.y..bounds.bottom())
+            .flat_map(|y| (bounds.x..bounds.right()).map(move |x| (x, y)))
+            .map(|(x, y)| color_of(self.get_tile(x, y)))
+            .collect();
+        let color_at = |x: i32, y: i32| -> u8 { bounds.index(x, y).map_or(0, |i| colors[i]) };
+        // The lowest color index present among the cells wins.
+        let shared = |cells: &[(i32, i32)]| -> u8 {
+            cells
+                .iter()
+                .map(|&(x, y)| color_at(x, y))
+                .filter(|&c| c != 0)
+                .min()
+                .unwrap_or(0)
+        };
+        let (use_corners, use_edges) = match wang_set.wang_type.as_str() {
+            "edge" => (false, true),
+            "mixed" => (true, true),
+            _ => (true, false),
+        };
+
+        for y in bounds.y..bounds.bottom() {
+            for x in bounds.x..bounds.right() {
+                if color_at(x, y) == 0 {
+                    continue;
+                }
+                let mut desired = [0u8; 8];
+                if use_corners {
+                    for &(i, dx, dy) in CORNERS.iter() {
+                        desired[i] = shared(&[(x, y), (x + dx, y), (x, y + dy), (x + dx, y + dy)]);
+                    }
+                }
+                if use_edges {
+                    for &(i, dx, dy) in EDGES.iter() {
+                        desired[i] = shared(&[(x, y), (x + dx, y + dy)]);
+                    }
+                }
+                let mismatches = |wang_id: &[u8; 8]| {
+                    (0..8)
+                        .filter(|&i| desired[i] != 0 && wang_id[i] != desired[i])
+                        .count()
+                };
+
+                let current = self
+                    .get_tile(x, y)
+                    .and_then(|t| t.gid.value().checked_sub(first_gid));
+                let current_score = wang_set
+                    .wang_tiles
+                    .iter()
+                    .find(|t| Some(t.tile_id) == current)
+                    .map(|t| mismatches(&t.wang_id));
+                let best = wang_set
+                    .wang_tiles
+                    .iter()
+                    .min_by_key(|t| mismatches(&t.wang_id));
+                if let Some(best) = best {
+                    if current_score.is_some_and(|score| score <= mismatches(&best.wang_id)) {
+                        continue;
+                    }
+                    let _ = self.set_tile(x, y, LayerTile::new(first_gid + best.tile_id));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Exports a map with one of the built-in export profiles.
+
+use std::env;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process;
+
+use tiled::{parse_file, ExportProfile, TiledError};
+
+const USAGE: &str = "Usage: tiled-export [--strip-hidden] <release|debug> <input.tmx> <output.tmx>";
+
+fn export(profile: &ExportProfile, input: &Path, output: &Path) -> Result<(), TiledError> {
+    let io = |error| TiledError::Io {
+        path: output.to_path_buf(),
+        error,
+    };
+    let map = parse_file(input)?;
+    let mut writer = BufWriter::new(File::create(output).map_err(io)?);
+    profile.export(&map, &mut writer)?;
+    writer.flush().map_err(io)
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let strip_hidden = match args.iter().position(|arg| arg == "--strip-hidden") {
+        Some(index) => {
+            args.remove(index);
+            true
+        }
+        None => false,
+    };
+    if args.len() != 3 {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+    let mut profile = match ExportProfile::named(&args[0]) {
+        Some(profile) => profile,
+        None => {
+            eprintln!("Unknown export profile: {}\n{}", args[0], USAGE);
+            process::exit(2);
+        }
+    };
+    profile.strip_hidden_layers |= strip_hidden;
+    if let Err(e) = export(&profile, Path::new(&args[1]), Path::new(&args[2])) {
+        eprintln!("Failed to export {}: {}", args[1], e);
+        process::exit(1);
+    }
+    println!("Exported {} with the {} profile", args[1], profile.name);
+}
@@ -0,0 +1,145 @@
+//! Stamping small, reusable grids of tiles onto tile layers.
+
+use crate::{LayerTile, TileLayer, TileRect};
+
+/// A small grid of tiles which can be stamped onto a tile layer. Cells which
+/// are `None` leave the tiles underneath them untouched.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Brush {
+    pub width: u32,
+    pub height: u32,
+    /// The cells of the brush, row by row.
+    pub tiles: Vec<Option<LayerTile>>,
+}
+
+impl Brush {
+    /// Creates a brush from its cells, given row by row. Returns `None` if
+    /// the number of cells doesn't match the size of the brush.
+    pub fn new(width: u32, height: u32, tiles: Vec<Option<LayerTile>>) -> Option<Brush> {
+        if (width as usize).checked_mul(height as usize) != Some(tiles.len()) {
+            return None;
+        }
+        Some(Brush {
+            width,
+            height,
+            tiles,
+        })
+    }
+
+    /// Cuts a brush out of a tile layer, which may belong to another map.
+    /// Empty tiles and positions outside of the layer become transparent
+    /// cells.
+    pub fn from_layer(layer: &TileLayer, rect: TileRect) -> Brush {
+        let mut tiles = Vec::with_capacity(rect.width as usize * rect.height as usize);
+        for row in 0..rect.height {
+            for column in 0..rect.width {
+                // Positions past the largest coordinates are outside of it too.
+                let x = rect.x.checked_add_unsigned(column);
+                let y = rect.y.checked_add_unsigned(row);
+                tiles.push(x.zip(y).and_then(|(x, y)| layer.get_tile(x, y)));
+            }
+        }
+        Brush {
+            width: rect.width,
+            height: rect.height,
+            tiles,
+        }
+    }
+
+    /// Returns the cell at the given position within the brush.
+    pub fn get(&self, x: u32, y: u32) -> Option<LayerTile> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.tiles[(y * self.width + x) as usize]
+    }
+
+    /// Returns a copy of the brush mirrored along its vertical axis.
+    pub fn flipped_horizontally(&self) -> Brush {
+        self.transformed(
+            self.width,
+            self.height,
+            |x, y| (self.width - 1 - x, y),
+            |tile| LayerTile {
+                flip_h: !tile.flip_h,
+                ..tile
+            },
+        )
+    }
+
+    /// Returns a copy of the brush mirrored along its horizontal axis.
+    pub fn flipped_vertically(&self) -> Brush {
+        self.transformed(
+            self.width,
+            self.height,
+            |x, y| (x, self.height - 1 - y),
+            |tile| LayerTile {
+                flip_v: !tile.flip_v,
+                ..tile
+            },
+        )
+    }
+
+    /// Returns a copy of the brush rotated by 90 degrees clockwise.
+    pub fn rotated_clockwise(&self) -> Brush {
+        // Tiled applies the diagonal flip first, so rotating a tile which is
+        // already flipped swaps its horizontal and vertical flags.
+        self.transformed(
+            self.height,
+            self.width,
+            |x, y| (y, self.height - 1 - x),
+            |tile| LayerTile {
+                flip_h: !tile.flip_v,
+                flip_v: tile.flip_h,
+                flip_d: !tile.flip_d,
+                ..tile
+            },
+        )
+    }
+
+    /// Returns a copy of the brush rotated by 90 degrees counterclockwise.
+    pub fn rotated_counterclockwise(&self) -> Brush {
+        self.rotated_clockwise()
+            .rotated_clockwise()
+            .rotated_clockwise()
+    }
+
+    /// Builds a brush of the given size, where `source` maps a destination
+    /// position to the position it is copied from and `tile` transforms the
+    /// copied tile.
+    fn transformed(
+        &self,
+        width: u32,
+        height: u32,
+        source: impl Fn(u32, u32) -> (u32, u32),
+        tile: impl Fn(LayerTile) -> LayerTile,
+    ) -> Brush {
+        let mut tiles = Vec::with_capacity(self.tiles.len());
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = source(x, y);
+                tiles.push(self.get(sx, sy).map(&tile));
+            }
+        }
+        Brush {
+            width,
+            height,
+            tiles,
+        }
+    }
+}
+
+impl TileLayer {
+    /// Applies the brush with its top-left corner at the given tile position.
+    /// Transparent cells of the brush are skipped, and on finite layers the
+    /// parts of the brush outside of the layer are ignored.
+    pub fn stamp(&mut self, at: (i32, i32), brush: &Brush) {
+        for y in 0..brush.height {
+            for x in 0..brush.width {
+                if let Some(tile) = brush.get(x, y) {
+                    let _ = self.set_tile(at.0 + x as i32, at.1 + y as i32, tile);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! Checksums embedded in written maps, to catch corrupted files.
+
+use std::io::Write;
+use std::ops::Range;
+
+use crate::hash::fnv1a;
+use crate::{Map, PropertyValue, TiledError, WriteOptions};
+
+/// The map property holding the checksum.
+pub(crate) const CHECKSUM_PROPERTY: &str = "checksum";
+/// Stands in for the checksum while hashing the document.
+const PLACEHOLDER: &str = "0000000000000000";
+
+/// Finds the value of the checksum property among the map's own
+/// properties, which come before its tilesets and layers.
+fn value_range(document: &[u8]) -> Option<Range<usize>> {
+    let find = |haystack: &[u8], needle: &[u8]| {
+        haystack
+            .windows(needle.len())
+            .position(|window| window == needle)
+    };
+    let children = [
+        &b"<tileset"[..],
+        b"<layer",
+        b"<objectgroup",
+        b"<imagelayer",
+        b"<group",
+    ];
+    let end = children
+        .iter()
+        .filter_map(|child| find(document, child))
+        .min()
+        .unwrap_or(document.len());
+    let header = &document[..end];
+    let name = find(header, b"name=\"checksum\"")?;
+    let tag_end = name + find(&header[name..], b">")?;
+    let value = name + find(&header[name..tag_end], b"value=\"")? + 7;
+    let len = find(&header[value..tag_end], b"\"")?;
+    Some(value..value + len)
+}
+
+/// Writes the map with a checksum property covering the whole document.
+pub(crate) fn write_with_checksum<W: Write>(
+    map: &Map,
+    mut writer: W,
+    options: &WriteOptions,
+) -> Result<(), TiledError> {
+    let mut map = map.clone();
+    map.properties.insert(
+        CHECKSUM_PROPERTY.to_string(),
+        PropertyValue::StringValue(PLACEHOLDER.to_string()),
+    );
+    let options = WriteOptions {
+        checksum: false,
+        ..options.clone()
+    };
+    let mut document = Vec::new();
+    map.write_with_options(&mut document, &options)?;
+    let range = value_range(&document)
+        .ok_or_else(|| TiledError::Other("Could not place the map checksum".to_string()))?;
+    let checksum = format!("{:016x}", fnv1a(&document));
+    document.splice(range, checksum.bytes());
+    writer
+        .write_all(&document)
+        .map_err(|e| TiledError::Other(format!("Failed to write the map: {}", e)))
+}
+
+/// Checks that the checksum property of a map document matches its
+/// contents.
+pub(crate) fn verify_checksum(document: &[u8]) -> Result<(), TiledError> {
+    let range = value_range(document)
+        .ok_or_else(|| TiledError::Other("Map has no checksum".to_string()))?;
+    let expected = String::from_utf8_lossy(&document[range.clone()]).into_owned();
+    let mut document = document.to_vec();
+    document.splice(range, PLACEHOLDER.bytes());
+    if format!("{:016x}", fnv1a(&document)) != expected {
+        return Err(TiledError::Other(
+            "Map checksum doesn't match its contents".to_string(),
+        ));
+    }
+    Ok(())
+}
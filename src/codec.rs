@@ -0,0 +1,101 @@
+//! User-provided handlers for layer data encodings.
+
+use std::fmt;
+
+use crate::TiledError;
+
+/// Decodes and encodes layer data stored with an `encoding` and
+/// `compression` the crate doesn't know about, such as an lz4 compression
+/// or a studio's own packaging. Codecs are registered with
+/// `Loader::with_codec` for parsing, and set in `WriteOptions::codec` for
+/// writing.
+///
+/// Tile data is handled as the little-endian bytes of each tile's gid,
+/// flip flags included, row by row, like base64 encoded data after
+/// decompression.
+pub trait TileDataCodec: Send + Sync {
+    /// The value of the `encoding` attribute this codec handles.
+    fn encoding(&self) -> &str;
+
+    /// The value of the `compression` attribute this codec handles, or
+    /// `None` for data without one.
+    fn compression(&self) -> Option<&str>;
+
+    /// Decodes the text content of a `data` or `chunk` element.
+    fn decode(&self, text: &str) -> Result<Vec<u8>, TiledError>;
+
+    /// Encodes tile data into the text content of a `data` or `chunk`
+    /// element.
+    fn encode(&self, data: &[u8]) -> Result<String, TiledError>;
+}
+
+impl dyn TileDataCodec {
+    /// Whether this codec handles the given attributes of a `data` element.
+    pub(crate) fn handles(&self, encoding: Option<&str>, compression: Option<&str>) -> bool {
+        encoding == Some(self.encoding()) && compression == self.compression()
+    }
+}
+
+impl fmt::Debug for dyn TileDataCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TileDataCodec")
+            .field("encoding", &self.encoding())
+            .field("compression", &self.compression())
+            .finish()
+    }
+}
+
+/// Codecs are considered equal if they handle the same attributes.
+impl PartialEq for dyn TileDataCodec {
+    fn eq(&self, other: &Self) -> bool {
+        self.encoding() == other.encoding() && self.compression() == other.compression()
+    }
+}
+
+impl Eq for dyn TileDataCodec {}
+
+type Transform = Box<dyn Fn(&[u8]) -> Result<Vec<u8>, TiledError> + Send + Sync>;
+
+/// A codec for base64 encoded data with a custom compression, so that
+/// plugging in a compression doesn't mean handling the base64 encoding
+/// around it too.
+pub struct CompressionCodec {
+    compression: String,
+    decompress: Transform,
+    compress: Transform,
+}
+
+impl CompressionCodec {
+    /// Creates a codec for the given value of the `compression` attribute.
+    pub fn new(
+        compression: impl Into<String>,
+        decompress: impl Fn(&[u8]) -> Result<Vec<u8>, TiledError> + Send + Sync + 'static,
+        compress: impl Fn(&[u8]) -> Result<Vec<u8>, TiledError> + Send + Sync + 'static,
+    ) -> CompressionCodec {
+        CompressionCodec {
+            compression: compression.into(),
+            decompress: Box::new(decompress),
+            compress: Box::new(compress),
+        }
+    }
+}
+
+impl TileDataCodec for CompressionCodec {
+    fn encoding(&self) -> &str {
+        "base64"
+    }
+
+    fn compression(&self) -> Option<&str> {
+        Some(&self.compression)
+    }
+
+    fn decode(&self, text: &str) -> Result<Vec<u8>, TiledError> {
+        let data =
+            base64::decode(text.trim().as_bytes()).map_err(TiledError::Base64DecodingError)?;
+        (self.decompress)(&data)
+    }
+
+    fn encode(&self, data: &[u8]) -> Result<String, TiledError> {
+        Ok(base64::encode(&(self.compress)(data)?))
+    }
+}
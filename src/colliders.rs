@@ -0,0 +1,133 @@
+//! The collision shapes of the tiles of a layer, placed in the world.
+
+use crate::{Layer, LayerTile, Map, Object, ObjectShape};
+
+/// The number of points used to approximate the outline of an ellipse.
+const ELLIPSE_SEGMENTS: usize = 32;
+
+/// A collision shape of a tile placed in a tile layer, found by
+/// `Map::tile_colliders`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TileCollider<'a> {
+    /// The position of the tile, in tiles.
+    pub tile: (i32, i32),
+    /// The object of the tile's collision group the shape comes from, for
+    /// its name, type and properties.
+    pub object: &'a Object,
+    /// The outline of the shape in world space, in pixels. Ellipses are
+    /// approximated by a polygon.
+    pub points: Vec<(f32, f32)>,
+    /// Whether the outline encloses an area, which polylines don't.
+    pub closed: bool,
+}
+
+/// Returns the outline of one of the collision shapes of a tile, in pixels
+/// relative to the tile's top left corner with its flips applied, along
+/// with whether it encloses an area. Points and text have no outline.
+pub(crate) fn shape_outline(
+    object: &Object,
+    tile: LayerTile,
+    (width, height): (f32, f32),
+) -> Option<(Vec<(f32, f32)>, bool)> {
+    let (points, closed) = match object.shape {
+        ObjectShape::Rect { width, height } => (
+            vec![(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)],
+            true,
+        ),
+        ObjectShape::Ellipse { width, height } => (
+            (0..ELLIPSE_SEGMENTS)
+                .map(|i| {
+                    let angle = i as f32 / ELLIPSE_SEGMENTS as f32 * std::f32::consts::TAU;
+                    (
+                        width / 2.0 * (1.0 + angle.cos()),
+                        height / 2.0 * (1.0 + angle.sin()),
+                    )
+                })
+                .collect(),
+            true,
+        ),
+        ObjectShape::Polygon { ref points } => (points.clone(), true),
+        ObjectShape::Polyline { ref points } => (points.clone(), false),
+        ObjectShape::Point(..) | ObjectShape::Text(_) => return None,
+    };
+    let flip = |(mut x, mut y): (f32, f32)| {
+        if tile.flip_d {
+            std::mem::swap(&mut x, &mut y);
+        }
+        if tile.flip_h {
+            x = width - x;
+        }
+        if tile.flip_v {
+            y = height - y;
+        }
+        (x, y)
+    };
+    // Objects rotate clockwise around their position.
+    let (sin, cos) = object.rotation.to_radians().sin_cos();
+    let points = points
+        .into_iter()
+        .map(|(x, y)| flip((object.x + x * cos - y * sin, object.y + x * sin + y * cos)))
+        .collect();
+    Some((points, closed))
+}
+
+impl Map {
+    /// Returns the collision shapes of the tiles of a tile layer, as set up
+    /// in Tiled's collision editor, placed at their tiles in world space
+    /// with the tiles' flips and the layer's offset applied. This is what
+    /// physics engines need to build static colliders from. Other kinds of
+    /// layers have none.
+    ///
+    /// Tiles of tilesets with a different tile size than the map are aligned
+    /// to the bottom left of their cell, like Tiled draws them.
+    pub fn tile_colliders<'a>(&'a self, layer: &Layer) -> Vec<TileCollider<'a>> {
+        let mut colliders = Vec::new();
+        let tiles = match layer.as_tile_layer() {
+            Some(tiles) => tiles,
+            None => return colliders,
+        };
+        let bounds = match tiles.bounds() {
+            Some(bounds) => bounds,
+            None => return colliders,
+        };
+        let tilesets = self.tileset_index();
+        for y in bounds.y..bounds.bottom() {
+            for x in bounds.x..bounds.right() {
+                let tile = match tiles.get_tile(x, y) {
+                    Some(tile) => tile,
+                    None => continue,
+                };
+                let (tileset, _) = match tilesets.tileset_for_gid(tile) {
+                    Some(found) => found,
+                    None => continue,
+                };
+                let group = match tilesets
+                    .get_tile_by_gid(tile)
+                    .and_then(|t| t.objectgroup.as_ref()?.as_object_layer())
+                {
+                    Some(group) => group,
+                    None => continue,
+                };
+                let size = (tileset.tile_width as f32, tileset.tile_height as f32);
+                let origin = (
+                    layer.offset_x + x as f32 * self.tile_width as f32,
+                    layer.offset_y + (y + 1) as f32 * self.tile_height as f32 - size.1,
+                );
+                for object in group.objects.iter() {
+                    if let Some((points, closed)) = shape_outline(object, tile, size) {
+                        colliders.push(TileCollider {
+                            tile: (x, y),
+                            object,
+                            points: points
+                                .into_iter()
+                                .map(|(px, py)| (origin.0 + px, origin.1 + py))
+                                .collect(),
+                            closed,
+                        });
+                    }
+                }
+            }
+        }
+        colliders
+    }
+}
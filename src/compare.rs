@@ -0,0 +1,244 @@
+//! Semantic comparison of maps, for testing exporters and transforms.
+
+use std::fmt::Debug;
+
+use crate::{Layer, LayerType, Map, Object, ObjectShape, TileLayer, Tileset};
+
+/// Collects the differences found between two maps.
+struct Differences {
+    tolerance: f32,
+    found: Vec<String>,
+}
+
+impl Differences {
+    fn equal<T: PartialEq + Debug>(&mut self, path: &str, a: &T, b: &T) {
+        if a != b {
+            self.found.push(format!("{}: {:?} != {:?}", path, a, b));
+        }
+    }
+
+    fn close(&mut self, path: &str, a: f32, b: f32) {
+        if (a - b).abs() > self.tolerance {
+            self.found.push(format!("{}: {} != {}", path, a, b));
+        }
+    }
+
+    fn points(&mut self, path: &str, a: &[(f32, f32)], b: &[(f32, f32)]) {
+        if a.len() != b.len() {
+            self.equal(&format!("{}.len()", path), &a.len(), &b.len());
+            return;
+        }
+        for (i, (p, q)) in a.iter().zip(b.iter()).enumerate() {
+            self.close(&format!("{}[{}].x", path, i), p.0, q.0);
+            self.close(&format!("{}[{}].y", path, i), p.1, q.1);
+        }
+    }
+
+    fn map(&mut self, a: &Map, b: &Map) {
+        self.equal("orientation", &a.orientation, &b.orientation);
+        self.equal("width", &a.width, &b.width);
+        self.equal("height", &a.height, &b.height);
+        self.equal("tile_width", &a.tile_width, &b.tile_width);
+        self.equal("tile_height", &a.tile_height, &b.tile_height);
+        self.equal("infinite", &a.infinite, &b.infinite);
+        self.equal(
+            "background_colour",
+            &a.background_colour,
+            &b.background_colour,
+        );
+        self.equal("user_type", &a.user_type, &b.user_type);
+        self.equal("hex_side_length", &a.hex_side_length, &b.hex_side_length);
+        self.equal("stagger_axis", &a.stagger_axis, &b.stagger_axis);
+        self.equal("stagger_index", &a.stagger_index, &b.stagger_index);
+        self.equal("properties", &a.properties, &b.properties);
+        if a.tilesets.len() != b.tilesets.len() {
+            self.equal("tilesets.len()", &a.tilesets.len(), &b.tilesets.len());
+        } else {
+            let without_origin = |tileset: &Tileset| Tileset {
+                source: None,
+                version: None,
+                tiled_version: None,
+                ..tileset.clone()
+            };
+            for (i, (p, q)) in a.tilesets.iter().zip(b.tilesets.iter()).enumerate() {
+                if without_origin(p) != without_origin(q) {
+                    self.found
+                        .push(format!("tilesets[{}]: {:?} differs", i, p.name));
+                }
+            }
+        }
+        self.equal(
+            "unresolved_tilesets",
+            &a.unresolved_tilesets,
+            &b.unresolved_tilesets,
+        );
+        self.layers("layers", &a.layers, &b.layers);
+    }
+
+    fn layers(&mut self, path: &str, a: &[Layer], b: &[Layer]) {
+        if a.len() != b.len() {
+            self.equal(&format!("{}.len()", path), &a.len(), &b.len());
+            return;
+        }
+        for (i, (p, q)) in a.iter().zip(b.iter()).enumerate() {
+            self.layer(&format!("{}[{}]", path, i), p, q);
+        }
+    }
+
+    fn layer(&mut self, path: &str, a: &Layer, b: &Layer) {
+        self.equal(&format!("{}.id", path), &a.id, &b.id);
+        self.equal(&format!("{}.name", path), &a.name, &b.name);
+        self.equal(&format!("{}.user_type", path), &a.user_type, &b.user_type);
+        self.close(&format!("{}.opacity", path), a.opacity, b.opacity);
+        self.equal(&format!("{}.visible", path), &a.visible, &b.visible);
+        self.equal(&format!("{}.locked", path), &a.locked, &b.locked);
+        self.close(&format!("{}.offset_x", path), a.offset_x, b.offset_x);
+        self.close(&format!("{}.offset_y", path), a.offset_y, b.offset_y);
+        self.equal(
+            &format!("{}.tint_colour", path),
+            &a.tint_colour,
+            &b.tint_colour,
+        );
+        self.equal(
+            &format!("{}.properties", path),
+            &a.properties,
+            &b.properties,
+        );
+        match (&a.layer_type, &b.layer_type) {
+            (LayerType::TileLayer(p), LayerType::TileLayer(q)) => self.tiles(path, p, q),
+            (LayerType::ObjectLayer(p), LayerType::ObjectLayer(q)) => {
+                self.equal(&format!("{}.colour", path), &p.colour, &q.colour);
+                self.equal(
+                    &format!("{}.draw_order", path),
+                    &p.draw_order,
+                    &q.draw_order,
+                );
+                if p.objects.len() != q.objects.len() {
+                    self.equal(
+                        &format!("{}.objects.len()", path),
+                        &p.objects.len(),
+                        &q.objects.len(),
+                    );
+                    return;
+                }
+                for (i, (o, r)) in p.objects.iter().zip(q.objects.iter()).enumerate() {
+                    self.object(&format!("{}.objects[{}]", path, i), o, r);
+                }
+            }
+            (LayerType::ImageLayer(p), LayerType::ImageLayer(q)) => {
+                self.equal(&format!("{}.image", path), &p.image, &q.image)
+            }
+            (LayerType::Group(p), LayerType::Group(q)) => {
+                self.layers(&format!("{}.layers", path), p, q)
+            }
+            _ => self.found.push(format!("{}: layer types differ", path)),
+        }
+    }
+
+    /// Compares tiles by position, so layers split into chunks differently
+    /// are still equivalent.
+    fn tiles(&mut self, path: &str, a: &TileLayer, b: &TileLayer) {
+        let area = match (a.bounds(), b.bounds()) {
+            (Some(p), Some(q)) => p.union(&q),
+            (Some(area), None) | (None, Some(area)) => area,
+            (None, None) => return,
+        };
+        let mut differing = Vec::new();
+        for y in area.y..area.bottom() {
+            for x in area.x..area.right() {
+                if a.get_tile(x, y) != b.get_tile(x, y) {
+                    differing.push((x, y));
+                }
+            }
+        }
+        if let Some(&(x, y)) = differing.first() {
+            self.found.push(format!(
+                "{}: {} tiles differ, the first at ({}, {})",
+                path,
+                differing.len(),
+                x,
+                y
+            ));
+        }
+    }
+
+    fn object(&mut self, path: &str, a: &Object, b: &Object) {
+        self.equal(&format!("{}.id", path), &a.id, &b.id);
+        self.equal(&format!("{}.gid", path), &a.gid, &b.gid);
+        self.equal(&format!("{}.name", path), &a.name, &b.name);
+        self.equal(&format!("{}.obj_type", path), &a.obj_type, &b.obj_type);
+        self.close(&format!("{}.x", path), a.x, b.x);
+        self.close(&format!("{}.y", path), a.y, b.y);
+        self.close(&format!("{}.width", path), a.width, b.width);
+        self.close(&format!("{}.height", path), a.height, b.height);
+        self.close(&format!("{}.rotation", path), a.rotation, b.rotation);
+        self.equal(&format!("{}.visible", path), &a.visible, &b.visible);
+        self.equal(&format!("{}.locked", path), &a.locked, &b.locked);
+        self.equal(
+            &format!("{}.properties", path),
+            &a.properties,
+            &b.properties,
+        );
+        let shape = format!("{}.shape", path);
+        match (&a.shape, &b.shape) {
+            (
+                ObjectShape::Rect {
+                    width: w1,
+                    height: h1,
+                },
+                ObjectShape::Rect {
+                    width: w2,
+                    height: h2,
+                },
+            )
+            | (
+                ObjectShape::Ellipse {
+                    width: w1,
+                    height: h1,
+                },
+                ObjectShape::Ellipse {
+                    width: w2,
+                    height: h2,
+                },
+            ) => {
+                self.close(&format!("{}.width", shape), *w1, *w2);
+                self.close(&format!("{}.height", shape), *h1, *h2);
+            }
+            (ObjectShape::Polyline { points: p }, ObjectShape::Polyline { points: q })
+            | (ObjectShape::Polygon { points: p }, ObjectShape::Polygon { points: q }) => {
+                self.points(&format!("{}.points", shape), p, q)
+            }
+            (ObjectShape::Point(x1, y1), ObjectShape::Point(x2, y2)) => {
+                self.points(&shape, &[(*x1, *y1)], &[(*x2, *y2)])
+            }
+            // Text, and shapes of different kinds.
+            (p, q) => self.equal(&shape, p, q),
+        }
+    }
+}
+
+impl Map {
+    /// Lists the differences between the contents of two maps, ignoring how
+    /// they are stored: the encoding and compression of layer data, the
+    /// chunks of infinite layers, the versions and the next ids. Positions,
+    /// sizes and other measures may differ by up to `tolerance`.
+    pub fn differences(&self, other: &Map, tolerance: f32) -> Vec<String> {
+        let mut differences = Differences {
+            tolerance,
+            found: Vec::new(),
+        };
+        differences.map(self, other);
+        differences.found
+    }
+}
+
+/// Asserts that two maps have the same contents, as compared by
+/// `Map::differences`, for tests of exporters and transforms. Panics with
+/// the list of differences otherwise.
+#[track_caller]
+pub fn assert_maps_equivalent(a: &Map, b: &Map, tolerance: f32) {
+    let differences = a.differences(b, tolerance);
+    if !differences.is_empty() {
+        panic!("maps are not equivalent:\n  {}", differences.join("\n  "));
+    }
+}
@@ -0,0 +1,218 @@
+//! Diagnostics for files using features the crate doesn't support.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Read;
+
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::{Map, TiledError, Tileset};
+
+/// The newest version of Tiled whose files the crate is known to read
+/// without losing data.
+pub const SUPPORTED_TILED_VERSION: &str = "1.9.2";
+
+/// The elements the crate parses. Anything else is skipped along with its
+/// contents.
+const SUPPORTED_ELEMENTS: &[&str] = &[
+    "animation",
+    "chunk",
+    "chunksize",
+    "data",
+    "editorsettings",
+    "ellipse",
+    "export",
+    "frame",
+    "group",
+    "image",
+    "imagelayer",
+    "layer",
+    "map",
+    "object",
+    "objectgroup",
+    "point",
+    "polygon",
+    "polyline",
+    "properties",
+    "property",
+    "text",
+    "tile",
+    "tileset",
+    "wangcolor",
+    "wangset",
+    "wangsets",
+    "wangtile",
+];
+
+/// A reason why a file may not be read faithfully.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CompatibilityWarning {
+    /// The file was saved by a version of Tiled newer than
+    /// `SUPPORTED_TILED_VERSION`, which may have added features the crate
+    /// doesn't know about.
+    NewerTiledVersion { version: String },
+    /// A tileset was saved by a version of Tiled newer than
+    /// `SUPPORTED_TILED_VERSION`.
+    NewerTilesetVersion { tileset: String, version: String },
+    /// A tileset was saved by a newer version of Tiled than the map using it,
+    /// which often means the assets were checked out at different versions.
+    MixedTilesetVersion {
+        tileset: String,
+        version: String,
+        map_version: String,
+    },
+    /// The file contains elements the crate skips, so their contents are
+    /// lost.
+    UnsupportedElement { name: String, count: usize },
+}
+
+impl fmt::Display for CompatibilityWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompatibilityWarning::NewerTiledVersion { version } => write!(
+                f,
+                "saved by Tiled {}, newer than the supported {}",
+                version, SUPPORTED_TILED_VERSION
+            ),
+            CompatibilityWarning::NewerTilesetVersion { tileset, version } => write!(
+                f,
+                "tileset {:?} saved by Tiled {}, newer than the supported {}",
+                tileset, version, SUPPORTED_TILED_VERSION
+            ),
+            CompatibilityWarning::MixedTilesetVersion {
+                tileset,
+                version,
+                map_version,
+            } => write!(
+                f,
+                "tileset {:?} saved by Tiled {}, newer than the map saved by Tiled {}",
+                tileset, version, map_version
+            ),
+            CompatibilityWarning::UnsupportedElement { name, count } => {
+                write!(f, "{} unsupported <{}> element(s)", count, name)
+            }
+        }
+    }
+}
+
+/// Splits a version like `1.10.2` into its numbers, for comparisons.
+/// Returns `None` for the dated versions of development snapshots, like
+/// `2020.05.20`, which can't be compared with releases.
+fn version_numbers(version: &str) -> Option<Vec<u32>> {
+    let numbers: Vec<u32> = version
+        .split('.')
+        .map(|part| {
+            let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+            digits.parse().unwrap_or(0)
+        })
+        .collect();
+    Some(numbers).filter(|numbers| numbers[0] < 2000)
+}
+
+/// Whether a version of Tiled is known to be newer than another.
+fn is_newer(version: &str, than: &str) -> bool {
+    match (version_numbers(version), version_numbers(than)) {
+        (Some(version), Some(than)) => version > than,
+        _ => false,
+    }
+}
+
+fn version_warning(tiled_version: &str) -> Option<CompatibilityWarning> {
+    if is_newer(tiled_version, SUPPORTED_TILED_VERSION) {
+        Some(CompatibilityWarning::NewerTiledVersion {
+            version: tiled_version.to_string(),
+        })
+    } else {
+        None
+    }
+}
+
+impl Tileset {
+    /// Returns a warning if the tileset was saved by a newer version of
+    /// Tiled than the crate supports.
+    pub fn compatibility_warnings(&self) -> Vec<CompatibilityWarning> {
+        match self.tiled_version {
+            Some(ref version) if is_newer(version, SUPPORTED_TILED_VERSION) => {
+                vec![CompatibilityWarning::NewerTilesetVersion {
+                    tileset: self.name.clone(),
+                    version: version.clone(),
+                }]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Map {
+    /// Returns the warnings which can be told from a parsed map: whether it
+    /// or its tilesets were saved by a newer version of Tiled than the crate
+    /// supports, and tilesets saved by a newer version than the map. See
+    /// `check_compatibility` for a thorough check of a file.
+    pub fn compatibility_warnings(&self) -> Vec<CompatibilityWarning> {
+        let mut warnings: Vec<CompatibilityWarning> = self
+            .tiled_version
+            .as_deref()
+            .and_then(version_warning)
+            .into_iter()
+            .collect();
+        for tileset in self.tilesets.iter() {
+            let tileset_warnings = tileset.compatibility_warnings();
+            if !tileset_warnings.is_empty() {
+                warnings.extend(tileset_warnings);
+                continue;
+            }
+            if let (Some(version), Some(map_version)) =
+                (&tileset.tiled_version, &self.tiled_version)
+            {
+                if is_newer(version, map_version) {
+                    warnings.push(CompatibilityWarning::MixedTilesetVersion {
+                        tileset: tileset.name.clone(),
+                        version: version.to_string(),
+                        map_version: map_version.to_string(),
+                    });
+                }
+            }
+        }
+        warnings
+    }
+}
+
+/// Scans a map or tileset document for anything the crate would lose when
+/// parsing it: a newer version of Tiled, and elements which aren't
+/// supported.
+pub fn check_compatibility<R: Read>(reader: R) -> Result<Vec<CompatibilityWarning>, TiledError> {
+    let mut parser = EventReader::new(reader);
+    let mut warnings = Vec::new();
+    let mut unsupported = BTreeMap::new();
+    let mut parents: Vec<String> = Vec::new();
+    loop {
+        match parser.next().map_err(TiledError::XmlDecodingError)? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                let element = name.local_name;
+                if parents.is_empty() {
+                    let version = attributes
+                        .iter()
+                        .find(|a| a.name.local_name == "tiledversion");
+                    warnings.extend(version.and_then(|a| version_warning(&a.value)));
+                }
+                if !SUPPORTED_ELEMENTS.contains(&element.as_str()) {
+                    *unsupported.entry(element.clone()).or_insert(0) += 1;
+                }
+                parents.push(element);
+            }
+            XmlEvent::EndElement { .. } => {
+                parents.pop();
+            }
+            XmlEvent::EndDocument => break,
+            _ => {}
+        }
+    }
+    warnings.extend(
+        unsupported
+            .into_iter()
+            .map(|(name, count)| CompatibilityWarning::UnsupportedElement { name, count }),
+    );
+    Ok(warnings)
+}
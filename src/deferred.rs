@@ -0,0 +1,55 @@
+//! Loading the external tilesets of a map after the map itself.
+
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::{Gid, Loader, Map, TiledError};
+
+/// An external tileset of a map which hasn't been loaded yet, see
+/// `Loader::with_deferred_tilesets`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TilesetSource {
+    pub first_gid: Gid,
+    /// The path of the tileset file as written in the map, relative to the
+    /// map file.
+    pub source: PathBuf,
+}
+
+impl Map {
+    /// Loads the tilesets left in `unresolved_tilesets`, opening each one
+    /// with `open`, which is given its source path as written in the map.
+    /// Tilesets loaded before an error are kept, and the rest stay
+    /// unresolved.
+    pub fn resolve_tilesets<R: Read>(
+        &mut self,
+        open: impl FnMut(&Path) -> io::Result<R>,
+    ) -> Result<(), TiledError> {
+        self.resolve_tilesets_with(&Loader::new(), open)
+    }
+
+    /// Same as `resolve_tilesets`, parsing the tilesets with `loader`.
+    pub fn resolve_tilesets_with<R: Read>(
+        &mut self,
+        loader: &Loader,
+        mut open: impl FnMut(&Path) -> io::Result<R>,
+    ) -> Result<(), TiledError> {
+        while let Some(source) = self.unresolved_tilesets.first() {
+            let file = open(&source.source).map_err(|e| TiledError::io(&source.source, e))?;
+            let mut tileset = loader.parse_tileset(file, source.first_gid.0)?;
+            if tileset.version.is_none() {
+                tileset.version = Some(self.version.clone());
+            }
+            tileset.source = Some(self.resolve_path(&source.source));
+            if tileset.tiled_version.is_none() {
+                tileset.tiled_version = self.tiled_version.clone();
+            }
+            let index = self
+                .tilesets
+                .partition_point(|other| other.first_gid.value() <= tileset.first_gid.value());
+            self.tilesets.insert(index, Arc::new(tileset));
+            self.unresolved_tilesets.remove(0);
+        }
+        Ok(())
+    }
+}
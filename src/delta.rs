@@ -0,0 +1,172 @@
+//! Replicating tile changes between copies of a map.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::slice;
+
+use crate::{cell_bits, LayerData, LayerTile, Map, TileLayer, TileRect, TiledError};
+
+/// Identifies encoded deltas, followed by the version of the format.
+const DELTA_MAGIC: &[u8; 4] = b"TMD\x01";
+/// The size of one encoded cell: its position and tile.
+const CELL_LEN: usize = 12;
+
+fn no_tile_layer(layer_id: u32) -> TiledError {
+    TiledError::Other(format!("There is no tile layer with id {}", layer_id))
+}
+
+/// A piece of a layer's data along with the area it covers.
+type Block<'a> = (TileRect, &'a [Vec<Option<LayerTile>>]);
+
+/// The pieces of a layer's data, the rows of finite layers and the chunks of
+/// infinite ones, by their origin.
+fn blocks(layer: &TileLayer) -> HashMap<(i32, i32), Block<'_>> {
+    match layer.tiles {
+        LayerData::Finite(ref rows) => rows
+            .iter()
+            .zip(0..=i32::MAX)
+            .map(|(row, y)| {
+                let width = row.len().try_into().unwrap_or(u32::MAX);
+                let area = TileRect::new(0, y, width, 1);
+                ((0, y), (area, slice::from_ref(row)))
+            })
+            .collect(),
+        LayerData::Infinite(ref chunks) => chunks
+            .iter()
+            .map(|(&key, chunk)| (key, (chunk.bounds(), &chunk.tiles[..])))
+            .collect(),
+    }
+}
+
+/// A set of changed tiles within one tile layer, which can be sent to other
+/// copies of the map and applied to them. The layer is identified by its
+/// id, so deltas still apply after layers are added or reordered.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TileDelta {
+    /// The id of the layer, as in `Layer::id`.
+    pub layer_id: u32,
+    /// The changed positions, in tile coordinates, along with their new
+    /// tiles.
+    pub cells: Vec<(i32, i32, Option<LayerTile>)>,
+}
+
+impl TileDelta {
+    pub fn new(layer_id: u32) -> TileDelta {
+        TileDelta {
+            layer_id,
+            cells: Vec::new(),
+        }
+    }
+
+    /// Returns the tiles which differ between two versions of a tile layer,
+    /// in row-major order. Positions outside of a layer's data count as
+    /// empty. Only the rows or chunks which differ are compared tile by tile.
+    pub fn between(layer_id: u32, before: &TileLayer, after: &TileLayer) -> TileDelta {
+        let mut delta = TileDelta::new(layer_id);
+        let (old, new) = (blocks(before), blocks(after));
+        let mut keys: Vec<(i32, i32)> = old.keys().chain(new.keys()).copied().collect();
+        keys.sort_unstable();
+        keys.dedup();
+        for key in keys {
+            let (old, new) = (old.get(&key), new.get(&key));
+            if old == new {
+                continue;
+            }
+            for &(area, _) in old.into_iter().chain(new) {
+                for y in area.y..area.bottom() {
+                    for x in area.x..area.right() {
+                        let tile = after.get_tile(x, y);
+                        if before.get_tile(x, y) != tile {
+                            delta.cells.push((x, y, tile));
+                        }
+                    }
+                }
+            }
+        }
+        delta.cells.sort_unstable_by_key(|&(x, y, _)| (y, x));
+        delta.cells.dedup();
+        delta
+    }
+
+    /// Runs `edit` on the tile layer of the map with the given id, returning
+    /// the tiles it changed, found by comparing the rows or chunks of the
+    /// layer from before the edit. This works with any of the editing operations, like
+    /// `TileLayer::set_tile`, `TileLayer::flood_fill` or `TileLayer::stamp`.
+    pub fn record(
+        map: &mut Map,
+        layer_id: u32,
+        edit: impl FnOnce(&mut TileLayer) -> Result<(), TiledError>,
+    ) -> Result<TileDelta, TiledError> {
+        let layer = map
+            .get_layer_by_id_mut(layer_id)
+            .and_then(|layer| layer.as_tile_layer_mut())
+            .ok_or_else(|| no_tile_layer(layer_id))?;
+        let before = layer.clone();
+        edit(layer)?;
+        Ok(TileDelta::between(layer_id, &before, layer))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Encodes the delta into a compact binary form.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(12 + self.cells.len() * CELL_LEN);
+        out.extend_from_slice(DELTA_MAGIC);
+        out.extend_from_slice(&self.layer_id.to_le_bytes());
+        out.extend_from_slice(&(self.cells.len() as u32).to_le_bytes());
+        for &(x, y, tile) in self.cells.iter() {
+            out.extend_from_slice(&x.to_le_bytes());
+            out.extend_from_slice(&y.to_le_bytes());
+            out.extend_from_slice(&cell_bits(tile).to_le_bytes());
+        }
+        out
+    }
+
+    /// Decodes a delta produced by `encode`.
+    pub fn decode(bytes: &[u8]) -> Result<TileDelta, TiledError> {
+        let malformed = |what: &str| TiledError::Other(format!("Malformed tile delta: {}", what));
+        if bytes.len() < 12 || !bytes.starts_with(DELTA_MAGIC) {
+            return Err(malformed("missing header"));
+        }
+        let word = |at: usize| -> [u8; 4] { bytes[at..at + 4].try_into().unwrap() };
+        let layer_id = u32::from_le_bytes(word(4));
+        let count = u32::from_le_bytes(word(8)) as usize;
+        let cells = &bytes[12..];
+        if count.checked_mul(CELL_LEN) != Some(cells.len()) {
+            return Err(malformed("cell count doesn't match its length"));
+        }
+        let cells = cells
+            .chunks_exact(CELL_LEN)
+            .map(|cell| {
+                let word = |at: usize| -> [u8; 4] { cell[at..at + 4].try_into().unwrap() };
+                (
+                    i32::from_le_bytes(word(0)),
+                    i32::from_le_bytes(word(4)),
+                    LayerTile::from_bits(u32::from_le_bytes(word(8))),
+                )
+            })
+            .collect();
+        Ok(TileDelta { layer_id, cells })
+    }
+}
+
+impl Map {
+    /// Applies the changes of a delta to the map. If any change can't be
+    /// applied, like a position outside of a finite layer, none of them are
+    /// and the layer is left as it was.
+    pub fn apply_delta(&mut self, delta: &TileDelta) -> Result<(), TiledError> {
+        let layer = self
+            .get_layer_by_id_mut(delta.layer_id)
+            .and_then(|layer| layer.as_tile_layer_mut())
+            .ok_or_else(|| no_tile_layer(delta.layer_id))?;
+        for &(x, y, _) in delta.cells.iter() {
+            layer.check_set_tile(x, y)?;
+        }
+        for &(x, y, tile) in delta.cells.iter() {
+            layer.set_tile(x, y, tile)?;
+        }
+        Ok(())
+    }
+}
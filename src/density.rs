@@ -0,0 +1,94 @@
+//! Statistics about the objects of object groups.
+
+use std::collections::BTreeMap;
+
+use crate::ObjectGroup;
+
+/// The number of objects within each cell of a grid laid over an object
+/// group, for balancing how items or enemies are spread out and for drawing
+/// heatmaps.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DensityGrid {
+    /// The position of the top left corner of the first cell, in pixels.
+    pub origin: (f32, f32),
+    /// The width and height of the cells, in pixels.
+    pub cell_size: f32,
+    /// The number of objects in each cell, row by row.
+    pub counts: Vec<Vec<u32>>,
+}
+
+impl DensityGrid {
+    /// Returns the number of objects in the cell containing a position, in
+    /// pixels.
+    pub fn count_at(&self, x: f32, y: f32) -> u32 {
+        let column = ((x - self.origin.0) / self.cell_size).floor();
+        let row = ((y - self.origin.1) / self.cell_size).floor();
+        if column < 0.0 || row < 0.0 {
+            return 0;
+        }
+        self.counts
+            .get(row as usize)
+            .and_then(|r| r.get(column as usize))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// The number of objects in the busiest cell.
+    pub fn max(&self) -> u32 {
+        self.counts.iter().flatten().copied().max().unwrap_or(0)
+    }
+
+    /// Returns the counts scaled relative to the busiest cell, from 0 for
+    /// empty cells to 1 for the busiest ones.
+    pub fn normalized(&self) -> Vec<Vec<f32>> {
+        let max = self.max().max(1) as f32;
+        self.counts
+            .iter()
+            .map(|row| row.iter().map(|&count| count as f32 / max).collect())
+            .collect()
+    }
+}
+
+impl ObjectGroup {
+    /// Counts the objects within each cell of a grid of `cell_size` pixels,
+    /// by their position. The grid covers every object, starting from the
+    /// cell containing the top left most one.
+    pub fn density_grid(&self, cell_size: f32) -> DensityGrid {
+        let mut grid = DensityGrid {
+            origin: (0.0, 0.0),
+            cell_size,
+            counts: Vec::new(),
+        };
+        if self.objects.is_empty() || cell_size.is_nan() || cell_size <= 0.0 {
+            return grid;
+        }
+        let cell = |v: f32| (v / cell_size).floor();
+        let (mut min_x, mut min_y) = (f32::MAX, f32::MAX);
+        let (mut max_x, mut max_y) = (f32::MIN, f32::MIN);
+        for object in self.objects.iter() {
+            min_x = min_x.min(cell(object.x));
+            min_y = min_y.min(cell(object.y));
+            max_x = max_x.max(cell(object.x));
+            max_y = max_y.max(cell(object.y));
+        }
+        let columns = (max_x - min_x) as usize + 1;
+        let rows = (max_y - min_y) as usize + 1;
+        grid.origin = (min_x * cell_size, min_y * cell_size);
+        grid.counts = vec![vec![0; columns]; rows];
+        for object in self.objects.iter() {
+            let column = (cell(object.x) - min_x) as usize;
+            let row = (cell(object.y) - min_y) as usize;
+            grid.counts[row][column] += 1;
+        }
+        grid
+    }
+
+    /// Returns the number of objects of each type.
+    pub fn type_counts(&self) -> BTreeMap<&str, usize> {
+        let mut counts = BTreeMap::new();
+        for object in self.objects.iter() {
+            *counts.entry(object.obj_type.as_str()).or_insert(0) += 1;
+        }
+        counts
+    }
+}
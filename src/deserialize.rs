@@ -0,0 +1,168 @@
+//! Deserializing properties into user types with serde.
+
+use std::collections::hash_map;
+use std::fmt;
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, Visitor};
+use serde::forward_to_deserialize_any;
+
+use crate::{Properties, PropertyValue, TiledError};
+
+/// An error deserializing properties, along with the names of the
+/// properties leading to the value which couldn't be deserialized.
+#[derive(Debug)]
+struct Error {
+    path: Vec<String>,
+    message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "property \"{}\": {}", self.path.join("."), self.message)
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Error {
+        Error {
+            path: Vec::new(),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Deserializes properties into user types, like structs with a field for
+/// each property.
+pub trait DeserializeProperties {
+    /// Deserializes the properties into a `T`, matching property names with
+    /// field names. Class properties deserialize into nested structs, enum
+    /// properties saved as names into unit variants, and colours into their
+    /// `#AARRGGBB` form. Fields of type `Option` may be missing.
+    fn deserialize_into<T: DeserializeOwned>(&self) -> Result<T, TiledError>;
+}
+
+impl DeserializeProperties for Properties {
+    fn deserialize_into<T: DeserializeOwned>(&self) -> Result<T, TiledError> {
+        T::deserialize(PropertiesDeserializer(self))
+            .map_err(|e| TiledError::Other(format!("Could not deserialize properties: {}", e)))
+    }
+}
+
+struct PropertiesDeserializer<'a>(&'a Properties);
+
+impl<'de, 'a> de::Deserializer<'de> for PropertiesDeserializer<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_map(PropertiesAccess {
+            iter: self.0.iter(),
+            value: None,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct PropertiesAccess<'a> {
+    iter: hash_map::Iter<'a, String, PropertyValue>,
+    value: Option<(&'a String, &'a PropertyValue)>,
+}
+
+impl<'de, 'a> MapAccess<'de> for PropertiesAccess<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.iter.next() {
+            Some((name, value)) => {
+                self.value = Some((name, value));
+                seed.deserialize(IntoDeserializer::<Error>::into_deserializer(name.as_str()))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (name, value) = self
+            .value
+            .take()
+            .ok_or_else(|| de::Error::custom("value is missing"))?;
+        seed.deserialize(ValueDeserializer(value)).map_err(|mut e| {
+            e.path.insert(0, name.clone());
+            e
+        })
+    }
+}
+
+struct ValueDeserializer<'a>(&'a PropertyValue);
+
+impl<'de, 'a> de::Deserializer<'de> for ValueDeserializer<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match *self.0 {
+            PropertyValue::BoolValue(v) => visitor.visit_bool(v),
+            PropertyValue::FloatValue(v) => visitor.visit_f32(v),
+            PropertyValue::IntValue(v) => visitor.visit_i32(v),
+            PropertyValue::ColorValue(v) => visitor.visit_string(v.to_string()),
+            PropertyValue::StringValue(ref v) => visitor.visit_str(v),
+            PropertyValue::FileValue(ref v) => visitor.visit_str(&v.to_string_lossy()),
+            PropertyValue::ObjectValue(v) => visitor.visit_u32(v),
+            PropertyValue::ClassValue { ref properties, .. } => {
+                PropertiesDeserializer(properties).deserialize_any(visitor)
+            }
+            PropertyValue::EnumValue { ref value, .. } => {
+                ValueDeserializer(value).deserialize_any(visitor)
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match *self.0 {
+            PropertyValue::StringValue(ref v) => {
+                IntoDeserializer::<Error>::into_deserializer(v.as_str())
+                    .deserialize_enum(name, variants, visitor)
+            }
+            PropertyValue::EnumValue { ref value, .. } => {
+                ValueDeserializer(value).deserialize_enum(name, variants, visitor)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
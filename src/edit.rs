@@ -0,0 +1,558 @@
+//! Editing operations on maps and tile layers.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::TryInto;
+use std::iter;
+use std::slice;
+
+use crate::geometry::area_end;
+use crate::group::max_object_id;
+use crate::{
+    Chunk, Layer, LayerData, LayerTile, LayerType, Map, Object, ObjectGroup, ObjectShape,
+    TileLayer, TileRect, TiledError,
+};
+
+/// The chunk size Tiled uses for infinite maps unless configured otherwise.
+const DEFAULT_CHUNK_SIZE: u32 = 16;
+
+fn out_of_bounds(what: &str, at: u32, count: u32) -> TiledError {
+    TiledError::Other(format!(
+        "{} {}..{} is outside of the layer",
+        what,
+        at,
+        at as u64 + count as u64
+    ))
+}
+
+fn too_large(what: &str, at: u32, count: u32) -> TiledError {
+    TiledError::Other(format!(
+        "Editing {}s {}..{} would make the layer too large",
+        what,
+        at,
+        at as u64 + count as u64
+    ))
+}
+
+pub(crate) fn not_a_tile_layer(layer_index: u32) -> TiledError {
+    TiledError::Other(format!("Layer {} is not a tile layer", layer_index))
+}
+
+fn not_an_object_layer(layer_index: usize) -> TiledError {
+    TiledError::Other(format!("Layer {} is not an object layer", layer_index))
+}
+
+fn infinite_unsupported() -> TiledError {
+    TiledError::Other("Structural edits are not supported on infinite layers".to_string())
+}
+
+impl TileLayer {
+    /// Returns the area covered by the layer's tile data. For infinite layers
+    /// this is the area spanned by the allocated chunks. Returns `None` if the
+    /// area reaches past the largest tile coordinates, which parsed layers
+    /// never do.
+    pub fn bounds(&self) -> Option<TileRect> {
+        let bounds = match self.tiles {
+            LayerData::Finite(ref rows) => {
+                let width = rows.first().map_or(0, Vec::len);
+                TileRect::new(0, 0, width.try_into().ok()?, rows.len().try_into().ok()?)
+            }
+            LayerData::Infinite(ref chunks) => chunks
+                .values()
+                .map(Chunk::bounds)
+                .reduce(|bounds, chunk| bounds.union(&chunk))?,
+        };
+        area_end(&bounds)?;
+        Some(bounds)
+    }
+
+    /// Returns the tile at the given tile coordinates, or `None` if the
+    /// cell is empty or lies outside of the layer's data.
+    pub fn get_tile(&self, x: i32, y: i32) -> Option<LayerTile> {
+        self.cell(x, y).flatten()
+    }
+
+    /// Returns the cell at the given tile coordinates, or `None` if the
+    /// position lies outside of the layer's data.
+    pub(crate) fn cell(&self, x: i32, y: i32) -> Option<Option<LayerTile>> {
+        match self.tiles {
+            LayerData::Finite(ref rows) => {
+                if x < 0 || y < 0 {
+                    return None;
+                }
+                rows.get(y as usize)?.get(x as usize).copied()
+            }
+            LayerData::Infinite(ref chunks) => {
+                let chunk = chunks.get(&chunk_at(chunks, x, y)?)?;
+                chunk
+                    .tiles
+                    .get((y - chunk.y) as usize)?
+                    .get((x - chunk.x) as usize)
+                    .copied()
+            }
+        }
+    }
+
+    /// Replaces the tile at the given tile coordinates, or empties the cell
+    /// when given `None`. Infinite layers allocate a new chunk when the
+    /// position isn't covered by one yet, while finite layers return an
+    /// error for positions outside of the layer.
+    pub fn set_tile(
+        &mut self,
+        x: i32,
+        y: i32,
+        tile: impl Into<Option<LayerTile>>,
+    ) -> Result<(), TiledError> {
+        let tile = tile.into();
+        match self.tiles {
+            LayerData::Finite(ref mut rows) => {
+                let cell = if x < 0 || y < 0 {
+                    None
+                } else {
+                    rows.get_mut(y as usize)
+                        .and_then(|row| row.get_mut(x as usize))
+                };
+                match cell {
+                    Some(cell) => {
+                        *cell = tile;
+                        Ok(())
+                    }
+                    None => Err(TiledError::Other(format!(
+                        "Tile position ({}, {}) is outside of the layer",
+                        x, y
+                    ))),
+                }
+            }
+            LayerData::Infinite(ref mut chunks) => {
+                let key = match chunk_at(chunks, x, y) {
+                    Some(key) => key,
+                    None => {
+                        let (width, height) = chunk_size(chunks);
+                        let (chunk_x, chunk_y) = chunk_origin(x, y, width, height)
+                            .ok_or_else(|| outside_chunks(x, y))?;
+                        chunks.entry((chunk_x, chunk_y)).or_insert_with(|| Chunk {
+                            x: chunk_x,
+                            y: chunk_y,
+                            width,
+                            height,
+                            tiles: vec![vec![None; width as usize]; height as usize],
+                        });
+                        (chunk_x, chunk_y)
+                    }
+                };
+                // Chunks may hold fewer tiles than their size, or share their
+                // origin with the new chunk while being smaller.
+                let cell = chunks.get_mut(&key).and_then(|chunk| {
+                    let row = (y as i64 - chunk.y as i64) as usize;
+                    let column = (x as i64 - chunk.x as i64) as usize;
+                    chunk.tiles.get_mut(row)?.get_mut(column)
+                });
+                match cell {
+                    Some(cell) => {
+                        *cell = tile;
+                        Ok(())
+                    }
+                    None => Err(outside_chunks(x, y)),
+                }
+            }
+        }
+    }
+
+    /// Returns the error `set_tile` would give for the given tile
+    /// coordinates, without changing the layer, so that several tiles can
+    /// be checked before writing any of them.
+    pub(crate) fn check_set_tile(&self, x: i32, y: i32) -> Result<(), TiledError> {
+        if self.cell(x, y).is_some() {
+            return Ok(());
+        }
+        match self.tiles {
+            LayerData::Finite(_) => Err(TiledError::Other(format!(
+                "Tile position ({}, {}) is outside of the layer",
+                x, y
+            ))),
+            LayerData::Infinite(ref chunks) => {
+                // Only a new chunk whose origin isn't taken can hold the tile.
+                let (width, height) = chunk_size(chunks);
+                match chunk_origin(x, y, width, height) {
+                    Some(key) if chunk_at(chunks, x, y).is_none() && !chunks.contains_key(&key) => {
+                        Ok(())
+                    }
+                    _ => Err(outside_chunks(x, y)),
+                }
+            }
+        }
+    }
+
+    /// Sets every tile within `rect` to `tile`, or empties them when given
+    /// `None`. On finite layers the part of the rectangle outside of the
+    /// layer is ignored, while infinite layers allocate chunks to cover the
+    /// whole rectangle.
+    pub fn fill_rect(&mut self, rect: TileRect, tile: impl Into<Option<LayerTile>>) {
+        let tile = tile.into();
+        let rect = match self.tiles {
+            LayerData::Finite(_) => match self.bounds().and_then(|b| b.intersection(&rect)) {
+                Some(rect) => rect,
+                None => return,
+            },
+            LayerData::Infinite(_) => rect,
+        };
+        for y in rect.y..rect.bottom() {
+            for x in rect.x..rect.right() {
+                // Positions are within bounds or allocated on demand.
+                let _ = self.set_tile(x, y, tile);
+            }
+        }
+    }
+
+    /// Replaces the area of identical, 4-connected tiles containing the given
+    /// position with `tile`, like Tiled's bucket fill tool. On infinite layers
+    /// the fill is limited to the area spanned by the existing chunks, with
+    /// positions not covered by a chunk being treated as empty.
+    pub fn flood_fill(&mut self, x: i32, y: i32, tile: impl Into<Option<LayerTile>>) {
+        let tile = tile.into();
+        let bounds = match self.bounds() {
+            Some(bounds) if bounds.contains(x, y) => bounds,
+            _ => return,
+        };
+        let target = self.get_tile(x, y);
+        if target == tile {
+            return;
+        }
+
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        visited.insert((x, y));
+        queue.push_back((x, y));
+        while let Some((x, y)) = queue.pop_front() {
+            let _ = self.set_tile(x, y, tile);
+            for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                if !bounds.contains(nx, ny) || visited.contains(&(nx, ny)) {
+                    continue;
+                }
+                if self.get_tile(nx, ny) == target {
+                    visited.insert((nx, ny));
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+    }
+
+    fn finite_rows_mut(&mut self) -> Result<&mut Vec<Vec<Option<LayerTile>>>, TiledError> {
+        match self.tiles {
+            LayerData::Finite(ref mut rows) => Ok(rows),
+            LayerData::Infinite(_) => Err(infinite_unsupported()),
+        }
+    }
+
+    /// Checks that `count` rows can be inserted before, or deleted from,
+    /// row `at`, without changing the layer.
+    fn check_rows(&self, at: u32, count: u32, inserting: bool) -> Result<(), TiledError> {
+        let rows = match self.tiles {
+            LayerData::Finite(ref rows) => rows,
+            LayerData::Infinite(_) => return Err(infinite_unsupported()),
+        };
+        let end = if inserting {
+            self.height
+                .checked_add(count)
+                .ok_or_else(|| too_large("row", at, count))?;
+            at as u64
+        } else {
+            at as u64 + count as u64
+        };
+        if end > rows.len() as u64 {
+            return Err(out_of_bounds("row", at, count));
+        }
+        Ok(())
+    }
+
+    /// Checks that `count` columns can be inserted before, or deleted from,
+    /// column `at`, without changing the layer.
+    fn check_cols(&self, at: u32, count: u32, inserting: bool) -> Result<(), TiledError> {
+        let rows = match self.tiles {
+            LayerData::Finite(ref rows) => rows,
+            LayerData::Infinite(_) => return Err(infinite_unsupported()),
+        };
+        let end = if inserting {
+            self.width
+                .checked_add(count)
+                .ok_or_else(|| too_large("column", at, count))?;
+            at as u64
+        } else {
+            at as u64 + count as u64
+        };
+        if rows.iter().any(|row| end > row.len() as u64) {
+            return Err(out_of_bounds("column", at, count));
+        }
+        Ok(())
+    }
+
+    /// Inserts `count` rows of empty tiles before row `at`, moving the rows
+    /// after it down.
+    pub fn insert_rows(&mut self, at: u32, count: u32) -> Result<(), TiledError> {
+        self.check_rows(at, count, true)?;
+        let rows = self.finite_rows_mut()?;
+        let width = rows.first().map_or(0, Vec::len);
+        let empty_row = vec![None; width];
+        let at = at as usize;
+        rows.splice(at..at, iter::repeat(empty_row).take(count as usize));
+        self.height += count;
+        Ok(())
+    }
+
+    /// Removes `count` rows starting at row `at`, moving the rows after them
+    /// up.
+    pub fn delete_rows(&mut self, at: u32, count: u32) -> Result<(), TiledError> {
+        self.check_rows(at, count, false)?;
+        let rows = self.finite_rows_mut()?;
+        rows.drain(at as usize..at as usize + count as usize);
+        self.height = self.height.saturating_sub(count);
+        Ok(())
+    }
+
+    /// Inserts `count` columns of empty tiles before column `at`, moving the
+    /// columns after it to the right.
+    pub fn insert_cols(&mut self, at: u32, count: u32) -> Result<(), TiledError> {
+        self.check_cols(at, count, true)?;
+        let at = at as usize;
+        for row in self.finite_rows_mut()?.iter_mut() {
+            row.splice(at..at, iter::repeat(None).take(count as usize));
+        }
+        self.width += count;
+        Ok(())
+    }
+
+    /// Removes `count` columns starting at column `at`, moving the columns
+    /// after them to the left.
+    pub fn delete_cols(&mut self, at: u32, count: u32) -> Result<(), TiledError> {
+        self.check_cols(at, count, false)?;
+        for row in self.finite_rows_mut()?.iter_mut() {
+            row.drain(at as usize..at as usize + count as usize);
+        }
+        self.width = self.width.saturating_sub(count);
+        Ok(())
+    }
+}
+
+fn outside_chunks(x: i32, y: i32) -> TiledError {
+    TiledError::Other(format!(
+        "Tile position ({}, {}) is outside of the layer's chunks",
+        x, y
+    ))
+}
+
+/// The size of the chunks allocated by `TileLayer::set_tile`: that of the
+/// first chunk by position, which is usually the size of them all.
+fn chunk_size(chunks: &HashMap<(i32, i32), Chunk>) -> (u32, u32) {
+    chunks
+        .iter()
+        .filter(|(_, c)| c.width > 0 && c.height > 0)
+        .filter(|(_, c)| c.width <= i32::MAX as u32 && c.height <= i32::MAX as u32)
+        .min_by_key(|(&key, _)| key)
+        .map_or((DEFAULT_CHUNK_SIZE, DEFAULT_CHUNK_SIZE), |(_, c)| {
+            (c.width, c.height)
+        })
+}
+
+/// Returns the origin of the chunk of the given size holding a position, or
+/// `None` if the chunk would reach beyond the range of `i32`.
+fn chunk_origin(x: i32, y: i32, width: u32, height: u32) -> Option<(i32, i32)> {
+    let origin = (
+        x.div_euclid(width as i32).checked_mul(width as i32)?,
+        y.div_euclid(height as i32).checked_mul(height as i32)?,
+    );
+    area_end(&TileRect::new(origin.0, origin.1, width, height))?;
+    Some(origin)
+}
+
+/// Returns the key of the chunk covering a position, if any. Chunks usually
+/// share a size, so the chunk found from the position is tried before the
+/// others.
+fn chunk_at(chunks: &HashMap<(i32, i32), Chunk>, x: i32, y: i32) -> Option<(i32, i32)> {
+    let covers = |chunk: &Chunk| {
+        let (x, y) = (x as i64, y as i64);
+        x >= chunk.x as i64
+            && y >= chunk.y as i64
+            && x < chunk.x as i64 + chunk.width as i64
+            && y < chunk.y as i64 + chunk.height as i64
+    };
+    let (width, height) = chunk_size(chunks);
+    chunk_origin(x, y, width, height)
+        .filter(|key| chunks.get(key).is_some_and(covers))
+        .or_else(|| {
+            chunks
+                .iter()
+                .find(|(_, chunk)| covers(chunk))
+                .map(|(&key, _)| key)
+        })
+}
+
+fn translate_object(object: &mut Object, dx: f32, dy: f32) {
+    object.x += dx;
+    object.y += dy;
+    if let ObjectShape::Point(ref mut x, ref mut y) = object.shape {
+        *x += dx;
+        *y += dy;
+    }
+}
+
+impl Map {
+    /// Checks that `count` rows or columns can be inserted before, or
+    /// deleted from, row or column `at` of the map and every tile layer, so
+    /// that a failing edit leaves the map unchanged. Returns the start and
+    /// end of the edited area in pixels.
+    fn check_structural_edit(
+        &self,
+        at: u32,
+        count: u32,
+        what: &str,
+        inserting: bool,
+    ) -> Result<(f32, f32), TiledError> {
+        if self.infinite {
+            return Err(infinite_unsupported());
+        }
+        let (len, tile_size) = match what {
+            "row" => (self.height, self.tile_height),
+            _ => (self.width, self.tile_width),
+        };
+        let end = at
+            .checked_add(count)
+            .ok_or_else(|| out_of_bounds(what, at, count))?;
+        if inserting {
+            len.checked_add(count)
+                .ok_or_else(|| too_large(what, at, count))?;
+        }
+        if (if inserting { at } else { end }) > len {
+            return Err(out_of_bounds(what, at, count));
+        }
+        for flat in self.flat_layers() {
+            if let LayerType::TileLayer(ref tiles) = flat.layer.layer_type {
+                match what {
+                    "row" => tiles.check_rows(at, count, inserting)?,
+                    _ => tiles.check_cols(at, count, inserting)?,
+                }
+            }
+        }
+        let pixels = |tiles: u32| tiles as u64 * tile_size as u64;
+        Ok((pixels(at) as f32, pixels(end) as f32))
+    }
+
+    /// Adds a layer on top of the others, giving it the map's next layer id
+    /// if it has none or another layer already uses its id. The map's next
+    /// ids are moved past the new layer and its objects.
+    pub fn add_layer(&mut self, mut layer: Layer) {
+        let max_id = max_object_id(slice::from_ref(&layer)).unwrap_or(0);
+        self.next_object_id = self.next_object_id.max(max_id.saturating_add(1));
+        if layer.id == 0 || self.get_layer_by_id(layer.id).is_some() {
+            layer.id = self.next_layer_id;
+        }
+        self.next_layer_id = self.next_layer_id.max(layer.id.saturating_add(1));
+        self.layers.push(layer);
+    }
+
+    /// Adds an object to an object layer, giving it the map's next object
+    /// id. Returns the id of the object.
+    pub fn add_object(
+        &mut self,
+        layer_index: usize,
+        mut object: Object,
+    ) -> Result<u32, TiledError> {
+        let group = self
+            .layers
+            .get_mut(layer_index)
+            .and_then(|layer| layer.as_object_layer_mut())
+            .ok_or_else(|| not_an_object_layer(layer_index))?;
+        let id = self.next_object_id;
+        object.id = id;
+        group.objects.push(object);
+        self.next_object_id = id.saturating_add(1);
+        Ok(id)
+    }
+
+    /// Applies a structural edit to every tile layer and object layer,
+    /// including those within groups, once `check_structural_edit` has made
+    /// sure it succeeds on all of them.
+    fn edit_layers(
+        &mut self,
+        mut edit_tiles: impl FnMut(&mut TileLayer) -> Result<(), TiledError>,
+        mut edit_objects: impl FnMut(&mut ObjectGroup),
+    ) -> Result<(), TiledError> {
+        for layer in self.leaf_layers_mut() {
+            match layer.layer_type {
+                LayerType::TileLayer(ref mut tiles) => edit_tiles(tiles)?,
+                LayerType::ObjectLayer(ref mut group) => edit_objects(group),
+                LayerType::ImageLayer(_) | LayerType::Group(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Inserts `count` empty rows before row `at` in every tile layer. Objects
+    /// below the inserted rows are moved down along with the tiles.
+    pub fn insert_rows(&mut self, at: u32, count: u32) -> Result<(), TiledError> {
+        let (start, end) = self.check_structural_edit(at, count, "row", true)?;
+        let offset = end - start;
+        self.edit_layers(
+            |tiles| tiles.insert_rows(at, count),
+            |group| {
+                for object in group.objects.iter_mut().filter(|o| o.y >= start) {
+                    translate_object(object, 0.0, offset);
+                }
+            },
+        )?;
+        self.height += count;
+        Ok(())
+    }
+
+    /// Removes `count` rows starting at row `at` from every tile layer.
+    /// Objects positioned within the removed rows are removed as well, and
+    /// the ones below them are moved up.
+    pub fn delete_rows(&mut self, at: u32, count: u32) -> Result<(), TiledError> {
+        let (start, end) = self.check_structural_edit(at, count, "row", false)?;
+        self.edit_layers(
+            |tiles| tiles.delete_rows(at, count),
+            |group| {
+                group.objects.retain(|o| o.y < start || o.y >= end);
+                for object in group.objects.iter_mut().filter(|o| o.y >= end) {
+                    translate_object(object, 0.0, start - end);
+                }
+            },
+        )?;
+        self.height -= count;
+        Ok(())
+    }
+
+    /// Inserts `count` empty columns before column `at` in every tile layer.
+    /// Objects to the right of the inserted columns are moved along with the
+    /// tiles.
+    pub fn insert_cols(&mut self, at: u32, count: u32) -> Result<(), TiledError> {
+        let (start, end) = self.check_structural_edit(at, count, "column", true)?;
+        let offset = end - start;
+        self.edit_layers(
+            |tiles| tiles.insert_cols(at, count),
+            |group| {
+                for object in group.objects.iter_mut().filter(|o| o.x >= start) {
+                    translate_object(object, offset, 0.0);
+                }
+            },
+        )?;
+        self.width += count;
+        Ok(())
+    }
+
+    /// Removes `count` columns starting at column `at` from every tile layer.
+    /// Objects positioned within the removed columns are removed as well, and
+    /// the ones to the right of them are moved left.
+    pub fn delete_cols(&mut self, at: u32, count: u32) -> Result<(), TiledError> {
+        let (start, end) = self.check_structural_edit(at, count, "column", false)?;
+        self.edit_layers(
+            |tiles| tiles.delete_cols(at, count),
+            |group| {
+                group.objects.retain(|o| o.x < start || o.x >= end);
+                for object in group.objects.iter_mut().filter(|o| o.x >= end) {
+                    translate_object(object, start - end, 0.0);
+                }
+            },
+        )?;
+        self.width -= count;
+        Ok(())
+    }
+}
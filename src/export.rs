@@ -0,0 +1,94 @@
+//! Named profiles for producing runtime maps from authored ones.
+
+use std::io::Write;
+
+use crate::{Compression, DataEncoding, Layer, Map, PropertyValue, TiledError, WriteOptions};
+
+/// A named set of transforms and output settings applied when exporting a
+/// map. Tilesets are always embedded in the exported map.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ExportProfile {
+    pub name: String,
+    /// Removes layers only meant for the editor, those with an
+    /// `editor_only` property set to true, along with any layers within
+    /// them. Layers are also removed from the groups they are in.
+    pub strip_editor_layers: bool,
+    /// Removes hidden layers the same way. Games which show layers at
+    /// runtime need them, so none of the built-in profiles do this.
+    pub strip_hidden_layers: bool,
+    pub write_options: WriteOptions,
+}
+
+/// Removes the layers for which `strip` returns true, including those
+/// within groups.
+fn strip_layers(layers: &mut Vec<Layer>, strip: &dyn Fn(&Layer) -> bool) {
+    layers.retain(|layer| !strip(layer));
+    for layer in layers.iter_mut() {
+        if let Some(children) = layer.as_group_layer_mut() {
+            strip_layers(children, strip);
+        }
+    }
+}
+
+impl ExportProfile {
+    /// Strips editor-only layers and writes compact, compressed layer data.
+    pub fn release() -> ExportProfile {
+        #[cfg(feature = "zstd")]
+        let compression = Compression::Zstd;
+        #[cfg(not(feature = "zstd"))]
+        let compression = Compression::Zlib;
+        ExportProfile {
+            name: "release".to_string(),
+            strip_editor_layers: true,
+            strip_hidden_layers: false,
+            write_options: WriteOptions {
+                data_encoding: DataEncoding::Base64(Some(compression)),
+                ..WriteOptions::default()
+            },
+        }
+    }
+
+    /// Keeps every layer and writes readable CSV layer data.
+    pub fn debug() -> ExportProfile {
+        ExportProfile {
+            name: "debug".to_string(),
+            strip_editor_layers: false,
+            strip_hidden_layers: false,
+            write_options: WriteOptions {
+                data_encoding: DataEncoding::Csv,
+                ..WriteOptions::default()
+            },
+        }
+    }
+
+    /// Returns one of the built-in profiles by name.
+    pub fn named(name: &str) -> Option<ExportProfile> {
+        match name {
+            "release" => Some(ExportProfile::release()),
+            "debug" => Some(ExportProfile::debug()),
+            _ => None,
+        }
+    }
+
+    /// Returns a copy of the map with the profile's transforms applied.
+    pub fn apply(&self, map: &Map) -> Map {
+        let mut map = map.clone();
+        let strip = |layer: &Layer| {
+            let editor_only = matches!(
+                layer.properties.get("editor_only"),
+                Some(PropertyValue::BoolValue(true))
+            );
+            (self.strip_editor_layers && editor_only)
+                || (self.strip_hidden_layers && !layer.visible)
+        };
+        strip_layers(&mut map.layers, &strip);
+        map
+    }
+
+    /// Applies the profile's transforms to the map and writes the result out
+    /// as a TMX document.
+    pub fn export<W: Write>(&self, map: &Map, writer: W) -> Result<(), TiledError> {
+        self.apply(map)
+            .write_with_options(writer, &self.write_options)
+    }
+}
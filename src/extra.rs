@@ -0,0 +1,144 @@
+//! Attributes and elements the parser doesn't know, kept so that writing a
+//! map back out doesn't lose them.
+
+use std::io::Read;
+
+use xml::attribute::OwnedAttribute;
+use xml::name::OwnedName;
+use xml::reader::{EventReader, ParserConfig, XmlEvent};
+
+use crate::TiledError;
+
+/// The attributes and child elements of an element which weren't
+/// recognized, like those added by newer versions of Tiled or by other
+/// tools. They're written back out after the known ones, or where they were
+/// with `WriteOptions::preserve_layout`.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Extra {
+    /// The names and values of the attributes, in the order they appeared.
+    pub attributes: Vec<(String, String)>,
+    pub elements: Vec<XmlElement>,
+    /// Boxed, as most elements have nothing else in `Extra`.
+    pub layout: Box<Layout>,
+}
+
+/// How the attributes and children of an element were arranged in the
+/// parsed file, so that rewriting it changes as little as possible.
+///
+/// Layouts always compare equal, as they don't change what a map holds.
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    /// The names of all of the element's attributes, known or not, in the
+    /// order they appeared.
+    pub attributes: Vec<String>,
+    /// The number of child elements before each of `Extra::elements`.
+    pub element_positions: Vec<usize>,
+    /// The comments among the children, along with the number of child
+    /// elements before each.
+    pub comments: Vec<(usize, String)>,
+}
+
+impl PartialEq for Layout {
+    fn eq(&self, _: &Layout) -> bool {
+        true
+    }
+}
+
+impl Extra {
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty() && self.elements.is_empty()
+    }
+
+    /// Returns the value of the attribute with the given name.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// An XML element along with everything within it.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct XmlElement {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<XmlNode>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum XmlNode {
+    Element(XmlElement),
+    Text(String),
+    Comment(String),
+}
+
+/// Creates a parser for TMX and TSX documents, which keeps comments for
+/// `Layout`.
+pub(crate) fn xml_reader<R: Read>(reader: R) -> EventReader<R> {
+    EventReader::new_with_config(reader, ParserConfig::new().ignore_comments(false))
+}
+
+/// Returns the name of an element or attribute as written, with its
+/// namespace prefix if it has one.
+pub(crate) fn qualified_name(name: &OwnedName) -> String {
+    match name.prefix {
+        Some(ref prefix) => format!("{}:{}", prefix, name.local_name),
+        None => name.local_name.clone(),
+    }
+}
+
+impl XmlElement {
+    /// Reads an element whose start tag was just read, up to and including
+    /// its end tag.
+    pub(crate) fn parse<R: Read>(
+        parser: &mut EventReader<R>,
+        name: &OwnedName,
+        attributes: &[OwnedAttribute],
+    ) -> Result<XmlElement, TiledError> {
+        // Elements are nested with a stack rather than recursion, so that
+        // deeply nested input can't overflow the call stack.
+        let mut open = vec![XmlElement::new(name, attributes)];
+        loop {
+            match parser.next().map_err(TiledError::XmlDecodingError)? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => open.push(XmlElement::new(&name, &attributes)),
+                XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                    if let Some(element) = open.last_mut() {
+                        element.children.push(XmlNode::Text(text));
+                    }
+                }
+                XmlEvent::Comment(text) => {
+                    if let Some(element) = open.last_mut() {
+                        element.children.push(XmlNode::Comment(text));
+                    }
+                }
+                XmlEvent::EndElement { .. } => {
+                    let element = open.pop().unwrap_or_default();
+                    match open.last_mut() {
+                        Some(parent) => parent.children.push(XmlNode::Element(element)),
+                        None => return Ok(element),
+                    }
+                }
+                XmlEvent::EndDocument => {
+                    return Err(TiledError::PrematureEnd(
+                        "Document ended within an unknown element".to_string(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn new(name: &OwnedName, attributes: &[OwnedAttribute]) -> XmlElement {
+        XmlElement {
+            name: qualified_name(name),
+            attributes: attributes
+                .iter()
+                .map(|a| (qualified_name(&a.name), a.value.clone()))
+                .collect(),
+            children: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Field of view computation using symmetric shadowcasting.
+
+use std::collections::HashSet;
+
+use crate::{LayerTile, TileLayer};
+
+/// Maps a (depth, column) pair within a quadrant to an offset from the
+/// origin.
+type Transform = fn(i64, i64) -> (i64, i64);
+
+/// A slope from the origin, kept as a fraction so rows are split exactly.
+#[derive(Debug, Copy, Clone)]
+struct Slope {
+    num: i64,
+    den: i64,
+}
+
+/// One row of tiles in a quadrant, `depth` tiles away from the origin.
+#[derive(Debug, Copy, Clone)]
+struct Row {
+    depth: i64,
+    start: Slope,
+    end: Slope,
+}
+
+impl Row {
+    /// The columns of the row between its start and end slopes.
+    fn columns(&self) -> std::ops::RangeInclusive<i64> {
+        // Round ties up at the start and down at the end.
+        let min = (2 * self.depth * self.start.num + self.start.den).div_euclid(2 * self.start.den);
+        let max = -(-(2 * self.depth * self.end.num - self.end.den)).div_euclid(2 * self.end.den);
+        min..=max
+    }
+
+    /// Whether the center of a tile lies within the row's slopes, which is
+    /// what keeps the field of view symmetric.
+    fn is_symmetric(&self, col: i64) -> bool {
+        col * self.start.den >= self.depth * self.start.num
+            && col * self.end.den <= self.depth * self.end.num
+    }
+}
+
+impl TileLayer {
+    /// Returns the tiles visible from `origin` using symmetric shadowcasting,
+    /// where `opaque` tells which tiles block the view. Opaque tiles at the
+    /// edge of the visible area are included, like the walls of a room.
+    /// Visibility is symmetric: if one tile can see another, the other can
+    /// see it too. Positions outside of the layer's data block the view, and
+    /// tiles further away than `radius` are never visible.
+    pub fn field_of_view(
+        &self,
+        origin: (i32, i32),
+        radius: Option<u32>,
+        mut opaque: impl FnMut(Option<LayerTile>) -> bool,
+    ) -> HashSet<(i32, i32)> {
+        let mut visible = HashSet::new();
+        if self.cell(origin.0, origin.1).is_none() {
+            return visible;
+        }
+        visible.insert(origin);
+        let in_radius =
+            |dx: i64, dy: i64| radius.map_or(true, |r| dx * dx + dy * dy <= r as i64 * r as i64);
+
+        let quadrants: [Transform; 4] = [
+            |depth, col| (col, -depth),
+            |depth, col| (col, depth),
+            |depth, col| (depth, col),
+            |depth, col| (-depth, col),
+        ];
+        for transform in quadrants.iter() {
+            let mut is_wall = |depth: i64, col: i64| {
+                let (dx, dy) = transform(depth, col);
+                let (x, y) = (origin.0 + dx as i32, origin.1 + dy as i32);
+                self.cell(x, y).map_or(true, &mut opaque)
+            };
+            let mut rows = vec![Row {
+                depth: 1,
+                start: Slope { num: -1, den: 1 },
+                end: Slope { num: 1, den: 1 },
+            }];
+            while let Some(mut row) = rows.pop() {
+                if radius.is_some_and(|r| row.depth > r as i64) {
+                    continue;
+                }
+                let mut previous_wall = None;
+                for col in row.columns() {
+                    let wall = is_wall(row.depth, col);
+                    if wall || row.is_symmetric(col) {
+                        let (dx, dy) = transform(row.depth, col);
+                        let (x, y) = (origin.0 + dx as i32, origin.1 + dy as i32);
+                        if in_radius(dx, dy) && self.cell(x, y).is_some() {
+                            visible.insert((x, y));
+                        }
+                    }
+                    let slope = Slope {
+                        num: 2 * col - 1,
+                        den: 2 * row.depth,
+                    };
+                    if previous_wall == Some(true) && !wall {
+                        row.start = slope;
+                    }
+                    if previous_wall == Some(false) && wall {
+                        rows.push(Row {
+                            depth: row.depth + 1,
+                            start: row.start,
+                            end: slope,
+                        });
+                    }
+                    previous_wall = Some(wall);
+                }
+                if previous_wall == Some(false) {
+                    rows.push(Row {
+                        depth: row.depth + 1,
+                        ..row
+                    });
+                }
+            }
+        }
+        visible
+    }
+}
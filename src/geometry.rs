@@ -0,0 +1,688 @@
+//! Points, sizes and rectangles in tile and pixel coordinates.
+
+use std::convert::TryInto;
+
+use crate::{Chunk, LayerData, Map, Object, ObjectShape, TileLayer};
+
+/// A position in tile coordinates.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
+pub struct TilePoint {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl TilePoint {
+    pub fn new(x: i32, y: i32) -> TilePoint {
+        TilePoint { x, y }
+    }
+}
+
+impl From<(i32, i32)> for TilePoint {
+    fn from((x, y): (i32, i32)) -> TilePoint {
+        TilePoint::new(x, y)
+    }
+}
+
+impl From<TilePoint> for (i32, i32) {
+    fn from(point: TilePoint) -> (i32, i32) {
+        (point.x, point.y)
+    }
+}
+
+/// A size in tiles, or the size of a tile in pixels.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
+pub struct TileSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TileSize {
+    pub fn new(width: u32, height: u32) -> TileSize {
+        TileSize { width, height }
+    }
+}
+
+impl From<(u32, u32)> for TileSize {
+    fn from((width, height): (u32, u32)) -> TileSize {
+        TileSize::new(width, height)
+    }
+}
+
+/// A rectangle of tiles, in tile coordinates.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
+pub struct TileRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TileRect {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> TileRect {
+        TileRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn from_origin_size(origin: TilePoint, size: TileSize) -> TileRect {
+        TileRect::new(origin.x, origin.y, size.width, size.height)
+    }
+
+    pub fn origin(&self) -> TilePoint {
+        TilePoint::new(self.x, self.y)
+    }
+
+    pub fn size(&self) -> TileSize {
+        TileSize::new(self.width, self.height)
+    }
+
+    /// The column just past the rectangle, or `i32::MAX` for rectangles
+    /// reaching past the largest tile coordinates.
+    pub fn right(&self) -> i32 {
+        (self.x as i64 + self.width as i64).min(i32::MAX as i64) as i32
+    }
+
+    /// The row just past the rectangle, or `i32::MAX` for rectangles reaching
+    /// past the largest tile coordinates.
+    pub fn bottom(&self) -> i32 {
+        (self.y as i64 + self.height as i64).min(i32::MAX as i64) as i32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && x < self.right() && y < self.bottom()
+    }
+
+    /// Returns the overlapping part of two rectangles, if there is one.
+    pub fn intersection(&self, other: &TileRect) -> Option<TileRect> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        if right <= x || bottom <= y {
+            return None;
+        }
+        Some(TileRect::new(x, y, span(x, right), span(y, bottom)))
+    }
+
+    /// Returns the smallest rectangle containing both rectangles.
+    pub fn union(&self, other: &TileRect) -> TileRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = self.right().max(other.right());
+        let bottom = self.bottom().max(other.bottom());
+        TileRect::new(x, y, span(x, right), span(y, bottom))
+    }
+
+    /// Returns the index of a position among the positions of the rectangle
+    /// taken row by row, or `None` if it lies outside of the rectangle.
+    pub(crate) fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if !self.contains(x, y) {
+            return None;
+        }
+        let (row, column) = (y as i64 - self.y as i64, x as i64 - self.x as i64);
+        Some((row * self.width as i64 + column) as usize)
+    }
+
+    /// Iterates over the positions of the tiles in the rectangle, row by
+    /// row.
+    pub fn positions(&self) -> impl Iterator<Item = TilePoint> {
+        let (x, right) = (self.x, self.right());
+        (self.y..self.bottom()).flat_map(move |y| (x..right).map(move |x| TilePoint::new(x, y)))
+    }
+
+    /// Returns the area covered by the rectangle in pixels, given the size
+    /// of the tiles of an orthogonal map.
+    pub fn to_map_rect(&self, tile_size: TileSize) -> MapRect {
+        let (width, height) = (tile_size.width as f32, tile_size.height as f32);
+        MapRect::new(
+            self.x as f32 * width,
+            self.y as f32 * height,
+            self.width as f32 * width,
+            self.height as f32 * height,
+        )
+    }
+}
+
+/// The number of tiles from `start` up to `end`, which always fits a `u32`.
+fn span(start: i32, end: i32) -> u32 {
+    (end as i64 - start as i64) as u32
+}
+
+/// Returns the tile coordinates just past the right and bottom of an area,
+/// or `None` if they don't fit in an `i32`.
+pub(crate) fn area_end(area: &TileRect) -> Option<(i32, i32)> {
+    let right = area.x as i64 + area.width as i64;
+    let bottom = area.y as i64 + area.height as i64;
+    Some((right.try_into().ok()?, bottom.try_into().ok()?))
+}
+
+/// A position in pixels.
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub struct MapPoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl MapPoint {
+    pub fn new(x: f32, y: f32) -> MapPoint {
+        MapPoint { x, y }
+    }
+}
+
+impl From<(f32, f32)> for MapPoint {
+    fn from((x, y): (f32, f32)) -> MapPoint {
+        MapPoint::new(x, y)
+    }
+}
+
+impl From<MapPoint> for (f32, f32) {
+    fn from(point: MapPoint) -> (f32, f32) {
+        (point.x, point.y)
+    }
+}
+
+/// A size in pixels.
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub struct MapSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl MapSize {
+    pub fn new(width: f32, height: f32) -> MapSize {
+        MapSize { width, height }
+    }
+}
+
+impl From<(f32, f32)> for MapSize {
+    fn from((width, height): (f32, f32)) -> MapSize {
+        MapSize::new(width, height)
+    }
+}
+
+/// A rectangle in pixels, like the view of a camera.
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub struct MapRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl MapRect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> MapRect {
+        MapRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn from_origin_size(origin: MapPoint, size: MapSize) -> MapRect {
+        MapRect::new(origin.x, origin.y, size.width, size.height)
+    }
+
+    pub fn origin(&self) -> MapPoint {
+        MapPoint::new(self.x, self.y)
+    }
+
+    pub fn size(&self) -> MapSize {
+        MapSize::new(self.width, self.height)
+    }
+
+    pub fn right(&self) -> f32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.y + self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0.0 || self.height <= 0.0
+    }
+
+    pub fn contains(&self, point: MapPoint) -> bool {
+        point.x >= self.x && point.y >= self.y && point.x < self.right() && point.y < self.bottom()
+    }
+
+    /// Returns the overlapping part of two rectangles, if there is one.
+    pub fn intersection(&self, other: &MapRect) -> Option<MapRect> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        if right <= x || bottom <= y {
+            return None;
+        }
+        Some(MapRect::new(x, y, right - x, bottom - y))
+    }
+
+    /// Returns the smallest rectangle containing both rectangles.
+    pub fn union(&self, other: &MapRect) -> MapRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = self.right().max(other.right());
+        let bottom = self.bottom().max(other.bottom());
+        MapRect::new(x, y, right - x, bottom - y)
+    }
+
+    /// Returns the tiles the rectangle touches, given the size of the tiles
+    /// of an orthogonal map.
+    pub fn to_tile_rect(&self, tile_size: TileSize) -> TileRect {
+        if self.is_empty() {
+            return TileRect::new(0, 0, 0, 0);
+        }
+        let (width, height) = (
+            tile_size.width.max(1) as f32,
+            tile_size.height.max(1) as f32,
+        );
+        let left = (self.x / width).floor() as i32;
+        let top = (self.y / height).floor() as i32;
+        let right = (self.right() / width).ceil() as i32;
+        let bottom = (self.bottom() / height).ceil() as i32;
+        TileRect::new(left, top, (right - left) as u32, (bottom - top) as u32)
+    }
+}
+
+impl Object {
+    /// The position of the object in pixels, which is its top left corner,
+    /// or its bottom left corner for tile objects.
+    pub fn position(&self) -> MapPoint {
+        MapPoint::new(self.x, self.y)
+    }
+
+    /// The points of a polygon or polyline object in pixels within the
+    /// layer, with the object's rotation applied. Other shapes have no
+    /// points.
+    pub fn points(&self) -> Vec<MapPoint> {
+        let points = match self.shape {
+            ObjectShape::Polygon { ref points } | ObjectShape::Polyline { ref points } => points,
+            _ => return Vec::new(),
+        };
+        // Objects rotate clockwise around their position.
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        points
+            .iter()
+            .map(|&(x, y)| MapPoint::new(self.x + x * cos - y * sin, self.y + x * sin + y * cos))
+            .collect()
+    }
+
+    /// Whether a position in pixels within the layer lies on the object,
+    /// taking its shape and rotation into account, for picking objects with
+    /// the mouse. Polylines and points have no area, so positions up to
+    /// `tolerance` pixels away from them count as well.
+    pub fn contains_point(&self, x: f32, y: f32, tolerance: f32) -> bool {
+        // Undo the rotation, which is clockwise around the object's position.
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let (dx, dy) = (x - self.x, y - self.y);
+        let (x, y) = (dx * cos + dy * sin, dy * cos - dx * sin);
+        let in_rect = |width: f32, height: f32| {
+            // Tile objects are positioned by their bottom left corner.
+            let top = if self.gid.is_empty() { 0.0 } else { -height };
+            x >= 0.0 && x <= width && y >= top && y <= top + height
+        };
+        match self.shape {
+            ObjectShape::Rect { width, height } => in_rect(width, height),
+            ObjectShape::Text(_) => in_rect(self.width, self.height),
+            ObjectShape::Ellipse { width, height } => {
+                if width <= 0.0 || height <= 0.0 {
+                    return false;
+                }
+                let (ex, ey) = (x * 2.0 / width - 1.0, y * 2.0 / height - 1.0);
+                ex * ex + ey * ey <= 1.0
+            }
+            ObjectShape::Polygon { ref points } => polygon_contains(points, (x, y)),
+            ObjectShape::Polyline { ref points } => points
+                .windows(2)
+                .any(|line| segment_distance(line[0], line[1], (x, y)) <= tolerance),
+            ObjectShape::Point(..) => x.hypot(y) <= tolerance,
+        }
+    }
+
+    /// Returns the smallest rectangle in pixels within the layer which holds
+    /// the object, taking its shape and rotation into account, for culling
+    /// and selection. Points give an empty rectangle at their position.
+    pub fn bounding_box(&self) -> MapRect {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let rect = |width: f32, height: f32| {
+            // Tile objects are positioned by their bottom left corner.
+            let top = if self.gid.is_empty() { 0.0 } else { -height };
+            vec![
+                (0.0, top),
+                (width, top),
+                (width, top + height),
+                (0.0, top + height),
+            ]
+        };
+        let points = match self.shape {
+            ObjectShape::Rect { width, height } => rect(width, height),
+            ObjectShape::Text(_) => rect(self.width, self.height),
+            ObjectShape::Ellipse { width, height } => {
+                // The extents of the rotated ellipse around its centre.
+                let (a, b) = (width / 2.0, height / 2.0);
+                let (cx, cy) = (self.x + a * cos - b * sin, self.y + a * sin + b * cos);
+                let half_width = (a * cos).hypot(b * sin);
+                let half_height = (a * sin).hypot(b * cos);
+                return MapRect::new(
+                    cx - half_width,
+                    cy - half_height,
+                    half_width * 2.0,
+                    half_height * 2.0,
+                );
+            }
+            ObjectShape::Polygon { ref points } | ObjectShape::Polyline { ref points } => {
+                points.clone()
+            }
+            ObjectShape::Point(..) => vec![(0.0, 0.0)],
+        };
+        let (mut left, mut top) = (f32::INFINITY, f32::INFINITY);
+        let (mut right, mut bottom) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for (x, y) in points {
+            let (x, y) = (self.x + x * cos - y * sin, self.y + x * sin + y * cos);
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x);
+            bottom = bottom.max(y);
+        }
+        if left > right {
+            return MapRect::new(self.x, self.y, 0.0, 0.0);
+        }
+        MapRect::new(left, top, right - left, bottom - top)
+    }
+}
+
+/// Whether a point lies within a polygon, by the even-odd rule.
+fn polygon_contains(polygon: &[(f32, f32)], (x, y): (f32, f32)) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+    for (i, &(xi, yi)) in polygon.iter().enumerate() {
+        let (xj, yj) = polygon[j];
+        if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// The distance from a point to the line segment from `a` to `b`.
+fn segment_distance(a: (f32, f32), b: (f32, f32), (x, y): (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+    let t = if length > 0.0 {
+        (((x - a.0) * dx + (y - a.1) * dy) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (a.0 + t * dx - x).hypot(a.1 + t * dy - y)
+}
+
+impl Chunk {
+    /// Returns the area of the layer covered by the chunk.
+    pub fn bounds(&self) -> TileRect {
+        TileRect::new(self.x, self.y, self.width, self.height)
+    }
+}
+
+impl Map {
+    /// The size of the map's tiles in pixels.
+    pub fn tile_size(&self) -> TileSize {
+        TileSize::new(self.tile_width, self.tile_height)
+    }
+
+    /// Returns the tiles of an orthogonal map within a view given in pixels,
+    /// for drawing only what a camera sees. Finite maps are clipped to their
+    /// size, so the rectangle may be empty.
+    pub fn tiles_in_view(&self, view: MapRect) -> TileRect {
+        let tiles = view.to_tile_rect(self.tile_size());
+        if self.infinite {
+            return tiles;
+        }
+        tiles
+            .intersection(&TileRect::new(0, 0, self.width, self.height))
+            .unwrap_or_default()
+    }
+}
+
+impl TileLayer {
+    /// Copies the tiles within `rect` into a new finite layer of its size.
+    /// Positions outside of the layer's data are left empty.
+    pub fn crop(&self, rect: TileRect) -> TileLayer {
+        let rows = (rect.y..rect.bottom())
+            .map(|y| {
+                (rect.x..rect.right())
+                    .map(|x| self.get_tile(x, y))
+                    .collect()
+            })
+            .collect();
+        TileLayer {
+            width: rect.width,
+            height: rect.height,
+            tiles: LayerData::Finite(rows),
+        }
+    }
+}
+
+#[cfg(feature = "euclid")]
+mod euclid_conversions {
+    use super::{MapPoint, MapRect, MapSize, TilePoint, TileRect, TileSize};
+
+    impl<U> From<TilePoint> for euclid::Point2D<i32, U> {
+        fn from(point: TilePoint) -> Self {
+            euclid::Point2D::new(point.x, point.y)
+        }
+    }
+
+    impl<U> From<euclid::Point2D<i32, U>> for TilePoint {
+        fn from(point: euclid::Point2D<i32, U>) -> Self {
+            TilePoint::new(point.x, point.y)
+        }
+    }
+
+    impl<U> From<TileSize> for euclid::Size2D<u32, U> {
+        fn from(size: TileSize) -> Self {
+            euclid::Size2D::new(size.width, size.height)
+        }
+    }
+
+    impl<U> From<euclid::Size2D<u32, U>> for TileSize {
+        fn from(size: euclid::Size2D<u32, U>) -> Self {
+            TileSize::new(size.width, size.height)
+        }
+    }
+
+    impl<U> From<TileRect> for euclid::Rect<i32, U> {
+        fn from(rect: TileRect) -> Self {
+            euclid::Rect::new(
+                euclid::Point2D::new(rect.x, rect.y),
+                euclid::Size2D::new(rect.width as i32, rect.height as i32),
+            )
+        }
+    }
+
+    impl<U> From<MapPoint> for euclid::Point2D<f32, U> {
+        fn from(point: MapPoint) -> Self {
+            euclid::Point2D::new(point.x, point.y)
+        }
+    }
+
+    impl<U> From<euclid::Point2D<f32, U>> for MapPoint {
+        fn from(point: euclid::Point2D<f32, U>) -> Self {
+            MapPoint::new(point.x, point.y)
+        }
+    }
+
+    impl<U> From<MapSize> for euclid::Size2D<f32, U> {
+        fn from(size: MapSize) -> Self {
+            euclid::Size2D::new(size.width, size.height)
+        }
+    }
+
+    impl<U> From<euclid::Size2D<f32, U>> for MapSize {
+        fn from(size: euclid::Size2D<f32, U>) -> Self {
+            MapSize::new(size.width, size.height)
+        }
+    }
+
+    impl<U> From<MapRect> for euclid::Rect<f32, U> {
+        fn from(rect: MapRect) -> Self {
+            euclid::Rect::new(
+                euclid::Point2D::new(rect.x, rect.y),
+                euclid::Size2D::new(rect.width, rect.height),
+            )
+        }
+    }
+
+    impl<U> From<euclid::Rect<f32, U>> for MapRect {
+        fn from(rect: euclid::Rect<f32, U>) -> Self {
+            MapRect::new(
+                rect.origin.x,
+                rect.origin.y,
+                rect.size.width,
+                rect.size.height,
+            )
+        }
+    }
+}
+
+#[cfg(feature = "glam")]
+mod glam_conversions {
+    use super::{MapPoint, MapSize, TilePoint, TileSize};
+
+    impl From<TilePoint> for glam::IVec2 {
+        fn from(point: TilePoint) -> Self {
+            glam::IVec2::new(point.x, point.y)
+        }
+    }
+
+    impl From<glam::IVec2> for TilePoint {
+        fn from(point: glam::IVec2) -> Self {
+            TilePoint::new(point.x, point.y)
+        }
+    }
+
+    impl From<TileSize> for glam::UVec2 {
+        fn from(size: TileSize) -> Self {
+            glam::UVec2::new(size.width, size.height)
+        }
+    }
+
+    impl From<glam::UVec2> for TileSize {
+        fn from(size: glam::UVec2) -> Self {
+            TileSize::new(size.x, size.y)
+        }
+    }
+
+    impl From<MapPoint> for glam::Vec2 {
+        fn from(point: MapPoint) -> Self {
+            glam::Vec2::new(point.x, point.y)
+        }
+    }
+
+    impl From<glam::Vec2> for MapPoint {
+        fn from(point: glam::Vec2) -> Self {
+            MapPoint::new(point.x, point.y)
+        }
+    }
+
+    impl From<MapSize> for glam::Vec2 {
+        fn from(size: MapSize) -> Self {
+            glam::Vec2::new(size.width, size.height)
+        }
+    }
+
+    impl From<glam::Vec2> for MapSize {
+        fn from(size: glam::Vec2) -> Self {
+            MapSize::new(size.x, size.y)
+        }
+    }
+}
+
+#[cfg(feature = "mint")]
+mod mint_conversions {
+    use super::{MapPoint, MapSize, TilePoint, TileSize};
+
+    impl From<TilePoint> for mint::Point2<i32> {
+        fn from(point: TilePoint) -> Self {
+            mint::Point2 {
+                x: point.x,
+                y: point.y,
+            }
+        }
+    }
+
+    impl From<mint::Point2<i32>> for TilePoint {
+        fn from(point: mint::Point2<i32>) -> Self {
+            TilePoint::new(point.x, point.y)
+        }
+    }
+
+    impl From<TileSize> for mint::Vector2<u32> {
+        fn from(size: TileSize) -> Self {
+            mint::Vector2 {
+                x: size.width,
+                y: size.height,
+            }
+        }
+    }
+
+    impl From<mint::Vector2<u32>> for TileSize {
+        fn from(size: mint::Vector2<u32>) -> Self {
+            TileSize::new(size.x, size.y)
+        }
+    }
+
+    impl From<MapPoint> for mint::Point2<f32> {
+        fn from(point: MapPoint) -> Self {
+            mint::Point2 {
+                x: point.x,
+                y: point.y,
+            }
+        }
+    }
+
+    impl From<mint::Point2<f32>> for MapPoint {
+        fn from(point: mint::Point2<f32>) -> Self {
+            MapPoint::new(point.x, point.y)
+        }
+    }
+
+    impl From<MapPoint> for mint::Vector2<f32> {
+        fn from(point: MapPoint) -> Self {
+            mint::Vector2 {
+                x: point.x,
+                y: point.y,
+            }
+        }
+    }
+
+    impl From<mint::Vector2<f32>> for MapPoint {
+        fn from(point: mint::Vector2<f32>) -> Self {
+            MapPoint::new(point.x, point.y)
+        }
+    }
+
+    impl From<MapSize> for mint::Vector2<f32> {
+        fn from(size: MapSize) -> Self {
+            mint::Vector2 {
+                x: size.width,
+                y: size.height,
+            }
+        }
+    }
+
+    impl From<mint::Vector2<f32>> for MapSize {
+        fn from(size: mint::Vector2<f32>) -> Self {
+            MapSize::new(size.x, size.y)
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Grids of values computed from the tiles of a layer, such as which tiles
+//! block movement.
+
+use crate::{LayerTile, Map, PropertyValue, Tile, TileLayer, TileRect};
+
+/// A value for each tile within an area of a layer, for pathfinding or
+/// collision checks without a renderer.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TileGrid<T> {
+    pub area: TileRect,
+    /// The values of the tiles in `area`, row by row.
+    pub cells: Vec<T>,
+}
+
+impl<T> TileGrid<T> {
+    /// Returns the value of the tile at the given tile coordinates, or
+    /// `None` if it lies outside of the grid.
+    pub fn get(&self, x: i32, y: i32) -> Option<&T> {
+        self.cells.get(self.area.index(x, y)?)
+    }
+}
+
+impl Map {
+    /// Builds a grid covering the tiles of a layer, with `value` computing
+    /// the value of each non-empty tile from the tile and its entry in its
+    /// tileset, if it has one. Empty tiles take the default value.
+    pub fn build_grid<T: Default>(
+        &self,
+        layer: &TileLayer,
+        mut value: impl FnMut(LayerTile, Option<&Tile>) -> T,
+    ) -> TileGrid<T> {
+        let area = layer.bounds().unwrap_or_else(|| TileRect::new(0, 0, 0, 0));
+        let mut cells = Vec::with_capacity(area.width as usize * area.height as usize);
+        let tilesets = self.tileset_index();
+        for y in area.y..area.bottom() {
+            for x in area.x..area.right() {
+                cells.push(match layer.get_tile(x, y) {
+                    Some(tile) => value(tile, tilesets.get_tile_by_gid(tile)),
+                    None => T::default(),
+                });
+            }
+        }
+        TileGrid { area, cells }
+    }
+
+    /// Builds a grid marking the tiles of a layer whose tile has the given
+    /// bool property set to true, like `solid`.
+    pub fn solid_grid(&self, layer: &TileLayer, property: &str) -> TileGrid<bool> {
+        self.build_grid(layer, |_, tile| {
+            tile.and_then(|tile| tile.properties.get(property))
+                == Some(&PropertyValue::BoolValue(true))
+        })
+    }
+}
@@ -0,0 +1,115 @@
+//! Walking the layers of a map through its group layers.
+
+use crate::{Layer, LayerType, Map};
+
+/// A layer which isn't a group, along with its visibility, opacity and
+/// offset combined with those of the groups it is in, as Tiled draws it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct FlatLayer<'a> {
+    pub layer: &'a Layer,
+    pub visible: bool,
+    pub opacity: f32,
+    pub offset_x: f32,
+    pub offset_y: f32,
+}
+
+fn flatten<'a>(
+    layers: &'a [Layer],
+    (visible, opacity, offset_x, offset_y): (bool, f32, f32, f32),
+    out: &mut Vec<FlatLayer<'a>>,
+) {
+    for layer in layers.iter() {
+        let flat = FlatLayer {
+            layer,
+            visible: visible && layer.visible,
+            opacity: opacity * layer.opacity,
+            offset_x: offset_x + layer.offset_x,
+            offset_y: offset_y + layer.offset_y,
+        };
+        match layer.layer_type {
+            LayerType::Group(ref children) => flatten(
+                children,
+                (flat.visible, flat.opacity, flat.offset_x, flat.offset_y),
+                out,
+            ),
+            _ => out.push(flat),
+        }
+    }
+}
+
+fn leaf_layers_mut<'a>(layers: &'a mut [Layer], out: &mut Vec<&'a mut Layer>) {
+    for layer in layers.iter_mut() {
+        if layer.as_group_layer().is_none() {
+            out.push(layer);
+        } else if let LayerType::Group(ref mut children) = layer.layer_type {
+            leaf_layers_mut(children, out);
+        }
+    }
+}
+
+/// Returns the layer with the given id among `layers` and the layers within
+/// their groups.
+pub(crate) fn find_layer(layers: &[Layer], id: u32) -> Option<&Layer> {
+    layers.iter().find_map(|layer| {
+        if layer.id == id {
+            return Some(layer);
+        }
+        find_layer(layer.as_group_layer()?, id)
+    })
+}
+
+/// Same as `find_layer`, for editing the layer.
+pub(crate) fn find_layer_mut(layers: &mut [Layer], id: u32) -> Option<&mut Layer> {
+    layers.iter_mut().find_map(|layer| {
+        if layer.id == id {
+            return Some(layer);
+        }
+        find_layer_mut(layer.as_group_layer_mut()?, id)
+    })
+}
+
+/// Returns the highest id of `layers` and the layers within their groups, or
+/// 0 if none of them has an id.
+pub(crate) fn max_layer_id(layers: &[Layer]) -> u32 {
+    layers
+        .iter()
+        .map(|layer| match layer.layer_type {
+            LayerType::Group(ref children) => layer.id.max(max_layer_id(children)),
+            _ => layer.id,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Returns the highest id of the objects in `layers`, including those in
+/// groups.
+pub(crate) fn max_object_id(layers: &[Layer]) -> Option<u32> {
+    layers
+        .iter()
+        .filter_map(|layer| match layer.layer_type {
+            LayerType::ObjectLayer(ref group) => group.objects.iter().map(|o| o.id).max(),
+            LayerType::Group(ref children) => max_object_id(children),
+            _ => None,
+        })
+        .max()
+}
+
+impl Map {
+    /// Returns the layers of the map which aren't groups, from the bottom
+    /// up, with the layers within groups taking the place of their group.
+    /// Each one comes with the visibility, opacity and offset it inherits
+    /// from its groups.
+    pub fn flat_layers(&self) -> Vec<FlatLayer<'_>> {
+        let mut out = Vec::new();
+        flatten(&self.layers, (true, 1.0, 0.0, 0.0), &mut out);
+        out
+    }
+
+    /// Returns the layers of the map which aren't groups, including the
+    /// layers within groups, for editing.
+    pub(crate) fn leaf_layers_mut(&mut self) -> Vec<&mut Layer> {
+        let mut out = Vec::new();
+        leaf_layers_mut(&mut self.layers, &mut out);
+        out
+    }
+}
@@ -0,0 +1,61 @@
+//! Stable content hashes of map elements.
+
+use crate::writer::{layer_fragment, tileset_fragment};
+use crate::{Layer, Map, TiledError, Tileset, WriteOptions};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Hashes bytes with 64-bit FNV-1a, which gives the same result on every
+/// platform and between runs.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    fnv1a_extend(FNV_OFFSET_BASIS, bytes)
+}
+
+/// Continues a hash from `fnv1a` with more bytes, giving the hash of all the
+/// bytes together.
+pub(crate) fn fnv1a_extend(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+impl Layer {
+    /// Returns a hash of the layer's contents, which is stable between runs
+    /// and platforms. Two layers with the same hash have the same contents,
+    /// barring collisions.
+    pub fn content_hash(&self) -> Result<u64, TiledError> {
+        Ok(fnv1a(&layer_fragment(
+            self,
+            &WriteOptions::default(),
+            None,
+        )?))
+    }
+}
+
+impl Tileset {
+    /// Returns a hash of the tileset's contents, including its `first_gid`,
+    /// which is stable between runs and platforms.
+    pub fn content_hash(&self) -> Result<u64, TiledError> {
+        Ok(fnv1a(&tileset_fragment(self)?))
+    }
+}
+
+impl Map {
+    /// Returns a hash of the whole map's contents, which is stable between
+    /// runs and platforms.
+    pub fn content_hash(&self) -> Result<u64, TiledError> {
+        let mut out = Vec::new();
+        self.write_to(&mut out)?;
+        Ok(fnv1a(&out))
+    }
+
+    /// Derives a seed for procedural passes from the map's contents and a
+    /// salt naming the pass, like `"loot"`. The seed only changes when the
+    /// map does, and different salts give unrelated seeds for the same map.
+    pub fn seed(&self, salt: &str) -> Result<u64, TiledError> {
+        let mut bytes = self.content_hash()?.to_le_bytes().to_vec();
+        bytes.extend_from_slice(salt.as_bytes());
+        Ok(fnv1a(&bytes))
+    }
+}
@@ -0,0 +1,81 @@
+//! Conversion of height maps, such as generated noise, into tile layers.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use crate::{
+    Extra, Gid, Layer, LayerData, LayerTile, LayerType, TileLayer, TiledError, Tileset, WangSet,
+};
+
+impl Layer {
+    /// Builds a finite tile layer from a grid of heights, given row by row.
+    /// Each threshold is a minimum height along with the global tile id used
+    /// from that height upwards; heights below every threshold become empty
+    /// tiles. When a wang set is given, the layer is then run through
+    /// `TileLayer::autotile` to smooth the transitions between the bands.
+    ///
+    /// Returns an error if the rows differ in length.
+    pub fn from_heightmap(
+        name: &str,
+        heights: &[Vec<f32>],
+        thresholds: &[(f32, Gid)],
+        wang_set: Option<(&Tileset, &WangSet)>,
+    ) -> Result<Layer, TiledError> {
+        let width = heights.first().map_or(0, Vec::len);
+        if let Some(y) = heights.iter().position(|row| row.len() != width) {
+            return Err(TiledError::Other(format!(
+                "Row {} of the height map holds {} heights instead of {}",
+                y,
+                heights[y].len(),
+                width
+            )));
+        }
+        let (width, height) = match (width.try_into(), heights.len().try_into()) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => {
+                return Err(TiledError::Other(
+                    "The height map is too large for a layer".to_string(),
+                ))
+            }
+        };
+        let mut thresholds = thresholds.to_vec();
+        thresholds.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let rows = heights
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&height| {
+                        let gid = thresholds
+                            .iter()
+                            .rev()
+                            .find(|t| height >= t.0)
+                            .map_or(Gid::EMPTY, |t| t.1);
+                        LayerTile::from_bits(gid.0)
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut layer = TileLayer {
+            width,
+            height,
+            tiles: LayerData::Finite(rows),
+        };
+        if let Some((tileset, wang_set)) = wang_set {
+            layer.autotile(tileset, wang_set);
+        }
+        Ok(Layer {
+            id: 0,
+            name: name.to_string(),
+            user_type: None,
+            opacity: 1.0,
+            visible: true,
+            locked: false,
+            offset_x: 0.0,
+            offset_y: 0.0,
+            tint_colour: None,
+            properties: HashMap::new(),
+            layer_type: LayerType::TileLayer(layer),
+            extra: Extra::default(),
+        })
+    }
+}
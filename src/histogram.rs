@@ -0,0 +1,85 @@
+//! Statistics about how often tiles are used.
+
+use std::collections::BTreeMap;
+
+use crate::{Layer, LayerData, LayerType, Map};
+
+impl Layer {
+    /// Counts how often each global tile id is used by the layer, flip
+    /// flags aside. Tile layers count their tiles, object layers their tile
+    /// objects and groups the layers within them. Empty tiles aren't
+    /// counted.
+    pub fn gid_histogram(&self) -> BTreeMap<u32, usize> {
+        let mut histogram = BTreeMap::new();
+        let mut count = |gid: u32| {
+            if gid != 0 {
+                *histogram.entry(gid).or_insert(0) += 1;
+            }
+        };
+        match self.layer_type {
+            LayerType::TileLayer(ref layer) => match layer.tiles {
+                LayerData::Finite(ref rows) => {
+                    rows.iter()
+                        .flatten()
+                        .flatten()
+                        .for_each(|tile| count(tile.gid.value()));
+                }
+                LayerData::Infinite(ref chunks) => {
+                    chunks
+                        .values()
+                        .flat_map(|chunk| chunk.tiles.iter().flatten().flatten())
+                        .for_each(|tile| count(tile.gid.value()));
+                }
+            },
+            LayerType::ObjectLayer(ref group) => {
+                for object in group.objects.iter() {
+                    count(object.gid.value());
+                }
+            }
+            LayerType::ImageLayer(_) => {}
+            LayerType::Group(ref layers) => {
+                for (gid, n) in layers.iter().flat_map(Layer::gid_histogram) {
+                    *histogram.entry(gid).or_insert(0) += n;
+                }
+            }
+        }
+        histogram
+    }
+}
+
+impl Map {
+    /// Counts how often each global tile id is used across all layers of
+    /// the map, see `Layer::gid_histogram`.
+    pub fn gid_histogram(&self) -> BTreeMap<u32, usize> {
+        let mut histogram = BTreeMap::new();
+        for layer in self.layers.iter() {
+            for (gid, count) in layer.gid_histogram() {
+                *histogram.entry(gid).or_insert(0) += count;
+            }
+        }
+        histogram
+    }
+
+    /// Suggests tiles which could be merged into others or removed from the
+    /// tilesets: the tiles used at most `max_uses` times, as pairs of global
+    /// tile id and use count, least used first. Unused tiles are included
+    /// for tilesets which know their tile count.
+    pub fn rarely_used_tiles(&self, max_uses: usize) -> Vec<(u32, usize)> {
+        let histogram = self.gid_histogram();
+        let mut tiles: Vec<(u32, usize)> = self
+            .tilesets
+            .iter()
+            .filter_map(|tileset| {
+                let first_gid = tileset.first_gid.value();
+                Some(first_gid..first_gid.saturating_add(tileset.tilecount?))
+            })
+            .flatten()
+            .filter(|gid| !histogram.contains_key(gid))
+            .map(|gid| (gid, 0))
+            .chain(histogram.iter().map(|(&gid, &count)| (gid, count)))
+            .filter(|&(_, count)| count <= max_uses)
+            .collect();
+        tiles.sort_by_key(|&(gid, count)| (count, gid));
+        tiles
+    }
+}
@@ -0,0 +1,335 @@
+//! Saving maps by rewriting only the layers which changed.
+
+#[cfg(feature = "fs")]
+use std::ffi::OsString;
+#[cfg(feature = "fs")]
+use std::fs::{self, File};
+#[cfg(feature = "fs")]
+use std::io::BufWriter;
+use std::io::Write;
+use std::ops::Range;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use crate::hash::{fnv1a, fnv1a_extend};
+use crate::writer::{layer_fragment, map_without_layers};
+use crate::{Compression, DataEncoding, Map, TiledError, WriteOptions};
+
+/// The names of the elements which are parsed as layers of a map.
+const LAYER_ELEMENTS: [&str; 4] = ["layer", "objectgroup", "imagelayer", "group"];
+
+/// Finds the byte ranges of the layer elements of a TMX document, in the
+/// order they are parsed in. Collision shapes within tilesets aren't layers
+/// and are skipped, and groups are one element along with the layers within
+/// them.
+fn layer_spans(xml: &[u8]) -> Result<Vec<Range<usize>>, TiledError> {
+    let malformed = || TiledError::Other("Unexpected end of the original map".to_string());
+    let find = |from: usize, pattern: &[u8]| {
+        xml[from..]
+            .windows(pattern.len())
+            .position(|w| w == pattern)
+            .map(|i| from + i + pattern.len())
+            .ok_or_else(malformed)
+    };
+
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    // The open elements, along with whether each one is a layer.
+    let mut stack: Vec<(&[u8], bool)> = Vec::new();
+    let mut pos = 0;
+    while let Some(offset) = xml[pos..].iter().position(|&b| b == b'<') {
+        let start = pos + offset;
+        let rest = &xml[start..];
+        if rest.starts_with(b"<?") {
+            pos = find(start, b"?>")?;
+        } else if rest.starts_with(b"<!--") {
+            pos = find(start, b"-->")?;
+        } else if rest.starts_with(b"<![CDATA[") {
+            pos = find(start, b"]]>")?;
+        } else if rest.starts_with(b"<!") {
+            pos = find(start, b">")?;
+        } else {
+            // Find the end of the tag, skipping over quoted attribute values.
+            let mut quote = None;
+            let mut end = start + 1;
+            loop {
+                match (*xml.get(end).ok_or_else(malformed)?, quote) {
+                    (b'>', None) => break,
+                    (c @ b'"', None) | (c @ b'\'', None) => quote = Some(c),
+                    (c, Some(q)) if c == q => quote = None,
+                    _ => {}
+                }
+                end += 1;
+            }
+            pos = end + 1;
+            let closing = rest.starts_with(b"</");
+            let name_start = if closing { start + 2 } else { start + 1 };
+            let name_len = xml[name_start..end]
+                .iter()
+                .position(|b| b.is_ascii_whitespace() || *b == b'/')
+                .unwrap_or(end - name_start);
+            let name = &xml[name_start..name_start + name_len];
+            if closing {
+                match stack.pop() {
+                    Some((open, true)) if open == name => {
+                        spans.last_mut().ok_or_else(malformed)?.end = pos
+                    }
+                    Some((open, _)) if open == name => {}
+                    _ => {
+                        return Err(TiledError::Other(format!(
+                            "Mismatched closing tag in the original map at byte {}",
+                            start
+                        )))
+                    }
+                }
+                continue;
+            }
+            let is_layer = LAYER_ELEMENTS.iter().any(|l| l.as_bytes() == name)
+                && stack
+                    .iter()
+                    .all(|&(open, layer)| !layer && open != b"tileset");
+            if is_layer {
+                spans.push(start..pos);
+            }
+            if xml[end - 1] != b'/' {
+                stack.push((name, is_layer));
+            }
+        }
+    }
+    Ok(spans)
+}
+
+/// Reads the encoding of the tile data within a layer element, so rewritten
+/// layers keep the encoding they had. Falls back to CSV for encodings which
+/// can't be written.
+fn data_encoding(element: &[u8]) -> DataEncoding {
+    let element = String::from_utf8_lossy(element);
+    let data = match element.find("<data") {
+        Some(start) => &element[start..],
+        None => return DataEncoding::Csv,
+    };
+    let data = &data[..data.find('>').unwrap_or(data.len())];
+    let attribute = |name: &str| {
+        let pattern = format!("{}=\"", name);
+        let start = data.find(&pattern)? + pattern.len();
+        let len = data[start..].find('"')?;
+        Some(data[start..start + len].to_string())
+    };
+    let compression = match attribute("compression").as_deref() {
+        None => None,
+        Some("zlib") => Some(Compression::Zlib),
+        Some("gzip") => Some(Compression::Gzip),
+        #[cfg(feature = "zstd")]
+        Some("zstd") => Some(Compression::Zstd),
+        Some(_) => return DataEncoding::Csv,
+    };
+    match attribute("encoding").as_deref() {
+        Some("base64") => DataEncoding::Base64(compression),
+        _ => DataEncoding::Csv,
+    }
+}
+
+/// Returns the whitespace a tag at `start` is indented with.
+fn indentation(xml: &[u8], start: usize) -> &[u8] {
+    let line_start = xml[..start]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+    let indent = &xml[line_start..start];
+    if indent.iter().all(|b| b.is_ascii_whitespace()) {
+        indent
+    } else {
+        &[]
+    }
+}
+
+/// Writes the output of an incremental save, keeping track of how much was
+/// written and of its hash.
+struct Output<W> {
+    writer: W,
+    written: usize,
+    hash: u64,
+}
+
+impl<W: Write> Output<W> {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), TiledError> {
+        self.writer
+            .write_all(bytes)
+            .map_err(|e| TiledError::Other(format!("Failed to write the map: {}", e)))?;
+        self.written += bytes.len();
+        self.hash = fnv1a_extend(self.hash, bytes);
+        Ok(())
+    }
+}
+
+/// What `Map::write_incremental` knows of the TMX document a map was loaded
+/// from or last saved to: where each layer element lies in the document and
+/// the content hash of the layer parsed from it. Saving compares the layers
+/// against these hashes instead of parsing the document again.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SavedLayers {
+    /// The hash of the whole document, to notice it changing behind the
+    /// map's back.
+    document: u64,
+    /// The hash of the map without its layers.
+    header: u64,
+    /// The byte range of each layer element, along with the hash of the
+    /// layer.
+    layers: Vec<(Range<usize>, u64)>,
+}
+
+impl SavedLayers {
+    /// Records the layers of `document`, the TMX document `map` was just
+    /// parsed from, before the map is edited.
+    pub fn new(map: &Map, document: &[u8]) -> Result<SavedLayers, TiledError> {
+        let spans = layer_spans(document)?;
+        if spans.len() != map.layers.len() {
+            return Err(TiledError::Other(format!(
+                "The document has {} layers but the map has {}",
+                spans.len(),
+                map.layers.len()
+            )));
+        }
+        let layers = spans
+            .into_iter()
+            .zip(map.layers.iter())
+            .map(|(span, layer)| Ok((span, layer.content_hash()?)))
+            .collect::<Result<_, TiledError>>()?;
+        Ok(SavedLayers {
+            document: fnv1a(document),
+            header: fnv1a(&map_without_layers(map)?),
+            layers,
+        })
+    }
+}
+
+impl Map {
+    /// Writes the map out as a TMX document, reusing the bytes of
+    /// `original`, the document described by `saved`. Only the layer
+    /// elements whose content hash differs from the one in `saved` are
+    /// rewritten, which makes saving large maps with few changes much
+    /// faster. `saved` is updated to describe the written document.
+    ///
+    /// If anything besides the layers changed, layers were added or
+    /// removed, or `original` isn't the document `saved` was recorded
+    /// from, the whole map is written as with `write_to`. Returns the
+    /// number of layers which were rewritten.
+    pub fn write_incremental<W: Write>(
+        &self,
+        original: &[u8],
+        saved: &mut SavedLayers,
+        writer: W,
+    ) -> Result<usize, TiledError> {
+        let mut out = Output {
+            writer,
+            written: 0,
+            hash: fnv1a(&[]),
+        };
+        if saved.document != fnv1a(original)
+            || saved.header != fnv1a(&map_without_layers(self)?)
+            || saved.layers.len() != self.layers.len()
+        {
+            let mut document = Vec::new();
+            self.write_to(&mut document)?;
+            let layers = SavedLayers::new(self, &document)?;
+            out.write(&document)?;
+            *saved = layers;
+            return Ok(self.layers.len());
+        }
+
+        let mut layers = Vec::with_capacity(self.layers.len());
+        let mut rewritten = 0;
+        let mut pos = 0;
+        for (layer, &(ref span, hash)) in self.layers.iter().zip(saved.layers.iter()) {
+            let new_hash = layer.content_hash()?;
+            let start = out.written + (span.start - pos);
+            if new_hash == hash {
+                layers.push((start..start + span.len(), hash));
+                continue;
+            }
+            let options = WriteOptions {
+                data_encoding: data_encoding(&original[span.clone()]),
+                ..WriteOptions::default()
+            };
+            let fragment = layer_fragment(layer, &options, self.compression_level)?;
+            let indent = indentation(original, span.start);
+            out.write(&original[pos..span.start])?;
+            // Indent every line starting with a tag like the original element,
+            // leaving text content such as CSV data untouched.
+            let mut line_start = 0;
+            for (i, &byte) in fragment.iter().enumerate() {
+                let starts_with_tag =
+                    || fragment[i + 1..].iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'<');
+                if byte == b'\n' && starts_with_tag() {
+                    out.write(&fragment[line_start..=i])?;
+                    out.write(indent)?;
+                    line_start = i + 1;
+                }
+            }
+            out.write(&fragment[line_start..])?;
+            layers.push((start..out.written, new_hash));
+            pos = span.end;
+            rewritten += 1;
+        }
+        out.write(&original[pos..])?;
+        saved.document = out.hash;
+        saved.layers = layers;
+        Ok(rewritten)
+    }
+
+    /// Saves the map over the TMX file at `path`, rewriting only the layers
+    /// which changed since it was loaded or last saved, as recorded in
+    /// `saved`. See `write_incremental`.
+    ///
+    /// The map is written to a file next to `path` which then replaces it,
+    /// so that the file is left as it was if saving fails.
+    #[cfg(feature = "fs")]
+    pub fn save_incremental(
+        &self,
+        path: impl AsRef<Path>,
+        saved: &mut SavedLayers,
+    ) -> Result<usize, TiledError> {
+        let path = path.as_ref();
+        let original = fs::read(path).map_err(|e| TiledError::io(path, e))?;
+        let mut layers = saved.clone();
+        let rewritten = replace_file(path, |writer| {
+            self.write_incremental(&original, &mut layers, writer)
+        })?;
+        *saved = layers;
+        Ok(rewritten)
+    }
+}
+
+/// Writes a temporary file next to `path` with `write`, then moves it over
+/// `path`. The temporary file is removed if anything fails.
+#[cfg(feature = "fs")]
+fn replace_file<T>(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<T, TiledError>,
+) -> Result<T, TiledError> {
+    let mut name = OsString::from(".");
+    name.push(
+        path.file_name()
+            .ok_or_else(|| TiledError::Other(format!("{} isn't a file", path.display())))?,
+    );
+    name.push(".tmp");
+    let temp = path.with_file_name(name);
+    let result = (|| {
+        let permissions = fs::metadata(path)
+            .map_err(|e| TiledError::io(path, e))?
+            .permissions();
+        let file = File::create(&temp).map_err(|e| TiledError::io(&temp, e))?;
+        let mut writer = BufWriter::new(file);
+        let value = write(&mut writer)?;
+        let file = writer
+            .into_inner()
+            .map_err(|e| TiledError::io(&temp, e.into_error()))?;
+        file.sync_all().map_err(|e| TiledError::io(&temp, e))?;
+        fs::set_permissions(&temp, permissions).map_err(|e| TiledError::io(&temp, e))?;
+        fs::rename(&temp, path).map_err(|e| TiledError::io(path, e))?;
+        Ok(value)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
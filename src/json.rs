@@ -0,0 +1,452 @@
+//! Export of maps and tilesets to Tiled's JSON formats (`.tmj`/`.tsj`).
+
+use std::io::Write;
+
+use serde_json::{json, Map as JsonObject, Value};
+
+use crate::{
+    cell_bits, Chunk, FillMode, Frame, Image, Layer, LayerData, LayerTile, LayerType, Map, Object,
+    ObjectGroup, ObjectShape, Properties, PropertyValue, Text, Tile, TileLayer, TileRenderSize,
+    TiledError, Tileset, WangSet,
+};
+
+fn map_to_json(map: &Map) -> Value {
+    let mut object = JsonObject::new();
+    object.insert("type".into(), json!("map"));
+    object.insert("version".into(), json!(map.version));
+    if let Some(ref tiled_version) = map.tiled_version {
+        object.insert("tiledversion".into(), json!(tiled_version));
+    }
+    object.insert("orientation".into(), json!(map.orientation.to_string()));
+    object.insert("renderorder".into(), json!("right-down"));
+    object.insert("width".into(), json!(map.width));
+    object.insert("height".into(), json!(map.height));
+    object.insert("tilewidth".into(), json!(map.tile_width));
+    object.insert("tileheight".into(), json!(map.tile_height));
+    object.insert("infinite".into(), json!(map.infinite));
+    if let Some(ref user_type) = map.user_type {
+        object.insert("class".into(), json!(user_type));
+    }
+    object.insert("nextlayerid".into(), json!(map.next_layer_id));
+    object.insert("nextobjectid".into(), json!(map.next_object_id));
+    if let Some(colour) = map.background_colour {
+        object.insert("backgroundcolor".into(), json!(colour.to_string()));
+    }
+    if let Some(level) = map.compression_level {
+        object.insert("compressionlevel".into(), json!(level));
+    }
+    if let Some(length) = map.hex_side_length {
+        object.insert("hexsidelength".into(), json!(length));
+    }
+    if let Some(axis) = map.stagger_axis {
+        object.insert("staggeraxis".into(), json!(axis.to_string()));
+    }
+    if let Some(index) = map.stagger_index {
+        object.insert("staggerindex".into(), json!(index.to_string()));
+    }
+    if let Some(ref settings) = map.editor_settings {
+        let mut editor_settings = JsonObject::new();
+        if let Some((width, height)) = settings.chunk_size {
+            editor_settings.insert(
+                "chunksize".into(),
+                json!({"width": width, "height": height}),
+            );
+        }
+        if settings.export_target.is_some() || settings.export_format.is_some() {
+            let mut export = JsonObject::new();
+            if let Some(ref target) = settings.export_target {
+                export.insert("target".into(), json!(target));
+            }
+            if let Some(ref format) = settings.export_format {
+                export.insert("format".into(), json!(format));
+            }
+            editor_settings.insert("export".into(), Value::Object(export));
+        }
+        object.insert("editorsettings".into(), Value::Object(editor_settings));
+    }
+    object.insert(
+        "tilesets".into(),
+        Value::Array(
+            map.tilesets
+                .iter()
+                .map(|t| tileset_to_json(t, true))
+                .chain(map.unresolved_tilesets.iter().map(|t| {
+                    json!({
+                        "firstgid": t.first_gid.0,
+                        "source": t.source.to_string_lossy(),
+                    })
+                }))
+                .collect(),
+        ),
+    );
+    object.insert(
+        "layers".into(),
+        Value::Array(map.layers.iter().map(layer_to_json).collect()),
+    );
+    insert_properties(&mut object, &map.properties);
+    Value::Object(object)
+}
+
+fn tileset_to_json(tileset: &Tileset, embedded: bool) -> Value {
+    let mut object = JsonObject::new();
+    if embedded {
+        object.insert("firstgid".into(), json!(tileset.first_gid.0));
+    } else {
+        object.insert("type".into(), json!("tileset"));
+        if let Some(ref version) = tileset.version {
+            object.insert("version".into(), json!(version));
+        }
+        if let Some(ref tiled_version) = tileset.tiled_version {
+            object.insert("tiledversion".into(), json!(tiled_version));
+        }
+    }
+    object.insert("name".into(), json!(tileset.name));
+    if let Some(ref user_type) = tileset.user_type {
+        object.insert("class".into(), json!(user_type));
+    }
+    object.insert("tilewidth".into(), json!(tileset.tile_width));
+    object.insert("tileheight".into(), json!(tileset.tile_height));
+    object.insert("spacing".into(), json!(tileset.spacing));
+    object.insert("margin".into(), json!(tileset.margin));
+    object.insert("columns".into(), json!(tileset.columns));
+    if let Some(tilecount) = tileset.tilecount {
+        object.insert("tilecount".into(), json!(tilecount));
+    }
+    if tileset.tile_render_size != TileRenderSize::default() {
+        object.insert(
+            "tilerendersize".into(),
+            json!(tileset.tile_render_size.to_string()),
+        );
+    }
+    if tileset.fill_mode != FillMode::default() {
+        object.insert("fillmode".into(), json!(tileset.fill_mode.to_string()));
+    }
+    // The JSON format only has room for a single tileset image.
+    if let Some(image) = tileset.images.first() {
+        insert_image(&mut object, image);
+    }
+    if !tileset.tiles.is_empty() {
+        object.insert(
+            "tiles".into(),
+            Value::Array(tileset.tiles.iter().map(tile_to_json).collect()),
+        );
+    }
+    if !tileset.wang_sets.is_empty() {
+        object.insert(
+            "wangsets".into(),
+            Value::Array(tileset.wang_sets.iter().map(wang_set_to_json).collect()),
+        );
+    }
+    insert_properties(&mut object, &tileset.properties);
+    Value::Object(object)
+}
+
+fn wang_set_to_json(wang_set: &WangSet) -> Value {
+    let mut object = JsonObject::new();
+    object.insert("name".into(), json!(wang_set.name));
+    object.insert("type".into(), json!(wang_set.wang_type));
+    object.insert("tile".into(), json!(wang_set.tile));
+    let colors = wang_set
+        .colors
+        .iter()
+        .map(|color| {
+            let mut object = JsonObject::new();
+            object.insert("name".into(), json!(color.name));
+            object.insert("color".into(), json!(color.colour.to_string()));
+            object.insert("tile".into(), json!(color.tile));
+            object.insert("probability".into(), json!(color.probability));
+            insert_properties(&mut object, &color.properties);
+            Value::Object(object)
+        })
+        .collect();
+    object.insert("colors".into(), Value::Array(colors));
+    let wang_tiles = wang_set
+        .wang_tiles
+        .iter()
+        .map(|t| json!({ "tileid": t.tile_id, "wangid": t.wang_id }))
+        .collect();
+    object.insert("wangtiles".into(), Value::Array(wang_tiles));
+    insert_properties(&mut object, &wang_set.properties);
+    Value::Object(object)
+}
+
+fn tile_to_json(tile: &Tile) -> Value {
+    let mut object = JsonObject::new();
+    object.insert("id".into(), json!(tile.id));
+    if let Some(ref tile_type) = tile.tile_type {
+        object.insert("type".into(), json!(tile_type));
+    }
+    if tile.probability != 1.0 {
+        object.insert("probability".into(), json!(tile.probability));
+    }
+    if let Some(image) = tile.images.first() {
+        insert_image(&mut object, image);
+    }
+    if let Some(rect) = tile.source_rect {
+        object.insert("x".into(), json!(rect.x));
+        object.insert("y".into(), json!(rect.y));
+        object.insert("width".into(), json!(rect.width));
+        object.insert("height".into(), json!(rect.height));
+    }
+    if let Some(ref group) = tile.objectgroup {
+        object.insert("objectgroup".into(), layer_to_json(group));
+    }
+    if let Some(ref animation) = tile.animation {
+        object.insert(
+            "animation".into(),
+            Value::Array(animation.iter().map(frame_to_json).collect()),
+        );
+    }
+    insert_properties(&mut object, &tile.properties);
+    Value::Object(object)
+}
+
+fn frame_to_json(frame: &Frame) -> Value {
+    json!({ "tileid": frame.tile_id, "duration": frame.duration })
+}
+
+fn insert_image(object: &mut JsonObject<String, Value>, image: &Image) {
+    object.insert("image".into(), json!(image.source.to_string_lossy()));
+    object.insert("imagewidth".into(), json!(image.width));
+    object.insert("imageheight".into(), json!(image.height));
+    if let Some(colour) = image.transparent_colour {
+        object.insert("transparentcolor".into(), json!(colour.to_string()));
+    }
+}
+
+fn layer_to_json(layer: &Layer) -> Value {
+    let mut object = JsonObject::new();
+    if layer.id != 0 {
+        object.insert("id".into(), json!(layer.id));
+    }
+    object.insert("name".into(), json!(layer.name));
+    if let Some(ref user_type) = layer.user_type {
+        object.insert("class".into(), json!(user_type));
+    }
+    object.insert("opacity".into(), json!(layer.opacity));
+    object.insert("visible".into(), json!(layer.visible));
+    if layer.locked {
+        object.insert("locked".into(), json!(true));
+    }
+    object.insert("x".into(), json!(0));
+    object.insert("y".into(), json!(0));
+    if layer.offset_x != 0.0 {
+        object.insert("offsetx".into(), json!(layer.offset_x));
+    }
+    if layer.offset_y != 0.0 {
+        object.insert("offsety".into(), json!(layer.offset_y));
+    }
+    if let Some(colour) = layer.tint_colour {
+        object.insert("tintcolor".into(), json!(colour.to_string()));
+    }
+    match layer.layer_type {
+        LayerType::TileLayer(ref tiles) => {
+            object.insert("type".into(), json!("tilelayer"));
+            object.insert("width".into(), json!(tiles.width));
+            object.insert("height".into(), json!(tiles.height));
+            insert_tile_data(&mut object, tiles);
+        }
+        LayerType::ObjectLayer(ref group) => {
+            object.insert("type".into(), json!("objectgroup"));
+            insert_objects(&mut object, group);
+        }
+        LayerType::ImageLayer(ref image_layer) => {
+            object.insert("type".into(), json!("imagelayer"));
+            match image_layer.image {
+                Some(ref image) => insert_image(&mut object, image),
+                None => {
+                    object.insert("image".into(), json!(""));
+                }
+            }
+        }
+        LayerType::Group(ref layers) => {
+            object.insert("type".into(), json!("group"));
+            object.insert(
+                "layers".into(),
+                Value::Array(layers.iter().map(layer_to_json).collect()),
+            );
+        }
+    }
+    insert_properties(&mut object, &layer.properties);
+    Value::Object(object)
+}
+
+fn insert_tile_data(object: &mut JsonObject<String, Value>, layer: &TileLayer) {
+    match layer.tiles {
+        LayerData::Finite(ref rows) => {
+            object.insert("data".into(), tiles_to_json(rows));
+        }
+        LayerData::Infinite(ref chunks) => {
+            let mut chunks: Vec<&Chunk> = chunks.values().collect();
+            chunks.sort_by_key(|c| (c.y, c.x));
+            object.insert(
+                "chunks".into(),
+                Value::Array(
+                    chunks
+                        .into_iter()
+                        .map(|c| {
+                            json!({
+                                "x": c.x,
+                                "y": c.y,
+                                "width": c.width,
+                                "height": c.height,
+                                "data": tiles_to_json(&c.tiles),
+                            })
+                        })
+                        .collect(),
+                ),
+            );
+        }
+    }
+}
+
+fn tiles_to_json(rows: &[Vec<Option<LayerTile>>]) -> Value {
+    Value::Array(
+        rows.iter()
+            .flat_map(|row| row.iter().map(|&tile| json!(cell_bits(tile))))
+            .collect(),
+    )
+}
+
+fn insert_objects(object: &mut JsonObject<String, Value>, group: &ObjectGroup) {
+    object.insert("draworder".into(), json!(group.draw_order.to_string()));
+    if let Some(colour) = group.colour {
+        object.insert("color".into(), json!(colour.to_string()));
+    }
+    object.insert(
+        "objects".into(),
+        Value::Array(group.objects.iter().map(object_to_json).collect()),
+    );
+}
+
+fn object_to_json(object: &Object) -> Value {
+    let mut json = JsonObject::new();
+    json.insert("id".into(), json!(object.id));
+    if !object.gid.is_empty() {
+        json.insert("gid".into(), json!(object.gid.0));
+    }
+    json.insert("name".into(), json!(object.name));
+    json.insert("type".into(), json!(object.obj_type));
+    json.insert("x".into(), json!(object.x));
+    json.insert("y".into(), json!(object.y));
+    json.insert("width".into(), json!(object.width));
+    json.insert("height".into(), json!(object.height));
+    json.insert("rotation".into(), json!(object.rotation));
+    json.insert("visible".into(), json!(object.visible));
+    if object.locked {
+        json.insert("locked".into(), json!(true));
+    }
+    match object.shape {
+        ObjectShape::Rect { .. } => {}
+        ObjectShape::Ellipse { .. } => {
+            json.insert("ellipse".into(), json!(true));
+        }
+        ObjectShape::Point(..) => {
+            json.insert("point".into(), json!(true));
+        }
+        ObjectShape::Polyline { ref points } => {
+            json.insert("polyline".into(), points_to_json(points));
+        }
+        ObjectShape::Polygon { ref points } => {
+            json.insert("polygon".into(), points_to_json(points));
+        }
+        ObjectShape::Text(ref text) => {
+            json.insert("text".into(), text_to_json(text));
+        }
+    }
+    insert_properties(&mut json, &object.properties);
+    Value::Object(json)
+}
+
+fn text_to_json(text: &Text) -> Value {
+    json!({
+        "text": text.contents,
+        "fontfamily": text.font_family,
+        "pixelsize": text.pixel_size,
+        "wrap": text.wrap,
+        "color": text.colour.to_string(),
+        "bold": text.bold,
+        "italic": text.italic,
+        "underline": text.underline,
+        "strikeout": text.strikeout,
+        "kerning": text.kerning,
+        "halign": text.halign.to_string(),
+        "valign": text.valign.to_string(),
+    })
+}
+
+fn points_to_json(points: &[(f32, f32)]) -> Value {
+    Value::Array(
+        points
+            .iter()
+            .map(|&(x, y)| json!({ "x": x, "y": y }))
+            .collect(),
+    )
+}
+
+fn insert_properties(object: &mut JsonObject<String, Value>, properties: &Properties) {
+    if properties.is_empty() {
+        return;
+    }
+    // Sort the properties so the output is stable between runs.
+    let mut properties: Vec<(&String, &PropertyValue)> = properties.iter().collect();
+    properties.sort_by(|a, b| a.0.cmp(b.0));
+    let properties = properties
+        .into_iter()
+        .map(|(name, value)| {
+            let (property_type, json) = property_to_json(value);
+            let mut property = json!({ "name": name, "type": property_type, "value": json });
+            match *value {
+                PropertyValue::ClassValue { ref type_name, .. }
+                | PropertyValue::EnumValue { ref type_name, .. } => {
+                    property["propertytype"] = json!(type_name);
+                }
+                _ => {}
+            }
+            property
+        })
+        .collect();
+    object.insert("properties".into(), Value::Array(properties));
+}
+
+fn property_to_json(value: &PropertyValue) -> (&'static str, Value) {
+    match *value {
+        PropertyValue::BoolValue(v) => ("bool", json!(v)),
+        PropertyValue::FloatValue(v) => ("float", json!(v)),
+        PropertyValue::IntValue(v) => ("int", json!(v)),
+        PropertyValue::ColorValue(v) => ("color", json!(v.to_string())),
+        PropertyValue::StringValue(ref v) => ("string", json!(v)),
+        PropertyValue::FileValue(ref v) => ("file", json!(v.to_string_lossy())),
+        PropertyValue::ObjectValue(v) => ("object", json!(v)),
+        PropertyValue::EnumValue { ref value, .. } => property_to_json(value),
+        // Members are plain values keyed by their names.
+        PropertyValue::ClassValue { ref properties, .. } => (
+            "class",
+            Value::Object(
+                properties
+                    .iter()
+                    .map(|(name, value)| (name.clone(), property_to_json(value).1))
+                    .collect(),
+            ),
+        ),
+    }
+}
+
+fn write_json<W: Write>(value: &Value, writer: W) -> Result<(), TiledError> {
+    serde_json::to_writer_pretty(writer, value).map_err(TiledError::JsonEncodingError)
+}
+
+impl Map {
+    /// Writes the map out in Tiled's JSON map format (`.tmj`). Tilesets are
+    /// always embedded in the map.
+    pub fn write_json_to<W: Write>(&self, writer: W) -> Result<(), TiledError> {
+        write_json(&map_to_json(self), writer)
+    }
+}
+
+impl Tileset {
+    /// Writes the tileset out in Tiled's JSON tileset format (`.tsj`).
+    pub fn write_json_to<W: Write>(&self, writer: W) -> Result<(), TiledError> {
+        write_json(&tileset_to_json(self, false), writer)
+    }
+}
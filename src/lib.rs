@@ -1,20 +1,138 @@
-use base64;
+// The original parsing code predates these lints.
+#![allow(
+    clippy::collapsible_match,
+    clippy::redundant_closure,
+    clippy::redundant_field_names
+)]
 
 use std::collections::HashMap;
 use std::fmt;
-use std::fs::File;
-use std::io::{BufReader, Error, Read};
-use std::path::Path;
+use std::io::{Error, Read};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
 use xml::attribute::OwnedAttribute;
+use xml::common::{Position, TextPosition};
 use xml::reader::XmlEvent;
 use xml::reader::{Error as XmlError, EventReader};
-
-#[derive(Debug, Copy, Clone)]
-pub enum ParseTileError {
-    ColourError,
-    OrientationError,
-}
+use xml::writer::Error as XmlWriterError;
+
+use crate::extra::{qualified_name, xml_reader};
+
+mod animation;
+#[cfg(feature = "async")]
+mod asynchronous;
+mod atlas;
+mod audio;
+mod autotile;
+mod brush;
+mod checksum;
+mod codec;
+mod colliders;
+mod compat;
+mod compare;
+mod deferred;
+mod delta;
+#[cfg(feature = "serde")]
+mod deserialize;
+mod density;
+mod edit;
+mod export;
+mod extra;
+mod fov;
+mod geometry;
+mod grid;
+mod group;
+mod hash;
+mod heightmap;
+mod histogram;
+mod incremental;
+mod json;
+mod loader;
+mod localization;
+mod materials;
+mod network;
+mod normals;
+mod object_types;
+mod outline;
+mod overlap;
+mod patch;
+#[cfg(feature = "pathfinding")]
+mod pathfinding;
+mod paths;
+mod project;
+mod query;
+mod random;
+mod raster;
+mod raycast;
+mod regions;
+mod registry;
+mod remap;
+mod runtime;
+mod scatter;
+mod slopes;
+mod strips;
+mod units;
+mod world;
+mod writer;
+
+#[cfg(feature = "async")]
+pub use crate::asynchronous::{ResourceFuture, ResourceReader};
+pub use crate::atlas::TileImage;
+pub use crate::audio::AudioZone;
+pub use crate::brush::Brush;
+pub use crate::codec::{CompressionCodec, TileDataCodec};
+pub use crate::colliders::TileCollider;
+pub use crate::compat::{check_compatibility, CompatibilityWarning, SUPPORTED_TILED_VERSION};
+pub use crate::compare::assert_maps_equivalent;
+pub use crate::deferred::TilesetSource;
+pub use crate::delta::TileDelta;
+#[cfg(feature = "serde")]
+pub use crate::deserialize::DeserializeProperties;
+pub use crate::density::DensityGrid;
+pub use crate::export::ExportProfile;
+pub use crate::extra::{Extra, Layout, XmlElement, XmlNode};
+pub use crate::geometry::{MapPoint, MapRect, MapSize, TilePoint, TileRect, TileSize};
+pub use crate::grid::TileGrid;
+pub use crate::group::FlatLayer;
+pub use crate::incremental::SavedLayers;
+pub use crate::loader::{LayerInfo, LayerKind, Loader};
+pub use crate::localization::StringTable;
+pub use crate::materials::MaterialTable;
+pub use crate::network::{FrameReceiver, MapFrame, MAX_PENDING_FRAMES};
+pub use crate::normals::CollisionEdge;
+#[cfg(feature = "fs")]
+pub use crate::object_types::parse_object_types_file;
+pub use crate::object_types::{ObjectType, ObjectTypes};
+pub use crate::outline::{simplify_polygon, TracedShape};
+pub use crate::overlap::TilesetOverlap;
+#[cfg(feature = "fs")]
+pub use crate::patch::parse_patch_file;
+pub use crate::patch::{
+    ConflictPolicy, LayerChange, MapPatch, ObjectEdit, PatchConflict, PatchStack, StackConflict,
+    TileChange,
+};
+#[cfg(feature = "pathfinding")]
+pub use crate::pathfinding::{find_path, CostGrid, Neighborhood, TilePath};
+#[cfg(feature = "fs")]
+pub use crate::project::parse_project_file;
+pub use crate::project::{EnumStorage, Project, PropertyType};
+pub use crate::random::Rng;
+pub use crate::raycast::RaycastHit;
+pub use crate::regions::Region;
+pub use crate::registry::TilesetRegistry;
+pub use crate::runtime::{
+    PropertyOwner, RuntimeLayerKind, RuntimeLayers, RuntimeMap, RuntimeObjects, RuntimeOptions,
+    RuntimeProperty, StringId,
+};
+pub use crate::scatter::Scatter;
+pub use crate::slopes::TileCollision;
+pub use crate::strips::{Strip, StripPartition};
+pub use crate::units::{PropertyUnits, Unit};
+#[cfg(feature = "fs")]
+pub use crate::world::parse_world_file;
+pub use crate::world::{World, WorldMap, WorldPattern};
+pub use crate::writer::{Compression, DataEncoding, WriteOptions};
 
 // Loops through the attributes once and pulls out the ones we ask it to. It
 // will check that the required ones are there. This could have been done with
@@ -22,22 +140,28 @@ pub enum ParseTileError {
 //
 // This is probably a really terrible way to do this. It does cut down on lines
 // though which is nice.
+//
+// Attributes it doesn't ask for are added to `extra`, if given.
 macro_rules! get_attrs {
     ($attrs:expr, optionals: [$(($oName:pat, $oVar:ident, $oMethod:expr)),* $(,)*],
-     required: [$(($name:pat, $var:ident, $method:expr)),* $(,)*], $err:expr) => {
+     required: [$(($name:literal, $var:ident, $method:expr)),* $(,)*], $element:expr
+     $(, extra: $extra:expr)?) => {
         {
             $(let mut $oVar = None;)*
             $(let mut $var = None;)*
             for attr in $attrs.iter() {
+                $($extra.layout.attributes.push(qualified_name(&attr.name));)?
                 match attr.name.local_name.as_ref() {
                     $($oName => $oVar = $oMethod(attr.value.clone()),)*
                     $($name => $var = $method(attr.value.clone()),)*
-                    _ => {}
+                    _ => {
+                        $($extra.attributes.push((qualified_name(&attr.name), attr.value.clone()));)?
+                    }
                 }
             }
-            if !(true $(&& $var.is_some())*) {
-                return Err($err);
-            }
+            $(if $var.is_none() {
+                return Err(TiledError::attribute($element, $name, &$attrs));
+            })*
             (($($oVar),*), ($($var.unwrap()),*))
         }
     }
@@ -46,25 +170,34 @@ macro_rules! get_attrs {
 // Goes through the children of the tag and will call the correct function for
 // that child. Closes the tag
 //
-// Not quite as bad.
+// Not quite as bad. Children it has no function for are added to `extra`, if
+// given.
 macro_rules! parse_tag {
-    ($parser:expr, $close_tag:expr, {$($open_tag:expr => $open_method:expr),* $(,)*}) => {
+    ($parser:expr, $close_tag:expr, {$($open_tag:expr => $open_method:expr),* $(,)*}
+     $(, extra: $extra:expr)?) => {
+        // The number of child elements read so far, for `Layout`.
+        let mut _children = 0;
         loop {
             match $parser.next().map_err(TiledError::XmlDecodingError)? {
                 XmlEvent::StartElement {name, attributes, ..} => {
+                    _children += 1;
                     if false {}
                     $(else if name.local_name == $open_tag {
+                        let context = ElementContext::new(&name.local_name, &attributes);
                         match $open_method(attributes) {
                             Ok(()) => {},
-                            Err(e) => return Err(e)
+                            Err(e) => return Err(TiledError::in_element(e, context))
                         };
                     })*
-                }
-                XmlEvent::EndElement {name, ..} => {
-                    if name.local_name == $close_tag {
-                        break;
+                    else {
+                        $(
+                            $extra.elements.push(XmlElement::parse($parser, &name, &attributes)?);
+                            $extra.layout.element_positions.push(_children - 1);
+                        )?
                     }
                 }
+                $(XmlEvent::Comment(text) => $extra.layout.comments.push((_children, text)),)?
+                XmlEvent::EndElement {name, ..} if name.local_name == $close_tag => break,
                 XmlEvent::EndDocument => return Err(TiledError::PrematureEnd("Document ended before we expected.".to_string())),
                 _ => {}
             }
@@ -77,54 +210,346 @@ pub struct Colour {
     pub red: u8,
     pub green: u8,
     pub blue: u8,
+    /// The opacity of the colour, 255 when opaque.
+    pub alpha: u8,
+}
+
+impl Colour {
+    /// Creates an opaque colour.
+    pub fn new(red: u8, green: u8, blue: u8) -> Colour {
+        Colour {
+            red,
+            green,
+            blue,
+            alpha: 255,
+        }
+    }
+
+    /// Returns the colour with the given opacity.
+    pub fn with_alpha(self, alpha: u8) -> Colour {
+        Colour { alpha, ..self }
+    }
+
+    /// Whether the colour is fully opaque.
+    pub fn is_opaque(&self) -> bool {
+        self.alpha == 255
+    }
+
+    /// Unpacks a colour from a number in the `0xAARRGGBB` form, as used by
+    /// colour properties.
+    pub fn from_argb(argb: u32) -> Colour {
+        let [alpha, red, green, blue] = argb.to_be_bytes();
+        Colour {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    /// Packs the colour into a number in the `0xAARRGGBB` form.
+    pub fn to_argb(&self) -> u32 {
+        u32::from_be_bytes([self.alpha, self.red, self.green, self.blue])
+    }
+
+    /// Returns the red, green, blue and alpha components from 0 to 1, as
+    /// renderers usually take them for tinting.
+    pub fn to_rgba_f32(&self) -> [f32; 4] {
+        [self.red, self.green, self.blue, self.alpha].map(|c| c as f32 / 255.0)
+    }
 }
 
 impl FromStr for Colour {
-    type Err = ParseTileError;
+    type Err = TiledError;
 
-    fn from_str(s: &str) -> Result<Colour, ParseTileError> {
-        let s = if s.starts_with("#") { &s[1..] } else { s };
-        if s.len() != 6 {
-            return Err(ParseTileError::ColourError);
+    /// Parses a colour in either the `#RRGGBB` or the `#AARRGGBB` form.
+    fn from_str(value: &str) -> Result<Colour, TiledError> {
+        let s = value.strip_prefix('#').unwrap_or(value);
+        if !s.is_ascii() {
+            return Err(TiledError::invalid_value("colour", value));
         }
+        let (a, s) = match s.len() {
+            6 => (Ok(255), s),
+            8 => (u8::from_str_radix(&s[0..2], 16), &s[2..]),
+            _ => return Err(TiledError::invalid_value("colour", value)),
+        };
         let r = u8::from_str_radix(&s[0..2], 16);
         let g = u8::from_str_radix(&s[2..4], 16);
         let b = u8::from_str_radix(&s[4..6], 16);
-        if r.is_ok() && g.is_ok() && b.is_ok() {
-            return Ok(Colour {
-                red: r.unwrap(),
-                green: g.unwrap(),
-                blue: b.unwrap(),
-            });
+        match (r, g, b, a) {
+            (Ok(red), Ok(green), Ok(blue), Ok(alpha)) => Ok(Colour {
+                red,
+                green,
+                blue,
+                alpha,
+            }),
+            _ => Err(TiledError::invalid_value("colour", value)),
+        }
+    }
+}
+
+impl fmt::Display for Colour {
+    /// Writes the colour in the `#RRGGBB` form, or `#AARRGGBB` if it isn't
+    /// opaque.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.alpha != 255 {
+            write!(f, "#{:02x}", self.alpha)?;
+        } else {
+            write!(f, "#")?;
         }
-        Err(ParseTileError::ColourError)
+        write!(f, "{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
     }
 }
 
 /// Errors which occured when parsing the file
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum TiledError {
     /// A attribute was missing, had the wrong type of wasn't formated
     /// correctly.
     MalformedAttributes(String),
+    /// An element lacks an attribute it must have.
+    MissingAttribute {
+        element: String,
+        attribute: String,
+    },
+    /// An attribute of an element has a value which couldn't be parsed.
+    InvalidAttribute {
+        element: String,
+        attribute: String,
+        value: String,
+    },
+    /// A value couldn't be parsed as the given kind of value, like a colour
+    /// or an orientation.
+    InvalidValue {
+        kind: String,
+        value: String,
+    },
+    /// A tile of layer data couldn't be parsed as a global tile id. `index`
+    /// is the position of the tile in the data, counted from 0.
+    InvalidTileData {
+        token: String,
+        index: usize,
+    },
+    /// A file couldn't be read or written.
+    Io {
+        path: PathBuf,
+        error: Error,
+    },
     /// An error occured when decompressing using the
     /// [flate2](https://github.com/alexcrichton/flate2-rs) crate.
     DecompressingError(Error),
     Base64DecodingError(base64::DecodeError),
     XmlDecodingError(XmlError),
+    /// An error occured when writing a map or tileset back out.
+    XmlEncodingError(XmlWriterError),
+    /// An error occured when writing a map or tileset out as JSON.
+    JsonEncodingError(serde_json::Error),
+    /// An error occured when reading a JSON file, such as a world.
+    JsonDecodingError(serde_json::Error),
     PrematureEnd(String),
     Other(String),
+    /// An error along with the position in the document it occurred at.
+    AtPosition {
+        position: TextPosition,
+        error: Box<TiledError>,
+    },
+    /// An error along with the elements it occurred in, from the root of
+    /// the document down.
+    InElements {
+        elements: Vec<ElementContext>,
+        error: Box<TiledError>,
+    },
+}
+
+/// An element an error occurred in, see `TiledError::element_path`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ElementContext {
+    pub element: String,
+    /// The `name` attribute of the element, if any.
+    pub name: Option<String>,
+    /// The `id` attribute of the element, if any.
+    pub id: Option<String>,
+}
+
+impl ElementContext {
+    fn new(element: &str, attrs: &[OwnedAttribute]) -> ElementContext {
+        let attr = |name: &str| {
+            attrs
+                .iter()
+                .find(|a| a.name.local_name == name)
+                .map(|a| a.value.clone())
+        };
+        ElementContext {
+            element: element.to_string(),
+            name: attr("name"),
+            id: attr("id"),
+        }
+    }
+}
+
+impl fmt::Display for ElementContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.name, &self.id) {
+            (Some(name), _) => write!(f, "{}({:?})", self.element, name),
+            (None, Some(id)) => write!(f, "{}({})", self.element, id),
+            (None, None) => write!(f, "{}", self.element),
+        }
+    }
+}
+
+impl TiledError {
+    pub(crate) fn invalid_value(kind: &str, value: &str) -> TiledError {
+        TiledError::InvalidValue {
+            kind: kind.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    /// Returns the error for a required attribute of `element` which is
+    /// missing or couldn't be parsed.
+    fn attribute(element: &str, attribute: &str, attrs: &[OwnedAttribute]) -> TiledError {
+        match attrs.iter().find(|a| a.name.local_name == attribute) {
+            Some(attr) => TiledError::InvalidAttribute {
+                element: element.to_string(),
+                attribute: attribute.to_string(),
+                value: attr.value.clone(),
+            },
+            None => TiledError::MissingAttribute {
+                element: element.to_string(),
+                attribute: attribute.to_string(),
+            },
+        }
+    }
+
+    /// Returns an error for a file at `path` which couldn't be read or
+    /// written.
+    pub(crate) fn io(path: &Path, error: Error) -> TiledError {
+        TiledError::Io {
+            path: path.to_path_buf(),
+            error,
+        }
+    }
+
+    /// Returns the line and column of the document the error occurred at,
+    /// counted from 0, if known.
+    pub fn position(&self) -> Option<TextPosition> {
+        match *self {
+            TiledError::XmlDecodingError(ref e) => Some(e.position()),
+            TiledError::AtPosition { position, .. } => Some(position),
+            TiledError::InElements { ref error, .. } => error.position(),
+            _ => None,
+        }
+    }
+
+    /// Returns the error itself, without the position and elements it
+    /// occurred in.
+    pub fn inner(&self) -> &TiledError {
+        match *self {
+            TiledError::AtPosition { ref error, .. } | TiledError::InElements { ref error, .. } => {
+                error.inner()
+            }
+            ref error => error,
+        }
+    }
+
+    /// Returns the elements the error occurred in, from the root of the
+    /// document down, like `map > layer("Ground") > data`.
+    pub fn element_path(&self) -> Vec<&ElementContext> {
+        match *self {
+            TiledError::AtPosition { ref error, .. } => error.element_path(),
+            TiledError::InElements {
+                ref elements,
+                ref error,
+            } => elements.iter().chain(error.element_path()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Notes that the error occurred in the given element, around the
+    /// elements it was already known to occur in.
+    pub(crate) fn in_element(self, context: ElementContext) -> TiledError {
+        match self {
+            TiledError::InElements {
+                mut elements,
+                error,
+            } => {
+                elements.insert(0, context);
+                TiledError::InElements { elements, error }
+            }
+            error => TiledError::InElements {
+                elements: vec![context],
+                error: Box::new(error),
+            },
+        }
+    }
+
+    /// Notes the position of `parser` as where the error occurred, unless
+    /// the error already has a position.
+    pub(crate) fn at<R: Read>(self, parser: &EventReader<R>) -> TiledError {
+        if self.position().is_some() {
+            return self;
+        }
+        TiledError::AtPosition {
+            position: parser.position(),
+            error: Box::new(self),
+        }
+    }
 }
 
 impl fmt::Display for TiledError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         match *self {
             TiledError::MalformedAttributes(ref s) => write!(fmt, "{}", s),
+            TiledError::MissingAttribute {
+                ref element,
+                ref attribute,
+            } => write!(fmt, "{} must have a \"{}\" attribute", element, attribute),
+            TiledError::InvalidAttribute {
+                ref element,
+                ref attribute,
+                ref value,
+            } => write!(
+                fmt,
+                "invalid value {:?} for the \"{}\" attribute of {}",
+                value, attribute, element
+            ),
+            TiledError::InvalidValue {
+                ref kind,
+                ref value,
+            } => write!(fmt, "invalid {} {:?}", kind, value),
+            TiledError::InvalidTileData { ref token, index } => {
+                write!(fmt, "invalid tile {:?} at index {} of layer data", token, index)
+            }
+            TiledError::Io {
+                ref path,
+                ref error,
+            } => write!(fmt, "{}: {}", path.display(), error),
             TiledError::DecompressingError(ref e) => write!(fmt, "{}", e),
             TiledError::Base64DecodingError(ref e) => write!(fmt, "{}", e),
             TiledError::XmlDecodingError(ref e) => write!(fmt, "{}", e),
+            TiledError::XmlEncodingError(ref e) => write!(fmt, "{}", e),
+            TiledError::JsonEncodingError(ref e) => write!(fmt, "{}", e),
+            TiledError::JsonDecodingError(ref e) => write!(fmt, "{}", e),
             TiledError::PrematureEnd(ref e) => write!(fmt, "{}", e),
             TiledError::Other(ref s) => write!(fmt, "{}", s),
+            TiledError::AtPosition {
+                position,
+                ref error,
+            } => write!(
+                fmt,
+                "line {}, column {}: {}",
+                position.row + 1,
+                position.column + 1,
+                error
+            ),
+            TiledError::InElements {
+                ref elements,
+                ref error,
+            } => {
+                let elements: Vec<String> = elements.iter().map(ToString::to_string).collect();
+                write!(fmt, "{}: {}", elements.join(" > "), error)
+            }
         }
     }
 }
@@ -133,12 +558,23 @@ impl fmt::Display for TiledError {
 impl std::error::Error for TiledError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self {
-            TiledError::MalformedAttributes(_) => None,
+            TiledError::MalformedAttributes(_)
+            | TiledError::MissingAttribute { .. }
+            | TiledError::InvalidAttribute { .. }
+            | TiledError::InvalidValue { .. }
+            | TiledError::InvalidTileData { .. } => None,
+            TiledError::Io { ref error, .. } => Some(error as &dyn std::error::Error),
             TiledError::DecompressingError(ref e) => Some(e as &dyn std::error::Error),
             TiledError::Base64DecodingError(ref e) => Some(e as &dyn std::error::Error),
             TiledError::XmlDecodingError(ref e) => Some(e as &dyn std::error::Error),
+            TiledError::XmlEncodingError(ref e) => Some(e as &dyn std::error::Error),
+            TiledError::JsonEncodingError(ref e) => Some(e as &dyn std::error::Error),
+            TiledError::JsonDecodingError(ref e) => Some(e as &dyn std::error::Error),
             TiledError::PrematureEnd(_) => None,
             TiledError::Other(_) => None,
+            TiledError::AtPosition { ref error, .. } | TiledError::InElements { ref error, .. } => {
+                Some(&**error as &dyn std::error::Error)
+            }
         }
     }
 }
@@ -148,10 +584,30 @@ pub enum PropertyValue {
     BoolValue(bool),
     FloatValue(f32),
     IntValue(i32),
-    ColorValue(u32),
+    /// A colour, which is opaque unless it was given in the `#AARRGGBB`
+    /// form.
+    ColorValue(Colour),
     StringValue(String),
-    /// Holds the path relative to the map or tileset
-    FileValue(String),
+    /// Refers to an object of the map by its id, see
+    /// `Map::resolve_object_ref`. 0 when no object is referred to.
+    ObjectValue(u32),
+    /// A value of a custom class, holding the values of its members. Members
+    /// left at their default value aren't saved by Tiled, so they may be
+    /// missing.
+    ClassValue {
+        type_name: String,
+        properties: Properties,
+    },
+    /// A value of a custom enum, holding a `StringValue` with the names of
+    /// its values or an `IntValue` with their indices, depending on how the
+    /// enum is saved. See `Project::apply` to turn the latter into names.
+    EnumValue {
+        type_name: String,
+        value: Box<PropertyValue>,
+    },
+    /// Holds the path relative to the map or tileset, see
+    /// `Map::resolve_path` and `Map::resolve_tileset_path`.
+    FileValue(PathBuf),
 }
 
 impl PropertyValue {
@@ -170,14 +626,18 @@ impl PropertyValue {
                 Ok(val) => Ok(PropertyValue::IntValue(val)),
                 Err(err) => Err(TiledError::Other(err.to_string())),
             },
-            "color" if value.len() > 1 => match u32::from_str_radix(&value[1..], 16) {
+            "color" if value.len() > 1 => match value.parse() {
                 Ok(color) => Ok(PropertyValue::ColorValue(color)),
-                Err(_) => Err(TiledError::Other(format!(
-                    "Improperly formatted color property"
-                ))),
+                Err(_) => Err(TiledError::Other(
+                    "Improperly formatted color property".to_string(),
+                )),
             },
             "string" => Ok(PropertyValue::StringValue(value)),
-            "file" => Ok(PropertyValue::FileValue(value)),
+            "file" => Ok(PropertyValue::FileValue(PathBuf::from(value))),
+            "object" => match value.parse() {
+                Ok(val) => Ok(PropertyValue::ObjectValue(val)),
+                Err(err) => Err(TiledError::Other(err.to_string())),
+            },
             _ => Err(TiledError::Other(format!(
                 "Unknown property type \"{}\"",
                 property_type
@@ -188,34 +648,134 @@ impl PropertyValue {
 
 pub type Properties = HashMap<String, PropertyValue>;
 
+/// Skips the rest of the element whose start was just read, along with
+/// everything inside it.
+fn skip_element<R: Read>(parser: &mut EventReader<R>) -> Result<(), TiledError> {
+    let mut depth = 0;
+    loop {
+        match parser.next().map_err(TiledError::XmlDecodingError)? {
+            XmlEvent::StartElement { .. } => depth += 1,
+            XmlEvent::EndElement { .. } if depth == 0 => return Ok(()),
+            XmlEvent::EndElement { .. } => depth -= 1,
+            XmlEvent::EndDocument => {
+                return Err(TiledError::PrematureEnd(
+                    "Document ended before element was parsed".to_string(),
+                ))
+            }
+            _ => {}
+        }
+    }
+}
+
 fn parse_properties<R: Read>(parser: &mut EventReader<R>) -> Result<Properties, TiledError> {
     let mut p = HashMap::new();
     parse_tag!(parser, "properties", {
         "property" => |attrs:Vec<OwnedAttribute>| {
-            let (t, (k, v)) = get_attrs!(
+            let ((t, v, type_name), k) = get_attrs!(
                 attrs,
                 optionals: [
-                    ("type", property_type, |v| Some(v)),
+                    ("type", property_type, Some),
+                    ("value", value, Some),
+                    ("propertytype", type_name, Some),
                 ],
                 required: [
-                    ("name", key, |v| Some(v)),
-                    ("value", value, |v| Some(v)),
+                    ("name", key, Some),
                 ],
-                TiledError::MalformedAttributes("property must have a name and a value".to_string())
+                "property"
             );
             let t = t.unwrap_or("string".into());
 
-            p.insert(k, PropertyValue::new(t, v)?);
+            let value = if t == "class" {
+                PropertyValue::ClassValue {
+                    type_name: type_name.unwrap_or_default(),
+                    properties: parse_class_members(parser)?,
+                }
+            } else {
+                let v = v.ok_or_else(|| TiledError::attribute("property", "value", &attrs))?;
+                match type_name {
+                    Some(type_name) => PropertyValue::EnumValue {
+                        type_name,
+                        value: Box::new(PropertyValue::new(t, v)?),
+                    },
+                    None => PropertyValue::new(t, v)?,
+                }
+            };
+            p.insert(k, value);
             Ok(())
         },
     });
     Ok(p)
 }
 
+/// Parses the members of a class property, which has its own properties.
+fn parse_class_members<R: Read>(parser: &mut EventReader<R>) -> Result<Properties, TiledError> {
+    let mut members = HashMap::new();
+    parse_tag!(parser, "property", {
+        "properties" => |_| {
+            members = parse_properties(parser)?;
+            Ok(())
+        },
+    });
+    Ok(members)
+}
+
+/// The settings Tiled keeps in a map for editing it.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct EditorSettings {
+    /// The width and height of the chunks Tiled creates for infinite maps,
+    /// in tiles.
+    pub chunk_size: Option<(u32, u32)>,
+    /// The file the map was last exported to.
+    pub export_target: Option<String>,
+    /// The format the map was last exported in, such as `json`.
+    pub export_format: Option<String>,
+}
+
+impl EditorSettings {
+    fn new<R: Read>(parser: &mut EventReader<R>) -> Result<EditorSettings, TiledError> {
+        let mut settings = EditorSettings::default();
+        parse_tag!(parser, "editorsettings", {
+            "chunksize" => |attrs: Vec<OwnedAttribute>| {
+                let ((), (width, height)) = get_attrs!(
+                    attrs,
+                    optionals: [],
+                    required: [
+                        ("width", width, |v:String| v.parse().ok()),
+                        ("height", height, |v:String| v.parse().ok()),
+                    ],
+                    "chunksize"
+                );
+                settings.chunk_size = Some((width, height));
+                Ok(())
+            },
+            "export" => |attrs: Vec<OwnedAttribute>| {
+                let ((target, format), ()) = get_attrs!(
+                    attrs,
+                    optionals: [
+                        ("target", target, Some),
+                        ("format", format, Some),
+                    ],
+                    required: [],
+                    "export"
+                );
+                settings.export_target = target;
+                settings.export_format = format;
+                Ok(())
+            },
+        });
+        Ok(settings)
+    }
+}
+
 /// All Tiled files will be parsed into this. Holds all the layers and tilesets
 #[derive(Debug, PartialEq, Clone)]
 pub struct Map {
+    /// The path of the map file, when it was parsed with its location.
+    /// Paths in the map are relative to it, see `Map::resolve_path`.
+    pub source: Option<PathBuf>,
     pub version: String,
+    /// The version of Tiled the map was saved with.
+    pub tiled_version: Option<String>,
     pub orientation: Orientation,
     /// Width of the map, in tiles
     pub width: u32,
@@ -223,13 +783,43 @@ pub struct Map {
     pub height: u32,
     pub tile_width: u32,
     pub tile_height: u32,
-    pub tilesets: Vec<Tileset>,
+    /// The tilesets of the map, shared so that other maps and renderers can
+    /// hold on to them without copying. `Arc::make_mut` gives a tileset to
+    /// edit, copying it if it is shared.
+    pub tilesets: Vec<Arc<Tileset>>,
+    /// The external tilesets left to load with `Map::resolve_tilesets`,
+    /// when parsed with `Loader::with_deferred_tilesets`.
+    pub unresolved_tilesets: Vec<TilesetSource>,
+    /// The layers of the map, in the order they appear in the file. This is
+    /// also the order Tiled draws them in, from the bottom up.
     pub layers: Vec<Layer>,
-    pub image_layers: Vec<ImageLayer>,
-    pub object_groups: Vec<ObjectGroup>,
     pub properties: Properties,
     pub background_colour: Option<Colour>,
     pub infinite: bool,
+    /// The class of the map, for game logic to branch on.
+    pub user_type: Option<String>,
+    /// The id the next layer added to the map will get.
+    pub next_layer_id: u32,
+    /// The id the next object added to the map will get. Editing operations
+    /// adding objects take their ids from here, so they never collide with
+    /// existing objects.
+    pub next_object_id: u32,
+    /// The level layer data is compressed at, where -1 stands for the default
+    /// level of the compression.
+    pub compression_level: Option<i32>,
+    /// The length of the straight sides of the tiles of hexagonal maps, in
+    /// pixels.
+    pub hex_side_length: Option<u32>,
+    /// Which axis is staggered, on staggered and hexagonal maps.
+    pub stagger_axis: Option<StaggerAxis>,
+    /// Which rows or columns are shifted, on staggered and hexagonal maps.
+    pub stagger_index: Option<StaggerIndex>,
+    /// The editor settings saved with the map, such as the chunk size used
+    /// for infinite maps.
+    pub editor_settings: Option<EditorSettings>,
+    /// Attributes and elements the parser doesn't know, written back out
+    /// along with the map.
+    pub extra: Extra,
 }
 
 impl Map {
@@ -237,43 +827,55 @@ impl Map {
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
         map_path: Option<&Path>,
+        loader: &Loader,
     ) -> Result<Map, TiledError> {
-        let ((c, infinite), (v, o, w, h, tw, th)) = get_attrs!(
+        let mut extra = Extra::default();
+        let ((c, tv, infinite, user_type, nl, no, compression_level, hex_side_length, stagger_axis, stagger_index), (v, o, w, h, tw, th)) = get_attrs!(
             attrs,
             optionals: [
                 ("backgroundcolor", colour, |v:String| v.parse().ok()),
+                ("tiledversion", tiled_version, Some),
                 ("infinite", infinite, |v:String| Some(v == "1")),
+                ("class", user_type, Some),
+                ("nextlayerid", next_layer_id, |v:String| v.parse().ok()),
+                ("nextobjectid", next_object_id, |v:String| v.parse().ok()),
+                ("compressionlevel", compression_level, |v:String| v.parse().ok()),
+                ("hexsidelength", hex_side_length, |v:String| v.parse().ok()),
+                ("staggeraxis", stagger_axis, |v:String| v.parse().ok()),
+                ("staggerindex", stagger_index, |v:String| v.parse().ok()),
             ],
             required: [
-                ("version", version, |v| Some(v)),
+                ("version", version, Some),
                 ("orientation", orientation, |v:String| v.parse().ok()),
                 ("width", width, |v:String| v.parse().ok()),
                 ("height", height, |v:String| v.parse().ok()),
                 ("tilewidth", tile_width, |v:String| v.parse().ok()),
                 ("tileheight", tile_height, |v:String| v.parse().ok()),
             ],
-            TiledError::MalformedAttributes("map must have a version, width and height with correct types".to_string())
+            "map",
+            extra: extra
         );
 
         let mut tilesets = Vec::new();
+        let mut unresolved_tilesets = Vec::new();
         let mut layers = Vec::new();
-        let mut image_layers = Vec::new();
         let mut properties = HashMap::new();
-        let mut object_groups = Vec::new();
-        let mut layer_index = 0;
+        let mut editor_settings = None;
         parse_tag!(parser, "map", {
+            "editorsettings" => |_| {
+                editor_settings = Some(EditorSettings::new(parser)?);
+                Ok(())
+            },
             "tileset" => | attrs| {
-                tilesets.push(Tileset::new(parser, attrs, map_path)?);
+                tilesets.extend(Tileset::new(parser, attrs, map_path, loader, &mut unresolved_tilesets)?);
                 Ok(())
             },
             "layer" => |attrs| {
-                layers.push(Layer::new(parser, attrs, w, layer_index, infinite.unwrap_or(false))?);
-                layer_index += 1;
+                layers.extend(Layer::new_filtered(parser, attrs, LayerTag::Tiles { width: w, height: h, infinite: infinite.unwrap_or(false) }, loader)?);
                 Ok(())
             },
             "imagelayer" => |attrs| {
-                image_layers.push(ImageLayer::new(parser, attrs, layer_index)?);
-                layer_index += 1;
+                layers.extend(Layer::new_filtered(parser, attrs, LayerTag::Image, loader)?);
                 Ok(())
             },
             "properties" => |_| {
@@ -281,39 +883,89 @@ impl Map {
                 Ok(())
             },
             "objectgroup" => |attrs| {
-                object_groups.push(ObjectGroup::new(parser, attrs, Some(layer_index))?);
-                layer_index += 1;
+                layers.extend(Layer::new_filtered(parser, attrs, LayerTag::Objects, loader)?);
                 Ok(())
             },
-        });
+            "group" => |attrs| {
+                layers.extend(Layer::new_filtered(parser, attrs, LayerTag::Group { width: w, height: h, infinite: infinite.unwrap_or(false) }, loader)?);
+                Ok(())
+            },
+        }, extra: extra);
+        for tileset in tilesets.iter_mut() {
+            if tileset.version.is_none() {
+                tileset.version = Some(v.clone());
+            }
+            if tileset.tiled_version.is_none() {
+                tileset.tiled_version = tv.clone();
+            }
+        }
+        let tilesets: Vec<_> = tilesets.into_iter().map(Arc::new).collect();
+        // Maps saved by old versions of Tiled lack the next ids.
+        let next_layer_id = nl.unwrap_or_else(|| group::max_layer_id(&layers).saturating_add(1));
+        let next_object_id = no
+            .unwrap_or_else(|| group::max_object_id(&layers).map_or(1, |id| id.saturating_add(1)));
         Ok(Map {
+            source: map_path.map(Path::to_path_buf),
             version: v,
+            tiled_version: tv,
             orientation: o,
             width: w,
             height: h,
             tile_width: tw,
             tile_height: th,
             tilesets,
+            unresolved_tilesets,
             layers,
-            image_layers,
-            object_groups,
             properties,
             background_colour: c,
             infinite: infinite.unwrap_or(false),
+            user_type,
+            next_layer_id,
+            next_object_id,
+            compression_level,
+            hex_side_length,
+            stagger_axis,
+            stagger_index,
+            editor_settings,
+            extra,
         })
     }
 
-    /// This function will return the correct Tileset given a GID.
-    pub fn get_tileset_by_gid(&self, gid: u32) -> Option<&Tileset> {
-        let mut maximum_gid: i32 = -1;
-        let mut maximum_ts = None;
-        for tileset in self.tilesets.iter() {
-            if tileset.first_gid as i32 > maximum_gid && tileset.first_gid <= gid {
-                maximum_gid = tileset.first_gid as i32;
-                maximum_ts = Some(tileset);
-            }
-        }
-        maximum_ts
+    /// This function will return the correct Tileset given a GID. Flip
+    /// flags are ignored, so the gids of tile objects can be passed as they
+    /// are.
+    pub fn get_tileset_by_gid(&self, gid: impl Into<Gid>) -> Option<&Tileset> {
+        self.tileset_for_gid(gid).map(|(tileset, _)| tileset)
+    }
+
+    /// Returns the tileset holding the tile with the given global tile id,
+    /// along with the tile's local id within it. Flip flags are ignored.
+    /// Resolving many tiles is faster through `Map::tileset_index`.
+    pub fn tileset_for_gid(&self, gid: impl Into<Gid>) -> Option<(&Tileset, u32)> {
+        let gid = gid.into().value();
+        let tileset = &**self.tilesets.get(find_tileset(&self.tilesets, gid)?)?;
+        Some((tileset, gid - tileset.first_gid.value()))
+    }
+
+    /// Returns the tile with the given global tile id from its tileset, for
+    /// its properties, animation or collision shapes. Layer tiles can be
+    /// passed as they are. Returns `None` for tiles which their tileset
+    /// doesn't list, like those without any properties.
+    pub fn get_tile_by_gid(&self, gid: impl Into<Gid>) -> Option<&Tile> {
+        let (tileset, id) = self.tileset_for_gid(gid)?;
+        tileset.get_tile(id)
+    }
+
+    /// Indexes the tilesets by their ranges of global tile ids, for
+    /// resolving many tiles with a binary search each.
+    pub fn tileset_index(&self) -> TilesetIndex<'_> {
+        TilesetIndex::new(&self.tilesets)
+    }
+
+    /// Finds the object with the given id in any of the map's object
+    /// layers, such as the target of an object property.
+    pub fn resolve_object_ref(&self, id: u32) -> Option<&Object> {
+        self.objects().find(|object| object.id == id)
     }
 }
 
@@ -326,15 +978,15 @@ pub enum Orientation {
 }
 
 impl FromStr for Orientation {
-    type Err = ParseTileError;
+    type Err = TiledError;
 
-    fn from_str(s: &str) -> Result<Orientation, ParseTileError> {
+    fn from_str(s: &str) -> Result<Orientation, TiledError> {
         match s {
             "orthogonal" => Ok(Orientation::Orthogonal),
             "isometric" => Ok(Orientation::Isometric),
             "staggered" => Ok(Orientation::Staggered),
             "hexagonal" => Ok(Orientation::Hexagonal),
-            _ => Err(ParseTileError::OrientationError),
+            _ => Err(TiledError::invalid_value("orientation", s)),
         }
     }
 }
@@ -350,23 +1002,308 @@ impl fmt::Display for Orientation {
     }
 }
 
+/// Which axis is staggered on staggered and hexagonal maps.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum StaggerAxis {
+    X,
+    Y,
+}
+
+impl FromStr for StaggerAxis {
+    type Err = TiledError;
+
+    fn from_str(s: &str) -> Result<StaggerAxis, TiledError> {
+        match s {
+            "x" => Ok(StaggerAxis::X),
+            "y" => Ok(StaggerAxis::Y),
+            _ => Err(TiledError::invalid_value("stagger axis", s)),
+        }
+    }
+}
+
+impl fmt::Display for StaggerAxis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StaggerAxis::X => write!(f, "x"),
+            StaggerAxis::Y => write!(f, "y"),
+        }
+    }
+}
+
+/// Whether the odd or the even rows (or columns) are shifted on staggered and
+/// hexagonal maps.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum StaggerIndex {
+    Odd,
+    Even,
+}
+
+impl FromStr for StaggerIndex {
+    type Err = TiledError;
+
+    fn from_str(s: &str) -> Result<StaggerIndex, TiledError> {
+        match s {
+            "odd" => Ok(StaggerIndex::Odd),
+            "even" => Ok(StaggerIndex::Even),
+            _ => Err(TiledError::invalid_value("stagger index", s)),
+        }
+    }
+}
+
+impl fmt::Display for StaggerIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StaggerIndex::Odd => write!(f, "odd"),
+            StaggerIndex::Even => write!(f, "even"),
+        }
+    }
+}
+
+/// The size tiles of a tileset are drawn at.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub enum TileRenderSize {
+    /// Tiles are drawn at the tile size of the tileset.
+    #[default]
+    Tile,
+    /// Tiles are scaled to the tile size of the map.
+    Grid,
+}
+
+impl FromStr for TileRenderSize {
+    type Err = TiledError;
+
+    fn from_str(s: &str) -> Result<TileRenderSize, TiledError> {
+        match s {
+            "tile" => Ok(TileRenderSize::Tile),
+            "grid" => Ok(TileRenderSize::Grid),
+            _ => Err(TiledError::invalid_value("tile render size", s)),
+        }
+    }
+}
+
+impl fmt::Display for TileRenderSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TileRenderSize::Tile => write!(f, "tile"),
+            TileRenderSize::Grid => write!(f, "grid"),
+        }
+    }
+}
+
+/// How tiles are fitted into the area they are drawn in when it has a
+/// different size, see `TileRenderSize`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub enum FillMode {
+    /// Tiles are stretched to fill the area.
+    #[default]
+    Stretch,
+    /// Tiles are scaled as large as they fit while keeping their aspect
+    /// ratio, and centered.
+    PreserveAspectFit,
+}
+
+impl FromStr for FillMode {
+    type Err = TiledError;
+
+    fn from_str(s: &str) -> Result<FillMode, TiledError> {
+        match s {
+            "stretch" => Ok(FillMode::Stretch),
+            "preserve-aspect-fit" => Ok(FillMode::PreserveAspectFit),
+            _ => Err(TiledError::invalid_value("fill mode", s)),
+        }
+    }
+}
+
+impl fmt::Display for FillMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FillMode::Stretch => write!(f, "stretch"),
+            FillMode::PreserveAspectFit => write!(f, "preserve-aspect-fit"),
+        }
+    }
+}
+
+/// The order the objects of an object group are drawn in.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub enum DrawOrder {
+    /// Objects are drawn sorted by their y position, so those further down
+    /// are drawn on top.
+    #[default]
+    TopDown,
+    /// Objects are drawn in the order they appear in the group.
+    Index,
+}
+
+impl FromStr for DrawOrder {
+    type Err = TiledError;
+
+    fn from_str(s: &str) -> Result<DrawOrder, TiledError> {
+        match s {
+            "topdown" => Ok(DrawOrder::TopDown),
+            "index" => Ok(DrawOrder::Index),
+            _ => Err(TiledError::invalid_value("draw order", s)),
+        }
+    }
+}
+
+impl fmt::Display for DrawOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DrawOrder::TopDown => write!(f, "topdown"),
+            DrawOrder::Index => write!(f, "index"),
+        }
+    }
+}
+
+/// Returns the index of the tileset holding the given global tile id.
+/// Where the ranges of tilesets overlap, the later tileset wins, and gids
+/// beyond the known ranges belong to the tileset with the highest first gid
+/// below them.
+fn find_tileset(tilesets: &[Arc<Tileset>], gid: u32) -> Option<usize> {
+    let containing = tilesets.iter().rposition(|tileset| {
+        let first_gid = tileset.first_gid.value();
+        first_gid <= gid && tileset.tilecount.is_some_and(|count| gid - first_gid < count)
+    });
+    if containing.is_some() {
+        return containing;
+    }
+    let mut maximum_gid: i64 = -1;
+    let mut maximum_ts = None;
+    for (i, tileset) in tilesets.iter().enumerate() {
+        let first_gid = tileset.first_gid.value();
+        if first_gid as i64 >= maximum_gid && first_gid <= gid {
+            maximum_gid = first_gid as i64;
+            maximum_ts = Some(i);
+        }
+    }
+    maximum_ts
+}
+
+/// The tilesets of a map indexed by their ranges of global tile ids, from
+/// `Map::tileset_index`, for resolving many tiles without searching through
+/// the tilesets each time, like every tile of a large layer each frame. The
+/// index borrows the map, so the tilesets can't change while it is in use.
+#[derive(Debug, Clone)]
+pub struct TilesetIndex<'a> {
+    tilesets: &'a [Arc<Tileset>],
+    /// The first gid of each range, sorted, along with the index of the
+    /// tileset holding the gids of the range.
+    ranges: Vec<(u32, Option<usize>)>,
+    /// The tiles of each tileset, indexed by their local ids.
+    tiles: Vec<TileIndex<'a>>,
+}
+
+impl<'a> TilesetIndex<'a> {
+    fn new(tilesets: &'a [Arc<Tileset>]) -> TilesetIndex<'a> {
+        // `find_tileset` only changes its answer where a range starts or
+        // ends, so it is enough to ask it there.
+        let mut starts: Vec<u32> = tilesets
+            .iter()
+            .flat_map(|tileset| {
+                let first_gid = tileset.first_gid.value();
+                let end = tileset
+                    .tilecount
+                    .and_then(|count| first_gid.checked_add(count));
+                std::iter::once(first_gid).chain(end)
+            })
+            .collect();
+        starts.sort_unstable();
+        starts.dedup();
+        TilesetIndex {
+            tilesets,
+            ranges: starts
+                .into_iter()
+                .map(|gid| (gid, find_tileset(tilesets, gid)))
+                .collect(),
+            tiles: tilesets.iter().map(|tileset| tileset.tile_index()).collect(),
+        }
+    }
+
+    fn find(&self, gid: u32) -> Option<usize> {
+        let range = self.ranges.partition_point(|&(start, _)| start <= gid);
+        self.ranges[range.checked_sub(1)?].1
+    }
+
+    /// Returns the tileset holding the tile with the given global tile id,
+    /// along with the tile's local id within it, as `Map::tileset_for_gid`
+    /// does. Tilesets are found by a binary search over their gid ranges.
+    pub fn tileset_for_gid(&self, gid: impl Into<Gid>) -> Option<(&'a Tileset, u32)> {
+        let gid = gid.into().value();
+        let tileset = &**self.tilesets.get(self.find(gid)?)?;
+        Some((tileset, gid - tileset.first_gid.value()))
+    }
+
+    /// Returns the tile with the given global tile id from its tileset, as
+    /// `Map::get_tile_by_gid` does.
+    pub fn get_tile_by_gid(&self, gid: impl Into<Gid>) -> Option<&'a Tile> {
+        let gid = gid.into().value();
+        let index = self.find(gid)?;
+        self.tiles[index].get(gid - self.tilesets[index].first_gid.value())
+    }
+}
+
 /// A tileset, usually the tilesheet image.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Tileset {
     /// The GID of the first tile stored
-    pub first_gid: u32,
+    pub first_gid: Gid,
+    /// The path of the tileset file, for external tilesets. Paths in the
+    /// tileset are relative to it, see `Map::resolve_tileset_path`.
+    pub source: Option<PathBuf>,
     pub name: String,
+    /// The class of the tileset, for game logic to branch on.
+    pub user_type: Option<String>,
+    /// The version of the file format of the tileset. Embedded tilesets,
+    /// and external ones which don't say, take the version of their map.
+    pub version: Option<String>,
+    /// The version of Tiled the tileset was saved with, taken from the map
+    /// like `version`.
+    pub tiled_version: Option<String>,
     pub tile_width: u32,
     pub tile_height: u32,
     pub spacing: u32,
     pub margin: u32,
     pub tilecount: Option<u32>,
     pub columns: u32,
+    pub tile_render_size: TileRenderSize,
+    pub fill_mode: FillMode,
     /// The Tiled spec says that a tileset can have mutliple images so a `Vec`
     /// is used. Usually you will only use one.
     pub images: Vec<Image>,
     pub tiles: Vec<Tile>,
     pub properties: Properties,
+    pub wang_sets: Vec<WangSet>,
+    /// Attributes and elements the parser doesn't know, written back out
+    /// along with the tileset.
+    pub extra: Extra,
+}
+
+/// The tiles of a tileset indexed by their local ids, from
+/// `Tileset::tile_index`, for looking up many tiles without searching
+/// through them each time. The index borrows the tileset, so the tiles can't
+/// change while it is in use.
+#[derive(Debug, Clone)]
+pub struct TileIndex<'a> {
+    tiles: &'a [Tile],
+    positions: HashMap<u32, usize>,
+}
+
+impl<'a> TileIndex<'a> {
+    fn new(tiles: &'a [Tile]) -> TileIndex<'a> {
+        // Later tiles with the same id don't replace the first one, as with
+        // `Tileset::get_tile`.
+        let mut positions = HashMap::with_capacity(tiles.len());
+        for (i, tile) in tiles.iter().enumerate() {
+            positions.entry(tile.id).or_insert(i);
+        }
+        TileIndex { tiles, positions }
+    }
+
+    /// Returns the tile with the given local id, if the tileset lists it.
+    pub fn get(&self, id: u32) -> Option<&'a Tile> {
+        let tiles = self.tiles;
+        self.positions.get(&id).map(|&i| &tiles[i])
+    }
 }
 
 impl Tileset {
@@ -374,34 +1311,46 @@ impl Tileset {
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
         map_path: Option<&Path>,
-    ) -> Result<Tileset, TiledError> {
-        Tileset::new_internal(parser, &attrs).or_else(|_| Tileset::new_reference(&attrs, map_path))
+        loader: &Loader,
+        unresolved: &mut Vec<TilesetSource>,
+    ) -> Result<Option<Tileset>, TiledError> {
+        Tileset::new_internal(parser, &attrs)
+            .map(Some)
+            .or_else(|_| Tileset::new_reference(&attrs, map_path, loader, unresolved))
     }
 
     fn new_internal<R: Read>(
         parser: &mut EventReader<R>,
-        attrs: &Vec<OwnedAttribute>,
+        attrs: &[OwnedAttribute],
     ) -> Result<Tileset, TiledError> {
-        let ((spacing, margin, tilecount), (first_gid, name, width, height, columns)) = get_attrs!(
+        let mut extra = Extra::default();
+        let ((spacing, margin, tilecount, render_size, fill_mode, user_type, version, tiled_version), (first_gid, name, width, height, columns)) = get_attrs!(
            attrs,
            optionals: [
                 ("spacing", spacing, |v:String| v.parse().ok()),
                 ("margin", margin, |v:String| v.parse().ok()),
                 ("tilecount", tilecount, |v:String| v.parse().ok()),
+                ("tilerendersize", render_size, |v:String| v.parse().ok()),
+                ("fillmode", fill_mode, |v:String| v.parse().ok()),
+                ("class", user_type, Some),
+                ("version", version, Some),
+                ("tiledversion", tiled_version, Some),
             ],
            required: [
-                ("firstgid", first_gid, |v:String| v.parse().ok()),
+                ("firstgid", first_gid, |v:String| v.parse().ok().map(Gid)),
                 ("name", name, |v| Some(v)),
                 ("tilewidth", width, |v:String| v.parse().ok()),
                 ("tileheight", height, |v:String| v.parse().ok()),
                 ("columns", columns, |v:String| v.parse().ok()),
             ],
-            TiledError::MalformedAttributes("tileset must have a firstgid, name tile width and height with correct types".to_string())
+            "tileset",
+            extra: extra
         );
 
         let mut images = Vec::new();
         let mut tiles = Vec::new();
         let mut properties = HashMap::new();
+        let mut wang_sets = Vec::new();
         parse_tag!(parser, "tileset", {
             "image" => |attrs| {
                 images.push(Image::new(parser, attrs)?);
@@ -415,49 +1364,69 @@ impl Tileset {
                 tiles.push(Tile::new(parser, attrs)?);
                 Ok(())
             },
-        });
+            "wangsets" => |_| {
+                wang_sets = parse_wang_sets(parser)?;
+                Ok(())
+            },
+        }, extra: extra);
 
         Ok(Tileset {
+            source: None,
             tile_width: width,
             tile_height: height,
             spacing: spacing.unwrap_or(0),
             margin: margin.unwrap_or(0),
             first_gid,
             name,
+            user_type,
+            version,
+            tiled_version,
             tilecount,
             columns,
+            tile_render_size: render_size.unwrap_or_default(),
+            fill_mode: fill_mode.unwrap_or_default(),
             images,
             tiles,
             properties,
+            wang_sets,
+            extra,
         })
     }
 
+    /// Loads an external tileset, unless the loader skips them or defers
+    /// them to `unresolved`.
     fn new_reference(
-        attrs: &Vec<OwnedAttribute>,
+        attrs: &[OwnedAttribute],
         map_path: Option<&Path>,
-    ) -> Result<Tileset, TiledError> {
+        loader: &Loader,
+        unresolved: &mut Vec<TilesetSource>,
+    ) -> Result<Option<Tileset>, TiledError> {
         let ((), (first_gid, source)) = get_attrs!(
             attrs,
             optionals: [],
             required: [
-                ("firstgid", first_gid, |v:String| v.parse().ok()),
-                ("source", name, |v| Some(v)),
+                ("firstgid", first_gid, |v:String| v.parse().ok().map(Gid)),
+                ("source", name, Some),
             ],
-            TiledError::MalformedAttributes("tileset must have a firstgid, name, tilewidth, tileheight, and columns with correct types".to_string())
+            "tileset"
         );
 
+        if loader.skip_external_tilesets {
+            return Ok(None);
+        }
+        if loader.defer_external_tilesets {
+            unresolved.push(TilesetSource {
+                first_gid,
+                source: PathBuf::from(source),
+            });
+            return Ok(None);
+        }
         let tileset_path = map_path.ok_or(TiledError::Other("Maps with external tilesets must know their file location.  See parse_with_path(Path).".to_string()))?.with_file_name(source);
-        let file = File::open(&tileset_path).map_err(|_| {
-            TiledError::Other(format!(
-                "External tileset file not found: {:?}",
-                tileset_path
-            ))
-        })?;
-        Tileset::new_external(file, first_gid)
+        loader.load_tileset(&tileset_path, first_gid).map(Some)
     }
 
-    fn new_external<R: Read>(file: R, first_gid: u32) -> Result<Tileset, TiledError> {
-        let mut tileset_parser = EventReader::new(file);
+    pub(crate) fn new_external<R: Read>(file: R, first_gid: Gid) -> Result<Tileset, TiledError> {
+        let mut tileset_parser = xml_reader(file);
         loop {
             match tileset_parser
                 .next()
@@ -465,14 +1434,16 @@ impl Tileset {
             {
                 XmlEvent::StartElement {
                     name, attributes, ..
-                } => {
-                    if name.local_name == "tileset" {
-                        return Tileset::parse_external_tileset(
-                            first_gid,
-                            &mut tileset_parser,
-                            &attributes,
-                        );
-                    }
+                } if name.local_name == "tileset" => {
+                    return Tileset::parse_external_tileset(
+                        first_gid,
+                        &mut tileset_parser,
+                        &attributes,
+                    )
+                    .map_err(|e| {
+                        e.in_element(ElementContext::new("tileset", &attributes))
+                            .at(&tileset_parser)
+                    });
                 }
                 XmlEvent::EndDocument => {
                     return Err(TiledError::PrematureEnd(
@@ -485,29 +1456,37 @@ impl Tileset {
     }
 
     fn parse_external_tileset<R: Read>(
-        first_gid: u32,
+        first_gid: Gid,
         parser: &mut EventReader<R>,
-        attrs: &Vec<OwnedAttribute>,
+        attrs: &[OwnedAttribute],
     ) -> Result<Tileset, TiledError> {
-        let ((spacing, margin, tilecount), (name, width, height, columns)) = get_attrs!(
+        let mut extra = Extra::default();
+        let ((spacing, margin, tilecount, render_size, fill_mode, user_type, version, tiled_version), (name, width, height, columns)) = get_attrs!(
             attrs,
             optionals: [
                 ("spacing", spacing, |v:String| v.parse().ok()),
                 ("margin", margin, |v:String| v.parse().ok()),
                 ("tilecount", tilecount, |v:String| v.parse().ok()),
+                ("tilerendersize", render_size, |v:String| v.parse().ok()),
+                ("fillmode", fill_mode, |v:String| v.parse().ok()),
+                ("class", user_type, Some),
+                ("version", version, Some),
+                ("tiledversion", tiled_version, Some),
             ],
             required: [
-                ("name", name, |v| Some(v)),
+                ("name", name, Some),
                 ("tilewidth", width, |v:String| v.parse().ok()),
                 ("tileheight", height, |v:String| v.parse().ok()),
                 ("columns", columns, |v:String| v.parse().ok()),
             ],
-            TiledError::MalformedAttributes("tileset must have a firstgid, name, tilewidth, tileheight, and columns with correct types".to_string())
+            "tileset",
+            extra: extra
         );
 
         let mut images = Vec::new();
         let mut tiles = Vec::new();
         let mut properties = HashMap::new();
+        let mut wang_sets = Vec::new();
         parse_tag!(parser, "tileset", {
             "image" => |attrs| {
                 images.push(Image::new(parser, attrs)?);
@@ -521,33 +1500,83 @@ impl Tileset {
                 properties = parse_properties(parser)?;
                 Ok(())
             },
-        });
+            "wangsets" => |_| {
+                wang_sets = parse_wang_sets(parser)?;
+                Ok(())
+            },
+        }, extra: extra);
 
         Ok(Tileset {
-            first_gid: first_gid,
-            name: name,
+            first_gid,
+            source: None,
+            name,
+            user_type,
+            version,
+            tiled_version,
             tile_width: width,
             tile_height: height,
             spacing: spacing.unwrap_or(0),
             margin: margin.unwrap_or(0),
-            tilecount: tilecount,
-            columns: columns,
-            images: images,
-            tiles: tiles,
+            tilecount,
+            columns,
+            tile_render_size: render_size.unwrap_or_default(),
+            fill_mode: fill_mode.unwrap_or_default(),
+            images,
+            tiles,
             properties,
+            wang_sets,
+            extra,
         })
     }
+
+    /// Returns the tile with the given local id, if the tileset lists it.
+    /// Looking up many tiles is faster through `Tileset::tile_index`.
+    pub fn get_tile(&self, id: u32) -> Option<&Tile> {
+        self.tiles.iter().find(|t| t.id == id)
+    }
+
+    /// Indexes the tiles by their local ids, for looking up many of them
+    /// in constant time each.
+    pub fn tile_index(&self) -> TileIndex<'_> {
+        TileIndex::new(&self.tiles)
+    }
+
+    /// Returns the properties of the tile with the given id, including the
+    /// properties of the dominant wang color of each wang set the tile belongs
+    /// to. Properties set on the tile itself take precedence, followed by
+    /// those of wang sets appearing earlier in the tileset.
+    pub fn tile_properties_with_wang(&self, id: u32) -> Properties {
+        let mut properties = Properties::new();
+        for wang_set in self.wang_sets.iter().rev() {
+            if let Some(color) = wang_set.dominant_color(id) {
+                properties.extend(color.properties.clone());
+            }
+        }
+        if let Some(tile) = self.get_tile(id) {
+            properties.extend(tile.properties.clone());
+        }
+        properties
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Tile {
     pub id: u32,
     pub images: Vec<Image>,
+    /// The part of the tile's image used for the tile, for tiles of image
+    /// collection tilesets sharing one image. `None` if the whole image is
+    /// used.
+    pub source_rect: Option<ImageRect>,
     pub properties: Properties,
-    pub objectgroup: Option<ObjectGroup>,
+    /// The collision shapes of the tile, as an object layer along with its
+    /// name, properties and the other attributes of the group.
+    pub objectgroup: Option<Layer>,
     pub animation: Option<Vec<Frame>>,
     pub tile_type: Option<String>,
     pub probability: f32,
+    /// Attributes and elements the parser doesn't know, written back out
+    /// along with the tile.
+    pub extra: Extra,
 }
 
 impl Tile {
@@ -555,16 +1584,22 @@ impl Tile {
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
     ) -> Result<Tile, TiledError> {
-        let ((tile_type, probability), id) = get_attrs!(
+        let mut extra = Extra::default();
+        let ((tile_type, probability, x, y, width, height), id) = get_attrs!(
             attrs,
             optionals: [
                 ("type", tile_type, |v:String| v.parse().ok()),
                 ("probability", probability, |v:String| v.parse().ok()),
-            ],
-            required: [
+                ("x", x, |v:String| v.parse().ok()),
+                ("y", y, |v:String| v.parse().ok()),
+                ("width", width, |v:String| v.parse().ok()),
+                ("height", height, |v:String| v.parse().ok()),
+            ],
+            required: [
                 ("id", id, |v:String| v.parse::<u32>().ok()),
             ],
-            TiledError::MalformedAttributes("tile must have an id with the correct type".to_string())
+            "tile",
+            extra: extra
         );
 
         let mut images = Vec::new();
@@ -581,139 +1616,684 @@ impl Tile {
                 Ok(())
             },
             "objectgroup" => |attrs| {
-                objectgroup = Some(ObjectGroup::new(parser, attrs, None)?);
+                objectgroup = Some(Layer::new(parser, attrs, LayerTag::Objects, &Loader::new())?);
                 Ok(())
             },
             "animation" => |_| {
                 animation = Some(parse_animation(parser)?);
                 Ok(())
             },
-        });
+        }, extra: extra);
+        // The rectangle defaults to the whole image when partially given.
+        let source_rect = if x.is_some() || y.is_some() || width.is_some() || height.is_some() {
+            let image = images.first();
+            Some(ImageRect {
+                x: x.unwrap_or(0),
+                y: y.unwrap_or(0),
+                width: width.or_else(|| image.map(|i| i.width)).unwrap_or(0),
+                height: height.or_else(|| image.map(|i| i.height)).unwrap_or(0),
+            })
+        } else {
+            None
+        };
         Ok(Tile {
             id,
             images,
+            source_rect,
             properties,
             objectgroup,
             animation,
             tile_type,
             probability: probability.unwrap_or(1.0),
+            extra,
+        })
+    }
+}
+
+/// A set of wang colors, used by Tiled's terrain brushes to describe how tiles
+/// connect to each other.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WangSet {
+    pub name: String,
+    /// Either `corner`, `edge` or `mixed`.
+    pub wang_type: String,
+    /// The tile id representing the set, or -1 if there is none.
+    pub tile: i32,
+    pub colors: Vec<WangColor>,
+    pub wang_tiles: Vec<WangTile>,
+    pub properties: Properties,
+}
+
+impl WangSet {
+    fn new<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+    ) -> Result<WangSet, TiledError> {
+        let ((wang_type, tile), name) = get_attrs!(
+            attrs,
+            optionals: [
+                ("type", wang_type, Some),
+                ("tile", tile, |v:String| v.parse().ok()),
+            ],
+            required: [
+                ("name", name, Some),
+            ],
+            "wangset"
+        );
+
+        let mut colors = Vec::new();
+        let mut wang_tiles = Vec::new();
+        let mut properties = HashMap::new();
+        parse_tag!(parser, "wangset", {
+            "wangcolor" => |attrs| {
+                colors.push(WangColor::new(parser, attrs)?);
+                Ok(())
+            },
+            "wangtile" => |attrs| {
+                wang_tiles.push(WangTile::new(attrs)?);
+                Ok(())
+            },
+            "properties" => |_| {
+                properties = parse_properties(parser)?;
+                Ok(())
+            },
+        });
+
+        Ok(WangSet {
+            name,
+            wang_type: wang_type.unwrap_or_else(|| "corner".to_string()),
+            tile: tile.unwrap_or(-1),
+            colors,
+            wang_tiles,
+            properties,
+        })
+    }
+
+    /// Returns the color which occurs most often in the given tile's wang id,
+    /// if the tile is part of this set. Ties are resolved in favour of the
+    /// color which comes first in the set.
+    pub fn dominant_color(&self, tile_id: u32) -> Option<&WangColor> {
+        let wang_tile = self.wang_tiles.iter().find(|t| t.tile_id == tile_id)?;
+        let mut counts = vec![0; self.colors.len()];
+        for &color in wang_tile.wang_id.iter() {
+            if color > 0 && (color as usize) <= counts.len() {
+                counts[color as usize - 1] += 1;
+            }
+        }
+        let (index, &count) = counts
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|&(_, count)| count)?;
+        if count == 0 {
+            return None;
+        }
+        self.colors.get(index)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WangColor {
+    pub name: String,
+    pub colour: Colour,
+    /// The tile id representing the color, or -1 if there is none.
+    pub tile: i32,
+    pub probability: f32,
+    pub properties: Properties,
+}
+
+impl WangColor {
+    fn new<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+    ) -> Result<WangColor, TiledError> {
+        let ((tile, probability), (name, colour)) = get_attrs!(
+            attrs,
+            optionals: [
+                ("tile", tile, |v:String| v.parse().ok()),
+                ("probability", probability, |v:String| v.parse().ok()),
+            ],
+            required: [
+                ("name", name, Some),
+                ("color", colour, |v:String| v.parse().ok()),
+            ],
+            "wangcolor"
+        );
+
+        let mut properties = HashMap::new();
+        parse_tag!(parser, "wangcolor", {
+            "properties" => |_| {
+                properties = parse_properties(parser)?;
+                Ok(())
+            },
+        });
+
+        Ok(WangColor {
+            name,
+            colour,
+            tile: tile.unwrap_or(-1),
+            probability: probability.unwrap_or(1.0),
+            properties,
+        })
+    }
+}
+
+/// Associates a tile with the wang colors of its edges and corners.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct WangTile {
+    pub tile_id: u32,
+    /// The color index (starting at 1, 0 meaning no color) of each side and
+    /// corner of the tile, clockwise starting from the top.
+    pub wang_id: [u8; 8],
+}
+
+impl WangTile {
+    fn new(attrs: Vec<OwnedAttribute>) -> Result<WangTile, TiledError> {
+        let ((), (tile_id, wang_id)) = get_attrs!(
+            attrs,
+            optionals: [],
+            required: [
+                ("tileid", tile_id, |v:String| v.parse().ok()),
+                ("wangid", wang_id, |v:String| parse_wang_id(&v)),
+            ],
+            "wangtile"
+        );
+        Ok(WangTile { tile_id, wang_id })
+    }
+}
+
+fn parse_wang_id(s: &str) -> Option<[u8; 8]> {
+    let mut wang_id = [0; 8];
+    let mut values = s.split(',');
+    for value in wang_id.iter_mut() {
+        *value = values.next()?.trim().parse().ok()?;
+    }
+    if values.next().is_some() {
+        return None;
+    }
+    Some(wang_id)
+}
+
+fn parse_wang_sets<R: Read>(parser: &mut EventReader<R>) -> Result<Vec<WangSet>, TiledError> {
+    let mut wang_sets = Vec::new();
+    parse_tag!(parser, "wangsets", {
+        "wangset" => |attrs| {
+            wang_sets.push(WangSet::new(parser, attrs)?);
+            Ok(())
+        },
+    });
+    Ok(wang_sets)
+}
+
+/// A rectangle within an image, in pixels.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct ImageRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Image data embedded in a map or tileset instead of being referenced by
+/// path.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ImageData {
+    /// The file format of the image, like `png`.
+    pub format: String,
+    /// The contents of the image file.
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Image {
+    /// The filepath of the image relative to the map or tileset, empty if
+    /// the image is embedded.
+    pub source: PathBuf,
+    pub width: i32,
+    pub height: i32,
+    pub transparent_colour: Option<Colour>,
+    /// The image itself, if it is embedded instead of referenced by `source`.
+    pub data: Option<ImageData>,
+}
+
+impl Image {
+    fn new<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+    ) -> Result<Image, TiledError> {
+        let ((c, s, f), (w, h)) = get_attrs!(
+            attrs,
+            optionals: [
+                ("trans", trans, |v:String| v.parse().ok()),
+                ("source", source, Some),
+                ("format", format, Some),
+            ],
+            required: [
+                ("width", width, |v:String| v.parse().ok()),
+                ("height", height, |v:String| v.parse().ok()),
+            ],
+            "image"
+        );
+
+        let mut bytes = None;
+        parse_tag!(parser, "image", {
+            "data" => |attrs: Vec<OwnedAttribute>| {
+                let encoding = attrs.iter().find(|a| a.name.local_name == "encoding");
+                if encoding.map(|a| a.value.as_str()) != Some("base64") {
+                    return Err(TiledError::Other("Embedded images must be base64 encoded".to_string()));
+                }
+                bytes = Some(parse_base64(parser)?);
+                Ok(())
+            },
+        });
+        let data = bytes.map(|bytes| ImageData {
+            format: f.unwrap_or_default(),
+            bytes,
+        });
+        if s.is_none() && data.is_none() {
+            return Err(TiledError::MalformedAttributes(
+                "image must have a source or embedded data".to_string(),
+            ));
+        }
+        Ok(Image {
+            source: s.map(PathBuf::from).unwrap_or_default(),
+            width: w,
+            height: h,
+            transparent_colour: c,
+            data,
+        })
+    }
+}
+
+const FLIPPED_HORIZONTALLY_FLAG: u32 = 0x80000000;
+const FLIPPED_VERTICALLY_FLAG: u32 = 0x40000000;
+const FLIPPED_DIAGONALLY_FLAG: u32 = 0x20000000;
+const ALL_FLIP_FLAGS: u32 =
+    FLIPPED_HORIZONTALLY_FLAG | FLIPPED_VERTICALLY_FLAG | FLIPPED_DIAGONALLY_FLAG;
+
+/// A global tile id as stored in layer data and on tile objects: the id of
+/// a tile across all tilesets of the map, with its flip flags in the highest
+/// bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Gid(pub u32);
+
+impl Gid {
+    /// The gid of empty cells and of objects which aren't tiles.
+    pub const EMPTY: Gid = Gid(0);
+
+    /// Returns the global tile id without the flip flags.
+    pub fn value(self) -> u32 {
+        self.0 & !ALL_FLIP_FLAGS
+    }
+
+    /// Whether the tile is flipped over the y axis.
+    pub fn flip_h(self) -> bool {
+        self.0 & FLIPPED_HORIZONTALLY_FLAG != 0
+    }
+
+    /// Whether the tile is flipped over the x axis.
+    pub fn flip_v(self) -> bool {
+        self.0 & FLIPPED_VERTICALLY_FLAG != 0
+    }
+
+    /// Whether the x and y axes of the tile are swapped, flipping it over the
+    /// y = -x line.
+    pub fn flip_d(self) -> bool {
+        self.0 & FLIPPED_DIAGONALLY_FLAG != 0
+    }
+
+    /// Whether the gid refers to no tile at all.
+    pub fn is_empty(self) -> bool {
+        self.value() == 0
+    }
+}
+
+impl fmt::Display for Gid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<u32> for Gid {
+    fn from(bits: u32) -> Gid {
+        Gid(bits)
+    }
+}
+
+impl From<LayerTile> for Gid {
+    fn from(tile: LayerTile) -> Gid {
+        Gid(tile.to_bits())
+    }
+}
+
+/// Stores the proper tile gid, along with how it is flipped. The cells of
+/// tile layers hold `Option<LayerTile>`, with `None` for empty cells, so a
+/// `LayerTile` always refers to a tile.
+// Maybe PartialEq and Eq should be custom, so that it ignores tile-flipping?
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayerTile {
+    /// The global tile id, without the flip flags.
+    pub gid: Gid,
+    pub flip_h: bool,
+    pub flip_v: bool,
+    pub flip_d: bool,
+}
+
+impl LayerTile {
+    /// Unpacks a tile from a `Gid` or the raw form used in layer data. The
+    /// gid should not be 0, which stands for an empty cell; see
+    /// `LayerTile::from_bits`.
+    pub fn new(gid: impl Into<Gid>) -> LayerTile {
+        let gid = gid.into();
+        LayerTile {
+            gid: Gid(gid.value()),
+            flip_h: gid.flip_h(),
+            flip_v: gid.flip_v(),
+            flip_d: gid.flip_d(),
+        }
+    }
+
+    /// Unpacks a cell of layer data, which is empty if its gid is 0.
+    pub fn from_bits(bits: u32) -> Option<LayerTile> {
+        let gid = Gid(bits);
+        if gid.is_empty() {
+            return None;
+        }
+        Some(LayerTile::new(gid))
+    }
+
+    /// Packs the gid and the flip flags back into the form used in layer data.
+    pub(crate) fn to_bits(self) -> u32 {
+        let mut bits = self.gid.value();
+        if self.flip_h {
+            bits |= FLIPPED_HORIZONTALLY_FLAG;
+        }
+        if self.flip_v {
+            bits |= FLIPPED_VERTICALLY_FLAG;
+        }
+        if self.flip_d {
+            bits |= FLIPPED_DIAGONALLY_FLAG;
+        }
+        bits
+    }
+}
+
+/// Packs a cell of layer data, with 0 for empty cells.
+pub(crate) fn cell_bits(cell: Option<LayerTile>) -> u32 {
+    cell.map_or(0, LayerTile::to_bits)
+}
+
+/// A layer of any type, along with the attributes shared by all of them.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Layer {
+    /// The id of the layer, unique within the map, or 0 for maps saved
+    /// before Tiled gave layers ids.
+    pub id: u32,
+    pub name: String,
+    /// The class of the layer, for game logic to branch on.
+    pub user_type: Option<String>,
+    pub opacity: f32,
+    pub visible: bool,
+    /// Whether the layer is locked against editing.
+    pub locked: bool,
+    pub offset_x: f32,
+    pub offset_y: f32,
+    /// A colour multiplied with the layer's contents when drawing it.
+    pub tint_colour: Option<Colour>,
+    pub properties: Properties,
+    pub layer_type: LayerType,
+    /// Attributes and elements the parser doesn't know, written back out
+    /// along with the layer.
+    pub extra: Extra,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LayerType {
+    TileLayer(TileLayer),
+    ObjectLayer(ObjectGroup),
+    ImageLayer(ImageLayer),
+    /// A group layer, holding the layers within it from the bottom up.
+    Group(Vec<Layer>),
+}
+
+/// The kind of layer element being parsed, along with what is needed to parse
+/// it.
+enum LayerTag {
+    /// A tile layer, along with the size of the map, used if the layer has
+    /// no size of its own.
+    Tiles { width: u32, height: u32, infinite: bool },
+    Objects,
+    Image,
+    /// A group layer, along with the size of the map for the tile layers
+    /// within it.
+    Group { width: u32, height: u32, infinite: bool },
+}
+
+impl Layer {
+    fn new<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+        tag: LayerTag,
+        loader: &Loader,
+    ) -> Result<Layer, TiledError> {
+        let mut extra = Extra::default();
+        let ((id, o, v, l, ox, oy, n, u, t, w, h), ()) = get_attrs!(
+            attrs,
+            optionals: [
+                ("id", id, |v:String| v.parse().ok()),
+                ("opacity", opacity, |v:String| v.parse().ok()),
+                ("visible", visible, |v:String| v.parse().ok().map(|x:i32| x == 1)),
+                ("locked", locked, |v:String| v.parse().ok().map(|x:i32| x == 1)),
+                ("offsetx", offset_x, |v:String| v.parse().ok()),
+                ("offsety", offset_y, |v:String| v.parse().ok()),
+                ("name", name, Some),
+                ("class", user_type, Some),
+                ("tintcolor", tint_colour, |v:String| v.parse().ok()),
+                ("width", width, |v:String| v.parse().ok()),
+                ("height", height, |v:String| v.parse().ok()),
+            ],
+            required: [],
+            "layer",
+            extra: extra
+        );
+        let (layer_type, properties) = match tag {
+            LayerTag::Tiles { width, height, infinite } => {
+                let (layer, properties) = TileLayer::new(parser, w.unwrap_or(width), h.unwrap_or(height), infinite, loader, &mut extra)?;
+                (LayerType::TileLayer(layer), properties)
+            }
+            LayerTag::Objects => {
+                // The attributes of object groups are read by the group.
+                extra
+                    .attributes
+                    .retain(|(name, _)| name != "color" && name != "draworder");
+                let (group, properties) = ObjectGroup::new(parser, attrs, &mut extra)?;
+                (LayerType::ObjectLayer(group), properties)
+            }
+            LayerTag::Image => {
+                let (layer, properties) = ImageLayer::new(parser, &mut extra)?;
+                (LayerType::ImageLayer(layer), properties)
+            }
+            LayerTag::Group { width, height, infinite } => {
+                let (layers, properties) = Layer::parse_group_layers(parser, (width, height, infinite), loader, &mut extra)?;
+                (LayerType::Group(layers), properties)
+            }
+        };
+
+        Ok(Layer {
+            id: id.unwrap_or(0),
+            name: n.unwrap_or_default(),
+            user_type: u,
+            opacity: o.unwrap_or(1.0),
+            visible: v.unwrap_or(true),
+            locked: l.unwrap_or(false),
+            offset_x: ox.unwrap_or(0.0),
+            offset_y: oy.unwrap_or(0.0),
+            tint_colour: t,
+            properties,
+            layer_type,
+            extra,
         })
     }
-}
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub struct Image {
-    /// The filepath of the image
-    pub source: String,
-    pub width: i32,
-    pub height: i32,
-    pub transparent_colour: Option<Colour>,
-}
+    /// Parses a layer, unless the loader's layer filter rejects it, in which
+    /// case its contents are skipped without being decoded.
+    fn new_filtered<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+        tag: LayerTag,
+        loader: &Loader,
+    ) -> Result<Option<Layer>, TiledError> {
+        let attr = |name: &str| {
+            attrs
+                .iter()
+                .find(|a| a.name.local_name == name)
+                .map(|a| a.value.as_str())
+        };
+        let info = LayerInfo {
+            name: attr("name").unwrap_or(""),
+            id: attr("id").and_then(|id| id.parse().ok()),
+            kind: match tag {
+                LayerTag::Tiles { .. } => LayerKind::Tiles,
+                LayerTag::Objects => LayerKind::Objects,
+                LayerTag::Image => LayerKind::Image,
+                LayerTag::Group { .. } => return Layer::new_group(parser, attrs, tag, loader),
+            },
+        };
+        if !loader.keeps_layer(&info) {
+            skip_element(parser)?;
+            return Ok(None);
+        }
+        Layer::new(parser, attrs, tag, loader).map(Some)
+    }
+
+    /// Parses a group layer. Groups aren't filtered themselves, but groups
+    /// left empty by the loader's layer filter are dropped.
+    fn new_group<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+        tag: LayerTag,
+        loader: &Loader,
+    ) -> Result<Option<Layer>, TiledError> {
+        let group = Layer::new(parser, attrs, tag, loader)?;
+        let emptied =
+            loader.filters_layers() && group.as_group_layer().is_some_and(<[Layer]>::is_empty);
+        Ok(if emptied { None } else { Some(group) })
+    }
+
+    /// Parses the layers and properties within a group layer.
+    fn parse_group_layers<R: Read>(
+        parser: &mut EventReader<R>,
+        (width, height, infinite): (u32, u32, bool),
+        loader: &Loader,
+        extra: &mut Extra,
+    ) -> Result<(Vec<Layer>, Properties), TiledError> {
+        let mut layers = Vec::new();
+        let mut properties = HashMap::new();
+        parse_tag!(parser, "group", {
+            "layer" => |attrs| {
+                layers.extend(Layer::new_filtered(parser, attrs, LayerTag::Tiles { width, height, infinite }, loader)?);
+                Ok(())
+            },
+            "imagelayer" => |attrs| {
+                layers.extend(Layer::new_filtered(parser, attrs, LayerTag::Image, loader)?);
+                Ok(())
+            },
+            "objectgroup" => |attrs| {
+                layers.extend(Layer::new_filtered(parser, attrs, LayerTag::Objects, loader)?);
+                Ok(())
+            },
+            "group" => |attrs| {
+                layers.extend(Layer::new_filtered(parser, attrs, LayerTag::Group { width, height, infinite }, loader)?);
+                Ok(())
+            },
+            "properties" => |_| {
+                properties = parse_properties(parser)?;
+                Ok(())
+            },
+        }, extra: extra);
+        Ok((layers, properties))
+    }
+
+    pub fn as_tile_layer(&self) -> Option<&TileLayer> {
+        match self.layer_type {
+            LayerType::TileLayer(ref layer) => Some(layer),
+            _ => None,
+        }
+    }
 
-impl Image {
-    fn new<R: Read>(
-        parser: &mut EventReader<R>,
-        attrs: Vec<OwnedAttribute>,
-    ) -> Result<Image, TiledError> {
-        let (c, (s, w, h)) = get_attrs!(
-            attrs,
-            optionals: [
-                ("trans", trans, |v:String| v.parse().ok()),
-            ],
-            required: [
-                ("source", source, |v| Some(v)),
-                ("width", width, |v:String| v.parse().ok()),
-                ("height", height, |v:String| v.parse().ok()),
-            ],
-            TiledError::MalformedAttributes("image must have a source, width and height with correct types".to_string())
-        );
+    pub fn as_tile_layer_mut(&mut self) -> Option<&mut TileLayer> {
+        match self.layer_type {
+            LayerType::TileLayer(ref mut layer) => Some(layer),
+            _ => None,
+        }
+    }
 
-        parse_tag!(parser, "image", { "" => |_| Ok(()) });
-        Ok(Image {
-            source: s,
-            width: w,
-            height: h,
-            transparent_colour: c,
-        })
+    pub fn as_object_layer(&self) -> Option<&ObjectGroup> {
+        match self.layer_type {
+            LayerType::ObjectLayer(ref group) => Some(group),
+            _ => None,
+        }
     }
-}
 
-/// Stores the proper tile gid, along with how it is flipped.
-// Maybe PartialEq and Eq should be custom, so that it ignores tile-flipping?
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct LayerTile {
-    pub gid: u32,
-    pub flip_h: bool,
-    pub flip_v: bool,
-    pub flip_d: bool,
-}
+    pub fn as_object_layer_mut(&mut self) -> Option<&mut ObjectGroup> {
+        match self.layer_type {
+            LayerType::ObjectLayer(ref mut group) => Some(group),
+            _ => None,
+        }
+    }
 
-const FLIPPED_HORIZONTALLY_FLAG: u32 = 0x80000000;
-const FLIPPED_VERTICALLY_FLAG: u32 = 0x40000000;
-const FLIPPED_DIAGONALLY_FLAG: u32 = 0x20000000;
-const ALL_FLIP_FLAGS: u32 =
-    FLIPPED_HORIZONTALLY_FLAG | FLIPPED_VERTICALLY_FLAG | FLIPPED_DIAGONALLY_FLAG;
+    pub fn as_image_layer(&self) -> Option<&ImageLayer> {
+        match self.layer_type {
+            LayerType::ImageLayer(ref layer) => Some(layer),
+            _ => None,
+        }
+    }
 
-impl LayerTile {
-    pub fn new(id: u32) -> LayerTile {
-        let flags = id & ALL_FLIP_FLAGS;
-        let gid = id & !ALL_FLIP_FLAGS;
-        let flip_d = flags & FLIPPED_DIAGONALLY_FLAG == FLIPPED_DIAGONALLY_FLAG; // Swap x and y axis (anti-diagonally) [flips over y = -x line]
-        let flip_h = flags & FLIPPED_HORIZONTALLY_FLAG == FLIPPED_HORIZONTALLY_FLAG; // Flip tile over y axis
-        let flip_v = flags & FLIPPED_VERTICALLY_FLAG == FLIPPED_VERTICALLY_FLAG; // Flip tile over x axis
+    /// Returns the layers within a group layer.
+    pub fn as_group_layer(&self) -> Option<&[Layer]> {
+        match self.layer_type {
+            LayerType::Group(ref layers) => Some(layers),
+            _ => None,
+        }
+    }
 
-        LayerTile {
-            gid,
-            flip_h,
-            flip_v,
-            flip_d,
+    pub fn as_group_layer_mut(&mut self) -> Option<&mut Vec<Layer>> {
+        match self.layer_type {
+            LayerType::Group(ref mut layers) => Some(layers),
+            _ => None,
         }
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub struct Layer {
-    pub name: String,
-    pub opacity: f32,
-    pub visible: bool,
-    pub offset_x: f32,
-    pub offset_y: f32,
+pub struct TileLayer {
+    /// The width of the layer in tiles. Usually the same as the map's width,
+    /// and used to split its data into rows.
+    pub width: u32,
+    /// The height of the layer in tiles.
+    pub height: u32,
     /// The tiles are arranged in rows. Each tile is a number which can be used
     ///  to find which tileset it belongs to and can then be rendered.
     pub tiles: LayerData,
-    pub properties: Properties,
-    pub layer_index: u32,
 }
 
-impl Layer {
+impl TileLayer {
     fn new<R: Read>(
         parser: &mut EventReader<R>,
-        attrs: Vec<OwnedAttribute>,
         width: u32,
-        layer_index: u32,
+        height: u32,
         infinite: bool,
-    ) -> Result<Layer, TiledError> {
-        let ((o, v, ox, oy), n) = get_attrs!(
-            attrs,
-            optionals: [
-                ("opacity", opacity, |v:String| v.parse().ok()),
-                ("visible", visible, |v:String| v.parse().ok().map(|x:i32| x == 1)),
-                ("offsetx", offset_x, |v:String| v.parse().ok()),
-                ("offsety", offset_y, |v:String| v.parse().ok()),
-            ],
-            required: [
-                ("name", name, |v| Some(v)),
-            ],
-            TiledError::MalformedAttributes("layer must have a name".to_string())
-        );
+        loader: &Loader,
+        extra: &mut Extra,
+    ) -> Result<(TileLayer, Properties), TiledError> {
         let mut tiles: LayerData = LayerData::Finite(Default::default());
         let mut properties = HashMap::new();
         parse_tag!(parser, "layer", {
             "data" => |attrs| {
                 if infinite {
-                    tiles = parse_infinite_data(parser, attrs, width)?;
+                    tiles = parse_infinite_data(parser, attrs, loader)?;
                 } else {
-                    tiles = parse_data(parser, attrs, width)?;
+                    tiles = parse_data(parser, attrs, width, height, loader)?;
                 }
                 Ok(())
             },
@@ -721,23 +2301,15 @@ impl Layer {
                 properties = parse_properties(parser)?;
                 Ok(())
             },
-        });
+        }, extra: extra);
 
-        Ok(Layer {
-            name: n,
-            opacity: o.unwrap_or(1.0),
-            visible: v.unwrap_or(true),
-            offset_x: ox.unwrap_or(0.0),
-            offset_y: oy.unwrap_or(0.0),
-            tiles: tiles,
-            properties: properties,
-            layer_index,
-        })
+        Ok((TileLayer { width, height, tiles }, properties))
     }
 }
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum LayerData {
-    Finite(Vec<Vec<LayerTile>>),
+    Finite(Vec<Vec<Option<LayerTile>>>),
     Infinite(HashMap<(i32, i32), Chunk>),
 }
 
@@ -747,7 +2319,7 @@ pub struct Chunk {
     pub y: i32,
     pub width: u32,
     pub height: u32,
-    pub tiles: Vec<Vec<LayerTile>>,
+    pub tiles: Vec<Vec<Option<LayerTile>>>,
 }
 
 impl Chunk {
@@ -756,6 +2328,7 @@ impl Chunk {
         attrs: Vec<OwnedAttribute>,
         encoding: Option<String>,
         compression: Option<String>,
+        loader: &Loader,
     ) -> Result<Chunk, TiledError> {
         let ((), (x, y, width, height)) = get_attrs!(
             attrs,
@@ -763,13 +2336,19 @@ impl Chunk {
             required: [
                 ("x", x, |v: String| v.parse().ok()),
                 ("y", y, |v: String| v.parse().ok()),
-                ("width", width, |v: String| v.parse().ok()),
-                ("height", height, |v: String| v.parse().ok()),
+                ("width", width, parse_chunk_size),
+                ("height", height, parse_chunk_size),
             ],
-            TiledError::MalformedAttributes("layer must have a name".to_string())
+            "chunk"
         );
+        if geometry::area_end(&TileRect::new(x, y, width, height)).is_none() {
+            return Err(TiledError::Other(format!(
+                "Chunk at ({}, {}) reaches past the largest tile coordinates",
+                x, y
+            )));
+        }
 
-        let tiles = parse_data_line(encoding, compression, parser, width)?;
+        let tiles = parse_data_line(encoding, compression, parser, width, height, loader)?;
 
         Ok(Chunk {
             x,
@@ -781,36 +2360,24 @@ impl Chunk {
     }
 }
 
+/// Parses the width or height of a chunk, which must be positive and fit an
+/// `i32` for its tiles to be found by position.
+fn parse_chunk_size(v: String) -> Option<u32> {
+    v.parse()
+        .ok()
+        .filter(|&size| size > 0 && size <= i32::MAX as u32)
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct ImageLayer {
-    pub name: String,
-    pub opacity: f32,
-    pub visible: bool,
-    pub offset_x: f32,
-    pub offset_y: f32,
     pub image: Option<Image>,
-    pub properties: Properties,
-    pub layer_index: u32,
 }
 
 impl ImageLayer {
     fn new<R: Read>(
         parser: &mut EventReader<R>,
-        attrs: Vec<OwnedAttribute>,
-        layer_index: u32,
-    ) -> Result<ImageLayer, TiledError> {
-        let ((o, v, ox, oy), n) = get_attrs!(
-            attrs,
-            optionals: [
-                ("opacity", opacity, |v:String| v.parse().ok()),
-                ("visible", visible, |v:String| v.parse().ok().map(|x:i32| x == 1)),
-                ("offsetx", offset_x, |v:String| v.parse().ok()),
-                ("offsety", offset_y, |v:String| v.parse().ok()),
-            ],
-            required: [
-                ("name", name, |v| Some(v)),
-            ],
-            TiledError::MalformedAttributes("layer must have a name".to_string()));
+        extra: &mut Extra,
+    ) -> Result<(ImageLayer, Properties), TiledError> {
         let mut properties = HashMap::new();
         let mut image: Option<Image> = None;
         parse_tag!(parser, "imagelayer", {
@@ -822,50 +2389,34 @@ impl ImageLayer {
                 properties = parse_properties(parser)?;
                 Ok(())
             },
-        });
-        Ok(ImageLayer {
-            name: n,
-            opacity: o.unwrap_or(1.0),
-            visible: v.unwrap_or(true),
-            offset_x: ox.unwrap_or(0.0),
-            offset_y: oy.unwrap_or(0.0),
-            image,
-            properties,
-            layer_index,
-        })
+        }, extra: extra);
+        Ok((ImageLayer { image }, properties))
     }
 }
 
+/// The objects of an object layer, or the collision shapes of a tile.
 #[derive(Debug, PartialEq, Clone)]
 pub struct ObjectGroup {
-    pub name: String,
-    pub opacity: f32,
-    pub visible: bool,
     pub objects: Vec<Object>,
     pub colour: Option<Colour>,
-    /**
-     * Layer index is not preset for tile collision boxes
-     */
-    pub layer_index: Option<u32>,
-    pub properties: Properties,
+    /// The order Tiled draws the objects in, see `objects_in_draw_order`.
+    pub draw_order: DrawOrder,
 }
 
 impl ObjectGroup {
     fn new<R: Read>(
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
-        layer_index: Option<u32>,
-    ) -> Result<ObjectGroup, TiledError> {
-        let ((o, v, c, n), ()) = get_attrs!(
+        extra: &mut Extra,
+    ) -> Result<(ObjectGroup, Properties), TiledError> {
+        let ((c, d), ()) = get_attrs!(
             attrs,
             optionals: [
-                ("opacity", opacity, |v:String| v.parse().ok()),
-                ("visible", visible, |v:String| v.parse().ok().map(|x:i32| x == 1)),
                 ("color", colour, |v:String| v.parse().ok()),
-                ("name", name, |v:String| v.into()),
+                ("draworder", draw_order, |v:String| v.parse().ok()),
             ],
             required: [],
-            TiledError::MalformedAttributes("object groups must have a name".to_string())
+            "objectgroup"
         );
         let mut objects = Vec::new();
         let mut properties = HashMap::new();
@@ -878,16 +2429,24 @@ impl ObjectGroup {
                 properties = parse_properties(parser)?;
                 Ok(())
             },
-        });
-        Ok(ObjectGroup {
-            name: n.unwrap_or(String::new()),
-            opacity: o.unwrap_or(1.0),
-            visible: v.unwrap_or(true),
-            objects: objects,
+        }, extra: extra);
+        let group = ObjectGroup {
+            objects,
             colour: c,
-            layer_index,
-            properties,
-        })
+            draw_order: d.unwrap_or_default(),
+        };
+        Ok((group, properties))
+    }
+
+    /// Returns the objects in the order Tiled draws them in, from the bottom
+    /// up. With the top down draw order, objects with the same y position
+    /// keep the order they appear in the group.
+    pub fn objects_in_draw_order(&self) -> Vec<&Object> {
+        let mut objects: Vec<&Object> = self.objects.iter().collect();
+        if self.draw_order == DrawOrder::TopDown {
+            objects.sort_by(|a, b| a.y.total_cmp(&b.y));
+        }
+        objects
     }
 }
 
@@ -898,12 +2457,172 @@ pub enum ObjectShape {
     Polyline { points: Vec<(f32, f32)> },
     Polygon { points: Vec<(f32, f32)> },
     Point(f32, f32),
+    Text(Text),
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum HorizontalAlignment {
+    Left,
+    Center,
+    Right,
+    Justify,
+}
+
+impl FromStr for HorizontalAlignment {
+    type Err = TiledError;
+
+    fn from_str(s: &str) -> Result<HorizontalAlignment, TiledError> {
+        match s {
+            "left" => Ok(HorizontalAlignment::Left),
+            "center" => Ok(HorizontalAlignment::Center),
+            "right" => Ok(HorizontalAlignment::Right),
+            "justify" => Ok(HorizontalAlignment::Justify),
+            _ => Err(TiledError::invalid_value("horizontal alignment", s)),
+        }
+    }
+}
+
+impl fmt::Display for HorizontalAlignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HorizontalAlignment::Left => write!(f, "left"),
+            HorizontalAlignment::Center => write!(f, "center"),
+            HorizontalAlignment::Right => write!(f, "right"),
+            HorizontalAlignment::Justify => write!(f, "justify"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum VerticalAlignment {
+    Top,
+    Center,
+    Bottom,
+}
+
+impl FromStr for VerticalAlignment {
+    type Err = TiledError;
+
+    fn from_str(s: &str) -> Result<VerticalAlignment, TiledError> {
+        match s {
+            "top" => Ok(VerticalAlignment::Top),
+            "center" => Ok(VerticalAlignment::Center),
+            "bottom" => Ok(VerticalAlignment::Bottom),
+            _ => Err(TiledError::invalid_value("vertical alignment", s)),
+        }
+    }
+}
+
+impl fmt::Display for VerticalAlignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerticalAlignment::Top => write!(f, "top"),
+            VerticalAlignment::Center => write!(f, "center"),
+            VerticalAlignment::Bottom => write!(f, "bottom"),
+        }
+    }
+}
+
+/// The contents and style of a text object.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Text {
+    pub font_family: String,
+    pub pixel_size: u32,
+    pub wrap: bool,
+    pub colour: Colour,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikeout: bool,
+    pub kerning: bool,
+    pub halign: HorizontalAlignment,
+    pub valign: VerticalAlignment,
+    pub contents: String,
+}
+
+impl Text {
+    fn new<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+    ) -> Result<Text, TiledError> {
+        let flag = |v: String| v.parse().ok().map(|x: i32| x == 1);
+        let (
+            (
+                font_family,
+                pixel_size,
+                wrap,
+                colour,
+                bold,
+                italic,
+                underline,
+                strikeout,
+                kerning,
+                halign,
+                valign,
+            ),
+            (),
+        ) = get_attrs!(
+            attrs,
+            optionals: [
+                ("fontfamily", font_family, Some),
+                ("pixelsize", pixel_size, |v:String| v.parse().ok()),
+                ("wrap", wrap, flag),
+                ("color", colour, |v:String| v.parse().ok()),
+                ("bold", bold, flag),
+                ("italic", italic, flag),
+                ("underline", underline, flag),
+                ("strikeout", strikeout, flag),
+                ("kerning", kerning, flag),
+                ("halign", halign, |v:String| v.parse().ok()),
+                ("valign", valign, |v:String| v.parse().ok()),
+            ],
+            required: [],
+            "text"
+        );
+
+        let mut contents = String::new();
+        loop {
+            match parser.next().map_err(TiledError::XmlDecodingError)? {
+                XmlEvent::Characters(text) | XmlEvent::Whitespace(text) => {
+                    contents.push_str(&text)
+                }
+                XmlEvent::EndElement { name, .. } if name.local_name == "text" => break,
+                XmlEvent::EndDocument => {
+                    return Err(TiledError::PrematureEnd(
+                        "Document ended before we expected.".to_string(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Text {
+            font_family: font_family.unwrap_or_else(|| "sans-serif".to_string()),
+            pixel_size: pixel_size.unwrap_or(16),
+            wrap: wrap.unwrap_or(false),
+            colour: colour.unwrap_or(Colour {
+                red: 0,
+                green: 0,
+                blue: 0,
+                alpha: 255,
+            }),
+            bold: bold.unwrap_or(false),
+            italic: italic.unwrap_or(false),
+            underline: underline.unwrap_or(false),
+            strikeout: strikeout.unwrap_or(false),
+            kerning: kerning.unwrap_or(true),
+            halign: halign.unwrap_or(HorizontalAlignment::Left),
+            valign: valign.unwrap_or(VerticalAlignment::Top),
+            contents,
+        })
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Object {
     pub id: u32,
-    pub gid: u32,
+    /// The tile shown by tile objects, or `Gid::EMPTY` for other objects.
+    pub gid: Gid,
     pub name: String,
     pub obj_type: String,
     pub width: f32,
@@ -912,8 +2631,13 @@ pub struct Object {
     pub y: f32,
     pub rotation: f32,
     pub visible: bool,
+    /// Whether the object is locked against editing.
+    pub locked: bool,
     pub shape: ObjectShape,
     pub properties: Properties,
+    /// Attributes and elements the parser doesn't know, written back out
+    /// along with the object.
+    pub extra: Extra,
 }
 
 impl Object {
@@ -921,30 +2645,33 @@ impl Object {
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
     ) -> Result<Object, TiledError> {
-        let ((id, gid, n, t, w, h, v, r), (x, y)) = get_attrs!(
+        let mut extra = Extra::default();
+        let ((id, gid, n, t, w, h, v, l, r), (x, y)) = get_attrs!(
             attrs,
             optionals: [
                 ("id", id, |v:String| v.parse().ok()),
-                ("gid", gid, |v:String| v.parse().ok()),
+                ("gid", gid, |v:String| v.parse().ok().map(Gid)),
                 ("name", name, |v:String| v.parse().ok()),
                 ("type", obj_type, |v:String| v.parse().ok()),
                 ("width", width, |v:String| v.parse().ok()),
                 ("height", height, |v:String| v.parse().ok()),
                 ("visible", visible, |v:String| v.parse().ok().map(|x:i32| x == 1)),
+                ("locked", locked, |v:String| v.parse().ok().map(|x:i32| x == 1)),
                 ("rotation", rotation, |v:String| v.parse().ok()),
             ],
             required: [
                 ("x", x, |v:String| v.parse().ok()),
                 ("y", y, |v:String| v.parse().ok()),
             ],
-            TiledError::MalformedAttributes("objects must have an x and a y number".to_string())
+            "object",
+            extra: extra
         );
         let v = v.unwrap_or(true);
         let w = w.unwrap_or(0f32);
         let h = h.unwrap_or(0f32);
         let r = r.unwrap_or(0f32);
         let id = id.unwrap_or(0u32);
-        let gid = gid.unwrap_or(0u32);
+        let gid = gid.unwrap_or(Gid::EMPTY);
         let n = n.unwrap_or(String::new());
         let t = t.unwrap_or(String::new());
         let mut shape = None;
@@ -970,11 +2697,15 @@ impl Object {
                 shape = Some(Object::new_point(x, y)?);
                 Ok(())
             },
+            "text" => |attrs| {
+                shape = Some(ObjectShape::Text(Text::new(parser, attrs)?));
+                Ok(())
+            },
             "properties" => |_| {
                 properties = parse_properties(parser)?;
                 Ok(())
             },
-        });
+        }, extra: extra);
 
         let shape = shape.unwrap_or(ObjectShape::Rect {
             width: w,
@@ -988,12 +2719,14 @@ impl Object {
             obj_type: t.clone(),
             width: w,
             height: h,
-            x: x,
-            y: y,
+            x,
+            y,
             rotation: r,
             visible: v,
-            shape: shape,
-            properties: properties,
+            locked: l.unwrap_or(false),
+            shape,
+            properties,
+            extra,
         })
     }
 
@@ -1002,9 +2735,9 @@ impl Object {
             attrs,
             optionals: [],
             required: [
-                ("points", points, |v| Some(v)),
+                ("points", points, Some),
             ],
-            TiledError::MalformedAttributes("A polyline must have points".to_string())
+            "polyline"
         );
         let points = Object::parse_points(s)?;
         Ok(ObjectShape::Polyline { points: points })
@@ -1015,9 +2748,9 @@ impl Object {
             attrs,
             optionals: [],
             required: [
-                ("points", points, |v| Some(v)),
+                ("points", points, Some),
             ],
-            TiledError::MalformedAttributes("A polygon must have points".to_string())
+            "polygon"
         );
         let points = Object::parse_points(s)?;
         Ok(ObjectShape::Polygon { points: points })
@@ -1064,11 +2797,11 @@ impl Frame {
                 ("tileid", tile_id, |v:String| v.parse().ok()),
                 ("duration", duration, |v:String| v.parse().ok()),
             ],
-            TiledError::MalformedAttributes("A frame must have tileid and duration".to_string())
+            "frame"
         );
         Ok(Frame {
-            tile_id: tile_id,
-            duration: duration,
+            tile_id,
+            duration,
         })
     }
 }
@@ -1087,22 +2820,22 @@ fn parse_animation<R: Read>(parser: &mut EventReader<R>) -> Result<Vec<Frame>, T
 fn parse_infinite_data<R: Read>(
     parser: &mut EventReader<R>,
     attrs: Vec<OwnedAttribute>,
-    _width: u32,
+    loader: &Loader,
 ) -> Result<LayerData, TiledError> {
     let ((e, c), ()) = get_attrs!(
         attrs,
         optionals: [
-            ("encoding", encoding, |v| Some(v)),
-            ("compression", compression, |v| Some(v)),
+            ("encoding", encoding, Some),
+            ("compression", compression, Some),
         ],
         required: [],
-        TiledError::MalformedAttributes("data must have an encoding and a compression".to_string())
+        "data"
     );
 
     let mut chunks = HashMap::<(i32, i32), Chunk>::new();
     parse_tag!(parser, "data", {
         "chunk" => |attrs| {
-            let chunk = Chunk::new(parser, attrs, e.clone(), c.clone())?;
+            let chunk = Chunk::new(parser, attrs, e.clone(), c.clone(), loader)?;
             chunks.insert((chunk.x, chunk.y), chunk);
             Ok(())
         }
@@ -1115,65 +2848,93 @@ fn parse_data<R: Read>(
     parser: &mut EventReader<R>,
     attrs: Vec<OwnedAttribute>,
     width: u32,
+    height: u32,
+    loader: &Loader,
 ) -> Result<LayerData, TiledError> {
     let ((e, c), ()) = get_attrs!(
         attrs,
         optionals: [
-            ("encoding", encoding, |v| Some(v)),
-            ("compression", compression, |v| Some(v)),
+            ("encoding", encoding, Some),
+            ("compression", compression, Some),
         ],
         required: [],
-        TiledError::MalformedAttributes("data must have an encoding and a compression".to_string())
+        "data"
     );
 
-    let tiles = parse_data_line(e, c, parser, width)?;
+    let tiles = parse_data_line(e, c, parser, width, height, loader)?;
 
     Ok(LayerData::Finite(tiles))
 }
 
+/// Decodes the tiles of a layer or chunk of `width` by `height` tiles, in
+/// any encoding, and splits them into rows. Data holding any other number of
+/// tiles is an error.
 fn parse_data_line<R: Read>(
     encoding: Option<String>,
     compression: Option<String>,
     parser: &mut EventReader<R>,
     width: u32,
-) -> Result<Vec<Vec<LayerTile>>, TiledError> {
-    match (encoding, compression) {
-        (None, None) => {
-            return Err(TiledError::Other(
-                "XML format is currently not supported".to_string(),
-            ))
-        }
-        (Some(e), None) => match e.as_ref() {
-            "base64" => return parse_base64(parser).map(|v| convert_to_tile(&v, width)),
-            "csv" => return decode_csv(width, parser),
-            e => return Err(TiledError::Other(format!("Unknown encoding format {}", e))),
-        },
-        (Some(e), Some(c)) => match (e.as_ref(), c.as_ref()) {
-            ("base64", "zlib") => {
-                return parse_base64(parser)
-                    .and_then(decode_zlib)
-                    .map(|v| convert_to_tile(&v, width))
-            }
-            ("base64", "gzip") => {
-                return parse_base64(parser)
-                    .and_then(decode_gzip)
-                    .map(|v| convert_to_tile(&v, width))
-            }
-            #[cfg(feature = "zstd")]
-            ("base64", "zstd") => {
-                return parse_base64(parser)
-                    .and_then(decode_zstd)
-                    .map(|v| convert_to_tile(&v, width))
-            }
-            (e, c) => {
-                return Err(TiledError::Other(format!(
-                    "Unknown combination of {} encoding and {} compression",
-                    e, c
-                )))
-            }
-        },
-        _ => return Err(TiledError::Other("Missing encoding format".to_string())),
+    height: u32,
+    loader: &Loader,
+) -> Result<Vec<Vec<Option<LayerTile>>>, TiledError> {
+    let expected = width as u64 * height as u64;
+    // Compressed data longer than the tiles it should hold is rejected as
+    // soon as it's inflated past them.
+    let bytes = expected.saturating_mul(4).min(usize::MAX as u64) as usize;
+    let limit = Some(
+        loader
+            .max_decompressed_size
+            .map_or(bytes, |max| max.min(bytes)),
+    );
+    let tiles = if let Some(codec) = loader.codec(encoding.as_deref(), compression.as_deref()) {
+        let data = codec.decode(&parse_text(parser)?)?;
+        if let Some(limit) = limit.filter(|&limit| data.len() > limit) {
+            return Err(TiledError::Other(format!(
+                "Layer data decoded by the {} codec is longer than {} bytes",
+                codec.encoding(),
+                limit
+            )));
+        }
+        convert_to_tile(&data)?
+    } else {
+        match (encoding, compression) {
+            (None, None) => decode_xml(parser)?,
+            (Some(e), None) => match e.as_ref() {
+                "base64" => convert_to_tile(&parse_base64(parser)?)?,
+                "csv" => decode_csv(parser)?,
+                e => return Err(TiledError::Other(format!("Unknown encoding format {}", e))),
+            },
+            (Some(e), Some(c)) => match (e.as_ref(), c.as_ref()) {
+                ("base64", "zlib") => convert_to_tile(&decode_zlib(parse_base64(parser)?, limit)?)?,
+                ("base64", "gzip") => convert_to_tile(&decode_gzip(parse_base64(parser)?, limit)?)?,
+                #[cfg(feature = "zstd")]
+                ("base64", "zstd") => convert_to_tile(&decode_zstd(parse_base64(parser)?, limit)?)?,
+                (e, c) => {
+                    return Err(TiledError::Other(format!(
+                        "Unknown combination of {} encoding and {} compression",
+                        e, c
+                    )))
+                }
+            },
+            _ => return Err(TiledError::Other("Missing encoding format".to_string())),
+        }
     };
+    if tiles.len() as u64 != expected {
+        return Err(TiledError::Other(format!(
+            "Layer data holds {} tiles rather than the {} of {} by {} tiles",
+            tiles.len(),
+            expected,
+            width,
+            height
+        )));
+    }
+    if width == 0 {
+        return Ok(Vec::new());
+    }
+    Ok(tiles
+        .chunks_exact(width as usize)
+        .map(|row| row.to_vec())
+        .collect())
 }
 
 fn parse_base64<R: Read>(parser: &mut EventReader<R>) -> Result<Vec<u8>, TiledError> {
@@ -1192,59 +2953,98 @@ fn parse_base64<R: Read>(parser: &mut EventReader<R>) -> Result<Vec<u8>, TiledEr
     }
 }
 
-fn decode_zlib(data: Vec<u8>) -> Result<Vec<u8>, TiledError> {
-    use libflate::zlib::Decoder;
-    let mut zd =
-        Decoder::new(BufReader::new(&data[..])).map_err(|e| TiledError::DecompressingError(e))?;
+/// Reads the text content of a `data` element, which is empty if there is
+/// none.
+fn parse_text<R: Read>(parser: &mut EventReader<R>) -> Result<String, TiledError> {
+    loop {
+        match parser.next().map_err(TiledError::XmlDecodingError)? {
+            XmlEvent::Characters(s) => return Ok(s),
+            XmlEvent::EndElement { name, .. } if name.local_name == "data" => {
+                return Ok(String::new());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads decompressed data, failing once it's longer than `limit`.
+fn read_limited<R: Read>(reader: R, limit: Option<usize>) -> Result<Vec<u8>, TiledError> {
     let mut data = Vec::new();
-    match zd.read_to_end(&mut data) {
-        Ok(_v) => {}
-        Err(e) => return Err(TiledError::DecompressingError(e)),
+    match limit {
+        Some(limit) => {
+            reader
+                .take(limit as u64 + 1)
+                .read_to_end(&mut data)
+                .map_err(TiledError::DecompressingError)?;
+            if data.len() > limit {
+                return Err(TiledError::Other(format!(
+                    "Layer data decompresses to more than {} bytes",
+                    limit
+                )));
+            }
+        }
+        None => {
+            let mut reader = reader;
+            reader
+                .read_to_end(&mut data)
+                .map_err(TiledError::DecompressingError)?;
+        }
     }
     Ok(data)
 }
 
-fn decode_gzip(data: Vec<u8>) -> Result<Vec<u8>, TiledError> {
-    use libflate::gzip::Decoder;
-    let mut zd =
-        Decoder::new(BufReader::new(&data[..])).map_err(|e| TiledError::DecompressingError(e))?;
+#[cfg(feature = "flate2")]
+fn decode_zlib(data: Vec<u8>, limit: Option<usize>) -> Result<Vec<u8>, TiledError> {
+    read_limited(flate2::read::ZlibDecoder::new(&data[..]), limit)
+}
 
-    let mut data = Vec::new();
-    zd.read_to_end(&mut data)
-        .map_err(|e| TiledError::DecompressingError(e))?;
-    Ok(data)
+#[cfg(feature = "flate2")]
+fn decode_gzip(data: Vec<u8>, limit: Option<usize>) -> Result<Vec<u8>, TiledError> {
+    read_limited(flate2::read::GzDecoder::new(&data[..]), limit)
+}
+
+#[cfg(not(feature = "flate2"))]
+fn decode_zlib(data: Vec<u8>, limit: Option<usize>) -> Result<Vec<u8>, TiledError> {
+    use libflate::zlib::Decoder;
+    use std::io::BufReader;
+    let zd = Decoder::new(BufReader::new(&data[..])).map_err(TiledError::DecompressingError)?;
+    read_limited(zd, limit)
+}
+
+#[cfg(not(feature = "flate2"))]
+fn decode_gzip(data: Vec<u8>, limit: Option<usize>) -> Result<Vec<u8>, TiledError> {
+    use libflate::gzip::Decoder;
+    use std::io::BufReader;
+    let zd = Decoder::new(BufReader::new(&data[..])).map_err(TiledError::DecompressingError)?;
+    read_limited(zd, limit)
 }
 
 #[cfg(feature = "zstd")]
-fn decode_zstd(data: Vec<u8>) -> Result<Vec<u8>, TiledError> {
+fn decode_zstd(data: Vec<u8>, limit: Option<usize>) -> Result<Vec<u8>, TiledError> {
     use std::io::Cursor;
     use zstd::stream::read::Decoder;
 
     let buff = Cursor::new(&data);
-    let mut zd = Decoder::with_buffer(buff).map_err(|e| TiledError::DecompressingError(e))?;
-
-    let mut data = Vec::new();
-    zd.read_to_end(&mut data)
-        .map_err(|e| TiledError::DecompressingError(e))?;
-    Ok(data)
+    let zd = Decoder::with_buffer(buff).map_err(TiledError::DecompressingError)?;
+    read_limited(zd, limit)
 }
 
-fn decode_csv<R: Read>(width: u32, parser: &mut EventReader<R>) -> Result<Vec<Vec<LayerTile>>, TiledError> {
+fn decode_csv<R: Read>(parser: &mut EventReader<R>) -> Result<Vec<Option<LayerTile>>, TiledError> {
     loop {
         match parser.next().map_err(TiledError::XmlDecodingError)? {
             XmlEvent::Characters(s) => {
-                let mut tiles_it = s
+                return s
                     .split(&['\n', '\r', ','][0..])
-                    .filter(|v| v.trim() != "")
-                    .map(|v| v.parse().unwrap())
-                    .map(LayerTile::new)
-                    .peekable();
-                let mut rows = Vec::new();
-                while tiles_it.peek().is_some() {
-                    let row = tiles_it.by_ref().take(width as usize).collect();
-                    rows.push(row);
-                }
-                return Ok(rows);
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+                    .enumerate()
+                    .map(|(index, v)| {
+                        v.parse().map(LayerTile::from_bits).map_err(|_| TiledError::InvalidTileData {
+                            token: v.to_string(),
+                            index,
+                        })
+                    })
+                    .collect();
             }
             XmlEvent::EndElement { name, .. } => {
                 if name.local_name == "data" {
@@ -1256,34 +3056,63 @@ fn decode_csv<R: Read>(width: u32, parser: &mut EventReader<R>) -> Result<Vec<Ve
     }
 }
 
-fn convert_to_tile(all: &Vec<u8>, width: u32) -> Vec<Vec<LayerTile>> {
-    let mut data = Vec::new();
-    for chunk in all.chunks((width * 4) as usize) {
-        let mut row = Vec::new();
-        for i in 0..width {
-            let start: usize = i as usize * 4;
-            let n = ((chunk[start + 3] as u32) << 24)
-                + ((chunk[start + 2] as u32) << 16)
-                + ((chunk[start + 1] as u32) << 8)
-                + chunk[start] as u32;
-            let n = LayerTile::new(n);
-            row.push(n);
+/// Reads tiles saved as `<tile gid="..."/>` elements, up to the end of the
+/// data or chunk holding them. Tiles without a gid are empty.
+fn decode_xml<R: Read>(parser: &mut EventReader<R>) -> Result<Vec<Option<LayerTile>>, TiledError> {
+    let mut tiles = Vec::new();
+    loop {
+        match parser.next().map_err(TiledError::XmlDecodingError)? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } if name.local_name == "tile" => {
+                let gid = match attributes.iter().find(|a| a.name.local_name == "gid") {
+                    Some(a) => a.value.parse().map_err(|_| TiledError::InvalidAttribute {
+                        element: "tile".to_string(),
+                        attribute: "gid".to_string(),
+                        value: a.value.clone(),
+                    })?,
+                    None => 0,
+                };
+                tiles.push(LayerTile::from_bits(gid));
+            }
+            XmlEvent::EndElement { name, .. }
+                if name.local_name == "data" || name.local_name == "chunk" =>
+            {
+                break;
+            }
+            XmlEvent::EndDocument => {
+                return Err(TiledError::PrematureEnd("Document ended before tile data".to_string()));
+            }
+            _ => {}
         }
-        data.push(row);
     }
-    data
+    Ok(tiles)
+}
+
+/// Reads the tiles of binary layer data, four little endian bytes per tile.
+fn convert_to_tile(all: &[u8]) -> Result<Vec<Option<LayerTile>>, TiledError> {
+    if all.len() % 4 != 0 {
+        return Err(TiledError::Other(format!(
+            "Layer data of {} bytes doesn't make up whole tiles",
+            all.len()
+        )));
+    }
+    Ok(all
+        .chunks_exact(4)
+        .map(|b| LayerTile::from_bits(u32::from_le_bytes([b[0], b[1], b[2], b[3]])))
+        .collect())
 }
 
-fn parse_impl<R: Read>(reader: R, map_path: Option<&Path>) -> Result<Map, TiledError> {
-    let mut parser = EventReader::new(reader);
+fn parse_impl<R: Read>(reader: R, map_path: Option<&Path>, loader: &Loader) -> Result<Map, TiledError> {
+    let mut parser = xml_reader(reader);
     loop {
         match parser.next().map_err(TiledError::XmlDecodingError)? {
             XmlEvent::StartElement {
                 name, attributes, ..
-            } => {
-                if name.local_name == "map" {
-                    return Map::new(&mut parser, attributes, map_path);
-                }
+            } if name.local_name == "map" => {
+                let context = ElementContext::new("map", &attributes);
+                return Map::new(&mut parser, attributes, map_path, loader)
+                    .map_err(|e| e.in_element(context).at(&parser));
             }
             XmlEvent::EndDocument => {
                 return Err(TiledError::PrematureEnd(
@@ -1299,23 +3128,32 @@ fn parse_impl<R: Read>(reader: R, map_path: Option<&Path>) -> Result<Map, TiledE
 /// parse it. This augments `parse` with a file location: some engines
 /// (e.g. Amethyst) simply hand over a byte stream (and file location) for parsing,
 /// in which case this function may be required.
-pub fn parse_with_path<R: Read>(reader: R, path: &Path) -> Result<Map, TiledError> {
-    parse_impl(reader, Some(path))
+pub fn parse_with_path<R: Read>(reader: R, path: impl AsRef<Path>) -> Result<Map, TiledError> {
+    Loader::new().parse_with_path(reader, path)
 }
 
 /// Parse a file hopefully containing a Tiled map and try to parse it.  If the
 /// file has an external tileset, the tileset file will be loaded using a path
 /// relative to the map file's path.
-pub fn parse_file(path: &Path) -> Result<Map, TiledError> {
-    let file = File::open(path)
-        .map_err(|_| TiledError::Other(format!("Map file not found: {:?}", path)))?;
-    parse_impl(file, Some(path))
+#[cfg(feature = "fs")]
+pub fn parse_file(path: impl AsRef<Path>) -> Result<Map, TiledError> {
+    Loader::new().parse_file(path)
 }
 
 /// Parse a buffer hopefully containing the contents of a Tiled file and try to
 /// parse it.
 pub fn parse<R: Read>(reader: R) -> Result<Map, TiledError> {
-    parse_impl(reader, None)
+    Loader::new().parse(reader)
+}
+
+/// Parse the contents of a Tiled file held in a string, see `parse`.
+pub fn parse_str(text: &str) -> Result<Map, TiledError> {
+    Loader::new().parse_str(text)
+}
+
+/// Parse the contents of a Tiled file held in memory, see `parse`.
+pub fn parse_bytes(bytes: &[u8]) -> Result<Map, TiledError> {
+    Loader::new().parse_bytes(bytes)
 }
 
 /// Parse a buffer hopefully containing the contents of a Tiled tileset.
@@ -1324,5 +3162,5 @@ pub fn parse<R: Read>(reader: R) -> Result<Map, TiledError> {
 /// map. You must pass in `first_gid`.  If you do not need to use gids for anything,
 /// passing in 1 will work fine.
 pub fn parse_tileset<R: Read>(reader: R, first_gid: u32) -> Result<Tileset, TiledError> {
-    Tileset::new_external(reader, first_gid)
+    Loader::new().parse_tileset(reader, first_gid)
 }
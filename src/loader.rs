@@ -0,0 +1,397 @@
+//! Parsing maps with configurable options.
+
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(feature = "fs")]
+use std::fs::File;
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::checksum::{verify_checksum, CHECKSUM_PROPERTY};
+use crate::{
+    parse_impl, CompatibilityWarning, Gid, Map, ObjectTypes, TileDataCodec, TiledError, Tileset,
+};
+
+/// A transformation of the raw bytes of map and tileset files.
+type Transform = dyn Fn(&[u8]) -> Result<Vec<u8>, TiledError> + Send + Sync;
+
+/// Opens map and tileset files by their path.
+type FileReader = dyn Fn(&Path) -> io::Result<Box<dyn Read>> + Send + Sync;
+
+/// Decides which layers are parsed.
+type LayerFilter = dyn Fn(&LayerInfo) -> bool + Send + Sync;
+
+/// External tilesets parsed so far, keyed by their canonical path.
+type TilesetCache = Mutex<HashMap<PathBuf, Tileset>>;
+
+/// The kind of a layer given to a layer filter.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum LayerKind {
+    Tiles,
+    Objects,
+    Image,
+}
+
+/// What a layer filter knows of a layer, read from its attributes before
+/// its contents are parsed.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct LayerInfo<'a> {
+    pub name: &'a str,
+    /// The id of the layer, missing in maps saved by old versions of Tiled.
+    pub id: Option<u32>,
+    pub kind: LayerKind,
+}
+
+/// Parses maps, with options set up beforehand. The free `parse` functions
+/// use a loader with the default options.
+#[derive(Default, Clone)]
+pub struct Loader {
+    pub(crate) codecs: Vec<Arc<dyn TileDataCodec>>,
+    pub(crate) transform: Option<Arc<Transform>>,
+    file_reader: Option<Arc<FileReader>>,
+    tileset_cache: Option<Arc<TilesetCache>>,
+    layer_filter: Option<Arc<LayerFilter>>,
+    pub(crate) skip_external_tilesets: bool,
+    pub(crate) defer_external_tilesets: bool,
+    pub(crate) max_decompressed_size: Option<usize>,
+    verify_checksums: bool,
+    strict_versions: bool,
+    strict_tileset_ranges: bool,
+    object_types: Option<Arc<ObjectTypes>>,
+}
+
+impl fmt::Debug for Loader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Loader")
+            .field("codecs", &self.codecs)
+            .field("transform", &self.transform.is_some())
+            .field("file_reader", &self.file_reader.is_some())
+            .field("tileset_cache", &self.tileset_cache.is_some())
+            .field("layer_filter", &self.layer_filter.is_some())
+            .field("skip_external_tilesets", &self.skip_external_tilesets)
+            .field("defer_external_tilesets", &self.defer_external_tilesets)
+            .field("max_decompressed_size", &self.max_decompressed_size)
+            .field("verify_checksums", &self.verify_checksums)
+            .field("strict_versions", &self.strict_versions)
+            .field("strict_tileset_ranges", &self.strict_tileset_ranges)
+            .field("object_types", &self.object_types)
+            .finish()
+    }
+}
+
+impl Loader {
+    pub fn new() -> Loader {
+        Loader::default()
+    }
+
+    /// Registers a codec for layer data. Codecs are consulted before the
+    /// built-in encodings, so they can also replace those.
+    pub fn with_codec(mut self, codec: impl TileDataCodec + 'static) -> Loader {
+        self.codecs.push(Arc::new(codec));
+        self
+    }
+
+    /// Passes the bytes of every map and external tileset file through
+    /// `transform` before parsing them, for files which are stored
+    /// encrypted or signed. Returning an error, like when a signature
+    /// doesn't match, stops the parsing.
+    pub fn with_transform(
+        mut self,
+        transform: impl Fn(&[u8]) -> Result<Vec<u8>, TiledError> + Send + Sync + 'static,
+    ) -> Loader {
+        self.transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// Opens map files and external tilesets with `open` instead of from
+    /// the file system, for maps stored in archives or a virtual file
+    /// system.
+    pub fn with_file_reader(
+        mut self,
+        open: impl Fn(&Path) -> io::Result<Box<dyn Read>> + Send + Sync + 'static,
+    ) -> Loader {
+        self.file_reader = Some(Arc::new(open));
+        self
+    }
+
+    /// Reads map files and external tilesets from `resources`, keyed by the
+    /// path they are opened with, instead of from the file system. This is
+    /// how maps are loaded where there is no file system, like on wasm32.
+    pub fn with_resources(self, resources: HashMap<PathBuf, Vec<u8>>) -> Loader {
+        self.with_file_reader(move |path| match resources.get(path) {
+            Some(bytes) => Ok(Box::new(Cursor::new(bytes.clone())) as Box<dyn Read>),
+            None => Err(io::ErrorKind::NotFound.into()),
+        })
+    }
+
+    /// Parses each external tileset only once, however many maps use it,
+    /// for loading worlds and other sets of maps sharing tilesets. Tilesets
+    /// are cached by their canonical path, and clones of the loader share
+    /// the cache. Changes to cached tileset files aren't picked up.
+    pub fn with_tileset_cache(mut self) -> Loader {
+        self.tileset_cache = Some(Arc::default());
+        self
+    }
+
+    /// Only parses the layers for which `filter` returns true, skipping the
+    /// contents of the others without decoding them, for programs needing
+    /// only some of the layers. Layers inside groups are filtered one by one,
+    /// and groups are dropped when the filter leaves them empty.
+    pub fn with_layer_filter(
+        mut self,
+        filter: impl Fn(&LayerInfo) -> bool + Send + Sync + 'static,
+    ) -> Loader {
+        self.layer_filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Skips external tilesets instead of loading them, for maps parsed
+    /// without their location or when only their layers are needed. The
+    /// tiles of skipped tilesets have no tileset in `Map::tilesets`.
+    pub fn without_external_tilesets(mut self) -> Loader {
+        self.skip_external_tilesets = true;
+        self
+    }
+
+    /// Leaves external tilesets unloaded, noting them in
+    /// `Map::unresolved_tilesets` for `Map::resolve_tilesets` to load
+    /// later, so that callers can schedule the reads themselves or load
+    /// maps whose tilesets are stored elsewhere.
+    pub fn with_deferred_tilesets(mut self) -> Loader {
+        self.defer_external_tilesets = true;
+        self
+    }
+
+    /// Rejects layer data which decompresses or decodes to more than
+    /// `bytes`, for each layer or chunk, so that small malicious files
+    /// can't exhaust memory.
+    pub fn with_max_decompressed_size(mut self, bytes: usize) -> Loader {
+        self.max_decompressed_size = Some(bytes);
+        self
+    }
+
+    /// Enables all checks rejecting maps which would otherwise be loaded
+    /// as well as possible: `with_strict_versions` and
+    /// `with_strict_tileset_ranges`.
+    pub fn with_strict(self) -> Loader {
+        self.with_strict_versions().with_strict_tileset_ranges()
+    }
+
+    /// Rejects maps whose `checksum` property, as written with
+    /// `WriteOptions::checksum`, is missing or doesn't match their contents.
+    /// The property is removed from the parsed maps.
+    pub fn with_checksum_verification(mut self) -> Loader {
+        self.verify_checksums = true;
+        self
+    }
+
+    /// Rejects maps and tilesets reporting version warnings through
+    /// `Map::compatibility_warnings` or `Tileset::compatibility_warnings`,
+    /// such as tilesets saved by a newer version of Tiled than their map,
+    /// instead of leaving the check to the caller.
+    pub fn with_strict_versions(mut self) -> Loader {
+        self.strict_versions = true;
+        self
+    }
+
+    /// Rejects maps with tilesets whose ranges of global tile ids overlap,
+    /// as reported by `Map::tileset_overlaps`, instead of resolving the
+    /// shared ids to the later tileset.
+    pub fn with_strict_tileset_ranges(mut self) -> Loader {
+        self.strict_tileset_ranges = true;
+        self
+    }
+
+    /// Adds the default properties of their types to the objects of parsed
+    /// maps, see `Map::apply_object_types`.
+    pub fn with_object_types(mut self, object_types: ObjectTypes) -> Loader {
+        self.object_types = Some(Arc::new(object_types));
+        self
+    }
+
+    /// Returns the registered codec handling the given attributes of a
+    /// `data` element.
+    pub(crate) fn codec(
+        &self,
+        encoding: Option<&str>,
+        compression: Option<&str>,
+    ) -> Option<&dyn TileDataCodec> {
+        self.codecs
+            .iter()
+            .map(|codec| &**codec)
+            .find(|codec| codec.handles(encoding, compression))
+    }
+
+    /// Parses a map from a reader. Maps with external tilesets need
+    /// `parse_with_path` instead.
+    pub fn parse<R: Read>(&self, reader: R) -> Result<Map, TiledError> {
+        self.parse_map(reader, None)
+    }
+
+    /// Parses a map held in a string, see `parse`.
+    pub fn parse_str(&self, text: &str) -> Result<Map, TiledError> {
+        self.parse(text.as_bytes())
+    }
+
+    /// Parses a map held in memory, see `parse`.
+    pub fn parse_bytes(&self, bytes: &[u8]) -> Result<Map, TiledError> {
+        self.parse(bytes)
+    }
+
+    /// Parses a map from a reader, loading external tilesets relative to
+    /// `path`.
+    pub fn parse_with_path<R: Read>(
+        &self,
+        reader: R,
+        path: impl AsRef<Path>,
+    ) -> Result<Map, TiledError> {
+        self.parse_map(reader, Some(path.as_ref()))
+    }
+
+    /// Parses a map file, loading external tilesets relative to it.
+    pub fn parse_file(&self, path: impl AsRef<Path>) -> Result<Map, TiledError> {
+        let path = path.as_ref();
+        let file = self.open_file(path).map_err(|e| TiledError::io(path, e))?;
+        self.parse_map(file, Some(path))
+    }
+
+    /// Opens a map or tileset file with the file reader, if any. Without
+    /// the `fs` feature, files can only be opened through a file reader.
+    pub(crate) fn open_file(&self, path: &Path) -> io::Result<Box<dyn Read>> {
+        match self.file_reader {
+            Some(ref open) => open(path),
+            #[cfg(feature = "fs")]
+            None => Ok(Box::new(File::open(path)?)),
+            #[cfg(not(feature = "fs"))]
+            None => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "no file system, files must be read with a file reader",
+            )),
+        }
+    }
+
+    /// Whether the layer filter, if any, lets the layer be parsed.
+    pub(crate) fn filters_layers(&self) -> bool {
+        self.layer_filter.is_some()
+    }
+
+    pub(crate) fn keeps_layer(&self, info: &LayerInfo) -> bool {
+        self.layer_filter
+            .as_ref()
+            .map_or(true, |filter| filter(info))
+    }
+
+    /// Loads the external tileset at `path`, from the tileset cache if
+    /// there is one.
+    pub(crate) fn load_tileset(&self, path: &Path, first_gid: Gid) -> Result<Tileset, TiledError> {
+        let cache = match self.tileset_cache {
+            Some(ref cache) => cache,
+            None => return self.read_tileset(path, first_gid),
+        };
+        let key = self.canonical_path(path);
+        if let Some(tileset) = cache.lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+            return Ok(Tileset {
+                first_gid,
+                source: Some(path.to_path_buf()),
+                ..tileset.clone()
+            });
+        }
+        let tileset = self.read_tileset(path, first_gid)?;
+        cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, tileset.clone());
+        Ok(tileset)
+    }
+
+    fn read_tileset(&self, path: &Path, first_gid: Gid) -> Result<Tileset, TiledError> {
+        let file = self.open_file(path).map_err(|e| TiledError::io(path, e))?;
+        let mut tileset = self.parse_tileset(file, first_gid.0)?;
+        tileset.source = Some(path.to_path_buf());
+        Ok(tileset)
+    }
+
+    /// Returns the path the tileset cache knows a file by. Paths of files
+    /// read with a file reader are taken as they are.
+    fn canonical_path(&self, path: &Path) -> PathBuf {
+        #[cfg(feature = "fs")]
+        if self.file_reader.is_none() {
+            if let Ok(path) = path.canonicalize() {
+                return path;
+            }
+        }
+        path.to_path_buf()
+    }
+
+    /// Parses a tileset from a reader, see `parse_tileset`.
+    pub fn parse_tileset<R: Read>(&self, reader: R, first_gid: u32) -> Result<Tileset, TiledError> {
+        let tileset = match self.transform {
+            Some(ref transform) => {
+                Tileset::new_external(&transform(&read_all(reader)?)?[..], Gid(first_gid))?
+            }
+            None => Tileset::new_external(reader, Gid(first_gid))?,
+        };
+        if self.strict_versions {
+            check_versions(&tileset.compatibility_warnings())?;
+        }
+        Ok(tileset)
+    }
+
+    fn parse_map<R: Read>(&self, reader: R, path: Option<&Path>) -> Result<Map, TiledError> {
+        let mut map = self.read_map(reader, path)?;
+        if let Some(ref object_types) = self.object_types {
+            map.apply_object_types(object_types);
+        }
+        if self.strict_versions {
+            check_versions(&map.compatibility_warnings())?;
+        }
+        if self.strict_tileset_ranges {
+            let overlaps = map.tileset_overlaps();
+            if !overlaps.is_empty() {
+                let overlaps: Vec<String> = overlaps.iter().map(ToString::to_string).collect();
+                return Err(TiledError::Other(format!(
+                    "Overlapping tilesets: {}",
+                    overlaps.join(", ")
+                )));
+            }
+        }
+        Ok(map)
+    }
+
+    fn read_map<R: Read>(&self, reader: R, path: Option<&Path>) -> Result<Map, TiledError> {
+        if self.transform.is_none() && !self.verify_checksums {
+            return parse_impl(reader, path, self);
+        }
+        let mut bytes = read_all(reader)?;
+        if let Some(ref transform) = self.transform {
+            bytes = transform(&bytes)?;
+        }
+        if !self.verify_checksums {
+            return parse_impl(&bytes[..], path, self);
+        }
+        verify_checksum(&bytes)?;
+        let mut map = parse_impl(&bytes[..], path, self)?;
+        map.properties.remove(CHECKSUM_PROPERTY);
+        Ok(map)
+    }
+}
+
+fn check_versions(warnings: &[CompatibilityWarning]) -> Result<(), TiledError> {
+    if warnings.is_empty() {
+        return Ok(());
+    }
+    let warnings: Vec<String> = warnings.iter().map(ToString::to_string).collect();
+    Err(TiledError::Other(format!(
+        "Incompatible versions: {}",
+        warnings.join(", ")
+    )))
+}
+
+fn read_all<R: Read>(mut reader: R) -> Result<Vec<u8>, TiledError> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .map_err(|e| TiledError::Other(format!("Could not read file: {}", e)))?;
+    Ok(bytes)
+}
@@ -0,0 +1,128 @@
+//! Collecting translatable strings from the properties of a map.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::{Layer, LayerType, Map, Properties, PropertyValue};
+
+/// Translatable strings keyed by where they were found in a map. Keys have
+/// the form:
+///
+/// - `map/<property>` for properties of the map,
+/// - `layers/<layer index>/<property>` for properties of layers, with the
+///   layers within a group keyed by the group's key and their index in it,
+///   like `layers/<group index>/<layer index>/<property>`,
+/// - `objects/<object id>/<property>` for properties of objects,
+/// - `tilesets/<tileset name>/<tile id>/<property>` for properties of tiles.
+pub type StringTable = BTreeMap<String, String>;
+
+/// Calls `f` with the key prefix and properties of the layers, the layers
+/// within them and their objects.
+fn for_each_layer_properties(
+    prefix: &str,
+    layers: &[Layer],
+    f: &mut impl FnMut(String, &Properties),
+) {
+    for (i, layer) in layers.iter().enumerate() {
+        let key = format!("{}/{}", prefix, i);
+        f(key.clone(), &layer.properties);
+        match layer.layer_type {
+            LayerType::ObjectLayer(ref group) => {
+                for object in group.objects.iter() {
+                    f(format!("objects/{}", object.id), &object.properties);
+                }
+            }
+            LayerType::Group(ref layers) => for_each_layer_properties(&key, layers, f),
+            _ => {}
+        }
+    }
+}
+
+/// Same as `for_each_layer_properties`, giving mutable access to the
+/// properties.
+fn for_each_layer_properties_mut(
+    prefix: &str,
+    layers: &mut [Layer],
+    f: &mut impl FnMut(String, &mut Properties),
+) {
+    for (i, layer) in layers.iter_mut().enumerate() {
+        let key = format!("{}/{}", prefix, i);
+        f(key.clone(), &mut layer.properties);
+        match layer.layer_type {
+            LayerType::ObjectLayer(ref mut group) => {
+                for object in group.objects.iter_mut() {
+                    f(format!("objects/{}", object.id), &mut object.properties);
+                }
+            }
+            LayerType::Group(ref mut layers) => for_each_layer_properties_mut(&key, layers, f),
+            _ => {}
+        }
+    }
+}
+
+/// Calls `f` with the key prefix and properties of everything in the map
+/// which can have properties, in a stable order.
+fn for_each_properties(map: &Map, mut f: impl FnMut(String, &Properties)) {
+    f("map".to_string(), &map.properties);
+    for_each_layer_properties("layers", &map.layers, &mut f);
+    for tileset in map.tilesets.iter() {
+        for tile in tileset.tiles.iter() {
+            f(
+                format!("tilesets/{}/{}", tileset.name, tile.id),
+                &tile.properties,
+            );
+        }
+    }
+}
+
+/// Same as `for_each_properties`, giving mutable access to the properties.
+pub(crate) fn for_each_properties_mut(map: &mut Map, mut f: impl FnMut(String, &mut Properties)) {
+    f("map".to_string(), &mut map.properties);
+    for_each_layer_properties_mut("layers", &mut map.layers, &mut f);
+    for tileset in map.tilesets.iter_mut() {
+        let tileset = Arc::make_mut(tileset);
+        for tile in tileset.tiles.iter_mut() {
+            f(
+                format!("tilesets/{}/{}", tileset.name, tile.id),
+                &mut tile.properties,
+            );
+        }
+    }
+}
+
+impl Map {
+    /// Collects every string property whose name starts with `prefix`, like
+    /// `text.`, into a table to be handed to translators.
+    pub fn collect_strings(&self, prefix: &str) -> StringTable {
+        let mut table = StringTable::new();
+        for_each_properties(self, |scope, properties| {
+            for (name, value) in properties.iter() {
+                if let PropertyValue::StringValue(ref text) = value {
+                    if name.starts_with(prefix) {
+                        table.insert(format!("{}/{}", scope, name), text.clone());
+                    }
+                }
+            }
+        });
+        table
+    }
+
+    /// Replaces the string properties listed in `table` with the strings in
+    /// it, like a table returned by `collect_strings` after translation.
+    /// Entries which don't match a string property of the map are ignored.
+    /// Returns the number of properties which were replaced.
+    pub fn apply_strings(&mut self, table: &StringTable) -> usize {
+        let mut replaced = 0;
+        for_each_properties_mut(self, |scope, properties| {
+            for (name, value) in properties.iter_mut() {
+                if let PropertyValue::StringValue(ref mut text) = value {
+                    if let Some(translated) = table.get(&format!("{}/{}", scope, name)) {
+                        *text = translated.clone();
+                        replaced += 1;
+                    }
+                }
+            }
+        });
+        replaced
+    }
+}
@@ -0,0 +1,97 @@
+//! Lookup tables from tiles to the materials given by their properties.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::{Gid, Map, PropertyValue, TileLayer};
+
+/// The materials of the tiles of a map, like grass or stone, read from a
+/// property of each tile for picking footstep sounds or particle effects.
+/// Tiles without the property take the material given by the same property
+/// on their tileset, if any.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MaterialTable<M> {
+    tiles: HashMap<u32, M>,
+    /// The first global tile id of each tileset with its fallback material,
+    /// sorted by first gid.
+    tilesets: Vec<(u32, Option<M>)>,
+    default: Option<M>,
+}
+
+/// Returns the text of a string property, or of an enum property saved by
+/// name.
+fn property_text(value: &PropertyValue) -> Option<&str> {
+    match value {
+        PropertyValue::StringValue(text) => Some(text),
+        PropertyValue::EnumValue { value, .. } => property_text(value),
+        _ => None,
+    }
+}
+
+impl<M: FromStr> MaterialTable<M> {
+    /// Builds the table from the given string or enum property, parsing its
+    /// values into materials. Values which don't parse are ignored.
+    pub fn new(map: &Map, property: &str) -> MaterialTable<M> {
+        MaterialTable::with_parser(map, property, |value| property_text(value)?.parse().ok())
+    }
+}
+
+impl<M> MaterialTable<M> {
+    /// Builds the table from the given property, turning its values into
+    /// materials with `parse`, which returns `None` to ignore a value.
+    pub fn with_parser(
+        map: &Map,
+        property: &str,
+        mut parse: impl FnMut(&PropertyValue) -> Option<M>,
+    ) -> MaterialTable<M> {
+        let mut tiles = HashMap::new();
+        let mut tilesets = Vec::new();
+        for tileset in map.tilesets.iter() {
+            for tile in tileset.tiles.iter() {
+                if let Some(material) = tile.properties.get(property).and_then(&mut parse) {
+                    tiles.insert(tileset.first_gid.value() + tile.id, material);
+                }
+            }
+            let fallback = tileset.properties.get(property).and_then(&mut parse);
+            tilesets.push((tileset.first_gid.value(), fallback));
+        }
+        tilesets.sort_by_key(|&(first_gid, _)| first_gid);
+        MaterialTable {
+            tiles,
+            tilesets,
+            default: None,
+        }
+    }
+
+    /// Sets the material of the tiles which have none, instead of `None`.
+    pub fn with_default(mut self, material: M) -> MaterialTable<M> {
+        self.default = Some(material);
+        self
+    }
+
+    /// Returns the material of the tile with the given global tile id,
+    /// ignoring its flip flags.
+    pub fn get(&self, gid: impl Into<Gid>) -> Option<&M> {
+        let gid = gid.into();
+        if gid.is_empty() {
+            return None;
+        }
+        let gid = gid.value();
+        if let Some(material) = self.tiles.get(&gid) {
+            return Some(material);
+        }
+        let tileset = self
+            .tilesets
+            .partition_point(|&(first_gid, _)| first_gid <= gid);
+        tileset
+            .checked_sub(1)
+            .and_then(|i| self.tilesets[i].1.as_ref())
+            .or(self.default.as_ref())
+    }
+
+    /// Returns the material of the tile at the given tile coordinates of a
+    /// layer. Empty tiles and positions outside of the layer have none.
+    pub fn material_at(&self, layer: &TileLayer, x: i32, y: i32) -> Option<&M> {
+        self.get(layer.get_tile(x, y)?.gid)
+    }
+}
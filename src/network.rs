@@ -0,0 +1,258 @@
+//! Compact frames for streaming regions of tile layers over the network.
+
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+
+use crate::edit::not_a_tile_layer;
+use crate::geometry::area_end;
+use crate::writer::compress;
+use crate::{
+    cell_bits, decode_zlib, Compression, LayerData, LayerTile, Map, TileLayer, TileRect, TiledError,
+};
+
+/// Identifies encoded frames, followed by the version of the format.
+const FRAME_MAGIC: &[u8; 4] = b"TMF\x01";
+/// The size of the frame header: the magic, sequence id, layer index and
+/// area.
+const HEADER_LEN: usize = 4 + 4 + 4 + 16;
+/// How far ahead of the next expected frame a `FrameReceiver` holds back
+/// frames, bounding the memory a sender can make it use.
+pub const MAX_PENDING_FRAMES: u32 = 1024;
+
+fn area_too_large(area: &TileRect) -> TiledError {
+    TiledError::Other(format!("Map frame area {:?} is too large", area))
+}
+
+/// The tiles of a rectangular region of one tile layer, such as a chunk or
+/// a whole layer, numbered so that frames can be applied in order.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MapFrame {
+    pub sequence: u32,
+    /// The index of the layer in `Map::layers`.
+    pub layer_index: u32,
+    pub area: TileRect,
+    /// The tiles of the area, row by row.
+    pub tiles: Vec<Option<LayerTile>>,
+}
+
+impl MapFrame {
+    /// Captures the tiles of `area` in a tile layer of the map. Positions
+    /// outside of the layer's data are empty.
+    pub fn from_region(
+        map: &Map,
+        layer_index: u32,
+        area: TileRect,
+        sequence: u32,
+    ) -> Result<MapFrame, TiledError> {
+        let layer = map
+            .layers
+            .get(layer_index as usize)
+            .and_then(|layer| layer.as_tile_layer())
+            .ok_or_else(|| not_a_tile_layer(layer_index))?;
+        let (right, bottom) = area_end(&area).ok_or_else(|| area_too_large(&area))?;
+        let mut tiles = Vec::with_capacity(area.width as usize * area.height as usize);
+        for y in area.y..bottom {
+            for x in area.x..right {
+                tiles.push(layer.get_tile(x, y));
+            }
+        }
+        Ok(MapFrame {
+            sequence,
+            layer_index,
+            area,
+            tiles,
+        })
+    }
+
+    /// Encodes the frame into a compact binary form: a small header followed
+    /// by the zlib compressed tiles.
+    pub fn encode(&self) -> Result<Vec<u8>, TiledError> {
+        let mut raw = Vec::with_capacity(self.tiles.len() * 4);
+        for tile in self.tiles.iter() {
+            raw.extend_from_slice(&cell_bits(*tile).to_le_bytes());
+        }
+        let tiles = compress(&raw, Compression::Zlib, None)
+            .map_err(|e| TiledError::Other(format!("Could not compress map frame: {}", e)))?;
+        let mut out = Vec::with_capacity(HEADER_LEN + tiles.len());
+        out.extend_from_slice(FRAME_MAGIC);
+        out.extend_from_slice(&self.sequence.to_le_bytes());
+        out.extend_from_slice(&self.layer_index.to_le_bytes());
+        out.extend_from_slice(&self.area.x.to_le_bytes());
+        out.extend_from_slice(&self.area.y.to_le_bytes());
+        out.extend_from_slice(&self.area.width.to_le_bytes());
+        out.extend_from_slice(&self.area.height.to_le_bytes());
+        out.extend_from_slice(&tiles);
+        Ok(out)
+    }
+
+    /// Decodes a frame produced by `encode`. The tiles are only decompressed
+    /// up to the size the area of the frame calls for.
+    pub fn decode(bytes: &[u8]) -> Result<MapFrame, TiledError> {
+        let malformed = |what: &str| TiledError::Other(format!("Malformed map frame: {}", what));
+        if bytes.len() < HEADER_LEN || !bytes.starts_with(FRAME_MAGIC) {
+            return Err(malformed("missing header"));
+        }
+        let field = |i: usize| -> [u8; 4] { bytes[4 + i * 4..8 + i * 4].try_into().unwrap() };
+        let sequence = u32::from_le_bytes(field(0));
+        let layer_index = u32::from_le_bytes(field(1));
+        let area = TileRect::new(
+            i32::from_le_bytes(field(2)),
+            i32::from_le_bytes(field(3)),
+            u32::from_le_bytes(field(4)),
+            u32::from_le_bytes(field(5)),
+        );
+        if area_end(&area).is_none() {
+            return Err(malformed("area extends past the largest tile coordinates"));
+        }
+        let expected = (area.width as usize)
+            .checked_mul(area.height as usize)
+            .and_then(|n| n.checked_mul(4))
+            .ok_or_else(|| malformed("area is too large"))?;
+        let raw = decode_zlib(bytes[HEADER_LEN..].to_vec(), Some(expected))?;
+        if raw.len() != expected {
+            return Err(malformed("tile count doesn't match the area"));
+        }
+        let tiles = raw
+            .chunks_exact(4)
+            .map(|b| LayerTile::from_bits(u32::from_le_bytes(b.try_into().unwrap())))
+            .collect();
+        Ok(MapFrame {
+            sequence,
+            layer_index,
+            area,
+            tiles,
+        })
+    }
+}
+
+impl Map {
+    /// Splits the data of a tile layer into frames of `chunk_size` by
+    /// `chunk_size` tiles, numbered from `first_sequence`.
+    pub fn layer_frames(
+        &self,
+        layer_index: u32,
+        chunk_size: u32,
+        first_sequence: u32,
+    ) -> Result<Vec<MapFrame>, TiledError> {
+        let bounds = self
+            .layers
+            .get(layer_index as usize)
+            .and_then(|layer| layer.as_tile_layer())
+            .ok_or_else(|| not_a_tile_layer(layer_index))?
+            .bounds();
+        let bounds = match bounds {
+            Some(bounds) if chunk_size > 0 => bounds,
+            _ => return Ok(Vec::new()),
+        };
+        let (right, bottom) = area_end(&bounds).ok_or_else(|| area_too_large(&bounds))?;
+        let mut areas = Vec::new();
+        for y in (bounds.y..bottom).step_by(chunk_size as usize) {
+            for x in (bounds.x..right).step_by(chunk_size as usize) {
+                let chunk = TileRect::new(x, y, chunk_size, chunk_size);
+                areas.extend(chunk.intersection(&bounds));
+            }
+        }
+        (first_sequence..)
+            .zip(areas)
+            .map(|(sequence, area)| MapFrame::from_region(self, layer_index, area, sequence))
+            .collect()
+    }
+
+    /// Writes the tiles of a frame into its layer. Infinite layers allocate
+    /// chunks as needed, while finite layers return an error if the frame
+    /// doesn't fit within them. Frames are checked as a whole before any of
+    /// their tiles are written, so a frame which can't be applied leaves the
+    /// layer as it was.
+    pub fn apply_frame(&mut self, frame: &MapFrame) -> Result<(), TiledError> {
+        let layer = self
+            .layers
+            .get_mut(frame.layer_index as usize)
+            .and_then(|layer| layer.as_tile_layer_mut())
+            .ok_or_else(|| not_a_tile_layer(frame.layer_index))?;
+        let area = frame.area;
+        let (right, bottom) = area_end(&area).ok_or_else(|| area_too_large(&area))?;
+        if frame.tiles.len() != area.width as usize * area.height as usize {
+            return Err(TiledError::Other(
+                "Map frame tile count doesn't match its area".to_string(),
+            ));
+        }
+        let positions = || (area.y..bottom).flat_map(move |y| (area.x..right).map(move |x| (x, y)));
+        // Avoid allocating chunks of infinite layers for empty tiles.
+        let infinite = matches!(layer.tiles, LayerData::Infinite(_));
+        let skipped = |layer: &TileLayer, x, y, tile: Option<LayerTile>| {
+            infinite && tile.is_none() && layer.cell(x, y).is_none()
+        };
+        for ((x, y), &tile) in positions().zip(frame.tiles.iter()) {
+            if !skipped(layer, x, y, tile) {
+                layer.check_set_tile(x, y)?;
+            }
+        }
+        for ((x, y), &tile) in positions().zip(frame.tiles.iter()) {
+            if !skipped(layer, x, y, tile) {
+                layer.set_tile(x, y, tile)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Applies frames to a map in the order of their sequence ids, holding back
+/// frames which arrive early and dropping ones which arrive again. At most
+/// `MAX_PENDING_FRAMES` frames are held back at a time.
+#[derive(Debug, Default, Clone)]
+pub struct FrameReceiver {
+    next_sequence: u32,
+    pending: BTreeMap<u32, MapFrame>,
+}
+
+impl FrameReceiver {
+    /// Creates a receiver expecting `first_sequence` to be the first frame.
+    pub fn new(first_sequence: u32) -> FrameReceiver {
+        FrameReceiver {
+            next_sequence: first_sequence,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// The sequence id of the next frame to apply.
+    pub fn next_sequence(&self) -> u32 {
+        self.next_sequence
+    }
+
+    /// Receives a frame, applying it and any held back frames following it
+    /// if it is the next one in sequence. Returns the number of frames
+    /// applied to the map. Frames too far ahead of the next one to hold back
+    /// return an error.
+    ///
+    /// A frame which can't be applied, like one reaching outside of a finite
+    /// layer, leaves the map as it was and is skipped, so that it doesn't
+    /// hold back the frames after it. Its error is returned once the held
+    /// back frames following it have been applied.
+    pub fn receive(&mut self, map: &mut Map, frame: MapFrame) -> Result<usize, TiledError> {
+        if frame.sequence < self.next_sequence {
+            return Ok(0);
+        }
+        if frame.sequence - self.next_sequence >= MAX_PENDING_FRAMES {
+            return Err(TiledError::Other(format!(
+                "Map frame {} is too far ahead of the expected frame {}",
+                frame.sequence, self.next_sequence
+            )));
+        }
+        self.pending.insert(frame.sequence, frame);
+        let mut applied = 0;
+        let mut error = None;
+        while let Some(frame) = self.pending.remove(&self.next_sequence) {
+            match map.apply_frame(&frame) {
+                Ok(()) => applied += 1,
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+            self.next_sequence = self.next_sequence.wrapping_add(1);
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(applied),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Surface normals of the collision shapes of tiles.
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::colliders::shape_outline;
+use crate::{LayerTile, Map, ObjectShape, TileLayer};
+
+/// An edge of a tile's collision shape which faces outwards, found by
+/// `Map::collision_edges`.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct CollisionEdge {
+    /// The start of the edge, in pixels within the layer.
+    pub start: (f32, f32),
+    /// The end of the edge, in pixels within the layer.
+    pub end: (f32, f32),
+    /// The unit vector perpendicular to the edge, pointing out of the shape.
+    /// Floors point up, with a negative y.
+    pub normal: (f32, f32),
+}
+
+/// Returns the rectangle and polygon collision shapes of a tile as polygons
+/// in pixels relative to the tile's top left corner, with its flips applied.
+fn tile_polygons(map: &Map, tile: LayerTile) -> Vec<Vec<(f32, f32)>> {
+    let (tileset, id) = match map.tileset_for_gid(tile) {
+        Some(found) if !tile.gid.is_empty() => found,
+        _ => return Vec::new(),
+    };
+    let group = match tileset
+        .get_tile(id)
+        .and_then(|t| t.objectgroup.as_ref()?.as_object_layer())
+    {
+        Some(group) => group,
+        None => return Vec::new(),
+    };
+    let size = (tileset.tile_width as f32, tileset.tile_height as f32);
+    group
+        .objects
+        .iter()
+        .filter(|object| {
+            matches!(
+                object.shape,
+                ObjectShape::Rect { .. } | ObjectShape::Polygon { .. }
+            )
+        })
+        .filter_map(|object| shape_outline(object, tile, size))
+        .map(|(polygon, _)| polygon)
+        .collect()
+}
+
+/// Returns the edges of a polygon with their outward normals, running
+/// clockwise on screen.
+fn polygon_edges(polygon: &[(f32, f32)]) -> Vec<CollisionEdge> {
+    let mut polygon = polygon.to_vec();
+    // With y pointing down, a negative area means the points run
+    // counterclockwise on screen.
+    let area: f32 = (0..polygon.len())
+        .map(|i| {
+            let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum();
+    if area < 0.0 {
+        polygon.reverse();
+    }
+    // Going clockwise, the outside is on the left of each edge.
+    (0..polygon.len())
+        .filter_map(|i| {
+            let (start, end) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+            let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+            let length = (dx * dx + dy * dy).sqrt();
+            if length == 0.0 {
+                return None;
+            }
+            Some(CollisionEdge {
+                start,
+                end,
+                normal: (dy / length, -dx / length),
+            })
+        })
+        .collect()
+}
+
+/// Rounds a point so that edges shared by neighbouring tiles compare equal.
+fn key((x, y): (f32, f32)) -> (i64, i64) {
+    ((x * 1000.0).round() as i64, (y * 1000.0).round() as i64)
+}
+
+impl Map {
+    /// Computes the outward facing edges of the collision shapes of a layer's
+    /// tiles, keyed by tile position, for character controllers needing the
+    /// normals of floors, slopes and walls. Edges shared by neighbouring
+    /// shapes, such as between two solid tiles, are left out since they
+    /// can't be touched. Only rectangles and polygons are taken into account.
+    ///
+    /// Tiles of tilesets with a different tile size than the map are aligned
+    /// to the bottom left of their cell, like Tiled draws them.
+    pub fn collision_edges(&self, layer: &TileLayer) -> BTreeMap<(i32, i32), Vec<CollisionEdge>> {
+        let mut edges = BTreeMap::new();
+        let bounds = match layer.bounds() {
+            Some(bounds) => bounds,
+            None => return edges,
+        };
+        for y in bounds.y..bounds.bottom() {
+            for x in bounds.x..bounds.right() {
+                let tile = match layer.get_tile(x, y) {
+                    Some(tile) => tile,
+                    None => continue,
+                };
+                let tile_height = self
+                    .get_tileset_by_gid(tile.gid)
+                    .map_or(0.0, |tileset| tileset.tile_height as f32);
+                let origin = (
+                    x as f32 * self.tile_width as f32,
+                    (y + 1) as f32 * self.tile_height as f32 - tile_height,
+                );
+                let tile_edges: Vec<CollisionEdge> = tile_polygons(self, tile)
+                    .iter()
+                    .flat_map(|polygon| polygon_edges(polygon))
+                    .map(|edge| CollisionEdge {
+                        start: (origin.0 + edge.start.0, origin.1 + edge.start.1),
+                        end: (origin.0 + edge.end.0, origin.1 + edge.end.1),
+                        normal: edge.normal,
+                    })
+                    .collect();
+                if !tile_edges.is_empty() {
+                    edges.insert((x, y), tile_edges);
+                }
+            }
+        }
+        // Edges shared by two shapes run in opposite directions.
+        let all: HashSet<((i64, i64), (i64, i64))> = edges
+            .values()
+            .flatten()
+            .map(|edge| (key(edge.start), key(edge.end)))
+            .collect();
+        for tile_edges in edges.values_mut() {
+            tile_edges.retain(|edge| !all.contains(&(key(edge.end), key(edge.start))));
+        }
+        edges.retain(|_, tile_edges| !tile_edges.is_empty());
+        edges
+    }
+}
@@ -0,0 +1,160 @@
+//! Loading of Tiled object types files (`objecttypes.xml`), which give the
+//! default properties of objects by their type.
+
+use std::collections::HashMap;
+#[cfg(feature = "fs")]
+use std::fs::File;
+use std::io::Read;
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::sync::Arc;
+
+use xml::attribute::OwnedAttribute;
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::{Colour, Layer, LayerType, Map, Object, Properties, PropertyValue, TiledError};
+
+/// A type of object along with the default values of its properties.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ObjectType {
+    pub name: String,
+    /// The colour Tiled draws objects of the type with.
+    pub colour: Option<Colour>,
+    pub properties: Properties,
+}
+
+/// The object types of a project, keyed by name.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ObjectTypes {
+    pub types: HashMap<String, ObjectType>,
+}
+
+fn attr<'a>(attrs: &'a [OwnedAttribute], name: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .find(|a| a.name.local_name == name)
+        .map(|a| a.value.as_str())
+}
+
+/// Returns the value a property of the given type takes when no default is
+/// given.
+fn empty_value(property_type: &str) -> Result<PropertyValue, TiledError> {
+    Ok(match property_type {
+        "bool" => PropertyValue::BoolValue(false),
+        "float" => PropertyValue::FloatValue(0.0),
+        "int" => PropertyValue::IntValue(0),
+        "color" => PropertyValue::ColorValue(Colour::new(0, 0, 0).with_alpha(0)),
+        "object" => PropertyValue::ObjectValue(0),
+        property_type => PropertyValue::new(property_type.to_string(), String::new())?,
+    })
+}
+
+impl ObjectTypes {
+    /// Parses an object types file.
+    pub fn parse<R: Read>(reader: R) -> Result<ObjectTypes, TiledError> {
+        let mut parser = EventReader::new(reader);
+        let mut types = HashMap::new();
+        let mut current: Option<ObjectType> = None;
+        loop {
+            match parser.next().map_err(TiledError::XmlDecodingError)? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => match name.local_name.as_str() {
+                    "objecttype" => {
+                        let name = attr(&attributes, "name").ok_or_else(|| {
+                            TiledError::MissingAttribute {
+                                element: "objecttype".to_string(),
+                                attribute: "name".to_string(),
+                            }
+                        })?;
+                        current = Some(ObjectType {
+                            name: name.to_string(),
+                            colour: attr(&attributes, "color").and_then(|c| c.parse().ok()),
+                            properties: HashMap::new(),
+                        });
+                    }
+                    "property" => {
+                        let object_type = match current {
+                            Some(ref mut object_type) => object_type,
+                            None => continue,
+                        };
+                        let name = attr(&attributes, "name").ok_or_else(|| {
+                            TiledError::MissingAttribute {
+                                element: "property".to_string(),
+                                attribute: "name".to_string(),
+                            }
+                        })?;
+                        let property_type = attr(&attributes, "type").unwrap_or("string");
+                        let value = match attr(&attributes, "default") {
+                            Some(value) if !value.is_empty() => {
+                                PropertyValue::new(property_type.to_string(), value.to_string())?
+                            }
+                            _ => empty_value(property_type)?,
+                        };
+                        object_type.properties.insert(name.to_string(), value);
+                    }
+                    _ => {}
+                },
+                XmlEvent::EndElement { name } if name.local_name == "objecttype" => {
+                    if let Some(object_type) = current.take() {
+                        types.insert(object_type.name.clone(), object_type);
+                    }
+                }
+                XmlEvent::EndDocument => break,
+                _ => {}
+            }
+        }
+        Ok(ObjectTypes { types })
+    }
+
+    /// Adds the default properties of the object's type which the object
+    /// doesn't set itself.
+    pub fn apply(&self, object: &mut Object) {
+        if let Some(object_type) = self.types.get(&object.obj_type) {
+            for (name, value) in object_type.properties.iter() {
+                object
+                    .properties
+                    .entry(name.clone())
+                    .or_insert_with(|| value.clone());
+            }
+        }
+    }
+}
+
+/// Parses an object types file.
+#[cfg(feature = "fs")]
+pub fn parse_object_types_file(path: impl AsRef<Path>) -> Result<ObjectTypes, TiledError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| TiledError::io(path, e))?;
+    ObjectTypes::parse(file)
+}
+
+impl Map {
+    /// Adds the default properties of their types to the objects of the map,
+    /// including the collision shapes of tiles, like Tiled does when showing
+    /// them. Properties set on the objects themselves are kept.
+    pub fn apply_object_types(&mut self, object_types: &ObjectTypes) {
+        for layer in self.leaf_layers_mut() {
+            if let LayerType::ObjectLayer(ref mut group) = layer.layer_type {
+                group
+                    .objects
+                    .iter_mut()
+                    .for_each(|object| object_types.apply(object));
+            }
+        }
+        for tileset in self.tilesets.iter_mut() {
+            for tile in Arc::make_mut(tileset).tiles.iter_mut() {
+                if let Some(group) = tile
+                    .objectgroup
+                    .as_mut()
+                    .and_then(Layer::as_object_layer_mut)
+                {
+                    group
+                        .objects
+                        .iter_mut()
+                        .for_each(|object| object_types.apply(object));
+                }
+            }
+        }
+    }
+}
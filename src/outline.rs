@@ -0,0 +1,240 @@
+//! Conversion of areas of tiles into polygon objects.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::{Extra, Gid, LayerTile, Object, ObjectShape, Region, TileLayer};
+
+/// An area of tiles traced by `TileLayer::trace_shapes`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TracedShape {
+    /// The outer boundary of the area, running clockwise.
+    pub outline: Vec<(f32, f32)>,
+    /// The boundaries of the holes within the area, running
+    /// counterclockwise.
+    pub holes: Vec<Vec<(f32, f32)>>,
+}
+
+type Edge = ((i32, i32), (i32, i32));
+
+/// Returns the boundary edges of a region's cells, running clockwise around
+/// the cells so the region is always to the right. Edges are ordered by
+/// their start, row by row.
+fn boundary_edges(region: &Region) -> BTreeSet<Edge> {
+    let cells: HashSet<(i32, i32)> = region.cells.iter().copied().collect();
+    let mut edges = BTreeSet::new();
+    let key = |(x, y): (i32, i32)| (y, x);
+    let mut insert = |from: (i32, i32), to: (i32, i32)| {
+        edges.insert((key(from), key(to)));
+    };
+    for &(x, y) in region.cells.iter() {
+        if !cells.contains(&(x, y - 1)) {
+            insert((x, y), (x + 1, y));
+        }
+        if !cells.contains(&(x + 1, y)) {
+            insert((x + 1, y), (x + 1, y + 1));
+        }
+        if !cells.contains(&(x, y + 1)) {
+            insert((x + 1, y + 1), (x, y + 1));
+        }
+        if !cells.contains(&(x - 1, y)) {
+            insert((x, y + 1), (x, y));
+        }
+    }
+    edges
+}
+
+/// Follows the boundary edges from the first remaining one until getting
+/// back to its start, removing the edges walked along. Returns the corners
+/// of the loop in tile coordinates.
+fn trace_loop(edges: &mut BTreeSet<Edge>) -> Vec<(i32, i32)> {
+    let (start, first) = match edges.pop_first() {
+        Some(((sy, sx), (ey, ex))) => ((sx, sy), (ex, ey)),
+        None => return Vec::new(),
+    };
+    let mut corners = vec![start];
+    let mut direction = (first.0 - start.0, first.1 - start.1);
+    let mut position = first;
+    while position != start {
+        let (dx, dy) = direction;
+        // Turning right first keeps to the current cell where the region
+        // touches itself diagonally.
+        let turns = [(-dy, dx), (dx, dy), (dy, -dx)];
+        let next = turns.iter().copied().find(|&(tx, ty)| {
+            edges.remove(&((position.1, position.0), (position.1 + ty, position.0 + tx)))
+        });
+        let turn = match next {
+            Some(turn) => turn,
+            None => break,
+        };
+        if turn != direction {
+            corners.push(position);
+            direction = turn;
+        }
+        position = (position.0 + turn.0, position.1 + turn.1);
+    }
+    corners
+}
+
+/// Traces the boundaries of a region, its outer boundary first and then
+/// those of its holes.
+fn trace_region(region: &Region) -> Vec<Vec<(i32, i32)>> {
+    // The top left cell's top edge comes first, and is always part of the
+    // outer boundary.
+    let mut edges = boundary_edges(region);
+    let mut loops = Vec::new();
+    while !edges.is_empty() {
+        loops.push(trace_loop(&mut edges));
+    }
+    loops
+}
+
+/// Returns the distance from a point to the line through two others.
+fn distance_to_line(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length == 0.0 {
+        return ((p.0 - a.0).powi(2) + (p.1 - a.1).powi(2)).sqrt();
+    }
+    ((p.0 - a.0) * dy - (p.1 - a.1) * dx).abs() / length
+}
+
+/// Marks the points between `first` and `last` to keep with the
+/// Douglas-Peucker algorithm.
+fn douglas_peucker(
+    points: &[(f32, f32)],
+    first: usize,
+    last: usize,
+    tolerance: f32,
+    keep: &mut [bool],
+) {
+    let mut farthest = (0.0, first);
+    for i in first + 1..last {
+        let distance = distance_to_line(points[i], points[first], points[last]);
+        if distance > farthest.0 {
+            farthest = (distance, i);
+        }
+    }
+    if farthest.0 > tolerance {
+        keep[farthest.1] = true;
+        douglas_peucker(points, first, farthest.1, tolerance, keep);
+        douglas_peucker(points, farthest.1, last, tolerance, keep);
+    }
+}
+
+/// Simplifies a closed polygon with the Douglas-Peucker algorithm, dropping
+/// the points which lie within `tolerance` of the outline formed by the
+/// remaining ones. Polygons which would be left with fewer than three
+/// points are returned as they are.
+pub fn simplify_polygon(points: &[(f32, f32)], tolerance: f32) -> Vec<(f32, f32)> {
+    if points.len() <= 3 || tolerance.is_nan() || tolerance <= 0.0 {
+        return points.to_vec();
+    }
+    // Split the polygon in two at the point farthest from the first one, and
+    // simplify both halves.
+    let distance = |i: usize| {
+        let (dx, dy) = (points[i].0 - points[0].0, points[i].1 - points[0].1);
+        dx * dx + dy * dy
+    };
+    let split = (1..points.len())
+        .max_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+        .unwrap_or(1);
+    let mut closed = points.to_vec();
+    closed.push(points[0]);
+    let mut keep = vec![false; closed.len()];
+    keep[0] = true;
+    keep[split] = true;
+    douglas_peucker(&closed, 0, split, tolerance, &mut keep);
+    douglas_peucker(&closed, split, points.len(), tolerance, &mut keep);
+    let simplified: Vec<(f32, f32)> = points
+        .iter()
+        .zip(keep.iter())
+        .filter(|(_, &keep)| keep)
+        .map(|(&point, _)| point)
+        .collect();
+    if simplified.len() < 3 {
+        return points.to_vec();
+    }
+    simplified
+}
+
+impl TileLayer {
+    /// Traces the outline of each area of 4-connected tiles for which
+    /// `predicate` returns true, such as solid walls painted with tiles. The
+    /// outlines are polygons running clockwise along the edges of the tiles,
+    /// with a point at each corner, in pixels given the size of the tiles.
+    /// Holes within an area aren't traced, so the outlines cover them; see
+    /// `trace_shapes` for those.
+    pub fn trace_outlines(
+        &self,
+        tile_size: (u32, u32),
+        predicate: impl FnMut(Option<LayerTile>) -> bool,
+    ) -> Vec<Vec<(f32, f32)>> {
+        self.trace_shapes(tile_size, 0.0, predicate)
+            .into_iter()
+            .map(|shape| shape.outline)
+            .collect()
+    }
+
+    /// Traces the areas of 4-connected tiles for which `predicate` returns
+    /// true like `trace_outlines`, along with the holes within them. The
+    /// outlines and holes are simplified with `simplify_polygon` given the
+    /// `tolerance` in pixels, where 0 keeps every corner.
+    pub fn trace_shapes(
+        &self,
+        tile_size: (u32, u32),
+        tolerance: f32,
+        predicate: impl FnMut(Option<LayerTile>) -> bool,
+    ) -> Vec<TracedShape> {
+        let (width, height) = (tile_size.0 as f32, tile_size.1 as f32);
+        let to_pixels = |corners: Vec<(i32, i32)>| -> Vec<(f32, f32)> {
+            let points: Vec<(f32, f32)> = corners
+                .into_iter()
+                .map(|(x, y)| (x as f32 * width, y as f32 * height))
+                .collect();
+            simplify_polygon(&points, tolerance)
+        };
+        self.connected_components(predicate)
+            .iter()
+            .map(|region| {
+                let mut loops = trace_region(region).into_iter();
+                TracedShape {
+                    outline: loops.next().map(&to_pixels).unwrap_or_default(),
+                    holes: loops.map(&to_pixels).collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// Converts the outlines found by `trace_outlines` into polygon objects,
+    /// for compact collision shapes. The objects have an id of 0, so they
+    /// can be given ids as they are added with `Map::add_object`.
+    pub fn to_polygon_objects(
+        &self,
+        tile_size: (u32, u32),
+        predicate: impl FnMut(Option<LayerTile>) -> bool,
+    ) -> Vec<Object> {
+        self.trace_outlines(tile_size, predicate)
+            .into_iter()
+            .map(|outline| {
+                let (x, y) = outline[0];
+                let points = outline.iter().map(|&(px, py)| (px - x, py - y)).collect();
+                Object {
+                    id: 0,
+                    gid: Gid::EMPTY,
+                    name: String::new(),
+                    obj_type: String::new(),
+                    width: 0.0,
+                    height: 0.0,
+                    x,
+                    y,
+                    rotation: 0.0,
+                    visible: true,
+                    locked: false,
+                    shape: ObjectShape::Polygon { points },
+                    properties: HashMap::new(),
+                    extra: Extra::default(),
+                }
+            })
+            .collect()
+    }
+}
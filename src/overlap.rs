@@ -0,0 +1,64 @@
+//! Detection of tilesets whose ranges of global tile ids overlap.
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::{Map, Tileset};
+
+/// Two tilesets of a map claiming the same global tile ids, found by
+/// `Map::tileset_overlaps`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TilesetOverlap {
+    /// The index in `Map::tilesets` of the earlier tileset.
+    pub first: usize,
+    /// The index in `Map::tilesets` of the later tileset, which the shared
+    /// ids resolve to.
+    pub second: usize,
+    /// The global tile ids claimed by both tilesets.
+    pub gids: Range<u32>,
+}
+
+impl fmt::Display for TilesetOverlap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "tilesets {} and {} both claim gids {} to {}",
+            self.first,
+            self.second,
+            self.gids.start,
+            self.gids.end - 1
+        )
+    }
+}
+
+/// Returns the global tile ids of a tileset. Tilesets which don't know
+/// their tile count are taken to hold a single tile, so only duplicate
+/// first gids are found for them.
+fn gid_range(tileset: &Tileset) -> Range<u32> {
+    let count = tileset.tilecount.unwrap_or(1).max(1);
+    let first_gid = tileset.first_gid.value();
+    first_gid..first_gid.saturating_add(count)
+}
+
+impl Map {
+    /// Finds the pairs of tilesets whose ranges of global tile ids overlap,
+    /// as found in maps edited by hand or written by buggy exporters. The
+    /// shared ids resolve to the later tileset in `get_tileset_by_gid`.
+    pub fn tileset_overlaps(&self) -> Vec<TilesetOverlap> {
+        let mut overlaps = Vec::new();
+        for (first, a) in self.tilesets.iter().enumerate() {
+            for (second, b) in self.tilesets.iter().enumerate().skip(first + 1) {
+                let (a, b) = (gid_range(a), gid_range(b));
+                let gids = a.start.max(b.start)..a.end.min(b.end);
+                if !gids.is_empty() {
+                    overlaps.push(TilesetOverlap {
+                        first,
+                        second,
+                        gids,
+                    });
+                }
+            }
+        }
+        overlaps
+    }
+}
@@ -0,0 +1,717 @@
+//! Overlays describing changes to a map, which mods can ship instead of
+//! full copies of the maps they change.
+
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(feature = "fs")]
+use std::fs::File;
+use std::io::Read;
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::slice;
+
+use xml::attribute::OwnedAttribute;
+use xml::reader::XmlEvent;
+
+use crate::delta::TileDelta;
+use crate::extra::xml_reader;
+use crate::group::max_object_id;
+use crate::{
+    parse_properties, Gid, Layer, LayerData, LayerTag, LayerTile, LayerType, Loader, Map, Object,
+    Properties, TiledError,
+};
+
+/// A set of changes to a map, made against a base version of it.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MapPatch {
+    /// Properties added to the map or replacing its own.
+    pub properties: Properties,
+    pub changes: Vec<LayerChange>,
+}
+
+/// A change to the layers of a map. Layers are found by name, since mods
+/// may add or remove layers, moving the others.
+#[derive(Debug, PartialEq, Clone)]
+pub enum LayerChange {
+    /// Replaces the layer with the same name, or adds the layer on top of
+    /// the others if there's none.
+    Set(Layer),
+    Remove(String),
+    /// Changes tiles of a tile layer.
+    Tiles {
+        layer: String,
+        tiles: Vec<TileChange>,
+    },
+    /// Changes objects of an object layer, found by id.
+    Objects {
+        layer: String,
+        edits: Vec<ObjectEdit>,
+    },
+    /// Adds properties to a layer or replaces its own.
+    Properties {
+        layer: String,
+        properties: Properties,
+    },
+}
+
+/// A changed tile, along with the tile the base map had there.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct TileChange {
+    pub x: i32,
+    pub y: i32,
+    pub from: Option<LayerTile>,
+    pub to: Option<LayerTile>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ObjectEdit {
+    Add(Object),
+    /// Replaces the object with the same id.
+    Replace(Object),
+    /// Removes the object with the given id.
+    Remove(u32),
+}
+
+/// A change of a patch which couldn't be applied because the map differs
+/// from the base the patch was made against, such as a tile another mod
+/// changed already.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PatchConflict {
+    pub layer: String,
+    pub reason: String,
+}
+
+impl fmt::Display for PatchConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "layer \"{}\": {}", self.layer, self.reason)
+    }
+}
+
+/// Returns a copy of the layer without its properties and content, to
+/// compare the rest of its attributes.
+fn layer_attributes(layer: &Layer) -> Layer {
+    let mut layer = layer.clone();
+    layer.properties.clear();
+    match layer.layer_type {
+        LayerType::TileLayer(ref mut tiles) => match tiles.tiles {
+            LayerData::Finite(ref mut rows) => rows.clear(),
+            LayerData::Infinite(ref mut chunks) => chunks.clear(),
+        },
+        LayerType::ObjectLayer(ref mut group) => group.objects.clear(),
+        // Groups keep their layers, so that any change within a group
+        // replaces it as a whole.
+        LayerType::ImageLayer(_) | LayerType::Group(_) => {}
+    }
+    layer
+}
+
+fn find_layer<'a>(map: &'a Map, name: &str) -> Option<&'a Layer> {
+    map.layers.iter().find(|layer| layer.name == name)
+}
+
+/// Returns the changes turning the `base` version of a layer into `layer`.
+fn layer_changes(base: &Layer, layer: &Layer) -> Vec<LayerChange> {
+    let removed_property = base
+        .properties
+        .keys()
+        .any(|name| !layer.properties.contains_key(name));
+    if removed_property || layer_attributes(base) != layer_attributes(layer) {
+        return vec![LayerChange::Set(layer.clone())];
+    }
+    let mut changes = Vec::new();
+    match (&base.layer_type, &layer.layer_type) {
+        (LayerType::TileLayer(before), LayerType::TileLayer(after)) => {
+            let tiles: Vec<TileChange> = TileDelta::between(0, before, after)
+                .cells
+                .into_iter()
+                .map(|(x, y, to)| TileChange {
+                    x,
+                    y,
+                    from: before.get_tile(x, y),
+                    to,
+                })
+                .collect();
+            if !tiles.is_empty() {
+                changes.push(LayerChange::Tiles {
+                    layer: layer.name.clone(),
+                    tiles,
+                });
+            }
+        }
+        (LayerType::ObjectLayer(before), LayerType::ObjectLayer(after)) => {
+            let old: HashMap<u32, &Object> = before.objects.iter().map(|o| (o.id, o)).collect();
+            let new: HashMap<u32, &Object> = after.objects.iter().map(|o| (o.id, o)).collect();
+            let mut edits: Vec<ObjectEdit> = before
+                .objects
+                .iter()
+                .filter(|object| !new.contains_key(&object.id))
+                .map(|object| ObjectEdit::Remove(object.id))
+                .collect();
+            for object in after.objects.iter() {
+                match old.get(&object.id) {
+                    Some(&old) if old == object => {}
+                    Some(_) => edits.push(ObjectEdit::Replace(object.clone())),
+                    None => edits.push(ObjectEdit::Add(object.clone())),
+                }
+            }
+            if !edits.is_empty() {
+                changes.push(LayerChange::Objects {
+                    layer: layer.name.clone(),
+                    edits,
+                });
+            }
+        }
+        _ => {}
+    }
+    let properties: Properties = layer
+        .properties
+        .iter()
+        .filter(|&(name, value)| base.properties.get(name) != Some(value))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    if !properties.is_empty() {
+        changes.push(LayerChange::Properties {
+            layer: layer.name.clone(),
+            properties,
+        });
+    }
+    changes
+}
+
+impl Map {
+    /// Returns the patch turning `base` into this map. Layers changed in
+    /// their attributes or type are replaced as a whole, as are layers which
+    /// lost properties, while tile and object layers otherwise only record
+    /// the tiles and objects which changed. Properties removed from the map
+    /// itself aren't recorded.
+    pub fn create_patch(&self, base: &Map) -> MapPatch {
+        let properties = self
+            .properties
+            .iter()
+            .filter(|&(name, value)| base.properties.get(name) != Some(value))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        let mut changes: Vec<LayerChange> = base
+            .layers
+            .iter()
+            .filter(|layer| find_layer(self, &layer.name).is_none())
+            .map(|layer| LayerChange::Remove(layer.name.clone()))
+            .collect();
+        for layer in self.layers.iter() {
+            match find_layer(base, &layer.name) {
+                Some(old) => changes.extend(layer_changes(old, layer)),
+                None => changes.push(LayerChange::Set(layer.clone())),
+            }
+        }
+        MapPatch {
+            properties,
+            changes,
+        }
+    }
+
+    /// Applies a patch to the map. Changes which don't fit the map, like
+    /// tiles which differ from the ones the patch expects or objects which
+    /// are missing, are skipped and returned as conflicts, while the rest of
+    /// the patch is applied.
+    pub fn apply_patch(&mut self, patch: &MapPatch) -> Vec<PatchConflict> {
+        let mut decide = |_: &str, _: Target, conflicting: bool| Decision {
+            conflicting,
+            apply: !conflicting,
+        };
+        self.apply_patch_with(patch, &mut decide)
+            .into_iter()
+            .map(|(conflict, _)| conflict)
+            .collect()
+    }
+
+    /// Applies a patch, asking `decide` whether to apply each change to a
+    /// layer, tile or object, given whether it conflicts with the map.
+    /// Returns the conflicts along with whether their changes were applied
+    /// anyway.
+    fn apply_patch_with(
+        &mut self,
+        patch: &MapPatch,
+        decide: &mut dyn FnMut(&str, Target, bool) -> Decision,
+    ) -> Vec<(PatchConflict, bool)> {
+        self.properties.extend(patch.properties.clone());
+        let mut conflicts = Vec::new();
+        for change in patch.changes.iter() {
+            if let Err(conflict) = self.apply_change(change, decide, &mut conflicts) {
+                conflicts.push((conflict, false));
+            }
+        }
+        conflicts
+    }
+
+    /// Applies a single change, returning an error if it can't be applied at
+    /// all and adding the conflicts of its parts to `conflicts`.
+    fn apply_change(
+        &mut self,
+        change: &LayerChange,
+        decide: &mut dyn FnMut(&str, Target, bool) -> Decision,
+        conflicts: &mut Vec<(PatchConflict, bool)>,
+    ) -> Result<(), PatchConflict> {
+        let conflict = |layer: &str, reason: String| PatchConflict {
+            layer: layer.to_string(),
+            reason,
+        };
+        // Changes to a whole layer only conflict with the changes earlier
+        // patches made to it, which `decide` knows about.
+        let mut decide_layer = |name: &str, conflicts: &mut Vec<(PatchConflict, bool)>| {
+            let decision = decide(name, Target::Layer, false);
+            if decision.conflicting {
+                let reason = "an earlier patch changed the layer".to_string();
+                conflicts.push((conflict(name, reason), decision.apply));
+            }
+            decision.apply
+        };
+        let name = match change {
+            LayerChange::Set(layer) => {
+                if decide_layer(&layer.name, conflicts) {
+                    match self.layers.iter().position(|l| l.name == layer.name) {
+                        Some(index) => {
+                            self.layers[index] = layer.clone();
+                            self.reserve_object_ids(layer);
+                        }
+                        None => self.add_layer(layer.clone()),
+                    }
+                }
+                return Ok(());
+            }
+            LayerChange::Remove(name) => {
+                let index = self
+                    .layers
+                    .iter()
+                    .position(|l| &l.name == name)
+                    .ok_or_else(|| conflict(name, "the layer is missing".to_string()))?;
+                if decide_layer(name, conflicts) {
+                    self.layers.remove(index);
+                }
+                return Ok(());
+            }
+            LayerChange::Properties { layer, properties } => {
+                let index = self
+                    .layers
+                    .iter()
+                    .position(|l| &l.name == layer)
+                    .ok_or_else(|| conflict(layer, "the layer is missing".to_string()))?;
+                if decide_layer(layer, conflicts) {
+                    self.layers[index].properties.extend(properties.clone());
+                }
+                return Ok(());
+            }
+            LayerChange::Tiles { layer, .. } | LayerChange::Objects { layer, .. } => layer,
+        };
+        let index = self
+            .layers
+            .iter()
+            .position(|l| &l.name == name)
+            .ok_or_else(|| conflict(name, "the layer is missing".to_string()))?;
+        let layer = &mut self.layers[index];
+        match change {
+            LayerChange::Tiles { tiles, .. } => {
+                let layer = layer
+                    .as_tile_layer_mut()
+                    .ok_or_else(|| conflict(name, "the layer isn't a tile layer".to_string()))?;
+                for change in tiles.iter() {
+                    let current = layer.get_tile(change.x, change.y);
+                    let conflicting = current != change.from && current != change.to;
+                    let Decision { conflicting, apply } =
+                        decide(name, Target::Tile(change.x, change.y), conflicting);
+                    if conflicting {
+                        let gid = |tile: Option<LayerTile>| tile.map_or(Gid::EMPTY, |t| t.gid);
+                        let reason = format!(
+                            "the tile at ({}, {}) has gid {} instead of {}",
+                            change.x,
+                            change.y,
+                            gid(current),
+                            gid(change.from)
+                        );
+                        conflicts.push((conflict(name, reason), apply));
+                    }
+                    if !apply {
+                        continue;
+                    }
+                    if let Err(e) = layer.set_tile(change.x, change.y, change.to) {
+                        conflicts.push((conflict(name, e.to_string()), false));
+                    }
+                }
+            }
+            LayerChange::Objects { edits, .. } => {
+                let group = match layer.layer_type {
+                    LayerType::ObjectLayer(ref mut group) => group,
+                    _ => {
+                        return Err(conflict(
+                            name,
+                            "the layer isn't an object layer".to_string(),
+                        ))
+                    }
+                };
+                let mut added = Vec::new();
+                for edit in edits.iter() {
+                    let id = match edit {
+                        ObjectEdit::Add(object) | ObjectEdit::Replace(object) => object.id,
+                        ObjectEdit::Remove(id) => *id,
+                    };
+                    let position = group.objects.iter().position(|o| o.id == id);
+                    let (object, conflicting) = match (edit, position) {
+                        (ObjectEdit::Remove(_), None) => {
+                            let reason = format!("the object with id {} is missing", id);
+                            conflicts.push((conflict(name, reason), false));
+                            continue;
+                        }
+                        (ObjectEdit::Remove(_), Some(_)) => (None, false),
+                        (ObjectEdit::Add(object), _) => (Some(object), position.is_some()),
+                        (ObjectEdit::Replace(object), _) => (Some(object), position.is_none()),
+                    };
+                    let Decision { conflicting, apply } =
+                        decide(name, Target::Object(id), conflicting);
+                    if conflicting {
+                        let reason = match position {
+                            Some(_) => format!("an object with id {} exists already", id),
+                            None => format!("the object with id {} is missing", id),
+                        };
+                        conflicts.push((conflict(name, reason), apply));
+                    }
+                    if !apply {
+                        continue;
+                    }
+                    match (object, position) {
+                        (Some(object), Some(i)) => group.objects[i] = object.clone(),
+                        (Some(object), None) => {
+                            group.objects.push(object.clone());
+                            added.push(id);
+                        }
+                        (None, Some(i)) => {
+                            group.objects.remove(i);
+                        }
+                        (None, None) => {}
+                    }
+                }
+                if let Some(&max) = added.iter().max() {
+                    self.next_object_id = self.next_object_id.max(max.saturating_add(1));
+                }
+            }
+            LayerChange::Set(_) | LayerChange::Remove(_) | LayerChange::Properties { .. } => {}
+        }
+        Ok(())
+    }
+
+    /// Keeps the ids of the objects of a replaced layer from being handed
+    /// out again.
+    fn reserve_object_ids(&mut self, layer: &Layer) {
+        if let Some(max) = max_object_id(slice::from_ref(layer)) {
+            self.next_object_id = self.next_object_id.max(max.saturating_add(1));
+        }
+    }
+}
+
+/// What a change of a patch applies to, to track which patch last changed
+/// what when applying several of them.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+enum Target {
+    Layer,
+    Tile(i32, i32),
+    Object(u32),
+}
+
+/// What to do with a change of a patch: whether it conflicts, and whether
+/// to apply it anyway.
+#[derive(Debug, Copy, Clone)]
+struct Decision {
+    conflicting: bool,
+    apply: bool,
+}
+
+/// How a `PatchStack` resolves changes of a patch which conflict with the
+/// map as left by the patches before it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ConflictPolicy {
+    /// Applies conflicting changes anyway, so later patches win.
+    LastWins,
+    /// Fails without changing the map on the first conflict.
+    Error,
+    /// Gives each layer, by name, the names of the patches allowed to
+    /// change it, highest priority first. A conflicting change is applied
+    /// unless a patch of higher priority changed the same tile or object
+    /// before, or anything on the layer for changes replacing, removing or
+    /// setting properties of a whole layer. Patches which aren't listed rank
+    /// below those which are, in load order. Layers without priorities behave
+    /// as with `LastWins`.
+    LayerPriority(HashMap<String, Vec<String>>),
+}
+
+/// A conflict found while applying a stack of patches.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StackConflict {
+    /// The name of the patch whose change conflicted.
+    pub patch: String,
+    pub conflict: PatchConflict,
+    /// Whether the change was applied anyway, as the policy decided.
+    pub applied: bool,
+}
+
+impl fmt::Display for StackConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "patch \"{}\", {}", self.patch, self.conflict)
+    }
+}
+
+/// Named patches applied in order, like the mods of a game in their load
+/// order.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PatchStack {
+    pub policy: ConflictPolicy,
+    pub patches: Vec<(String, MapPatch)>,
+}
+
+impl PatchStack {
+    pub fn new(policy: ConflictPolicy) -> PatchStack {
+        PatchStack {
+            policy,
+            patches: Vec::new(),
+        }
+    }
+
+    /// Adds a patch after the others.
+    pub fn with_patch(mut self, name: impl Into<String>, patch: MapPatch) -> PatchStack {
+        self.patches.push((name.into(), patch));
+        self
+    }
+
+    /// Applies the patches to the map in order, resolving conflicts with the
+    /// policy. Returns the conflicts found, or an error if the policy is
+    /// `ConflictPolicy::Error` and there were any.
+    pub fn apply(&self, map: &mut Map) -> Result<Vec<StackConflict>, TiledError> {
+        if self.policy == ConflictPolicy::Error {
+            let mut patched = map.clone();
+            let conflicts = self.run(&mut patched, true)?;
+            *map = patched;
+            return Ok(conflicts);
+        }
+        self.run(map, false)
+    }
+
+    /// Returns the conflicts applying the patches to the map would find,
+    /// without changing it. With `ConflictPolicy::Error` conflicting changes
+    /// are skipped, to report every conflict rather than the first.
+    pub fn dry_run(&self, map: &Map) -> Vec<StackConflict> {
+        self.run(&mut map.clone(), false).unwrap_or_default()
+    }
+
+    fn run(&self, map: &mut Map, fail: bool) -> Result<Vec<StackConflict>, TiledError> {
+        // The index of the patch which last changed each layer, tile and
+        // object.
+        let mut owners: HashMap<(String, Target), usize> = HashMap::new();
+        let mut found = Vec::new();
+        for (index, (name, patch)) in self.patches.iter().enumerate() {
+            let mut decide = |layer: &str, target: Target, conflicting: bool| {
+                let key = (layer.to_string(), target);
+                // A change to a whole layer competes with every change
+                // earlier patches made to it, and conflicts with any of them.
+                let earlier: Vec<usize> = match target {
+                    Target::Layer => owners
+                        .iter()
+                        .filter(|&((name, _), &owner)| name == layer && owner < index)
+                        .map(|(_, &owner)| owner)
+                        .collect(),
+                    _ => owners
+                        .get(&key)
+                        .or_else(|| owners.get(&(layer.to_string(), Target::Layer)))
+                        .copied()
+                        .into_iter()
+                        .collect(),
+                };
+                let conflicting = conflicting || (target == Target::Layer && !earlier.is_empty());
+                let apply = !conflicting
+                    || match self.policy {
+                        ConflictPolicy::LastWins => true,
+                        ConflictPolicy::Error => false,
+                        ConflictPolicy::LayerPriority(_) => earlier
+                            .iter()
+                            .all(|&owner| self.outranks(layer, index, owner)),
+                    };
+                if apply {
+                    if target == Target::Layer {
+                        owners.retain(|(name, _), _| name != layer);
+                    }
+                    owners.insert(key, index);
+                }
+                Decision { conflicting, apply }
+            };
+            let conflicts = map.apply_patch_with(patch, &mut decide);
+            if let (true, Some((conflict, _))) = (fail, conflicts.first()) {
+                return Err(TiledError::Other(format!(
+                    "Patch \"{}\" conflicts with the map: {}",
+                    name, conflict
+                )));
+            }
+            found.extend(
+                conflicts
+                    .into_iter()
+                    .map(|(conflict, applied)| StackConflict {
+                        patch: name.clone(),
+                        conflict,
+                        applied,
+                    }),
+            );
+        }
+        Ok(found)
+    }
+
+    /// Returns whether the patch at `challenger` may override the changes
+    /// the patch at `owner` made to a layer.
+    fn outranks(&self, layer: &str, challenger: usize, owner: usize) -> bool {
+        let priorities = match self.policy {
+            ConflictPolicy::LayerPriority(ref priorities) => priorities.get(layer),
+            _ => None,
+        };
+        let rank = |index: usize| {
+            let name = &self.patches[index].0;
+            priorities.and_then(|names| names.iter().position(|n| n == name))
+        };
+        match (rank(challenger), rank(owner)) {
+            (Some(challenger), Some(owner)) => challenger <= owner,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => true,
+        }
+    }
+}
+
+fn attr<'a>(attrs: &'a [OwnedAttribute], name: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .find(|a| a.name.local_name == name)
+        .map(|a| a.value.as_str())
+}
+
+fn required_attr<T: std::str::FromStr>(
+    attrs: &[OwnedAttribute],
+    element: &str,
+    name: &str,
+) -> Result<T, TiledError> {
+    attr(attrs, name)
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| {
+            TiledError::MalformedAttributes(format!("{} must have a valid {}", element, name))
+        })
+}
+
+impl MapPatch {
+    /// Parses a patch written by `MapPatch::write_to`.
+    pub fn parse<R: Read>(reader: R) -> Result<MapPatch, TiledError> {
+        let mut parser = xml_reader(reader);
+        let loader = Loader::new();
+        let mut patch = MapPatch::default();
+        // The elements enclosing the current one, apart from layers,
+        // objects and properties, which are parsed along with their
+        // children.
+        let mut parents: Vec<String> = Vec::new();
+        let mut infinite = false;
+        loop {
+            let (name, attrs) = match parser.next().map_err(TiledError::XmlDecodingError)? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => (name.local_name, attributes),
+                XmlEvent::EndElement { .. } => {
+                    parents.pop();
+                    continue;
+                }
+                XmlEvent::EndDocument => break,
+                _ => continue,
+            };
+            let tag = match name.as_str() {
+                "layer" => Some(LayerTag::Tiles {
+                    width: 0,
+                    height: 0,
+                    infinite,
+                }),
+                "objectgroup" => Some(LayerTag::Objects),
+                "imagelayer" => Some(LayerTag::Image),
+                "group" => Some(LayerTag::Group {
+                    width: 0,
+                    height: 0,
+                    infinite,
+                }),
+                _ => None,
+            };
+            if let Some(tag) = tag {
+                let layer = Layer::new(&mut parser, attrs, tag, &loader)?;
+                patch.changes.push(LayerChange::Set(layer));
+                continue;
+            }
+            match name.as_str() {
+                "object" => {
+                    let object = Object::new(&mut parser, attrs)?;
+                    let edit = match parents.last().map(String::as_str) {
+                        Some("replace") => ObjectEdit::Replace(object),
+                        _ => ObjectEdit::Add(object),
+                    };
+                    if let Some(LayerChange::Objects { edits, .. }) = patch.changes.last_mut() {
+                        edits.push(edit);
+                    }
+                    continue;
+                }
+                "properties" => {
+                    let properties = parse_properties(&mut parser)?;
+                    match patch.changes.last_mut() {
+                        Some(LayerChange::Properties {
+                            properties: ref mut layer_properties,
+                            ..
+                        }) if parents.last().is_some_and(|p| p == "layerproperties") => {
+                            *layer_properties = properties
+                        }
+                        _ => patch.properties = properties,
+                    }
+                    continue;
+                }
+                "setlayer" => infinite = attr(&attrs, "infinite") == Some("1"),
+                "removelayer" => {
+                    let layer = required_attr(&attrs, "removelayer", "name")?;
+                    patch.changes.push(LayerChange::Remove(layer));
+                }
+                "tiles" => patch.changes.push(LayerChange::Tiles {
+                    layer: required_attr(&attrs, "tiles", "layer")?,
+                    tiles: Vec::new(),
+                }),
+                "tile" => {
+                    let change = TileChange {
+                        x: required_attr(&attrs, "tile", "x")?,
+                        y: required_attr(&attrs, "tile", "y")?,
+                        from: LayerTile::from_bits(required_attr(&attrs, "tile", "from")?),
+                        to: LayerTile::from_bits(required_attr(&attrs, "tile", "to")?),
+                    };
+                    if let Some(LayerChange::Tiles { tiles, .. }) = patch.changes.last_mut() {
+                        tiles.push(change);
+                    }
+                }
+                "objects" => patch.changes.push(LayerChange::Objects {
+                    layer: required_attr(&attrs, "objects", "layer")?,
+                    edits: Vec::new(),
+                }),
+                "remove" => {
+                    let id = required_attr(&attrs, "remove", "id")?;
+                    if let Some(LayerChange::Objects { edits, .. }) = patch.changes.last_mut() {
+                        edits.push(ObjectEdit::Remove(id));
+                    }
+                }
+                "layerproperties" => patch.changes.push(LayerChange::Properties {
+                    layer: required_attr(&attrs, "layerproperties", "layer")?,
+                    properties: HashMap::new(),
+                }),
+                _ => {}
+            }
+            parents.push(name);
+        }
+        Ok(patch)
+    }
+}
+
+/// Parses a patch file.
+#[cfg(feature = "fs")]
+pub fn parse_patch_file(path: impl AsRef<Path>) -> Result<MapPatch, TiledError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| TiledError::io(path, e))?;
+    MapPatch::parse(file)
+}
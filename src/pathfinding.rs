@@ -0,0 +1,452 @@
+//! A* pathfinding over grids of movement costs.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use crate::geometry::area_end;
+use crate::{
+    LayerTile, Map, Orientation, StaggerAxis, StaggerIndex, TileGrid, TileLayer, TileRect,
+};
+
+/// The cost of entering each tile within an area, with `None` marking tiles
+/// which can't be entered at all.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CostGrid {
+    pub area: TileRect,
+    /// The costs of the tiles in `area`, row by row.
+    pub costs: Vec<Option<f32>>,
+}
+
+impl CostGrid {
+    /// Creates a grid from its costs, given row by row. Returns `None` if the
+    /// number of costs doesn't match the size of the area.
+    pub fn new(area: TileRect, costs: Vec<Option<f32>>) -> Option<CostGrid> {
+        if Some(costs.len()) != (area.width as usize).checked_mul(area.height as usize) {
+            return None;
+        }
+        Some(CostGrid { area, costs })
+    }
+
+    /// Builds a grid covering the tiles of a layer, with `cost` giving the
+    /// cost of entering each tile, or `None` if the tile blocks movement.
+    pub fn from_layer(
+        layer: &TileLayer,
+        mut cost: impl FnMut(Option<LayerTile>) -> Option<f32>,
+    ) -> CostGrid {
+        let area = layer.bounds().unwrap_or_else(|| TileRect::new(0, 0, 0, 0));
+        let capacity = (area.width as usize).checked_mul(area.height as usize);
+        let mut costs = Vec::with_capacity(capacity.unwrap_or(0));
+        for y in area.y..area.bottom() {
+            for x in area.x..area.right() {
+                costs.push(cost(layer.get_tile(x, y)));
+            }
+        }
+        CostGrid { area, costs }
+    }
+
+    /// Builds a grid from one marking the tiles which block movement, like
+    /// those from `Map::solid_grid`. The other tiles cost 1 to enter.
+    pub fn from_solid(solid: &TileGrid<bool>) -> CostGrid {
+        CostGrid {
+            area: solid.area,
+            costs: solid
+                .cells
+                .iter()
+                .map(|&solid| if solid { None } else { Some(1.0) })
+                .collect(),
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        self.area.index(x, y)
+    }
+
+    /// Returns the cost of entering the given tile, or `None` if it is
+    /// blocked or outside of the grid.
+    pub fn cost(&self, x: i32, y: i32) -> Option<f32> {
+        self.costs[self.index(x, y)?]
+    }
+
+    pub fn is_walkable(&self, x: i32, y: i32) -> bool {
+        self.cost(x, y).is_some()
+    }
+}
+
+/// Which tiles count as neighbours when moving across a grid.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Neighborhood {
+    /// Moves along rows and columns only.
+    Four,
+    /// Diagonal moves are allowed too, as long as they don't cut the corner
+    /// of a blocked tile.
+    Eight,
+    /// The six neighbours of a tile on a hexagonal map.
+    Hexagonal {
+        stagger_axis: StaggerAxis,
+        stagger_index: StaggerIndex,
+        hex_side_length: u32,
+    },
+}
+
+impl Neighborhood {
+    /// Returns the neighbourhood of the tiles of a hexagonal map, or `None`
+    /// if the map isn't hexagonal or lacks its stagger attributes.
+    pub fn from_map(map: &Map) -> Option<Neighborhood> {
+        if map.orientation != Orientation::Hexagonal {
+            return None;
+        }
+        Some(Neighborhood::Hexagonal {
+            stagger_axis: map.stagger_axis?,
+            stagger_index: map.stagger_index?,
+            hex_side_length: map.hex_side_length.unwrap_or(0),
+        })
+    }
+
+    /// Returns the offsets of the neighbours of a tile, along with the
+    /// length of the step to each of them.
+    fn neighbours(&self, x: i32, y: i32) -> Vec<(i32, i32, f32)> {
+        const ORTHOGONAL: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+        const DIAGONAL: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+        match *self {
+            Neighborhood::Four => ORTHOGONAL.iter().map(|&(dx, dy)| (dx, dy, 1.0)).collect(),
+            Neighborhood::Eight => ORTHOGONAL
+                .iter()
+                .map(|&(dx, dy)| (dx, dy, 1.0))
+                .chain(
+                    DIAGONAL
+                        .iter()
+                        .map(|&(dx, dy)| (dx, dy, std::f32::consts::SQRT_2)),
+                )
+                .collect(),
+            Neighborhood::Hexagonal {
+                stagger_axis,
+                stagger_index,
+                ..
+            } => {
+                let along = match stagger_axis {
+                    StaggerAxis::X => x,
+                    StaggerAxis::Y => y,
+                };
+                // The shifted rows (or columns) connect to the next tile
+                // over, the others to the previous one.
+                let shift = if is_staggered(along, stagger_index) {
+                    1
+                } else {
+                    -1
+                };
+                let offsets = [(1, 0), (-1, 0), (0, 1), (shift, 1), (0, -1), (shift, -1)];
+                offsets
+                    .iter()
+                    .map(|&(a, b)| match stagger_axis {
+                        StaggerAxis::Y => (a, b, 1.0),
+                        StaggerAxis::X => (b, a, 1.0),
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// A lower bound on the number of steps between two tiles.
+    fn distance(&self, from: (i32, i32), to: (i32, i32)) -> f32 {
+        let (dx, dy) = ((to.0 - from.0).abs(), (to.1 - from.1).abs());
+        match *self {
+            Neighborhood::Four => (dx + dy) as f32,
+            Neighborhood::Eight => {
+                let (long, short) = (dx.max(dy) as f32, dx.min(dy) as f32);
+                long - short + short * std::f32::consts::SQRT_2
+            }
+            Neighborhood::Hexagonal {
+                stagger_axis,
+                stagger_index,
+                ..
+            } => {
+                let a = to_axial(from, stagger_axis, stagger_index);
+                let b = to_axial(to, stagger_axis, stagger_index);
+                let (dq, dr) = (b.0 - a.0, b.1 - a.1);
+                ((dq.abs() + dr.abs() + (dq + dr).abs()) / 2) as f32
+            }
+        }
+    }
+}
+
+fn is_staggered(index: i32, stagger_index: StaggerIndex) -> bool {
+    let odd = index & 1 == 1;
+    match stagger_index {
+        StaggerIndex::Odd => odd,
+        StaggerIndex::Even => !odd,
+    }
+}
+
+/// Converts staggered tile coordinates to axial hex coordinates.
+fn to_axial(
+    (x, y): (i32, i32),
+    stagger_axis: StaggerAxis,
+    stagger_index: StaggerIndex,
+) -> (i32, i32) {
+    let (along, across) = match stagger_axis {
+        StaggerAxis::X => (x, y),
+        StaggerAxis::Y => (y, x),
+    };
+    let shifted = match stagger_index {
+        StaggerIndex::Odd => (along - (along & 1)) / 2,
+        StaggerIndex::Even => (along + (along & 1)) / 2,
+    };
+    (across - shifted, along)
+}
+
+/// A path found by `find_path`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TilePath {
+    /// The tiles along the path, including the start and the goal.
+    pub tiles: Vec<(i32, i32)>,
+    /// The total cost of the path.
+    pub cost: f32,
+}
+
+#[derive(PartialEq)]
+struct Node {
+    estimate: f32,
+    cost: f32,
+    index: usize,
+}
+
+impl Eq for Node {}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Node) -> Ordering {
+        // Reversed, so the heap pops the cheapest estimate first.
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Node) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Finds the cheapest path between two tiles using A*. Entering a tile costs
+/// its cost in the grid, multiplied by the length of the step for diagonal
+/// moves. Returns `None` if the goal can't be reached.
+pub fn find_path(
+    grid: &CostGrid,
+    start: (i32, i32),
+    goal: (i32, i32),
+    neighborhood: Neighborhood,
+) -> Option<TilePath> {
+    // Positions in areas reaching past the largest coordinates don't fit.
+    area_end(&grid.area)?;
+    let start_index = grid.index(start.0, start.1)?;
+    let goal_index = grid.index(goal.0, goal.1)?;
+    if !grid.is_walkable(goal.0, goal.1) {
+        return None;
+    }
+    // The cheapest tile cost keeps the heuristic from overestimating.
+    let min_cost = grid
+        .costs
+        .iter()
+        .flatten()
+        .fold(f32::INFINITY, |a, &b| a.min(b.max(0.0)));
+    let min_cost = if min_cost.is_finite() { min_cost } else { 0.0 };
+    let position = |index: usize| {
+        let width = grid.area.width as usize;
+        (
+            grid.area.x + (index % width) as i32,
+            grid.area.y + (index / width) as i32,
+        )
+    };
+
+    let mut best = vec![f32::INFINITY; grid.costs.len()];
+    let mut came_from = vec![usize::MAX; grid.costs.len()];
+    let mut open = BinaryHeap::new();
+    best[start_index] = 0.0;
+    open.push(Node {
+        estimate: neighborhood.distance(start, goal) * min_cost,
+        cost: 0.0,
+        index: start_index,
+    });
+    while let Some(Node { cost, index, .. }) = open.pop() {
+        let (x, y) = position(index);
+        if index == goal_index {
+            let mut tiles = vec![goal];
+            let mut current = index;
+            while current != start_index {
+                current = came_from[current];
+                tiles.push(position(current));
+            }
+            tiles.reverse();
+            return Some(TilePath {
+                tiles,
+                cost: best[goal_index],
+            });
+        }
+        if cost > best[index] {
+            // A cheaper route to this tile was already expanded.
+            continue;
+        }
+        for (dx, dy, step) in neighborhood.neighbours(x, y) {
+            let (nx, ny) = (x + dx, y + dy);
+            let (next, step_cost) = match (grid.index(nx, ny), grid.cost(nx, ny)) {
+                (Some(next), Some(cost)) => (next, cost.max(0.0)),
+                _ => continue,
+            };
+            if dx != 0
+                && dy != 0
+                && neighborhood == Neighborhood::Eight
+                && !(grid.is_walkable(nx, y) && grid.is_walkable(x, ny))
+            {
+                continue;
+            }
+            let total = best[index] + step_cost * step;
+            if total < best[next] {
+                best[next] = total;
+                came_from[next] = index;
+                open.push(Node {
+                    estimate: total + neighborhood.distance((nx, ny), goal) * min_cost,
+                    cost: total,
+                    index: next,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Checks whether the straight line between the centers of two tiles only
+/// crosses walkable tiles. Lines passing exactly through a corner need both
+/// tiles next to it to be walkable.
+fn line_clear(grid: &CostGrid, from: (i32, i32), to: (i32, i32)) -> bool {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let (nx, ny) = (dx.abs(), dy.abs());
+    let (sx, sy) = (dx.signum(), dy.signum());
+    let (mut x, mut y) = from;
+    let (mut ix, mut iy) = (0, 0);
+    while ix < nx || iy < ny {
+        let decision = (1 + 2 * ix) * ny - (1 + 2 * iy) * nx;
+        if decision == 0 {
+            if !grid.is_walkable(x + sx, y) || !grid.is_walkable(x, y + sy) {
+                return false;
+            }
+            x += sx;
+            y += sy;
+            ix += 1;
+            iy += 1;
+        } else if decision < 0 {
+            x += sx;
+            ix += 1;
+        } else {
+            y += sy;
+            iy += 1;
+        }
+        if !grid.is_walkable(x, y) {
+            return false;
+        }
+    }
+    true
+}
+
+impl TilePath {
+    /// Returns the waypoints an agent can follow in pixels, at the centers
+    /// of the tiles of the path. On grids with four or eight neighbours,
+    /// tiles which can be skipped by walking in a straight line are left
+    /// out; on hexagonal grids, only tiles on a straight line between their
+    /// neighbours are.
+    pub fn waypoints(
+        &self,
+        map: &Map,
+        grid: &CostGrid,
+        neighborhood: Neighborhood,
+    ) -> Vec<(f32, f32)> {
+        let mut tiles: Vec<(i32, i32)> = Vec::new();
+        match neighborhood {
+            Neighborhood::Four | Neighborhood::Eight => {
+                let mut anchor = match self.tiles.first() {
+                    Some(&first) => first,
+                    None => return Vec::new(),
+                };
+                tiles.push(anchor);
+                for window in self.tiles.windows(2).skip(1) {
+                    let (previous, next) = (window[0], window[1]);
+                    if !line_clear(grid, anchor, next) {
+                        tiles.push(previous);
+                        anchor = previous;
+                    }
+                }
+                if let Some(&last) = self.tiles.last() {
+                    if self.tiles.len() > 1 {
+                        tiles.push(last);
+                    }
+                }
+            }
+            Neighborhood::Hexagonal { .. } => tiles = self.tiles.clone(),
+        }
+
+        let mut waypoints: Vec<(f32, f32)> = Vec::with_capacity(tiles.len());
+        for (x, y) in tiles {
+            let point = tile_center(map, neighborhood, x, y);
+            let len = waypoints.len();
+            if len >= 2 && collinear(waypoints[len - 2], waypoints[len - 1], point) {
+                waypoints[len - 1] = point;
+            } else {
+                waypoints.push(point);
+            }
+        }
+        waypoints
+    }
+}
+
+fn collinear(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> bool {
+    let cross = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+    cross.abs() < 1e-3
+}
+
+/// Returns the center of a tile in pixels.
+fn tile_center(map: &Map, neighborhood: Neighborhood, x: i32, y: i32) -> (f32, f32) {
+    let (tw, th) = (map.tile_width as f32, map.tile_height as f32);
+    let (x, y) = (x as f32, y as f32);
+    match map.orientation {
+        Orientation::Orthogonal => ((x + 0.5) * tw, (y + 0.5) * th),
+        Orientation::Isometric => {
+            let origin_x = map.height as f32 * tw / 2.0;
+            ((x - y) * tw / 2.0 + origin_x, (x + y + 1.0) * th / 2.0)
+        }
+        Orientation::Staggered | Orientation::Hexagonal => {
+            let (stagger_axis, stagger_index, side) = match neighborhood {
+                Neighborhood::Hexagonal {
+                    stagger_axis,
+                    stagger_index,
+                    hex_side_length,
+                } => (stagger_axis, stagger_index, hex_side_length as f32),
+                // Maps without stagger attributes take Tiled's defaults.
+                _ => (
+                    map.stagger_axis.unwrap_or(StaggerAxis::Y),
+                    map.stagger_index.unwrap_or(StaggerIndex::Odd),
+                    match map.orientation {
+                        Orientation::Hexagonal => map.hex_side_length.unwrap_or(0) as f32,
+                        _ => 0.0,
+                    },
+                ),
+            };
+            match stagger_axis {
+                StaggerAxis::Y => {
+                    let shift = if is_staggered(y as i32, stagger_index) {
+                        tw / 2.0
+                    } else {
+                        0.0
+                    };
+                    let row_height = (th + side) / 2.0;
+                    ((x + 0.5) * tw + shift, y * row_height + th / 2.0)
+                }
+                StaggerAxis::X => {
+                    let shift = if is_staggered(x as i32, stagger_index) {
+                        th / 2.0
+                    } else {
+                        0.0
+                    };
+                    let column_width = (tw + side) / 2.0;
+                    (x * column_width + tw / 2.0, (y + 0.5) * th + shift)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! Resolving the relative paths found in maps and tilesets.
+
+use std::path::{Path, PathBuf};
+
+use crate::{Map, Tileset};
+
+/// Joins `relative` to the directory of the file at `base`.
+fn resolve(base: &Path, relative: &Path) -> PathBuf {
+    match base.parent() {
+        Some(dir) => dir.join(relative),
+        None => relative.to_path_buf(),
+    }
+}
+
+impl Map {
+    /// Resolves a path relative to the map file, like the source of an
+    /// image layer or a file property of the map, its layers or objects.
+    /// Maps parsed without their location leave the path as it is.
+    pub fn resolve_path(&self, relative: impl AsRef<Path>) -> PathBuf {
+        match self.source {
+            Some(ref source) => resolve(source, relative.as_ref()),
+            None => relative.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Resolves a path relative to one of the map's tilesets, like the
+    /// source of its image or a file property of its tiles. Paths in
+    /// external tilesets are relative to the tileset file, and in embedded
+    /// ones to the map file.
+    pub fn resolve_tileset_path(&self, tileset: &Tileset, relative: impl AsRef<Path>) -> PathBuf {
+        match tileset.source {
+            Some(ref source) => resolve(source, relative.as_ref()),
+            None => self.resolve_path(relative),
+        }
+    }
+}
@@ -0,0 +1,287 @@
+//! Loading of the custom property types of Tiled project files
+//! (`.tiled-project`).
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+#[cfg(feature = "fs")]
+use std::fs::File;
+use std::io::Read;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::localization::for_each_properties_mut;
+use crate::{Colour, Map, Properties, PropertyValue, TiledError};
+
+/// How the values of a custom enum are saved.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum EnumStorage {
+    /// By the names of the values.
+    String,
+    /// By the indices of the values, or their bits for flags.
+    Int,
+}
+
+/// A custom property type defined in a project.
+#[derive(Debug, PartialEq, Clone)]
+pub enum PropertyType {
+    Enum {
+        id: u32,
+        name: String,
+        storage: EnumStorage,
+        /// The names of the values, in order.
+        values: Vec<String>,
+        /// Whether several values can be set at once, each taking a bit
+        /// when saved as an int.
+        values_as_flags: bool,
+    },
+    Class {
+        id: u32,
+        name: String,
+        /// The members of the class with their default values.
+        members: Properties,
+    },
+}
+
+impl PropertyType {
+    pub fn name(&self) -> &str {
+        match self {
+            PropertyType::Enum { name, .. } | PropertyType::Class { name, .. } => name,
+        }
+    }
+}
+
+/// The parts of a Tiled project relevant to reading maps.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Project {
+    pub property_types: Vec<PropertyType>,
+}
+
+impl Project {
+    /// Parses a project from JSON.
+    pub fn parse<R: Read>(reader: R) -> Result<Project, TiledError> {
+        let value: Value =
+            serde_json::from_reader(reader).map_err(TiledError::JsonDecodingError)?;
+        let property_types = match value.get("propertyTypes") {
+            Some(Value::Array(types)) => types
+                .iter()
+                .filter_map(|t| parse_property_type(t).transpose())
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => Vec::new(),
+        };
+        Ok(Project { property_types })
+    }
+
+    /// Returns the custom property type with the given name.
+    pub fn property_type(&self, name: &str) -> Option<&PropertyType> {
+        self.property_types.iter().find(|t| t.name() == name)
+    }
+
+    /// Gives properties of custom types their full representation: enum
+    /// values saved as ints are turned into the names of their values,
+    /// joined by commas for flags like Tiled does when saving them as
+    /// strings, and the members missing from class values are filled in
+    /// with their defaults. Properties of unknown types are left as they
+    /// are.
+    pub fn apply(&self, properties: &mut Properties) {
+        for value in properties.values_mut() {
+            self.apply_value(value);
+        }
+    }
+
+    fn apply_value(&self, value: &mut PropertyValue) {
+        match value {
+            PropertyValue::EnumValue { type_name, value } => {
+                let names = match (self.property_type(type_name), &**value) {
+                    (
+                        Some(PropertyType::Enum {
+                            values,
+                            values_as_flags,
+                            ..
+                        }),
+                        PropertyValue::IntValue(v),
+                    ) => enum_names(values, *values_as_flags, *v),
+                    _ => None,
+                };
+                if let Some(names) = names {
+                    **value = PropertyValue::StringValue(names);
+                }
+            }
+            PropertyValue::ClassValue {
+                type_name,
+                properties,
+            } => {
+                if let Some(PropertyType::Class { members, .. }) = self.property_type(type_name) {
+                    for (name, default) in members.iter() {
+                        properties
+                            .entry(name.clone())
+                            .or_insert_with(|| default.clone());
+                    }
+                }
+                self.apply(properties);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns the names of the values of an enum saved as an int, or `None` if
+/// the int doesn't match the values.
+fn enum_names(values: &[String], values_as_flags: bool, value: i32) -> Option<String> {
+    if !values_as_flags {
+        return values.get(usize::try_from(value).ok()?).cloned();
+    }
+    if value < 0 || (values.len() < 31 && value >> values.len() != 0) {
+        return None;
+    }
+    let names: Vec<&str> = values
+        .iter()
+        .enumerate()
+        .filter(|&(bit, _)| bit < 31 && value & (1 << bit) != 0)
+        .map(|(_, name)| name.as_str())
+        .collect();
+    Some(names.join(","))
+}
+
+fn parse_property_type(value: &Value) -> Result<Option<PropertyType>, TiledError> {
+    let id = value.get("id").and_then(Value::as_u64).unwrap_or(0) as u32;
+    let name = value
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| TiledError::MissingAttribute {
+            element: "property type".to_string(),
+            attribute: "name".to_string(),
+        })?
+        .to_string();
+    let property_type = match value.get("type").and_then(Value::as_str) {
+        Some("enum") => PropertyType::Enum {
+            id,
+            name,
+            storage: match value.get("storageType").and_then(Value::as_str) {
+                Some("int") => EnumStorage::Int,
+                _ => EnumStorage::String,
+            },
+            values: value
+                .get("values")
+                .and_then(Value::as_array)
+                .map(|values| {
+                    values
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            values_as_flags: value
+                .get("valuesAsFlags")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+        },
+        Some("class") => PropertyType::Class {
+            id,
+            name,
+            members: match value.get("members") {
+                Some(Value::Array(members)) => members.iter().map(parse_member).collect::<Result<
+                    HashMap<_, _>,
+                    _,
+                >>(
+                )?,
+                _ => HashMap::new(),
+            },
+        },
+        // Types this version of the crate doesn't know are skipped.
+        _ => return Ok(None),
+    };
+    Ok(Some(property_type))
+}
+
+/// Parses a class member along with its default value.
+fn parse_member(member: &Value) -> Result<(String, PropertyValue), TiledError> {
+    let malformed = || TiledError::MalformedAttributes("malformed class member".to_string());
+    let name = member
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or_else(malformed)?;
+    let property_type = member
+        .get("type")
+        .and_then(Value::as_str)
+        .unwrap_or("string");
+    let default = member.get("value").unwrap_or(&Value::Null);
+    let value = match property_type {
+        "bool" => PropertyValue::BoolValue(default.as_bool().unwrap_or(false)),
+        "float" => PropertyValue::FloatValue(default.as_f64().unwrap_or(0.0) as f32),
+        "int" => PropertyValue::IntValue(default.as_i64().unwrap_or(0) as i32),
+        "color" => PropertyValue::ColorValue(
+            default
+                .as_str()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(Colour::new(0, 0, 0).with_alpha(0)),
+        ),
+        "object" => PropertyValue::ObjectValue(default.as_u64().unwrap_or(0) as u32),
+        "file" => PropertyValue::FileValue(default.as_str().unwrap_or_default().into()),
+        "class" => PropertyValue::ClassValue {
+            type_name: property_type_name(member),
+            properties: match default {
+                Value::Object(members) => members
+                    .iter()
+                    .map(|(name, value)| (name.clone(), plain_value(value)))
+                    .collect(),
+                _ => HashMap::new(),
+            },
+        },
+        _ => PropertyValue::StringValue(default.as_str().unwrap_or_default().to_string()),
+    };
+    let value = match member.get("propertyType") {
+        Some(_) if property_type != "class" => PropertyValue::EnumValue {
+            type_name: property_type_name(member),
+            value: Box::new(value),
+        },
+        _ => value,
+    };
+    Ok((name.to_string(), value))
+}
+
+fn property_type_name(member: &Value) -> String {
+    member
+        .get("propertyType")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Converts the value of a nested class member, which is saved without its
+/// type.
+fn plain_value(value: &Value) -> PropertyValue {
+    match value {
+        Value::Bool(v) => PropertyValue::BoolValue(*v),
+        Value::Number(v) if v.is_i64() => PropertyValue::IntValue(v.as_i64().unwrap_or(0) as i32),
+        Value::Number(v) => PropertyValue::FloatValue(v.as_f64().unwrap_or(0.0) as f32),
+        Value::String(v) => PropertyValue::StringValue(v.clone()),
+        Value::Object(members) => PropertyValue::ClassValue {
+            type_name: String::new(),
+            properties: members
+                .iter()
+                .map(|(name, value)| (name.clone(), plain_value(value)))
+                .collect(),
+        },
+        _ => PropertyValue::StringValue(String::new()),
+    }
+}
+
+/// Parses a Tiled project file.
+#[cfg(feature = "fs")]
+pub fn parse_project_file(path: impl AsRef<Path>) -> Result<Project, TiledError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| TiledError::io(path, e))?;
+    Project::parse(file)
+}
+
+impl Map {
+    /// Gives the properties of the map, its layers, objects and tiles their
+    /// full representation with the custom types of a project, see
+    /// `Project::apply`.
+    pub fn apply_project(&mut self, project: &Project) {
+        for_each_properties_mut(self, |_, properties| project.apply(properties));
+    }
+}
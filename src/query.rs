@@ -0,0 +1,66 @@
+//! Queries across the layers of a map.
+
+use crate::group::{find_layer, find_layer_mut};
+use crate::{Layer, LayerTile, Map, Object, PropertyValue};
+
+impl Map {
+    /// Returns the top-most non-empty tile at the given tile coordinates,
+    /// along with its layer, such as the ground a character stands on.
+    /// Layers are scanned from the top down, through the layers within
+    /// groups, skipping hidden layers, including those in hidden groups,
+    /// layers made fully transparent by their own or their groups' opacity,
+    /// and layers for which `layer_filter` returns false.
+    pub fn topmost_tile_at(
+        &self,
+        x: i32,
+        y: i32,
+        mut layer_filter: impl FnMut(&Layer) -> bool,
+    ) -> Option<(&Layer, LayerTile)> {
+        self.flat_layers()
+            .into_iter()
+            .rev()
+            .filter(|flat| flat.visible && flat.opacity > 0.0 && layer_filter(flat.layer))
+            .find_map(|flat| Some((flat.layer, flat.layer.as_tile_layer()?.get_tile(x, y)?)))
+    }
+
+    /// Returns the layer with the given id, which may be within a group.
+    pub fn get_layer_by_id(&self, id: u32) -> Option<&Layer> {
+        find_layer(&self.layers, id)
+    }
+
+    /// Returns the layer with the given id, for editing.
+    pub fn get_layer_by_id_mut(&mut self, id: u32) -> Option<&mut Layer> {
+        find_layer_mut(&mut self.layers, id)
+    }
+
+    /// Returns the objects of all of the map's object layers, including
+    /// those within groups, layer by layer.
+    pub fn objects(&self) -> impl Iterator<Item = &Object> {
+        self.flat_layers()
+            .into_iter()
+            .filter_map(|flat| flat.layer.as_object_layer())
+            .flat_map(|group| group.objects.iter())
+    }
+
+    /// Returns the objects of the given type, like all of the spawn points.
+    pub fn objects_by_type<'a>(&'a self, obj_type: &'a str) -> impl Iterator<Item = &'a Object> {
+        self.objects()
+            .filter(move |object| object.obj_type == obj_type)
+    }
+
+    /// Returns the objects with the given name.
+    pub fn objects_by_name<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Object> {
+        self.objects().filter(move |object| object.name == name)
+    }
+
+    /// Returns the objects whose property with the given name has the given
+    /// value.
+    pub fn objects_with_property<'a>(
+        &'a self,
+        name: &'a str,
+        value: &'a PropertyValue,
+    ) -> impl Iterator<Item = &'a Object> {
+        self.objects()
+            .filter(move |object| object.properties.get(name) == Some(value))
+    }
+}
@@ -0,0 +1,52 @@
+//! A small, seedable random number generator for procedural operations.
+
+/// A deterministic SplitMix64 generator. The same seed always produces the
+/// same sequence on every platform, which keeps procedural passes
+/// reproducible.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in the range `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        // The top 24 bits fit exactly into the mantissa of an `f32`.
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Picks an index into `weights`, with each index being chosen in
+    /// proportion to its weight. Returns `None` if no weight is positive.
+    pub fn choose_weighted(&mut self, weights: &[f32]) -> Option<usize> {
+        let total: f32 = weights.iter().filter(|w| **w > 0.0).sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut roll = self.next_f32() * total;
+        let mut last = None;
+        for (i, &weight) in weights.iter().enumerate() {
+            if weight <= 0.0 {
+                continue;
+            }
+            if roll < weight {
+                return Some(i);
+            }
+            roll -= weight;
+            last = Some(i);
+        }
+        // Rounding can leave a tiny remainder past the last weight.
+        last
+    }
+}
@@ -0,0 +1,147 @@
+//! Conversion of object groups into tile layers.
+
+use crate::{LayerData, LayerTile, Object, ObjectGroup, ObjectShape, TileLayer};
+
+/// The number of points used to approximate the outline of an ellipse.
+const ELLIPSE_SEGMENTS: usize = 32;
+
+/// Returns the points of an object's shape relative to the layer, along with
+/// whether they enclose an area.
+fn outline(object: &Object) -> (Vec<(f32, f32)>, bool) {
+    let rect = |width: f32, height: f32| {
+        // Tile objects are positioned by their bottom left corner.
+        let top = if object.gid.is_empty() { 0.0 } else { -height };
+        vec![
+            (0.0, top),
+            (width, top),
+            (width, top + height),
+            (0.0, top + height),
+        ]
+    };
+    let (points, closed) = match object.shape {
+        ObjectShape::Rect { width, height } => (rect(width, height), true),
+        ObjectShape::Text(_) => (rect(object.width, object.height), true),
+        ObjectShape::Ellipse { width, height } => (
+            (0..ELLIPSE_SEGMENTS)
+                .map(|i| {
+                    let angle = i as f32 / ELLIPSE_SEGMENTS as f32 * std::f32::consts::TAU;
+                    (
+                        width / 2.0 * (1.0 + angle.cos()),
+                        height / 2.0 * (1.0 + angle.sin()),
+                    )
+                })
+                .collect(),
+            true,
+        ),
+        ObjectShape::Polygon { ref points } => (points.clone(), true),
+        ObjectShape::Polyline { ref points } => (points.clone(), false),
+        ObjectShape::Point(_, _) => (vec![(0.0, 0.0)], false),
+    };
+    // Objects rotate clockwise around their position.
+    let (sin, cos) = object.rotation.to_radians().sin_cos();
+    let points = points
+        .into_iter()
+        .map(|(x, y)| (object.x + x * cos - y * sin, object.y + x * sin + y * cos))
+        .collect();
+    (points, closed)
+}
+
+fn contains(polygon: &[(f32, f32)], (x, y): (f32, f32)) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+    for (i, &(xi, yi)) in polygon.iter().enumerate() {
+        let (xj, yj) = polygon[j];
+        if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+impl ObjectGroup {
+    /// Builds a finite tile layer of `width` by `height` tiles from the
+    /// group's objects, for turning drawn shapes into collision or terrain
+    /// layers. `gid_for_object` gives the global tile id stamped for each
+    /// object, or `None` to skip it.
+    ///
+    /// Rectangles, ellipses, polygons, texts and tile objects fill the tiles
+    /// whose centre they contain, polylines the tiles they pass through and
+    /// points the tile they lie in. Later objects are stamped over earlier
+    /// ones. The layer's offset isn't taken into account.
+    pub fn rasterize<F>(
+        &self,
+        width: u32,
+        height: u32,
+        tile_size: (u32, u32),
+        mut gid_for_object: F,
+    ) -> TileLayer
+    where
+        F: FnMut(&Object) -> Option<u32>,
+    {
+        let mut rows = vec![vec![None; width as usize]; height as usize];
+        let (tile_width, tile_height) = (tile_size.0.max(1) as f32, tile_size.1.max(1) as f32);
+        let mut stamp = |x: f32, y: f32, tile: Option<LayerTile>| {
+            let (column, row) = ((x / tile_width).floor(), (y / tile_height).floor());
+            if column >= 0.0 && row >= 0.0 && column < width as f32 && row < height as f32 {
+                rows[row as usize][column as usize] = tile;
+            }
+        };
+        for object in self.objects.iter() {
+            let tile = match gid_for_object(object) {
+                Some(gid) => LayerTile::from_bits(gid),
+                None => continue,
+            };
+            let (points, closed) = outline(object);
+            if closed {
+                let (mut min_x, mut min_y) = (f32::MAX, f32::MAX);
+                let (mut max_x, mut max_y) = (f32::MIN, f32::MIN);
+                for &(x, y) in points.iter() {
+                    min_x = min_x.min(x);
+                    min_y = min_y.min(y);
+                    max_x = max_x.max(x);
+                    max_y = max_y.max(y);
+                }
+                let first_column = (min_x / tile_width).floor().max(0.0) as u32;
+                let first_row = (min_y / tile_height).floor().max(0.0) as u32;
+                let last_column = ((max_x / tile_width).ceil().max(0.0) as u32).min(width);
+                let last_row = ((max_y / tile_height).ceil().max(0.0) as u32).min(height);
+                for row in first_row..last_row {
+                    for column in first_column..last_column {
+                        let centre = (
+                            (column as f32 + 0.5) * tile_width,
+                            (row as f32 + 0.5) * tile_height,
+                        );
+                        if contains(&points, centre) {
+                            stamp(centre.0, centre.1, tile);
+                        }
+                    }
+                }
+            } else if let [(x, y)] = points[..] {
+                stamp(x, y, tile);
+            } else {
+                // Walk along each segment in steps small enough not to skip
+                // over any tile.
+                let step = tile_width.min(tile_height) / 4.0;
+                for segment in points.windows(2) {
+                    let ((x1, y1), (x2, y2)) = (segment[0], segment[1]);
+                    let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt();
+                    let steps = (length / step).ceil() as usize;
+                    for i in 0..=steps {
+                        let t = if steps == 0 {
+                            0.0
+                        } else {
+                            i as f32 / steps as f32
+                        };
+                        stamp(x1 + (x2 - x1) * t, y1 + (y2 - y1) * t, tile);
+                    }
+                }
+            }
+        }
+        TileLayer {
+            width,
+            height,
+            tiles: LayerData::Finite(rows),
+        }
+    }
+}
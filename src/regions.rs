@@ -0,0 +1,77 @@
+//! Detection of connected areas of tiles.
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::{LayerTile, TileLayer, TileRect};
+
+/// An area of 4-connected tiles found by `TileLayer::connected_components`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Region {
+    /// The index of the region, in the order the regions were found.
+    pub label: usize,
+    /// The smallest rectangle containing every cell of the region.
+    pub bounds: TileRect,
+    /// The tile coordinates of the cells in the region.
+    pub cells: Vec<(i32, i32)>,
+}
+
+impl TileLayer {
+    /// Groups the tiles for which `predicate` returns true into areas of
+    /// 4-connected tiles, such as the rooms or lakes of a map. Regions are
+    /// labelled in the order their first cell is found, scanning row by row.
+    /// Empty tiles, and on infinite layers positions not covered by a chunk,
+    /// are passed to the predicate as `None`.
+    pub fn connected_components(
+        &self,
+        mut predicate: impl FnMut(Option<LayerTile>) -> bool,
+    ) -> Vec<Region> {
+        let bounds = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return Vec::new(),
+        };
+        let mut matches = |x: i32, y: i32| predicate(self.get_tile(x, y));
+
+        let mut regions = Vec::new();
+        let mut visited = HashSet::new();
+        for y in bounds.y..bounds.bottom() {
+            for x in bounds.x..bounds.right() {
+                if visited.contains(&(x, y)) || !matches(x, y) {
+                    continue;
+                }
+                let mut cells = Vec::new();
+                let mut queue = VecDeque::new();
+                let (mut left, mut top, mut right, mut bottom) = (x, y, x, y);
+                visited.insert((x, y));
+                queue.push_back((x, y));
+                while let Some((x, y)) = queue.pop_front() {
+                    cells.push((x, y));
+                    left = left.min(x);
+                    top = top.min(y);
+                    right = right.max(x);
+                    bottom = bottom.max(y);
+                    for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                        if !bounds.contains(nx, ny) || visited.contains(&(nx, ny)) {
+                            continue;
+                        }
+                        if matches(nx, ny) {
+                            visited.insert((nx, ny));
+                            queue.push_back((nx, ny));
+                        }
+                    }
+                }
+                cells.sort_by_key(|&(x, y)| (y, x));
+                regions.push(Region {
+                    label: regions.len(),
+                    bounds: TileRect::new(
+                        left,
+                        top,
+                        (right - left + 1) as u32,
+                        (bottom - top + 1) as u32,
+                    ),
+                    cells,
+                });
+            }
+        }
+        regions
+    }
+}
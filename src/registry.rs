@@ -0,0 +1,82 @@
+//! Sharing identical tilesets between maps.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+
+use crate::{Map, TiledError, Tileset};
+
+/// Deduplicates identical tilesets by their content, so maps sharing the same
+/// tilesets keep a single copy of them in memory. Tilesets are only held
+/// weakly: once every `Arc` handed out for one is dropped, it is freed.
+///
+/// A registry can be shared between threads. `TilesetRegistry::global`
+/// returns one shared by the whole process.
+#[derive(Debug, Default)]
+pub struct TilesetRegistry {
+    entries: Mutex<HashMap<u64, Vec<Weak<Tileset>>>>,
+}
+
+impl TilesetRegistry {
+    pub fn new() -> TilesetRegistry {
+        TilesetRegistry::default()
+    }
+
+    /// Returns the registry shared by the whole process.
+    pub fn global() -> &'static TilesetRegistry {
+        static GLOBAL: OnceLock<TilesetRegistry> = OnceLock::new();
+        GLOBAL.get_or_init(TilesetRegistry::new)
+    }
+
+    /// Returns the registered tileset equal to `tileset`, or registers it if
+    /// there isn't one. Tilesets are only equal if they have the same
+    /// `first_gid`.
+    pub fn intern(&self, tileset: Tileset) -> Result<Arc<Tileset>, TiledError> {
+        let hash = tileset.content_hash()?;
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let bucket = entries.entry(hash).or_default();
+        bucket.retain(|entry| entry.strong_count() > 0);
+        // Compare the contents as well in case of a hash collision.
+        if let Some(existing) = bucket
+            .iter()
+            .filter_map(Weak::upgrade)
+            .find(|existing| **existing == tileset)
+        {
+            return Ok(existing);
+        }
+        let tileset = Arc::new(tileset);
+        bucket.push(Arc::downgrade(&tileset));
+        Ok(tileset)
+    }
+
+    /// The number of distinct tilesets currently alive in the registry.
+    pub fn len(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, bucket| {
+            bucket.retain(|entry| entry.strong_count() > 0);
+            !bucket.is_empty()
+        });
+        entries.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Map {
+    /// Moves the map's tilesets into the registry, returning shared handles to
+    /// them in the same order and leaving `tilesets` empty. Tilesets equal to
+    /// ones already registered by other maps are replaced by the registered
+    /// copy.
+    pub fn intern_tilesets(
+        &mut self,
+        registry: &TilesetRegistry,
+    ) -> Result<Vec<Arc<Tileset>>, TiledError> {
+        self.tilesets
+            .drain(..)
+            .map(|tileset| {
+                registry.intern(Arc::try_unwrap(tileset).unwrap_or_else(|t| (*t).clone()))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,77 @@
+//! Translating the global tile ids used by a map, for merging or compacting
+//! tilesets.
+
+use std::collections::HashMap;
+
+use crate::{cell_bits, Gid, LayerData, LayerTile, LayerType, Map, TiledError};
+
+impl Map {
+    /// Returns whether a tileset of the map holds the tile with the given
+    /// global tile id. Tilesets which don't know their tile count are taken
+    /// to hold every gid from their first one on.
+    fn has_gid(&self, gid: Gid) -> bool {
+        self.tileset_for_gid(gid)
+            .is_some_and(|(tileset, id)| tileset.tilecount.map_or(true, |count| id < count))
+    }
+
+    /// Replaces global tile ids across the tiles of all layers, including the
+    /// chunks of infinite ones, and the tile objects of object layers. Flip
+    /// flags are kept, and gids missing from `mapping` are left as they are.
+    /// The gids of `mapping` are taken without their flip flags. Mapping a
+    /// gid to `Gid::EMPTY` erases its tiles.
+    ///
+    /// Fails without changing the map if a gid is mapped to a tile which no
+    /// tileset of the map holds.
+    pub fn remap_gids(&mut self, mapping: &HashMap<Gid, Gid>) -> Result<(), TiledError> {
+        let mapping: HashMap<Gid, Gid> = mapping
+            .iter()
+            .map(|(from, to)| (Gid(from.value()), Gid(to.value())))
+            .collect();
+        let mut targets: Vec<u32> = mapping.values().map(|gid| gid.value()).collect();
+        targets.sort_unstable();
+        if let Some(gid) = targets
+            .into_iter()
+            .find(|&gid| gid != 0 && !self.has_gid(Gid(gid)))
+        {
+            return Err(TiledError::Other(format!(
+                "Cannot remap tiles to gid {}, which is in none of the map's tilesets",
+                gid
+            )));
+        }
+
+        let remap = |cell: &mut Option<LayerTile>| {
+            if let Some(ref mut tile) = *cell {
+                if let Some(&gid) = mapping.get(&tile.gid) {
+                    tile.gid = gid;
+                    if gid.is_empty() {
+                        *cell = None;
+                    }
+                }
+            }
+        };
+        for layer in self.leaf_layers_mut() {
+            match layer.layer_type {
+                LayerType::TileLayer(ref mut layer) => match layer.tiles {
+                    LayerData::Finite(ref mut rows) => {
+                        rows.iter_mut().flatten().for_each(remap);
+                    }
+                    LayerData::Infinite(ref mut chunks) => {
+                        chunks
+                            .values_mut()
+                            .flat_map(|chunk| chunk.tiles.iter_mut().flatten())
+                            .for_each(remap);
+                    }
+                },
+                LayerType::ObjectLayer(ref mut group) => {
+                    for object in group.objects.iter_mut() {
+                        let mut tile = LayerTile::from_bits(object.gid.0);
+                        remap(&mut tile);
+                        object.gid = Gid(cell_bits(tile));
+                    }
+                }
+                LayerType::ImageLayer(_) | LayerType::Group(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,285 @@
+//! A compact, read-only form of a map for shipping games.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::{
+    Colour, Gid, LayerTile, LayerType, Map, ObjectShape, Orientation, PropertyValue, TileRect,
+    Tileset,
+};
+
+/// An interned string of a `RuntimeMap`.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub struct StringId(u32);
+
+/// Stores each distinct string once.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+struct Interner {
+    strings: Vec<String>,
+    ids: HashMap<String, StringId>,
+}
+
+impl Interner {
+    fn intern(&mut self, s: &str) -> StringId {
+        if let Some(&id) = self.ids.get(s) {
+            return id;
+        }
+        let id = StringId(self.strings.len() as u32);
+        self.strings.push(s.to_string());
+        self.ids.insert(s.to_string(), id);
+        id
+    }
+}
+
+/// What to keep of the editor data when converting a map with
+/// `RuntimeMap::new`. By default everything is dropped.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct RuntimeOptions {
+    /// Keeps the names of layers and objects.
+    pub keep_names: bool,
+    /// The names of the properties to keep, for the map, layers, objects and
+    /// tiles alike.
+    pub properties: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum RuntimeLayerKind {
+    Tiles,
+    Objects,
+    Image,
+}
+
+/// What a property of a `RuntimeMap` belongs to.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum PropertyOwner {
+    Map,
+    /// A layer, by index.
+    Layer(usize),
+    /// An object, by index into `RuntimeMap::objects`.
+    Object(usize),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RuntimeProperty {
+    pub owner: PropertyOwner,
+    pub name: StringId,
+    pub value: PropertyValue,
+}
+
+/// The layers of a `RuntimeMap`, stored as one array per attribute. Every
+/// array has one entry per layer, with groups flattened as in
+/// `Map::flat_layers`.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct RuntimeLayers {
+    pub kind: Vec<RuntimeLayerKind>,
+    pub name: Vec<Option<StringId>>,
+    pub opacity: Vec<f32>,
+    pub visible: Vec<bool>,
+    pub offset: Vec<(f32, f32)>,
+    pub tint_colour: Vec<Option<Colour>>,
+    /// The area covered by the tiles of tile layers, empty for other layers.
+    pub area: Vec<TileRect>,
+    /// The range of `RuntimeMap::tiles` holding the tiles of tile layers, or
+    /// the range of objects of object layers.
+    pub contents: Vec<Range<usize>>,
+    /// The image of image layers.
+    pub image: Vec<Option<StringId>>,
+}
+
+/// The objects of a `RuntimeMap`, stored as one array per attribute. Every
+/// array has one entry per object.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct RuntimeObjects {
+    pub id: Vec<u32>,
+    pub name: Vec<Option<StringId>>,
+    pub obj_type: Vec<StringId>,
+    /// The gid of tile objects, or `Gid::EMPTY`.
+    pub gid: Vec<Gid>,
+    pub position: Vec<(f32, f32)>,
+    pub rotation: Vec<f32>,
+    pub visible: Vec<bool>,
+    pub shape: Vec<ObjectShape>,
+}
+
+/// A read-only form of a map taking as little memory as possible. Editor
+/// data such as names and properties is dropped unless asked for, strings
+/// are interned, and layers and objects are flattened into arrays. Infinite
+/// layers are stored as dense arrays covering their chunks.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RuntimeMap {
+    pub orientation: Orientation,
+    pub width: u32,
+    pub height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub background_colour: Option<Colour>,
+    pub tilesets: Vec<Tileset>,
+    pub layers: RuntimeLayers,
+    /// The tiles of all tile layers, row by row, packed with their flip
+    /// flags as in layer data.
+    pub tiles: Vec<u32>,
+    pub objects: RuntimeObjects,
+    pub properties: Vec<RuntimeProperty>,
+    strings: Interner,
+}
+
+impl RuntimeMap {
+    pub fn new(map: &Map, options: &RuntimeOptions) -> RuntimeMap {
+        let mut strings = Interner::default();
+        let mut properties = Vec::new();
+        let mut keep_properties =
+            |owner, props: &HashMap<String, PropertyValue>, strings: &mut Interner| {
+                for name in options.properties.iter() {
+                    if let Some(value) = props.get(name) {
+                        properties.push(RuntimeProperty {
+                            owner,
+                            name: strings.intern(name),
+                            value: value.clone(),
+                        });
+                    }
+                }
+            };
+        keep_properties(PropertyOwner::Map, &map.properties, &mut strings);
+
+        let mut layers = RuntimeLayers::default();
+        let mut tiles = Vec::new();
+        let mut objects = RuntimeObjects::default();
+        for (index, flat) in map.flat_layers().into_iter().enumerate() {
+            let layer = flat.layer;
+            let name = |strings: &mut Interner, name: &str| {
+                if options.keep_names {
+                    Some(strings.intern(name))
+                } else {
+                    None
+                }
+            };
+            layers.name.push(name(&mut strings, &layer.name));
+            layers.opacity.push(flat.opacity);
+            layers.visible.push(flat.visible);
+            layers.offset.push((flat.offset_x, flat.offset_y));
+            layers.tint_colour.push(layer.tint_colour);
+            keep_properties(PropertyOwner::Layer(index), &layer.properties, &mut strings);
+            let empty = TileRect::new(0, 0, 0, 0);
+            match layer.layer_type {
+                LayerType::TileLayer(ref tile_layer) => {
+                    let area = tile_layer.bounds().unwrap_or(empty);
+                    let start = tiles.len();
+                    for y in area.y..area.bottom() {
+                        for x in area.x..area.right() {
+                            tiles.push(tile_layer.get_tile(x, y).map_or(0, LayerTile::to_bits));
+                        }
+                    }
+                    layers.kind.push(RuntimeLayerKind::Tiles);
+                    layers.area.push(area);
+                    layers.contents.push(start..tiles.len());
+                    layers.image.push(None);
+                }
+                LayerType::ObjectLayer(ref group) => {
+                    let start = objects.id.len();
+                    for object in group.objects.iter() {
+                        keep_properties(
+                            PropertyOwner::Object(objects.id.len()),
+                            &object.properties,
+                            &mut strings,
+                        );
+                        objects.id.push(object.id);
+                        objects.name.push(name(&mut strings, &object.name));
+                        objects.obj_type.push(strings.intern(&object.obj_type));
+                        objects.gid.push(object.gid);
+                        objects.position.push((object.x, object.y));
+                        objects.rotation.push(object.rotation);
+                        objects.visible.push(object.visible);
+                        objects.shape.push(object.shape.clone());
+                    }
+                    layers.kind.push(RuntimeLayerKind::Objects);
+                    layers.area.push(empty);
+                    layers.contents.push(start..objects.id.len());
+                    layers.image.push(None);
+                }
+                LayerType::ImageLayer(ref image_layer) => {
+                    let image = image_layer
+                        .image
+                        .as_ref()
+                        .map(|image| strings.intern(&image.source.to_string_lossy()));
+                    layers.kind.push(RuntimeLayerKind::Image);
+                    layers.area.push(empty);
+                    layers.contents.push(0..0);
+                    layers.image.push(image);
+                }
+                LayerType::Group(_) => unreachable!("groups are flattened"),
+            }
+        }
+
+        let tilesets = map
+            .tilesets
+            .iter()
+            .map(|tileset| {
+                let mut tileset = Tileset::clone(tileset);
+                let keep = |props: &mut HashMap<String, PropertyValue>| {
+                    props.retain(|name, _| options.properties.contains(name))
+                };
+                keep(&mut tileset.properties);
+                for tile in tileset.tiles.iter_mut() {
+                    keep(&mut tile.properties);
+                }
+                tileset
+            })
+            .collect();
+
+        strings.ids = HashMap::new();
+        RuntimeMap {
+            orientation: map.orientation,
+            width: map.width,
+            height: map.height,
+            tile_width: map.tile_width,
+            tile_height: map.tile_height,
+            background_colour: map.background_colour,
+            tilesets,
+            layers,
+            tiles,
+            objects,
+            properties,
+            strings,
+        }
+    }
+
+    /// Returns an interned string.
+    pub fn string(&self, id: StringId) -> &str {
+        &self.strings.strings[id.0 as usize]
+    }
+
+    /// The number of layers.
+    pub fn layer_count(&self) -> usize {
+        self.layers.kind.len()
+    }
+
+    /// Returns the tile at the given tile coordinates of a tile layer, or
+    /// `None` if the position lies outside of the layer's data.
+    pub fn tile_at(&self, layer: usize, x: i32, y: i32) -> Option<LayerTile> {
+        if *self.layers.kind.get(layer)? != RuntimeLayerKind::Tiles {
+            return None;
+        }
+        let area = self.layers.area[layer];
+        if !area.contains(x, y) {
+            return None;
+        }
+        let offset = (y - area.y) as usize * area.width as usize + (x - area.x) as usize;
+        Some(LayerTile::new(
+            self.tiles[self.layers.contents[layer].start + offset],
+        ))
+    }
+
+    /// Returns a kept property.
+    pub fn property(&self, owner: PropertyOwner, name: &str) -> Option<&PropertyValue> {
+        self.properties
+            .iter()
+            .find(|p| p.owner == owner && self.string(p.name) == name)
+            .map(|p| &p.value)
+    }
+}
+
+impl From<&Map> for RuntimeMap {
+    fn from(map: &Map) -> RuntimeMap {
+        RuntimeMap::new(map, &RuntimeOptions::default())
+    }
+}
@@ -0,0 +1,133 @@
+//! Random placement of decoration tiles and objects.
+
+use std::collections::HashMap;
+
+use crate::{
+    Extra, Gid, LayerTile, Map, Object, ObjectShape, Rng, TileLayer, TileRect, TiledError, Tileset,
+};
+
+/// Scatters tiles randomly across an area, as a decoration pass for
+/// procedurally generated levels. Each candidate tile is picked in proportion
+/// to the `probability` Tiled stores for it, so the same weights used by
+/// Tiled's own random mode apply here.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Scatter {
+    /// The global tile ids to pick from, along with their weights.
+    pub candidates: Vec<(u32, f32)>,
+    /// The chance, between 0 and 1, for a cell to receive a tile.
+    pub density: f32,
+}
+
+impl Scatter {
+    /// Creates a scatter pass over the given global tile ids, looking up the
+    /// weight of each tile in the map's tilesets. Tiles without an explicit
+    /// probability have a weight of 1.
+    pub fn new(map: &Map, gids: &[u32], density: f32) -> Scatter {
+        let candidates = gids
+            .iter()
+            .map(|&gid| (gid, tile_probability(map, gid)))
+            .collect();
+        Scatter {
+            candidates,
+            density,
+        }
+    }
+
+    /// Decides which cells of `area` receive a tile. Only cells for which
+    /// `mask` returns true are considered. The result only depends on the
+    /// state of `rng`, so a generator created from the same seed gives the
+    /// same placements.
+    pub fn placements(
+        &self,
+        area: TileRect,
+        rng: &mut Rng,
+        mut mask: impl FnMut(i32, i32) -> bool,
+    ) -> Vec<(i32, i32, u32)> {
+        let weights: Vec<f32> = self.candidates.iter().map(|c| c.1).collect();
+        let mut placements = Vec::new();
+        for y in area.y..area.bottom() {
+            for x in area.x..area.right() {
+                if !mask(x, y) || rng.next_f32() >= self.density {
+                    continue;
+                }
+                if let Some(i) = rng.choose_weighted(&weights) {
+                    placements.push((x, y, self.candidates[i].0));
+                }
+            }
+        }
+        placements
+    }
+
+    /// Scatters tiles onto a tile layer. Placements outside of a finite
+    /// layer are ignored.
+    pub fn scatter_tiles(
+        &self,
+        layer: &mut TileLayer,
+        area: TileRect,
+        rng: &mut Rng,
+        mask: impl FnMut(i32, i32) -> bool,
+    ) {
+        for (x, y, gid) in self.placements(area, rng, mask) {
+            let _ = layer.set_tile(x, y, LayerTile::new(gid));
+        }
+    }
+
+    /// Scatters tile objects into an object layer of the map, one per chosen
+    /// cell. The objects are given the size of a map tile and, like tile
+    /// objects in Tiled, are positioned by their bottom-left corner. Their
+    /// ids are taken from the map's next object id.
+    pub fn scatter_objects(
+        &self,
+        map: &mut Map,
+        layer_index: usize,
+        area: TileRect,
+        rng: &mut Rng,
+        mask: impl FnMut(i32, i32) -> bool,
+    ) -> Result<(), TiledError> {
+        let (width, height) = (map.tile_width as f32, map.tile_height as f32);
+        for (x, y, gid) in self.placements(area, rng, mask) {
+            let object = Object {
+                id: 0,
+                gid: Gid(gid),
+                name: String::new(),
+                obj_type: String::new(),
+                width,
+                height,
+                x: x as f32 * width,
+                y: (y + 1) as f32 * height,
+                rotation: 0.0,
+                visible: true,
+                locked: false,
+                shape: ObjectShape::Rect { width, height },
+                properties: HashMap::new(),
+                extra: Extra::default(),
+            };
+            map.add_object(layer_index, object)?;
+        }
+        Ok(())
+    }
+}
+
+fn tile_probability(map: &Map, gid: u32) -> f32 {
+    map.tileset_for_gid(gid)
+        .map_or(1.0, |(tileset, id)| tileset.tile_probability(id))
+}
+
+impl Tileset {
+    /// Returns the weight Tiled gives the tile with the given local id when
+    /// painting randomly, which is 1 for tiles that don't set one.
+    pub fn tile_probability(&self, id: u32) -> f32 {
+        self.get_tile(id).map_or(1.0, |t| t.probability)
+    }
+
+    /// Picks one of the given local tile ids at random, in proportion to
+    /// the probability of each tile, like Tiled's random mode. Returns
+    /// `None` if none of the candidates has a positive probability.
+    pub fn choose_tile(&self, candidates: &[u32], rng: &mut Rng) -> Option<u32> {
+        let weights: Vec<f32> = candidates
+            .iter()
+            .map(|&id| self.tile_probability(id))
+            .collect();
+        rng.choose_weighted(&weights).map(|i| candidates[i])
+    }
+}
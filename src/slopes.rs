@@ -0,0 +1,177 @@
+//! Classification of tile collision shapes for platformer physics.
+
+use crate::{LayerTile, Map, Object, ObjectShape, Tileset};
+
+/// How far a corner may be from where it is expected, as a fraction of the
+/// tile's size.
+const TOLERANCE: f32 = 0.05;
+
+/// The shape of a tile's collision, as commonly drawn for platformers. Heights
+/// are fractions of the tile's height.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum TileCollision {
+    /// The tile has no collision shapes.
+    Empty,
+    /// The whole tile is solid.
+    Full,
+    /// The tile is solid from its bottom up to a line between the given
+    /// heights at its left and right edges. Ramps rise from 0 to 1 across
+    /// the tile, while partial blocks such as half tiles have equal heights.
+    Slope { left: f32, right: f32 },
+    /// The tile is solid from its top down to a line between the given
+    /// depths at its left and right edges, the upside down of `Slope`.
+    Ceiling { left: f32, right: f32 },
+    /// A thin platform along the top of the tile, usually only solid when
+    /// landed on from above.
+    Platform,
+    /// Any other shape, to be handled from the collision shapes themselves.
+    Custom,
+}
+
+impl TileCollision {
+    /// Returns the height of the solid surface at a position across the
+    /// tile, from 0 at its left edge to 1 at its right edge. The height is a
+    /// fraction of the tile's height from its bottom, or `None` where there
+    /// is no surface to stand on or it can't be told.
+    pub fn surface_height(&self, x: f32) -> Option<f32> {
+        match *self {
+            TileCollision::Full | TileCollision::Ceiling { .. } | TileCollision::Platform => {
+                Some(1.0)
+            }
+            TileCollision::Slope { left, right } => Some(left + (right - left) * x.clamp(0.0, 1.0)),
+            TileCollision::Empty | TileCollision::Custom => None,
+        }
+    }
+
+    /// Returns the collision as seen on a flipped tile. Diagonal flips turn
+    /// slopes into walls, which are classified as `Custom`.
+    pub fn flipped(self, flip_h: bool, flip_v: bool, flip_d: bool) -> TileCollision {
+        let swap = |left: f32, right: f32| if flip_h { (right, left) } else { (left, right) };
+        match self {
+            TileCollision::Empty | TileCollision::Full | TileCollision::Custom => self,
+            _ if flip_d => TileCollision::Custom,
+            TileCollision::Slope { left, right } => {
+                let (left, right) = swap(left, right);
+                if flip_v {
+                    TileCollision::Ceiling { left, right }
+                } else {
+                    TileCollision::Slope { left, right }
+                }
+            }
+            TileCollision::Ceiling { left, right } => {
+                let (left, right) = swap(left, right);
+                if flip_v {
+                    TileCollision::Slope { left, right }
+                } else {
+                    TileCollision::Ceiling { left, right }
+                }
+            }
+            TileCollision::Platform if flip_v => TileCollision::Custom,
+            TileCollision::Platform => TileCollision::Platform,
+        }
+    }
+
+    /// Classifies collision shapes given relative to a tile of the given
+    /// size, in pixels.
+    pub fn classify(objects: &[Object], tile_width: f32, tile_height: f32) -> TileCollision {
+        let object = match objects {
+            [] => return TileCollision::Empty,
+            [object] if object.rotation == 0.0 => object,
+            _ => return TileCollision::Custom,
+        };
+        let scale =
+            |(x, y): (f32, f32)| ((object.x + x) / tile_width, (object.y + y) / tile_height);
+        let points: Vec<(f32, f32)> = match object.shape {
+            ObjectShape::Rect { width, height } => {
+                vec![(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)]
+            }
+            ObjectShape::Polygon { ref points } => points.clone(),
+            ObjectShape::Polyline { ref points } => {
+                let points: Vec<(f32, f32)> = points.iter().copied().map(scale).collect();
+                let along_top = points.len() >= 2
+                    && points.iter().all(|&(_, y)| y.abs() <= TOLERANCE)
+                    && spans_width(&points);
+                return if along_top {
+                    TileCollision::Platform
+                } else {
+                    TileCollision::Custom
+                };
+            }
+            _ => return TileCollision::Custom,
+        };
+        let points: Vec<(f32, f32)> = points.into_iter().map(scale).collect();
+        classify_polygon(&points)
+    }
+}
+
+/// Whether the points reach from the left edge of the tile to its right.
+fn spans_width(points: &[(f32, f32)]) -> bool {
+    points.iter().any(|&(x, _)| x <= TOLERANCE) && points.iter().any(|&(x, _)| x >= 1.0 - TOLERANCE)
+}
+
+/// Classifies a polygon given as fractions of the tile's size.
+fn classify_polygon(points: &[(f32, f32)]) -> TileCollision {
+    let near = |a: f32, b: f32| (a - b).abs() <= TOLERANCE;
+    let on_edge = |x: f32| near(x, 0.0) || near(x, 1.0);
+    if points.len() < 3 || !spans_width(points) || !points.iter().all(|&(x, _)| on_edge(x)) {
+        return TileCollision::Custom;
+    }
+    // Every corner lies on the left or right edge, so the polygon is bounded
+    // by those edges and a line along its top and bottom each.
+    let edge = |left: bool, top: bool| {
+        let ys = points
+            .iter()
+            .filter(|&&(x, _)| near(x, 0.0) == left)
+            .map(|&(_, y)| y);
+        if top {
+            ys.fold(f32::MAX, f32::min)
+        } else {
+            ys.fold(f32::MIN, f32::max)
+        }
+    };
+    let (top_left, top_right) = (edge(true, true), edge(false, true));
+    let (bottom_left, bottom_right) = (edge(true, false), edge(false, false));
+    let top = near(top_left, 0.0) && near(top_right, 0.0);
+    let bottom = near(bottom_left, 1.0) && near(bottom_right, 1.0);
+    match (top, bottom) {
+        (true, true) => TileCollision::Full,
+        (false, true) => TileCollision::Slope {
+            left: 1.0 - top_left,
+            right: 1.0 - top_right,
+        },
+        (true, false)
+            if bottom_left.max(bottom_right) <= 0.25 && near(bottom_left, bottom_right) =>
+        {
+            TileCollision::Platform
+        }
+        (true, false) => TileCollision::Ceiling {
+            left: bottom_left,
+            right: bottom_right,
+        },
+        (false, false) => TileCollision::Custom,
+    }
+}
+
+impl Tileset {
+    /// Classifies the collision shapes of the tile with the given local id.
+    pub fn tile_collision(&self, id: u32) -> TileCollision {
+        let objects = self
+            .get_tile(id)
+            .and_then(|tile| tile.objectgroup.as_ref()?.as_object_layer())
+            .map_or(&[][..], |group| &group.objects[..]);
+        TileCollision::classify(objects, self.tile_width as f32, self.tile_height as f32)
+    }
+}
+
+impl Map {
+    /// Classifies the collision shapes of a layer tile, taking its flips into
+    /// account. Empty tiles and unknown global tile ids have no collision.
+    pub fn tile_collision(&self, tile: LayerTile) -> TileCollision {
+        match self.tileset_for_gid(tile) {
+            Some((tileset, id)) if !tile.gid.is_empty() => tileset
+                .tile_collision(id)
+                .flipped(tile.flip_h, tile.flip_v, tile.flip_d),
+            _ => TileCollision::Empty,
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Partitioning of finite maps into vertical strips for side-scrolling.
+
+use std::ops::Range;
+
+use crate::{LayerType, Map, TileRect, TiledError};
+
+/// A vertical slice of a map, spanning its full height.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Strip {
+    /// The tiles covered by the strip.
+    pub area: TileRect,
+    /// The smallest rectangle containing every non-empty tile of the strip
+    /// across all tile layers, or `None` if the strip has no tiles.
+    pub bounds: Option<TileRect>,
+    /// The objects positioned within the strip, as pairs of index into
+    /// `Map::flat_layers` and index into the layer's objects.
+    pub objects: Vec<(usize, usize)>,
+}
+
+/// A finite map divided into strips of a fixed number of columns, which
+/// side-scrolling games can activate and deactivate as the view moves.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StripPartition {
+    /// The width of each strip in tiles. The last strip may be narrower.
+    pub strip_width: u32,
+    /// The width of a tile in pixels.
+    pub tile_width: u32,
+    pub strips: Vec<Strip>,
+}
+
+impl StripPartition {
+    /// Returns the index of the strip containing the given horizontal
+    /// position in pixels.
+    pub fn strip_at(&self, x: f32) -> Option<usize> {
+        if x < 0.0 {
+            return None;
+        }
+        let index = (x / (self.strip_width * self.tile_width) as f32) as usize;
+        if index < self.strips.len() {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the range of strips overlapping the horizontal span between
+    /// `left` and `right` in pixels, like the visible part of the map plus a
+    /// margin.
+    pub fn strips_between(&self, left: f32, right: f32) -> Range<usize> {
+        let strip_px = (self.strip_width * self.tile_width) as f32;
+        let clamp = |x: f32| (x.max(0.0) as usize).min(self.strips.len());
+        let start = clamp((left / strip_px).floor());
+        let end = clamp((right / strip_px).floor() + 1.0);
+        start..end.max(start)
+    }
+}
+
+impl Map {
+    /// Divides the map into vertical strips `strip_width` tiles wide. Only
+    /// finite maps can be divided.
+    pub fn vertical_strips(&self, strip_width: u32) -> Result<StripPartition, TiledError> {
+        if self.infinite {
+            return Err(TiledError::Other(
+                "Only finite maps can be divided into strips".to_string(),
+            ));
+        }
+        if strip_width == 0 {
+            return Err(TiledError::Other(
+                "Strips must be at least one tile wide".to_string(),
+            ));
+        }
+        let mut strips: Vec<Strip> = (0..self.width)
+            .step_by(strip_width as usize)
+            .map(|x| Strip {
+                area: TileRect::new(x as i32, 0, strip_width.min(self.width - x), self.height),
+                bounds: None,
+                objects: Vec::new(),
+            })
+            .collect();
+        let strip_px = (strip_width * self.tile_width) as f32;
+        for (layer_index, flat) in self.flat_layers().into_iter().enumerate() {
+            match flat.layer.layer_type {
+                LayerType::TileLayer(ref tiles) => {
+                    for strip in strips.iter_mut() {
+                        let area = strip.area;
+                        for y in area.y..area.bottom() {
+                            for x in area.x..area.right() {
+                                if tiles.get_tile(x, y).is_some() {
+                                    let tile = TileRect::new(x, y, 1, 1);
+                                    strip.bounds =
+                                        Some(strip.bounds.map_or(tile, |b| b.union(&tile)));
+                                }
+                            }
+                        }
+                    }
+                }
+                LayerType::ObjectLayer(ref group) => {
+                    for (object_index, object) in group.objects.iter().enumerate() {
+                        let x = object.x + flat.offset_x;
+                        if x < 0.0 {
+                            continue;
+                        }
+                        if let Some(strip) = strips.get_mut((x / strip_px) as usize) {
+                            strip.objects.push((layer_index, object_index));
+                        }
+                    }
+                }
+                LayerType::ImageLayer(_) | LayerType::Group(_) => {}
+            }
+        }
+        Ok(StripPartition {
+            strip_width,
+            tile_width: self.tile_width,
+            strips,
+        })
+    }
+}
@@ -0,0 +1,144 @@
+//! Units for numeric properties.
+//!
+//! Tiled has no notion of units, so they are given by a suffix of the
+//! property's name: a `range_tiles` property is a distance in tiles, and a
+//! `cooldown_ms` property a duration in milliseconds. The accessors take the
+//! name without the suffix and convert the value to the unit asked for.
+
+use std::fmt;
+
+use crate::{Properties, PropertyValue, TiledError};
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Unit {
+    Pixels,
+    Tiles,
+    Seconds,
+    Milliseconds,
+}
+
+impl Unit {
+    /// The suffixes marking a property as having this unit.
+    pub fn suffixes(self) -> &'static [&'static str] {
+        match self {
+            Unit::Pixels => &["px", "pixels"],
+            Unit::Tiles => &["tiles"],
+            Unit::Seconds => &["s", "seconds"],
+            Unit::Milliseconds => &["ms", "milliseconds"],
+        }
+    }
+
+    fn is_distance(self) -> bool {
+        matches!(self, Unit::Pixels | Unit::Tiles)
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unit::Pixels => write!(f, "pixels"),
+            Unit::Tiles => write!(f, "tiles"),
+            Unit::Seconds => write!(f, "seconds"),
+            Unit::Milliseconds => write!(f, "milliseconds"),
+        }
+    }
+}
+
+const UNITS: [Unit; 4] = [Unit::Pixels, Unit::Tiles, Unit::Seconds, Unit::Milliseconds];
+
+/// Accessors for numeric properties whose unit is given by a suffix of their
+/// name. Each one returns `Ok(None)` if there is no such property, and an
+/// error if the property isn't a number, has no unit or has a unit of the
+/// wrong kind, like a duration where a distance is expected.
+pub trait PropertyUnits {
+    /// Returns the value of the property `name` followed by a unit suffix,
+    /// along with its unit.
+    fn get_with_unit(&self, name: &str) -> Result<Option<(f32, Unit)>, TiledError>;
+
+    /// Returns a distance in tiles, given the size of a tile in pixels.
+    fn get_distance_in_tiles(&self, name: &str, tile_size: f32) -> Result<Option<f32>, TiledError>;
+
+    /// Returns a distance in pixels, given the size of a tile in pixels.
+    fn get_distance_in_pixels(&self, name: &str, tile_size: f32)
+        -> Result<Option<f32>, TiledError>;
+
+    /// Returns a duration in seconds.
+    fn get_duration_in_seconds(&self, name: &str) -> Result<Option<f32>, TiledError>;
+}
+
+impl PropertyUnits for Properties {
+    fn get_with_unit(&self, name: &str) -> Result<Option<(f32, Unit)>, TiledError> {
+        let mut found = None;
+        for unit in UNITS.iter() {
+            for suffix in unit.suffixes() {
+                let full_name = format!("{}_{}", name, suffix);
+                let value = match self.get(&full_name) {
+                    Some(PropertyValue::FloatValue(v)) => *v,
+                    Some(PropertyValue::IntValue(v)) => *v as f32,
+                    Some(_) => {
+                        return Err(TiledError::Other(format!(
+                            "Property \"{}\" is not a number",
+                            full_name
+                        )))
+                    }
+                    None => continue,
+                };
+                if found.is_some() {
+                    return Err(TiledError::Other(format!(
+                        "Property \"{}\" is given in more than one unit",
+                        name
+                    )));
+                }
+                found = Some((value, *unit));
+            }
+        }
+        if found.is_none() && self.contains_key(name) {
+            return Err(TiledError::Other(format!(
+                "Property \"{}\" has no unit suffix",
+                name
+            )));
+        }
+        Ok(found)
+    }
+
+    fn get_distance_in_tiles(&self, name: &str, tile_size: f32) -> Result<Option<f32>, TiledError> {
+        Ok(match distance(self, name)? {
+            Some((v, Unit::Pixels)) => Some(v / tile_size),
+            other => other.map(|(v, _)| v),
+        })
+    }
+
+    fn get_distance_in_pixels(
+        &self,
+        name: &str,
+        tile_size: f32,
+    ) -> Result<Option<f32>, TiledError> {
+        Ok(match distance(self, name)? {
+            Some((v, Unit::Tiles)) => Some(v * tile_size),
+            other => other.map(|(v, _)| v),
+        })
+    }
+
+    fn get_duration_in_seconds(&self, name: &str) -> Result<Option<f32>, TiledError> {
+        match self.get_with_unit(name)? {
+            Some((v, Unit::Seconds)) => Ok(Some(v)),
+            Some((v, Unit::Milliseconds)) => Ok(Some(v / 1000.0)),
+            Some((_, unit)) => Err(wrong_unit(name, unit, "duration")),
+            None => Ok(None),
+        }
+    }
+}
+
+fn distance(properties: &Properties, name: &str) -> Result<Option<(f32, Unit)>, TiledError> {
+    match properties.get_with_unit(name)? {
+        Some((_, unit)) if !unit.is_distance() => Err(wrong_unit(name, unit, "distance")),
+        found => Ok(found),
+    }
+}
+
+fn wrong_unit(name: &str, unit: Unit, expected: &str) -> TiledError {
+    TiledError::Other(format!(
+        "Property \"{}\" is in {}, which is not a {}",
+        name, unit, expected
+    ))
+}
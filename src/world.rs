@@ -0,0 +1,232 @@
+//! Loading of Tiled world files (`.world`), which lay out several maps next
+//! to each other.
+
+#[cfg(feature = "fs")]
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+#[cfg(feature = "fs")]
+use std::path::PathBuf;
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::{Loader, Map, TiledError};
+
+/// A set of maps placed in a shared coordinate space.
+#[derive(Debug, PartialEq, Clone)]
+pub struct World {
+    /// The maps of the world, both the ones listed explicitly and the ones
+    /// found through the patterns.
+    pub maps: Vec<WorldMap>,
+    pub patterns: Vec<WorldPattern>,
+    pub only_show_adjacent_maps: bool,
+}
+
+/// A map placed within a world.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WorldMap {
+    /// The file name of the map, relative to the world file.
+    pub file_name: String,
+    /// The position of the map's top-left corner, in pixels.
+    pub x: i32,
+    pub y: i32,
+    /// The size of the map in pixels, if the world file records it.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// The map itself, if it has been loaded.
+    pub map: Option<Map>,
+}
+
+/// A regular expression matching the file names of maps, where the first two
+/// captured numbers give the position of each map.
+#[derive(Debug, Clone)]
+pub struct WorldPattern {
+    pub regexp: Regex,
+    pub multiplier_x: i32,
+    pub multiplier_y: i32,
+    pub offset_x: i32,
+    pub offset_y: i32,
+    /// The size of the maps matched by the pattern. Defaults to the
+    /// multipliers when the world file doesn't specify it.
+    pub map_width: u32,
+    pub map_height: u32,
+}
+
+impl PartialEq for WorldPattern {
+    fn eq(&self, other: &WorldPattern) -> bool {
+        self.regexp.as_str() == other.regexp.as_str()
+            && self.multiplier_x == other.multiplier_x
+            && self.multiplier_y == other.multiplier_y
+            && self.offset_x == other.offset_x
+            && self.offset_y == other.offset_y
+            && self.map_width == other.map_width
+            && self.map_height == other.map_height
+    }
+}
+
+impl WorldPattern {
+    /// Returns the placement of the map with the given file name, if the
+    /// name matches the pattern.
+    pub fn match_file_name(&self, file_name: &str) -> Option<WorldMap> {
+        let captures = self.regexp.captures(file_name)?;
+        // Like Tiled, require the whole file name to match.
+        if captures.get(0)?.as_str() != file_name {
+            return None;
+        }
+        let x: i32 = captures.get(1)?.as_str().parse().ok()?;
+        let y: i32 = captures.get(2)?.as_str().parse().ok()?;
+        Some(WorldMap {
+            file_name: file_name.to_string(),
+            x: x * self.multiplier_x + self.offset_x,
+            y: y * self.multiplier_y + self.offset_y,
+            width: Some(self.map_width),
+            height: Some(self.map_height),
+            map: None,
+        })
+    }
+}
+
+impl World {
+    /// Parses a world from JSON. Patterns are kept but not matched against
+    /// any files, since there is no directory to look in; use
+    /// `parse_world_file` to include the maps they match.
+    pub fn parse<R: Read>(reader: R) -> Result<World, TiledError> {
+        let value: Value =
+            serde_json::from_reader(reader).map_err(TiledError::JsonDecodingError)?;
+        let maps = match value.get("maps") {
+            Some(Value::Array(maps)) => maps
+                .iter()
+                .map(parse_world_map)
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => Vec::new(),
+        };
+        let patterns = match value.get("patterns") {
+            Some(Value::Array(patterns)) => patterns
+                .iter()
+                .map(parse_pattern)
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => Vec::new(),
+        };
+        let only_show_adjacent_maps = value
+            .get("onlyShowAdjacentMaps")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        Ok(World {
+            maps,
+            patterns,
+            only_show_adjacent_maps,
+        })
+    }
+
+    /// Loads every map of the world which hasn't been loaded yet. File names
+    /// are resolved relative to `dir`, the directory of the world file.
+    pub fn load_maps(&mut self, dir: impl AsRef<Path>) -> Result<(), TiledError> {
+        self.load_maps_with(dir, &Loader::new())
+    }
+
+    /// Same as `load_maps`, parsing the maps with `loader`.
+    pub fn load_maps_with(
+        &mut self,
+        dir: impl AsRef<Path>,
+        loader: &Loader,
+    ) -> Result<(), TiledError> {
+        let dir = dir.as_ref();
+        for world_map in self.maps.iter_mut().filter(|m| m.map.is_none()) {
+            world_map.map = Some(loader.parse_file(dir.join(&world_map.file_name))?);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "fs")]
+    fn add_pattern_maps(&mut self, dir: &Path) -> Result<(), TiledError> {
+        if self.patterns.is_empty() {
+            return Ok(());
+        }
+        let entries = fs::read_dir(dir).map_err(|e| TiledError::io(dir, e))?;
+        let mut file_names: Vec<String> = entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .collect();
+        file_names.sort();
+        for file_name in file_names {
+            if self.maps.iter().any(|m| m.file_name == file_name) {
+                continue;
+            }
+            let matched = self
+                .patterns
+                .iter()
+                .find_map(|p| p.match_file_name(&file_name));
+            if let Some(world_map) = matched {
+                self.maps.push(world_map);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn get_i32(value: &Value, key: &str) -> Option<i32> {
+    value.get(key)?.as_i64().map(|v| v as i32)
+}
+
+fn get_u32(value: &Value, key: &str) -> Option<u32> {
+    value.get(key)?.as_u64().map(|v| v as u32)
+}
+
+fn parse_world_map(value: &Value) -> Result<WorldMap, TiledError> {
+    let file_name = value
+        .get("fileName")
+        .and_then(Value::as_str)
+        .ok_or_else(|| TiledError::MissingAttribute {
+            element: "world map".to_string(),
+            attribute: "fileName".to_string(),
+        })?;
+    Ok(WorldMap {
+        file_name: file_name.to_string(),
+        x: get_i32(value, "x").unwrap_or(0),
+        y: get_i32(value, "y").unwrap_or(0),
+        width: get_u32(value, "width"),
+        height: get_u32(value, "height"),
+        map: None,
+    })
+}
+
+fn parse_pattern(value: &Value) -> Result<WorldPattern, TiledError> {
+    let regexp = value.get("regexp").and_then(Value::as_str).ok_or_else(|| {
+        TiledError::MissingAttribute {
+            element: "world pattern".to_string(),
+            attribute: "regexp".to_string(),
+        }
+    })?;
+    let regexp = Regex::new(regexp)
+        .map_err(|e| TiledError::MalformedAttributes(format!("invalid world pattern: {}", e)))?;
+    let multiplier_x = get_i32(value, "multiplierX").unwrap_or(1);
+    let multiplier_y = get_i32(value, "multiplierY").unwrap_or(1);
+    Ok(WorldPattern {
+        regexp,
+        multiplier_x,
+        multiplier_y,
+        offset_x: get_i32(value, "offsetX").unwrap_or(0),
+        offset_y: get_i32(value, "offsetY").unwrap_or(0),
+        map_width: get_u32(value, "mapWidth").unwrap_or(multiplier_x.unsigned_abs()),
+        map_height: get_u32(value, "mapHeight").unwrap_or(multiplier_y.unsigned_abs()),
+    })
+}
+
+/// Parses a world file, including the maps matched by its patterns among the
+/// files next to it. When `load_maps` is set, every map of the world is
+/// parsed as well.
+#[cfg(feature = "fs")]
+pub fn parse_world_file(path: impl AsRef<Path>, load_maps: bool) -> Result<World, TiledError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| TiledError::io(path, e))?;
+    let mut world = World::parse(file)?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    world.add_pattern_maps(&dir)?;
+    if load_maps {
+        world.load_maps(&dir)?;
+    }
+    Ok(world)
+}
@@ -0,0 +1,1017 @@
+//! Serialization of maps and tilesets back into the TMX/TSX formats.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use xml::writer::{EmitterConfig, EventWriter, XmlEvent};
+
+use crate::checksum::write_with_checksum;
+use crate::{
+    cell_bits, Chunk, Colour, DrawOrder, EditorSettings, Extra, FillMode, Frame,
+    HorizontalAlignment, Image, Layer, LayerChange, LayerData, LayerTile, LayerType, Map, MapPatch,
+    Object, ObjectEdit, ObjectShape, Properties, PropertyValue, Text, Tile, TileDataCodec,
+    TileLayer, TileRenderSize, TiledError, Tileset, VerticalAlignment, WangSet, XmlNode,
+};
+
+type Attributes<'a> = Vec<(&'a str, String)>;
+
+/// The compression applied to base64 encoded layer data.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Compression {
+    Zlib,
+    Gzip,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compression::Zlib => write!(f, "zlib"),
+            Compression::Gzip => write!(f, "gzip"),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+/// How the tiles of tile layers are written.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub enum DataEncoding {
+    #[default]
+    Csv,
+    Base64(Option<Compression>),
+}
+
+/// Options controlling how maps are written.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct WriteOptions {
+    pub data_encoding: DataEncoding,
+    /// A codec encoding layer data in place of `data_encoding`.
+    pub codec: Option<Arc<dyn TileDataCodec>>,
+    /// The level compressed layer data is written at in place of the map's
+    /// `compression_level`, such as 19 for small zstd data at the cost of
+    /// slower writing. It's saved as the map's `compressionlevel`.
+    pub compression_level: Option<i32>,
+    /// Adds a `checksum` property to the map holding a hash of the written
+    /// document, which `Loader::with_checksum_verification` checks.
+    pub checksum: bool,
+    /// Writes attributes in the order they were parsed in, and unknown
+    /// elements and comments where they were, following the `Layout` of
+    /// each element. Along with Tiled's indentation, this keeps the changes
+    /// to a rewritten file small. Layer data is still written as set by
+    /// `data_encoding`.
+    pub preserve_layout: bool,
+}
+
+/// An element being written, along with the unknown elements and comments
+/// yet to be written within it, each after the given number of children.
+#[derive(Default)]
+struct OpenElement {
+    children: usize,
+    pending: VecDeque<(usize, XmlNode)>,
+}
+
+struct TmxWriter<W: Write> {
+    writer: EventWriter<W>,
+    options: WriteOptions,
+    /// The compression level of the map being written.
+    compression_level: Option<i32>,
+    /// The version and Tiled version of the map being written.
+    map_versions: Option<(String, Option<String>)>,
+    /// Whether the layers of maps are left out.
+    skip_layers: bool,
+    open: Vec<OpenElement>,
+}
+
+impl<W: Write> TmxWriter<W> {
+    fn new(writer: W, options: WriteOptions) -> TmxWriter<W> {
+        let writer = EmitterConfig::new()
+            .perform_indent(true)
+            .indent_string(" ")
+            .autopad_comments(false)
+            .create_writer(writer);
+        TmxWriter {
+            writer,
+            options,
+            compression_level: None,
+            map_versions: None,
+            skip_layers: false,
+            open: Vec::new(),
+        }
+    }
+
+    /// Creates a writer for a single element, without a document
+    /// declaration.
+    fn fragment(writer: W, options: WriteOptions) -> TmxWriter<W> {
+        let writer = EmitterConfig::new()
+            .perform_indent(true)
+            .indent_string(" ")
+            .autopad_comments(false)
+            .write_document_declaration(false)
+            .create_writer(writer);
+        TmxWriter {
+            writer,
+            options,
+            compression_level: None,
+            map_versions: None,
+            skip_layers: false,
+            open: Vec::new(),
+        }
+    }
+
+    fn start(&mut self, name: &str, attrs: &[(&str, String)]) -> Result<(), TiledError> {
+        // Unknown elements and comments which came before this child go
+        // first.
+        loop {
+            let node = match self.open.last_mut() {
+                Some(parent)
+                    if parent
+                        .pending
+                        .front()
+                        .is_some_and(|&(position, _)| position <= parent.children) =>
+                {
+                    parent.pending.pop_front()
+                }
+                _ => None,
+            };
+            match node {
+                Some((_, node)) => self.write_node(&node)?,
+                None => break,
+            }
+        }
+        self.open_element(name, attrs)
+    }
+
+    /// Writes a start tag without writing pending nodes before it.
+    fn open_element(&mut self, name: &str, attrs: &[(&str, String)]) -> Result<(), TiledError> {
+        if let Some(parent) = self.open.last_mut() {
+            parent.children += 1;
+        }
+        let mut event = XmlEvent::start_element(name);
+        for (key, value) in attrs {
+            event = event.attr(*key, value.as_str());
+        }
+        self.writer
+            .write(event)
+            .map_err(TiledError::XmlEncodingError)?;
+        self.open.push(OpenElement::default());
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), TiledError> {
+        while let Some((_, node)) = self
+            .open
+            .last_mut()
+            .and_then(|element| element.pending.pop_front())
+        {
+            self.write_node(&node)?;
+        }
+        self.open.pop();
+        self.writer
+            .write(XmlEvent::end_element())
+            .map_err(TiledError::XmlEncodingError)
+    }
+
+    fn empty(&mut self, name: &str, attrs: &[(&str, String)]) -> Result<(), TiledError> {
+        self.start(name, attrs)?;
+        self.end()
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), TiledError> {
+        self.writer
+            .write(XmlEvent::characters(text))
+            .map_err(TiledError::XmlEncodingError)
+    }
+
+    /// Adds the unknown attributes kept from parsing, putting all of them
+    /// back in their order when preserving the layout.
+    fn push_extra_attrs<'a>(&self, attrs: &mut Attributes<'a>, extra: &'a Extra) {
+        attrs.extend(
+            extra
+                .attributes
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.clone())),
+        );
+        if self.options.preserve_layout {
+            let order = &extra.layout.attributes;
+            attrs.sort_by_key(|(name, _)| {
+                order.iter().position(|n| n == name).unwrap_or(usize::MAX)
+            });
+        }
+    }
+
+    /// Sets the unknown elements kept from parsing to be written within the
+    /// element just started, at the end or where they were when preserving
+    /// the layout.
+    fn place_extra(&mut self, extra: &Extra) {
+        let preserve = self.options.preserve_layout;
+        let mut nodes: Vec<(usize, XmlNode)> = extra
+            .elements
+            .iter()
+            .enumerate()
+            .map(|(i, element)| {
+                let position = extra.layout.element_positions.get(i).copied();
+                let position = position.filter(|_| preserve).unwrap_or(usize::MAX);
+                (position, XmlNode::Element(element.clone()))
+            })
+            .collect();
+        if preserve {
+            nodes.extend(
+                extra
+                    .layout
+                    .comments
+                    .iter()
+                    .map(|(position, text)| (*position, XmlNode::Comment(text.clone()))),
+            );
+        }
+        // Comments come before elements after the same number of children.
+        nodes.sort_by_key(|(position, node)| (*position, matches!(node, XmlNode::Element(_))));
+        if let Some(element) = self.open.last_mut() {
+            element.pending = nodes.into();
+        }
+    }
+
+    fn write_node(&mut self, node: &XmlNode) -> Result<(), TiledError> {
+        match node {
+            XmlNode::Element(element) => {
+                let attrs: Attributes = element
+                    .attributes
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.clone()))
+                    .collect();
+                self.open_element(&element.name, &attrs)?;
+                for child in element.children.iter() {
+                    self.write_node(child)?;
+                }
+                self.end()
+            }
+            XmlNode::Text(text) => self.text(text),
+            XmlNode::Comment(text) => self
+                .writer
+                .write(XmlEvent::comment(text))
+                .map_err(TiledError::XmlEncodingError),
+        }
+    }
+
+    fn write_map(&mut self, map: &Map) -> Result<(), TiledError> {
+        let mut attrs: Attributes = vec![("version", map.version.clone())];
+        if let Some(ref tiled_version) = map.tiled_version {
+            attrs.push(("tiledversion", tiled_version.clone()));
+        }
+        attrs.extend(vec![
+            ("orientation", map.orientation.to_string()),
+            ("width", map.width.to_string()),
+            ("height", map.height.to_string()),
+            ("tilewidth", map.tile_width.to_string()),
+            ("tileheight", map.tile_height.to_string()),
+            ("infinite", bool_attr(map.infinite)),
+            ("nextlayerid", map.next_layer_id.to_string()),
+            ("nextobjectid", map.next_object_id.to_string()),
+        ]);
+        if let Some(colour) = map.background_colour {
+            attrs.push(("backgroundcolor", colour.to_string()));
+        }
+        if let Some(ref user_type) = map.user_type {
+            attrs.push(("class", user_type.clone()));
+        }
+        let compression_level = self.options.compression_level.or(map.compression_level);
+        if let Some(level) = compression_level {
+            attrs.push(("compressionlevel", level.to_string()));
+        }
+        if let Some(length) = map.hex_side_length {
+            attrs.push(("hexsidelength", length.to_string()));
+        }
+        if let Some(axis) = map.stagger_axis {
+            attrs.push(("staggeraxis", axis.to_string()));
+        }
+        if let Some(index) = map.stagger_index {
+            attrs.push(("staggerindex", index.to_string()));
+        }
+        self.push_extra_attrs(&mut attrs, &map.extra);
+        self.compression_level = compression_level;
+        self.map_versions = Some((map.version.clone(), map.tiled_version.clone()));
+        self.start("map", &attrs)?;
+        self.place_extra(&map.extra);
+        if let Some(ref settings) = map.editor_settings {
+            self.write_editor_settings(settings)?;
+        }
+        self.write_properties(&map.properties)?;
+        for tileset in map.tilesets.iter() {
+            self.write_tileset(tileset, true)?;
+        }
+        for tileset in map.unresolved_tilesets.iter() {
+            self.empty(
+                "tileset",
+                &[
+                    ("firstgid", tileset.first_gid.to_string()),
+                    ("source", tileset.source.to_string_lossy().into_owned()),
+                ],
+            )?;
+        }
+
+        if !self.skip_layers {
+            for layer in map.layers.iter() {
+                self.write_layer(layer)?;
+            }
+        }
+        self.end()
+    }
+
+    fn write_patch(&mut self, patch: &MapPatch) -> Result<(), TiledError> {
+        self.start("patch", &[])?;
+        self.write_properties(&patch.properties)?;
+        for change in patch.changes.iter() {
+            match change {
+                LayerChange::Set(layer) => {
+                    let infinite = has_infinite_tiles(layer);
+                    self.start("setlayer", &[("infinite", (infinite as u8).to_string())])?;
+                    self.write_layer(layer)?;
+                    self.end()?;
+                }
+                LayerChange::Remove(name) => {
+                    self.empty("removelayer", &[("name", name.clone())])?
+                }
+                LayerChange::Tiles { layer, tiles } => {
+                    self.start("tiles", &[("layer", layer.clone())])?;
+                    for tile in tiles.iter() {
+                        self.empty(
+                            "tile",
+                            &[
+                                ("x", tile.x.to_string()),
+                                ("y", tile.y.to_string()),
+                                ("from", cell_bits(tile.from).to_string()),
+                                ("to", cell_bits(tile.to).to_string()),
+                            ],
+                        )?;
+                    }
+                    self.end()?;
+                }
+                LayerChange::Objects { layer, edits } => {
+                    self.start("objects", &[("layer", layer.clone())])?;
+                    for edit in edits.iter() {
+                        match edit {
+                            ObjectEdit::Add(object) => {
+                                self.start("add", &[])?;
+                                self.write_object(object)?;
+                                self.end()?;
+                            }
+                            ObjectEdit::Replace(object) => {
+                                self.start("replace", &[])?;
+                                self.write_object(object)?;
+                                self.end()?;
+                            }
+                            ObjectEdit::Remove(id) => {
+                                self.empty("remove", &[("id", id.to_string())])?
+                            }
+                        }
+                    }
+                    self.end()?;
+                }
+                LayerChange::Properties { layer, properties } => {
+                    self.start("layerproperties", &[("layer", layer.clone())])?;
+                    self.write_properties(properties)?;
+                    self.end()?;
+                }
+            }
+        }
+        self.end()
+    }
+
+    fn write_editor_settings(&mut self, settings: &EditorSettings) -> Result<(), TiledError> {
+        self.start("editorsettings", &[])?;
+        if let Some((width, height)) = settings.chunk_size {
+            self.empty(
+                "chunksize",
+                &[("width", width.to_string()), ("height", height.to_string())],
+            )?;
+        }
+        let mut export: Attributes = Vec::new();
+        if let Some(ref target) = settings.export_target {
+            export.push(("target", target.clone()));
+        }
+        if let Some(ref format) = settings.export_format {
+            export.push(("format", format.clone()));
+        }
+        if !export.is_empty() {
+            self.empty("export", &export)?;
+        }
+        self.end()
+    }
+
+    fn write_tileset(&mut self, tileset: &Tileset, embedded: bool) -> Result<(), TiledError> {
+        let mut attrs: Attributes = Vec::new();
+        if embedded {
+            attrs.push(("firstgid", tileset.first_gid.to_string()));
+        }
+        // Embedded tilesets only note their versions when they were loaded
+        // from an external tileset saved with other versions than the map.
+        let versions = (tileset.version.clone(), tileset.tiled_version.clone());
+        let write_versions = match self.map_versions {
+            Some((ref version, ref tiled_version)) => {
+                versions != (Some(version.clone()), tiled_version.clone())
+            }
+            None => !embedded,
+        };
+        if write_versions {
+            if let Some(ref version) = tileset.version {
+                attrs.push(("version", version.clone()));
+            }
+            if let Some(ref tiled_version) = tileset.tiled_version {
+                attrs.push(("tiledversion", tiled_version.clone()));
+            }
+        }
+        attrs.push(("name", tileset.name.clone()));
+        if let Some(ref user_type) = tileset.user_type {
+            attrs.push(("class", user_type.clone()));
+        }
+        attrs.push(("tilewidth", tileset.tile_width.to_string()));
+        attrs.push(("tileheight", tileset.tile_height.to_string()));
+        if tileset.spacing != 0 {
+            attrs.push(("spacing", tileset.spacing.to_string()));
+        }
+        if tileset.margin != 0 {
+            attrs.push(("margin", tileset.margin.to_string()));
+        }
+        if let Some(tilecount) = tileset.tilecount {
+            attrs.push(("tilecount", tilecount.to_string()));
+        }
+        attrs.push(("columns", tileset.columns.to_string()));
+        if tileset.tile_render_size != TileRenderSize::default() {
+            attrs.push(("tilerendersize", tileset.tile_render_size.to_string()));
+        }
+        if tileset.fill_mode != FillMode::default() {
+            attrs.push(("fillmode", tileset.fill_mode.to_string()));
+        }
+        self.push_extra_attrs(&mut attrs, &tileset.extra);
+        self.start("tileset", &attrs)?;
+        self.place_extra(&tileset.extra);
+        self.write_properties(&tileset.properties)?;
+        for image in tileset.images.iter() {
+            self.write_image(image)?;
+        }
+        for tile in tileset.tiles.iter() {
+            self.write_tile(tile)?;
+        }
+        if !tileset.wang_sets.is_empty() {
+            self.start("wangsets", &[])?;
+            for wang_set in tileset.wang_sets.iter() {
+                self.write_wang_set(wang_set)?;
+            }
+            self.end()?;
+        }
+        self.end()
+    }
+
+    fn write_wang_set(&mut self, wang_set: &WangSet) -> Result<(), TiledError> {
+        self.start(
+            "wangset",
+            &[
+                ("name", wang_set.name.clone()),
+                ("type", wang_set.wang_type.clone()),
+                ("tile", wang_set.tile.to_string()),
+            ],
+        )?;
+        self.write_properties(&wang_set.properties)?;
+        for color in wang_set.colors.iter() {
+            self.start(
+                "wangcolor",
+                &[
+                    ("name", color.name.clone()),
+                    ("color", color.colour.to_string()),
+                    ("tile", color.tile.to_string()),
+                    ("probability", color.probability.to_string()),
+                ],
+            )?;
+            self.write_properties(&color.properties)?;
+            self.end()?;
+        }
+        for wang_tile in wang_set.wang_tiles.iter() {
+            let wang_id: Vec<String> = wang_tile.wang_id.iter().map(|c| c.to_string()).collect();
+            self.empty(
+                "wangtile",
+                &[
+                    ("tileid", wang_tile.tile_id.to_string()),
+                    ("wangid", wang_id.join(",")),
+                ],
+            )?;
+        }
+        self.end()
+    }
+
+    fn write_tile(&mut self, tile: &Tile) -> Result<(), TiledError> {
+        let mut attrs: Attributes = vec![("id", tile.id.to_string())];
+        if let Some(ref tile_type) = tile.tile_type {
+            attrs.push(("type", tile_type.clone()));
+        }
+        if tile.probability != 1.0 {
+            attrs.push(("probability", tile.probability.to_string()));
+        }
+        if let Some(rect) = tile.source_rect {
+            attrs.push(("x", rect.x.to_string()));
+            attrs.push(("y", rect.y.to_string()));
+            attrs.push(("width", rect.width.to_string()));
+            attrs.push(("height", rect.height.to_string()));
+        }
+        self.push_extra_attrs(&mut attrs, &tile.extra);
+        self.start("tile", &attrs)?;
+        self.place_extra(&tile.extra);
+        self.write_properties(&tile.properties)?;
+        for image in tile.images.iter() {
+            self.write_image(image)?;
+        }
+        if let Some(ref group) = tile.objectgroup {
+            self.write_layer(group)?;
+        }
+        if let Some(ref animation) = tile.animation {
+            self.write_animation(animation)?;
+        }
+        self.end()
+    }
+
+    fn write_animation(&mut self, animation: &[Frame]) -> Result<(), TiledError> {
+        self.start("animation", &[])?;
+        for frame in animation {
+            self.empty(
+                "frame",
+                &[
+                    ("tileid", frame.tile_id.to_string()),
+                    ("duration", frame.duration.to_string()),
+                ],
+            )?;
+        }
+        self.end()
+    }
+
+    fn write_image(&mut self, image: &Image) -> Result<(), TiledError> {
+        let mut attrs: Attributes = Vec::new();
+        if let Some(ref data) = image.data {
+            attrs.push(("format", data.format.clone()));
+        }
+        if !image.source.as_os_str().is_empty() || image.data.is_none() {
+            attrs.push(("source", image.source.to_string_lossy().into_owned()));
+        }
+        if let Some(colour) = image.transparent_colour {
+            attrs.push(("trans", colour.to_string()));
+        }
+        attrs.push(("width", image.width.to_string()));
+        attrs.push(("height", image.height.to_string()));
+        match image.data {
+            Some(ref data) => {
+                self.start("image", &attrs)?;
+                self.start("data", &[("encoding", "base64".to_string())])?;
+                self.text(&base64::encode(&data.bytes))?;
+                self.end()?;
+                self.end()
+            }
+            None => self.empty("image", &attrs),
+        }
+    }
+
+    fn write_layer(&mut self, layer: &Layer) -> Result<(), TiledError> {
+        let mut attrs: Attributes = Vec::new();
+        if layer.id != 0 {
+            attrs.push(("id", layer.id.to_string()));
+        }
+        attrs.push(("name", layer.name.clone()));
+        let tag = match layer.layer_type {
+            LayerType::TileLayer(ref tiles) => {
+                attrs.push(("width", tiles.width.to_string()));
+                attrs.push(("height", tiles.height.to_string()));
+                "layer"
+            }
+            LayerType::ObjectLayer(ref group) => {
+                if let Some(colour) = group.colour {
+                    attrs.push(("color", colour.to_string()));
+                }
+                if group.draw_order != DrawOrder::TopDown {
+                    attrs.push(("draworder", group.draw_order.to_string()));
+                }
+                "objectgroup"
+            }
+            LayerType::ImageLayer(_) => "imagelayer",
+            LayerType::Group(_) => "group",
+        };
+        push_common_layer_attrs(&mut attrs, layer);
+        self.push_extra_attrs(&mut attrs, &layer.extra);
+        self.start(tag, &attrs)?;
+        self.place_extra(&layer.extra);
+        self.write_properties(&layer.properties)?;
+        match layer.layer_type {
+            LayerType::TileLayer(ref tiles) => self.write_tile_data(tiles)?,
+            LayerType::ObjectLayer(ref group) => {
+                for object in group.objects.iter() {
+                    self.write_object(object)?;
+                }
+            }
+            LayerType::ImageLayer(ref image_layer) => {
+                if let Some(ref image) = image_layer.image {
+                    self.write_image(image)?;
+                }
+            }
+            LayerType::Group(ref layers) => {
+                for layer in layers.iter() {
+                    self.write_layer(layer)?;
+                }
+            }
+        }
+        self.end()
+    }
+
+    fn write_tile_data(&mut self, layer: &TileLayer) -> Result<(), TiledError> {
+        let encoding = self.options.data_encoding;
+        let codec = self.options.codec.clone();
+        let level = self.compression_level;
+        let encode = |rows: &[Vec<Option<LayerTile>>]| match codec {
+            Some(ref codec) => codec.encode(&tile_bytes(rows)),
+            None => encode_tiles(rows, encoding, level),
+        };
+        let mut attrs: Attributes = Vec::new();
+        match (&codec, encoding) {
+            (Some(codec), _) => {
+                attrs.push(("encoding", codec.encoding().to_string()));
+                if let Some(compression) = codec.compression() {
+                    attrs.push(("compression", compression.to_string()));
+                }
+            }
+            (None, DataEncoding::Csv) => attrs.push(("encoding", "csv".to_string())),
+            (None, DataEncoding::Base64(compression)) => {
+                attrs.push(("encoding", "base64".to_string()));
+                if let Some(compression) = compression {
+                    attrs.push(("compression", compression.to_string()));
+                }
+            }
+        }
+        self.start("data", &attrs)?;
+        match layer.tiles {
+            LayerData::Finite(ref rows) => self.text(&encode(rows)?)?,
+            LayerData::Infinite(ref chunks) => {
+                let mut chunks: Vec<&Chunk> = chunks.values().collect();
+                chunks.sort_by_key(|c| (c.y, c.x));
+                for chunk in chunks {
+                    self.start(
+                        "chunk",
+                        &[
+                            ("x", chunk.x.to_string()),
+                            ("y", chunk.y.to_string()),
+                            ("width", chunk.width.to_string()),
+                            ("height", chunk.height.to_string()),
+                        ],
+                    )?;
+                    self.text(&encode(&chunk.tiles)?)?;
+                    self.end()?;
+                }
+            }
+        }
+        self.end()
+    }
+
+    /// Writes the collision shapes of a tile.
+    fn write_object(&mut self, object: &Object) -> Result<(), TiledError> {
+        let mut attrs: Attributes = Vec::new();
+        if object.id != 0 {
+            attrs.push(("id", object.id.to_string()));
+        }
+        if !object.name.is_empty() {
+            attrs.push(("name", object.name.clone()));
+        }
+        if !object.obj_type.is_empty() {
+            attrs.push(("type", object.obj_type.clone()));
+        }
+        if !object.gid.is_empty() {
+            attrs.push(("gid", object.gid.0.to_string()));
+        }
+        attrs.push(("x", object.x.to_string()));
+        attrs.push(("y", object.y.to_string()));
+        if object.width != 0.0 {
+            attrs.push(("width", object.width.to_string()));
+        }
+        if object.height != 0.0 {
+            attrs.push(("height", object.height.to_string()));
+        }
+        if object.rotation != 0.0 {
+            attrs.push(("rotation", object.rotation.to_string()));
+        }
+        if !object.visible {
+            attrs.push(("visible", "0".to_string()));
+        }
+        if object.locked {
+            attrs.push(("locked", "1".to_string()));
+        }
+        self.push_extra_attrs(&mut attrs, &object.extra);
+        self.start("object", &attrs)?;
+        self.place_extra(&object.extra);
+        self.write_properties(&object.properties)?;
+        match object.shape {
+            ObjectShape::Rect { .. } => {}
+            ObjectShape::Ellipse { .. } => self.empty("ellipse", &[])?,
+            ObjectShape::Point(..) => self.empty("point", &[])?,
+            ObjectShape::Polyline { ref points } => {
+                self.empty("polyline", &[("points", encode_points(points))])?
+            }
+            ObjectShape::Polygon { ref points } => {
+                self.empty("polygon", &[("points", encode_points(points))])?
+            }
+            ObjectShape::Text(ref text) => self.write_text(text)?,
+        }
+        self.end()
+    }
+
+    fn write_text(&mut self, text: &Text) -> Result<(), TiledError> {
+        let mut attrs: Attributes = Vec::new();
+        if text.font_family != "sans-serif" {
+            attrs.push(("fontfamily", text.font_family.clone()));
+        }
+        if text.pixel_size != 16 {
+            attrs.push(("pixelsize", text.pixel_size.to_string()));
+        }
+        let flags = [
+            ("wrap", text.wrap, false),
+            ("bold", text.bold, false),
+            ("italic", text.italic, false),
+            ("underline", text.underline, false),
+            ("strikeout", text.strikeout, false),
+            ("kerning", text.kerning, true),
+        ];
+        for &(name, value, default) in flags.iter() {
+            if value != default {
+                attrs.push((name, bool_attr(value)));
+            }
+        }
+        if text.colour != Colour::new(0, 0, 0) {
+            attrs.push(("color", text.colour.to_string()));
+        }
+        if text.halign != HorizontalAlignment::Left {
+            attrs.push(("halign", text.halign.to_string()));
+        }
+        if text.valign != VerticalAlignment::Top {
+            attrs.push(("valign", text.valign.to_string()));
+        }
+        self.start("text", &attrs)?;
+        self.text(&text.contents)?;
+        self.end()
+    }
+
+    fn write_properties(&mut self, properties: &Properties) -> Result<(), TiledError> {
+        if properties.is_empty() {
+            return Ok(());
+        }
+        // Sort the properties so the output is stable between runs.
+        let mut properties: Vec<(&String, &PropertyValue)> = properties.iter().collect();
+        properties.sort_by(|a, b| a.0.cmp(b.0));
+        self.start("properties", &[])?;
+        for (name, value) in properties {
+            let mut attrs: Attributes = vec![("name", name.clone())];
+            if let PropertyValue::ClassValue {
+                ref type_name,
+                ref properties,
+            } = *value
+            {
+                attrs.push(("type", "class".to_string()));
+                attrs.push(("propertytype", type_name.clone()));
+                self.start("property", &attrs)?;
+                self.write_properties(properties)?;
+                self.end()?;
+                continue;
+            }
+            let (property_type, text) = property_attrs(value);
+            if property_type != "string" {
+                attrs.push(("type", property_type.to_string()));
+            }
+            if let PropertyValue::EnumValue { ref type_name, .. } = *value {
+                attrs.push(("propertytype", type_name.clone()));
+            }
+            attrs.push(("value", text));
+            self.empty("property", &attrs)?;
+        }
+        self.end()
+    }
+}
+
+fn bool_attr(value: bool) -> String {
+    if value { "1" } else { "0" }.to_string()
+}
+
+/// Returns whether the layer, or a layer within it, is an infinite tile
+/// layer.
+fn has_infinite_tiles(layer: &Layer) -> bool {
+    match layer.layer_type {
+        LayerType::TileLayer(ref tiles) => matches!(tiles.tiles, LayerData::Infinite(_)),
+        LayerType::Group(ref layers) => layers.iter().any(has_infinite_tiles),
+        _ => false,
+    }
+}
+
+fn push_common_layer_attrs(attrs: &mut Attributes, layer: &Layer) {
+    if let Some(ref user_type) = layer.user_type {
+        attrs.push(("class", user_type.clone()));
+    }
+    if layer.opacity != 1.0 {
+        attrs.push(("opacity", layer.opacity.to_string()));
+    }
+    if !layer.visible {
+        attrs.push(("visible", "0".to_string()));
+    }
+    if layer.locked {
+        attrs.push(("locked", "1".to_string()));
+    }
+    if layer.offset_x != 0.0 {
+        attrs.push(("offsetx", layer.offset_x.to_string()));
+    }
+    if layer.offset_y != 0.0 {
+        attrs.push(("offsety", layer.offset_y.to_string()));
+    }
+    if let Some(colour) = layer.tint_colour {
+        attrs.push(("tintcolor", colour.to_string()));
+    }
+}
+
+fn property_attrs(value: &PropertyValue) -> (&'static str, String) {
+    match *value {
+        PropertyValue::BoolValue(v) => ("bool", v.to_string()),
+        PropertyValue::FloatValue(v) => ("float", v.to_string()),
+        PropertyValue::IntValue(v) => ("int", v.to_string()),
+        PropertyValue::ColorValue(v) => ("color", v.to_string()),
+        PropertyValue::StringValue(ref v) => ("string", v.clone()),
+        PropertyValue::FileValue(ref v) => ("file", v.to_string_lossy().into_owned()),
+        PropertyValue::ObjectValue(v) => ("object", v.to_string()),
+        PropertyValue::EnumValue { ref value, .. } => property_attrs(value),
+        // Written with their members as nested properties instead.
+        PropertyValue::ClassValue { .. } => ("class", String::new()),
+    }
+}
+
+fn encode_points(points: &[(f32, f32)]) -> String {
+    points
+        .iter()
+        .map(|(x, y)| format!("{},{}", x, y))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn encode_tiles(
+    rows: &[Vec<Option<LayerTile>>],
+    encoding: DataEncoding,
+    level: Option<i32>,
+) -> Result<String, TiledError> {
+    let compression = match encoding {
+        DataEncoding::Csv => return Ok(encode_csv(rows)),
+        DataEncoding::Base64(compression) => compression,
+    };
+    let data = tile_bytes(rows);
+    let data = match compression {
+        None => data,
+        Some(compression) => compress(&data, compression, level)
+            .map_err(|e| TiledError::Other(format!("Could not compress layer data: {}", e)))?,
+    };
+    Ok(base64::encode(&data))
+}
+
+/// Returns the little-endian bytes of the tiles, as stored in base64 data.
+fn tile_bytes(rows: &[Vec<Option<LayerTile>>]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(rows.iter().map(Vec::len).sum::<usize>() * 4);
+    for tile in rows.iter().flatten() {
+        data.extend_from_slice(&cell_bits(*tile).to_le_bytes());
+    }
+    data
+}
+
+/// Compresses data at the given level, where `None` or -1 stand for the
+/// default level of the compression, as in the `compressionlevel` attribute
+/// of maps. Zlib and gzip only distinguish level 0, which stores the data
+/// uncompressed, from the others unless the `flate2` feature is enabled, in
+/// which case they take levels from 0 to 9. Zstd takes levels from 1 to 22.
+pub(crate) fn compress(
+    data: &[u8],
+    compression: Compression,
+    level: Option<i32>,
+) -> io::Result<Vec<u8>> {
+    let level = level.filter(|&level| level != -1);
+    match compression {
+        #[cfg(feature = "flate2")]
+        Compression::Zlib => {
+            let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2_level(level));
+            encoder.write_all(data)?;
+            encoder.finish()
+        }
+        #[cfg(feature = "flate2")]
+        Compression::Gzip => {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2_level(level));
+            encoder.write_all(data)?;
+            encoder.finish()
+        }
+        #[cfg(not(feature = "flate2"))]
+        Compression::Zlib => {
+            let mut options = libflate::zlib::EncodeOptions::new();
+            if level == Some(0) {
+                options = options.no_compression();
+            }
+            let mut encoder = libflate::zlib::Encoder::with_options(Vec::new(), options)?;
+            encoder.write_all(data)?;
+            encoder.finish().into_result()
+        }
+        #[cfg(not(feature = "flate2"))]
+        Compression::Gzip => {
+            let mut options = libflate::gzip::EncodeOptions::new();
+            if level == Some(0) {
+                options = options.no_compression();
+            }
+            let mut encoder = libflate::gzip::Encoder::with_options(Vec::new(), options)?;
+            encoder.write_all(data)?;
+            encoder.finish().into_result()
+        }
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => zstd::stream::encode_all(data, level.unwrap_or(0)),
+    }
+}
+
+#[cfg(feature = "flate2")]
+fn flate2_level(level: Option<i32>) -> flate2::Compression {
+    match level {
+        Some(level) => flate2::Compression::new(level.clamp(0, 9) as u32),
+        None => flate2::Compression::default(),
+    }
+}
+
+fn encode_csv(rows: &[Vec<Option<LayerTile>>]) -> String {
+    let rows: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|&tile| cell_bits(tile).to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect();
+    format!("\n{}\n", rows.join(",\n"))
+}
+
+/// Writes a single layer element of a map.
+pub(crate) fn layer_fragment(
+    layer: &Layer,
+    options: &WriteOptions,
+    compression_level: Option<i32>,
+) -> Result<Vec<u8>, TiledError> {
+    let mut out = Vec::new();
+    let mut writer = TmxWriter::fragment(&mut out, options.clone());
+    writer.compression_level = options.compression_level.or(compression_level);
+    writer.write_layer(layer)?;
+    Ok(out)
+}
+
+/// Writes a map without its layers, for comparing everything else.
+pub(crate) fn map_without_layers(map: &Map) -> Result<Vec<u8>, TiledError> {
+    let mut out = Vec::new();
+    let mut writer = TmxWriter::new(&mut out, WriteOptions::default());
+    writer.skip_layers = true;
+    writer.write_map(map)?;
+    Ok(out)
+}
+
+/// Writes a tileset element as embedded in a map, including its `first_gid`.
+pub(crate) fn tileset_fragment(tileset: &Tileset) -> Result<Vec<u8>, TiledError> {
+    let mut out = Vec::new();
+    TmxWriter::fragment(&mut out, WriteOptions::default()).write_tileset(tileset, true)?;
+    Ok(out)
+}
+
+impl Map {
+    /// Writes the map out as a TMX document which can be opened by the Tiled
+    /// editor. Loaded tilesets are always embedded in the map, unresolved
+    /// ones are referenced by their source, and layer data is written in the
+    /// CSV encoding.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<(), TiledError> {
+        self.write_with_options(writer, &WriteOptions::default())
+    }
+
+    /// Writes the map out as a TMX document, encoding layer data as set in
+    /// `options`.
+    pub fn write_with_options<W: Write>(
+        &self,
+        writer: W,
+        options: &WriteOptions,
+    ) -> Result<(), TiledError> {
+        if options.checksum {
+            return write_with_checksum(self, writer, options);
+        }
+        TmxWriter::new(writer, options.clone()).write_map(self)
+    }
+}
+
+impl MapPatch {
+    /// Writes the patch out as an XML document, with layers, objects and
+    /// properties written as in TMX documents.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<(), TiledError> {
+        TmxWriter::new(writer, WriteOptions::default()).write_patch(self)
+    }
+}
+
+impl Tileset {
+    /// Writes the tileset out as a standalone TSX document. The `first_gid` is
+    /// not written since it belongs to the map referencing the tileset.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<(), TiledError> {
+        TmxWriter::new(writer, WriteOptions::default()).write_tileset(self, false)
+    }
+}
@@ -1,6 +1,34 @@
+// The original tests predate these lints.
+#![allow(
+    clippy::assertions_on_constants,
+    clippy::expect_fun_call,
+    clippy::needless_borrow,
+    clippy::needless_borrowed_reference,
+    clippy::needless_return
+)]
+
+use std::collections::HashMap;
 use std::fs::File;
-use std::path::Path;
-use tiled::{parse, parse_file, parse_tileset, Map, PropertyValue, TiledError, LayerData};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "async")]
+use tiled::{ResourceFuture, ResourceReader};
+use tiled::{
+    assert_maps_equivalent, check_compatibility, parse, parse_bytes, parse_file,
+    parse_object_types_file, parse_project_file, parse_str, parse_tileset, parse_world_file,
+    simplify_polygon, Brush, Chunk, Colour, CompatibilityWarning, Compression, CompressionCodec,
+    ConflictPolicy, DataEncoding, DrawOrder, EnumStorage, ExportProfile, FillMode, FrameReceiver,
+    Gid, HorizontalAlignment, ImageRect, Layer, LayerChange, LayerData, LayerKind, LayerTile,
+    LayerType, Loader, Map, MapFrame, MapPatch, MapPoint, MapRect, MaterialTable, Object,
+    ObjectEdit, ObjectGroup, ObjectShape, Orientation, PatchStack, Properties, PropertyOwner,
+    PropertyType, PropertyUnits, PropertyValue, Rng, RuntimeLayerKind, RuntimeMap, RuntimeOptions,
+    SavedLayers, Scatter, StaggerAxis, StaggerIndex, StringTable, TileCollision, TileDataCodec,
+    TileDelta, TileLayer, TilePoint, TileRect, TileRenderSize, TileSize, TiledError, Tileset,
+    TilesetOverlap, TilesetRegistry, TilesetSource, TracedShape, Unit, VerticalAlignment,
+    WriteOptions, XmlElement, XmlNode, MAX_PENDING_FRAMES,
+};
 
 fn read_from_file(p: &Path) -> Result<Map, TiledError> {
     let file = File::open(p).unwrap();
@@ -11,6 +39,16 @@ fn read_from_file_with_path(p: &Path) -> Result<Map, TiledError> {
     return parse_file(p);
 }
 
+/// Forgets where the map and its tilesets were loaded from, to compare maps
+/// loaded from different places.
+fn without_sources(mut map: Map) -> Map {
+    map.source = None;
+    for tileset in map.tilesets.iter_mut() {
+        Arc::make_mut(tileset).source = None;
+    }
+    map
+}
+
 #[test]
 fn test_gzip_and_zlib_encoded_and_raw_are_the_same() {
     let z = read_from_file(&Path::new("assets/tiled_base64_zlib.tmx")).unwrap();
@@ -21,15 +59,15 @@ fn test_gzip_and_zlib_encoded_and_raw_are_the_same() {
     assert_eq!(z, r);
     assert_eq!(z, c);
     
-    if let LayerData::Finite(tiles) = &c.layers[0].tiles {
+    if let LayerData::Finite(tiles) = &c.layers[0].as_tile_layer().unwrap().tiles {
         assert_eq!(tiles.len(), 100);
         assert_eq!(tiles[0].len(), 100);
         assert_eq!(tiles[99].len(), 100);
-        assert_eq!(tiles[0][0].gid, 35);
-        assert_eq!(tiles[1][0].gid, 17);
-        assert_eq!(tiles[2][0].gid, 0);
-        assert_eq!(tiles[2][1].gid, 17);
-        assert!(tiles[99].iter().map(|t| t.gid).all(|g| g == 0));
+        assert_eq!(tiles[0][0].unwrap().gid, Gid(35));
+        assert_eq!(tiles[1][0].unwrap().gid, Gid(17));
+        assert_eq!(tiles[2][0], None);
+        assert_eq!(tiles[2][1].unwrap().gid, Gid(17));
+        assert!(tiles[99].iter().all(Option::is_none));
     } else {
         assert!(false, "It is wrongly recognised as an infinite map");
     }
@@ -39,21 +77,21 @@ fn test_gzip_and_zlib_encoded_and_raw_are_the_same() {
 fn test_external_tileset() {
     let r = read_from_file(&Path::new("assets/tiled_base64.tmx")).unwrap();
     let e = read_from_file_with_path(&Path::new("assets/tiled_base64_external.tmx")).unwrap();
-    assert_eq!(r, e);
+    assert_eq!(without_sources(r), without_sources(e));
 }
 
 #[test]
 fn test_just_tileset() {
     let r = read_from_file(&Path::new("assets/tiled_base64.tmx")).unwrap();
     let t = parse_tileset(File::open(Path::new("assets/tilesheet.tsx")).unwrap(), 1).unwrap();
-    assert_eq!(r.tilesets[0], t);
+    assert_eq!(*r.tilesets[0], t);
 }
 
 #[test]
 fn test_infinite_tileset() {
     let r = read_from_file_with_path(&Path::new("assets/tiled_base64_zlib_infinite.tmx")).unwrap();    
 
-    if let LayerData::Infinite(chunks) = &r.layers[0].tiles {
+    if let LayerData::Infinite(chunks) = &r.layers[0].as_tile_layer().unwrap().tiles {
         assert_eq!(chunks.len(), 4);
 
         assert_eq!(chunks[&(0, 0)].width, 32);
@@ -70,27 +108,40 @@ fn test_infinite_tileset() {
 #[test]
 fn test_image_layers() {
     let r = read_from_file(&Path::new("assets/tiled_image_layers.tmx")).unwrap();
-    assert_eq!(r.image_layers.len(), 2);
+    assert_eq!(r.layers.len(), 2);
     {
-        let first = &r.image_layers[0];
+        let first = &r.layers[0];
         assert_eq!(first.name, "Image Layer 1");
         assert!(
-            first.image.is_none(),
+            first.as_image_layer().unwrap().image.is_none(),
             "{}'s image should be None",
             first.name
         );
     }
     {
-        let second = &r.image_layers[1];
+        let second = &r.layers[1];
         assert_eq!(second.name, "Image Layer 2");
         let image = second
+            .as_image_layer()
+            .unwrap()
             .image
             .as_ref()
             .expect(&format!("{}'s image shouldn't be None", second.name));
-        assert_eq!(image.source, "tilesheet.png");
+        assert_eq!(image.source, Path::new("tilesheet.png"));
         assert_eq!(image.width, 448);
         assert_eq!(image.height, 192);
+        assert_eq!(
+            second.tint_colour,
+            Some(Colour {
+                red: 255,
+                green: 0,
+                blue: 0,
+                alpha: 128
+            })
+        );
+        assert_eq!(second.tint_colour.unwrap().to_string(), "#80ff0000");
     }
+    assert_eq!(r.layers[0].tint_colour, None);
 }
 
 #[test]
@@ -109,7 +160,9 @@ fn test_tile_property() {
 #[test]
 fn test_object_group_property() {
     let r = read_from_file(&Path::new("assets/tiled_object_groups.tmx")).unwrap();
-    let prop_value: bool = if let Some(&PropertyValue::BoolValue(ref v)) = r.object_groups[0]
+    let prop_value: bool = if let Some(&PropertyValue::BoolValue(ref v)) = r.layers[1]
+        .as_group_layer()
+        .unwrap()[0]
         .properties
         .get("an object group property")
     {
@@ -136,11 +189,11 @@ fn test_tileset_property() {
 fn test_flipped_gid() {
     let r = read_from_file_with_path(&Path::new("assets/tiled_flipped.tmx")).unwrap();
     
-    if let LayerData::Finite(tiles) = &r.layers[0].tiles {
-        let t1 = tiles[0][0];
-        let t2 = tiles[0][1];
-        let t3 = tiles[1][0];
-        let t4 = tiles[1][1];
+    if let LayerData::Finite(tiles) = &r.layers[0].as_tile_layer().unwrap().tiles {
+        let t1 = tiles[0][0].unwrap();
+        let t2 = tiles[0][1].unwrap();
+        let t3 = tiles[1][0].unwrap();
+        let t4 = tiles[1][1].unwrap();
         assert_eq!(t1.gid, t2.gid);
         assert_eq!(t2.gid, t3.gid);
         assert_eq!(t3.gid, t4.gid);
@@ -165,12 +218,3661 @@ fn test_flipped_gid() {
 #[test]
 fn test_ldk_export() {
     let r = read_from_file_with_path(&Path::new("assets/ldk_tiled_export.tmx")).unwrap();
-    if let LayerData::Finite(tiles) = &r.layers[0].tiles {
+    if let LayerData::Finite(tiles) = &r.layers[0].as_tile_layer().unwrap().tiles {
         assert_eq!(tiles.len(), 8);
         assert_eq!(tiles[0].len(), 8);
-        assert_eq!(tiles[0][0].gid, 0);
-        assert_eq!(tiles[1][0].gid, 1);
+        assert_eq!(tiles[0][0], None);
+        assert_eq!(tiles[1][0].unwrap().gid, Gid(1));
     } else {
         assert!(false, "It is wrongly recognised as an infinite map");
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_write_round_trip() {
+    for path in [
+        "assets/tiled_base64.tmx",
+        "assets/tiled_base64_zlib_infinite.tmx",
+        "assets/tiled_image_layers.tmx",
+        "assets/tiled_object_groups.tmx",
+        "assets/tiled_flipped.tmx",
+        "assets/tiled_text.tmx",
+        "assets/tiled_group.tmx",
+    ] {
+        let r = read_from_file_with_path(Path::new(path)).unwrap();
+        let mut out = Vec::new();
+        r.write_to(&mut out).unwrap();
+        let w = parse(&out[..]).unwrap();
+        assert_eq!(without_sources(r), w, "{} changed after being written", path);
+    }
+}
+
+#[test]
+fn test_write_tileset() {
+    let t = parse_tileset(File::open(Path::new("assets/tilesheet.tsx")).unwrap(), 1).unwrap();
+    let mut out = Vec::new();
+    t.write_to(&mut out).unwrap();
+    assert_eq!(t, parse_tileset(&out[..], 1).unwrap());
+}
+
+#[test]
+fn test_write_json() {
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    let mut out = Vec::new();
+    r.write_json_to(&mut out).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(json["type"], "map");
+    assert_eq!(json["width"], 100);
+    assert_eq!(json["backgroundcolor"], "#ff00ff");
+    assert_eq!(json["tilesets"][0]["firstgid"], 1);
+    assert_eq!(json["tilesets"][0]["image"], "tilesheet.png");
+    assert_eq!(json["tilesets"][0]["tiles"][0]["properties"][0]["value"], "123");
+    let layer = &json["layers"][0];
+    assert_eq!(layer["type"], "tilelayer");
+    assert_eq!(layer["data"].as_array().unwrap().len(), 100 * 100);
+    assert_eq!(layer["data"][0], 35);
+
+    let r = read_from_file(Path::new("assets/tiled_group.tmx")).unwrap();
+    let mut out = Vec::new();
+    r.write_json_to(&mut out).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    let group = &json["layers"][1];
+    assert_eq!(group["type"], "group");
+    assert_eq!(group["id"], 2);
+    assert_eq!(group["opacity"], 0.5);
+    assert_eq!(group["properties"][0]["value"], 2);
+    assert_eq!(group["layers"][1]["layers"][0]["name"], "Secret");
+}
+
+#[test]
+fn test_wang_color_properties() {
+    let t = parse_tileset(File::open(Path::new("assets/tilesheet_wang.tsx")).unwrap(), 1).unwrap();
+    assert_eq!(t.wang_sets.len(), 1);
+    assert_eq!(t.wang_sets[0].colors.len(), 2);
+    assert_eq!(t.wang_sets[0].dominant_color(2).unwrap().name, "Water");
+    assert!(t.wang_sets[0].dominant_color(3).is_none());
+
+    let grass = t.tile_properties_with_wang(0);
+    assert_eq!(grass["terrain"], PropertyValue::StringValue("grass".to_string()));
+    assert_eq!(grass["walkable"], PropertyValue::BoolValue(true));
+    // The tile's own properties win over the ones inherited from its color.
+    let edge = t.tile_properties_with_wang(1);
+    assert_eq!(edge["terrain"], PropertyValue::StringValue("grass".to_string()));
+    assert_eq!(edge["walkable"], PropertyValue::BoolValue(false));
+    assert!(t.tile_properties_with_wang(10).is_empty());
+
+    let mut out = Vec::new();
+    t.write_to(&mut out).unwrap();
+    assert_eq!(t, parse_tileset(&out[..], 1).unwrap());
+}
+
+#[test]
+fn test_insert_and_delete_rows_and_cols() {
+    let mut r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    r.insert_rows(1, 2).unwrap();
+    assert_eq!(r.height, 12);
+    let objects = &r.layers[1].as_object_layer().unwrap().objects;
+    assert_eq!((objects[0].x, objects[0].y), (48.0, 112.0));
+    assert_eq!(objects[0].shape, ObjectShape::Point(48.0, 112.0));
+    assert_eq!(objects[4].y, 352.0);
+    if let LayerData::Finite(tiles) = &r.layers[0].as_tile_layer().unwrap().tiles {
+        assert_eq!(tiles.len(), 12);
+        assert_eq!(tiles[1][1], None);
+        assert_eq!(tiles[3][1].unwrap().gid, Gid(2));
+    } else {
+        panic!("It is wrongly recognised as an infinite map");
+    }
+
+    r.delete_cols(0, 2).unwrap();
+    assert_eq!(r.width, 8);
+    let objects = &r.layers[1].as_object_layer().unwrap().objects;
+    assert_eq!(objects.len(), 3, "objects in the deleted columns are removed");
+    assert_eq!(objects[0].name, "room");
+    assert_eq!(objects[0].x, 0.0);
+    if let LayerData::Finite(tiles) = &r.layers[0].as_tile_layer().unwrap().tiles {
+        assert!(tiles.iter().all(|row| row.len() == 8));
+        assert_eq!(tiles[3][0].unwrap().gid, Gid(2));
+    }
+
+    r.delete_rows(1, 2).unwrap();
+    r.insert_cols(0, 2).unwrap();
+    assert!(r.delete_rows(9, 2).is_err());
+    assert!(r.insert_cols(11, 1).is_err());
+    if let LayerData::Finite(tiles) = &r.layers[0].as_tile_layer().unwrap().tiles {
+        assert_eq!(tiles.len(), 10);
+        assert_eq!(tiles[1][3].unwrap().gid, Gid(2));
+    }
+}
+
+#[test]
+fn test_failed_structural_edit_leaves_map_unchanged() {
+    let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.4" orientation="orthogonal" width="3" height="3" tilewidth="16" tileheight="16">
+ <layer name="full" width="3" height="3">
+  <data encoding="csv">1,1,1,1,1,1,1,1,1</data>
+ </layer>
+ <layer name="small" width="2" height="2">
+  <data encoding="csv">1,1,1,1</data>
+ </layer>
+</map>"#;
+    let mut r = parse_str(text).unwrap();
+    let original = r.clone();
+    assert!(r.delete_rows(1, 2).is_err());
+    assert!(r.delete_cols(1, 2).is_err());
+    assert!(r.insert_rows(3, 1).is_err());
+    assert_eq!(r, original);
+
+    assert!(r.insert_rows(u32::MAX, u32::MAX).is_err());
+    assert!(r.delete_cols(u32::MAX, u32::MAX).is_err());
+    assert!(r.insert_cols(0, u32::MAX).is_err());
+    assert_eq!(r, original);
+}
+
+#[test]
+fn test_fill_rect_and_flood_fill() {
+    let mut r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let layer = r.layers[0].as_tile_layer_mut().unwrap();
+    layer.flood_fill(0, 0, LayerTile::new(7));
+    assert_eq!(layer.get_tile(9, 7).unwrap().gid, Gid(7));
+    assert_eq!(layer.get_tile(1, 1).unwrap().gid, Gid(2));
+    assert_eq!(layer.get_tile(5, 4).unwrap().gid, Gid(3));
+    assert_eq!(layer.get_tile(9, 9).unwrap().gid, Gid(4));
+
+    layer.fill_rect(TileRect::new(8, 8, 5, 5), LayerTile::new(9));
+    assert_eq!(layer.get_tile(8, 8).unwrap().gid, Gid(9));
+    assert_eq!(layer.get_tile(9, 9).unwrap().gid, Gid(9));
+    assert_eq!(layer.get_tile(7, 7).unwrap().gid, Gid(7));
+    assert!(layer.get_tile(10, 10).is_none());
+
+    let mut r = read_from_file_with_path(Path::new("assets/tiled_base64_zlib_infinite.tmx")).unwrap();
+    let layer = r.layers[0].as_tile_layer_mut().unwrap();
+    layer.fill_rect(TileRect::new(-40, 60, 10, 10), LayerTile::new(3));
+    assert_eq!(layer.get_tile(-40, 60).unwrap().gid, Gid(3));
+    assert_eq!(layer.get_tile(-31, 69).unwrap().gid, Gid(3));
+    assert_eq!(layer.get_tile(-30, 69), None);
+    if let LayerData::Infinite(chunks) = &layer.tiles {
+        assert_eq!(chunks.len(), 7);
+        assert_eq!(chunks[&(-64, 32)].width, 32);
+    }
+}
+
+#[test]
+fn test_layer_order() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let names: Vec<&str> = r.layers.iter().map(|l| l.name.as_str()).collect();
+    assert_eq!(names, ["Ground", "Objects"]);
+    assert!(matches!(r.layers[0].layer_type, LayerType::TileLayer(_)));
+    assert!(matches!(r.layers[1].layer_type, LayerType::ObjectLayer(_)));
+}
+
+#[test]
+fn test_brush_stamp() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let source = r.layers[0].as_tile_layer().unwrap();
+    let brush = Brush::from_layer(source, TileRect::new(4, 4, 3, 2));
+    assert_eq!(brush.get(0, 0).unwrap().gid, Gid(1));
+    assert_eq!(brush.get(2, 1).unwrap().gid, Gid(3));
+
+    let rotated = brush.rotated_clockwise();
+    assert_eq!((rotated.width, rotated.height), (2, 3));
+    assert_eq!(rotated.get(0, 0).unwrap().gid, Gid(1));
+    assert_eq!(rotated.get(1, 1).unwrap().gid, Gid(3));
+    let tile = rotated.get(0, 1).unwrap();
+    assert!(tile.flip_h && !tile.flip_v && tile.flip_d);
+    assert_eq!(rotated.rotated_counterclockwise(), brush);
+    assert_eq!(brush.flipped_horizontally().flipped_horizontally(), brush);
+    assert!(brush.flipped_vertically().get(1, 0).unwrap().flip_v);
+
+    let sparse = Brush::new(2, 1, vec![Some(LayerTile::new(8)), None]).unwrap();
+    let mut target = r.layers[0].as_tile_layer().unwrap().clone();
+    target.stamp((9, 0), &sparse);
+    target.stamp((0, 9), &brush.flipped_horizontally());
+    assert_eq!(target.get_tile(9, 0).unwrap().gid, Gid(8));
+    assert_eq!(target.get_tile(1, 9).unwrap().gid, Gid(3));
+    assert_eq!(target.get_tile(2, 9).unwrap().gid, Gid(1));
+    assert!(target.get_tile(2, 9).unwrap().flip_h);
+}
+
+#[test]
+fn test_scatter() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let scatter = Scatter::new(&r, &[11, 12, 13], 0.5);
+    assert_eq!(scatter.candidates, vec![(11, 0.0), (12, 3.0), (13, 1.0)]);
+
+    let ground = r.layers[0].as_tile_layer().unwrap().clone();
+    let mask = |x: i32, y: i32| ground.get_tile(x, y).is_some_and(|t| t.gid == Gid(1));
+    let area = TileRect::new(0, 0, 10, 10);
+    let placements = scatter.placements(area, &mut Rng::new(7), mask);
+    assert_eq!(placements, scatter.placements(area, &mut Rng::new(7), mask));
+    assert_ne!(placements, scatter.placements(area, &mut Rng::new(8), mask));
+    assert!(!placements.is_empty() && placements.len() < 84);
+    assert!(placements.iter().all(|&(x, y, gid)| mask(x, y) && gid != 11));
+
+    let mut layer = ground.clone();
+    scatter.scatter_tiles(&mut layer, area, &mut Rng::new(7), mask);
+    for &(x, y, gid) in placements.iter() {
+        assert_eq!(layer.get_tile(x, y).unwrap().gid.value(), gid);
+    }
+
+    let mut map = r.clone();
+    scatter
+        .scatter_objects(&mut map, 1, area, &mut Rng::new(7), mask)
+        .unwrap();
+    let scattered = &map.layers[1].as_object_layer().unwrap().objects[6..];
+    assert_eq!(scattered.len(), placements.len());
+    assert_eq!(scattered[0].id, 7);
+    assert_eq!(scattered[0].x, placements[0].0 as f32 * 32.0);
+    assert_eq!(scattered[0].y, (placements[0].1 + 1) as f32 * 32.0);
+    assert_eq!(map.next_object_id, 7 + placements.len() as u32);
+    assert!(scatter
+        .scatter_objects(&mut map, 0, area, &mut Rng::new(7), mask)
+        .is_err());
+}
+
+#[test]
+fn test_world_explicit_maps() {
+    let world = parse_world_file(Path::new("assets/world/explicit.world"), true).unwrap();
+    assert!(!world.only_show_adjacent_maps);
+    assert_eq!(world.maps.len(), 2);
+    assert_eq!(world.maps[1].file_name, "../tiled_objects.tmx");
+    assert_eq!((world.maps[1].x, world.maps[1].y), (-320, 0));
+    assert_eq!(world.maps[1].width, Some(320));
+    let map = world.maps[1].map.as_ref().unwrap();
+    assert_eq!(map.layers[1].name, "Objects");
+}
+
+#[test]
+fn test_world_patterns() {
+    let world = parse_world_file(Path::new("assets/world/pattern.world"), false).unwrap();
+    assert!(world.only_show_adjacent_maps);
+    let placements: Vec<_> = world
+        .maps
+        .iter()
+        .map(|m| (m.file_name.as_str(), m.x, m.y))
+        .collect();
+    assert_eq!(
+        placements,
+        vec![
+            ("chunk_0_0.tmx", 0, 0),
+            ("chunk_0_1.tmx", 0, 320),
+            ("chunk_1_0.tmx", 320, 0),
+        ]
+    );
+    assert!(world.maps.iter().all(|m| m.map.is_none()));
+
+    let loaded = parse_world_file(Path::new("assets/world/pattern.world"), true).unwrap();
+    let map = loaded.maps[2].map.as_ref().unwrap();
+    let layer = map.layers[0].as_tile_layer().unwrap();
+    assert_eq!(layer.get_tile(0, 0).unwrap().gid, Gid(3));
+}
+
+#[test]
+fn test_from_heightmap() {
+    let heights = vec![
+        vec![0.1, 0.2, 0.3, 0.1],
+        vec![0.4, 0.1, 0.2, 0.3],
+        vec![0.6, 0.8, 0.9, 0.7],
+        vec![0.9, 0.7, 0.6, -1.0],
+    ];
+    let thresholds = [(0.5, Gid(16)), (0.0, Gid(1))];
+    let layer = Layer::from_heightmap("Terrain", &heights, &thresholds, None).unwrap();
+    assert_eq!(layer.name, "Terrain");
+    let gids = |layer: &Layer| -> Vec<Vec<u32>> {
+        match layer.as_tile_layer().unwrap().tiles {
+            LayerData::Finite(ref rows) => rows
+                .iter()
+                .map(|row| row.iter().map(|t| t.map_or(0, |t| t.gid.value())).collect())
+                .collect(),
+            _ => panic!("expected a finite layer"),
+        }
+    };
+    assert_eq!(gids(&layer)[2], vec![16, 16, 16, 16]);
+    assert_eq!(gids(&layer)[3], vec![16, 16, 16, 0]);
+
+    let file = File::open(Path::new("assets/tilesheet_autotile.tsx")).unwrap();
+    let tileset = parse_tileset(file, 1).unwrap();
+    let wang_set = &tileset.wang_sets[0];
+    let wang_set = Some((&tileset, wang_set));
+    let layer = Layer::from_heightmap("Terrain", &heights, &thresholds, wang_set).unwrap();
+    let gids = gids(&layer);
+    assert_eq!(gids[1], vec![1, 1, 1, 1]);
+    // Grass claims the top corners of the water row next to it.
+    assert_eq!(gids[2], vec![7, 7, 7, 7]);
+    assert_eq!(gids[3], vec![16, 16, 16, 0]);
+
+    let ragged = vec![vec![0.1, 0.2], vec![0.3], vec![0.4, 0.5, 0.6]];
+    assert!(Layer::from_heightmap("Terrain", &ragged, &thresholds, None).is_err());
+}
+
+#[test]
+fn test_text_objects() {
+    let r = read_from_file(Path::new("assets/tiled_text.tmx")).unwrap();
+    let objects = &r.layers[0].as_object_layer().unwrap().objects;
+    let title = match objects[0].shape {
+        ObjectShape::Text(ref text) => text,
+        ref shape => panic!("expected a text object, got {:?}", shape),
+    };
+    assert_eq!(title.contents, "Hello & welcome");
+    assert_eq!(title.font_family, "Serif");
+    assert_eq!(title.pixel_size, 24);
+    assert!(title.wrap && title.bold && !title.italic && !title.kerning);
+    assert_eq!(title.colour.to_string(), "#ff8000");
+    assert_eq!(title.halign, HorizontalAlignment::Center);
+    assert_eq!(title.valign, VerticalAlignment::Bottom);
+
+    let hint = match objects[1].shape {
+        ObjectShape::Text(ref text) => text,
+        ref shape => panic!("expected a text object, got {:?}", shape),
+    };
+    assert_eq!(hint.contents, "Press any key");
+    assert_eq!(hint.pixel_size, 16);
+    assert!(hint.kerning);
+    assert_eq!(hint.halign, HorizontalAlignment::Left);
+
+    let shadow = match objects[2].shape {
+        ObjectShape::Text(ref text) => text,
+        ref shape => panic!("expected a text object, got {:?}", shape),
+    };
+    assert_eq!(shadow.colour, Colour::new(0, 0, 0).with_alpha(0x80));
+}
+
+#[test]
+fn test_connected_components() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let layer = r.layers[0].as_tile_layer().unwrap();
+    let regions = layer.connected_components(|tile| tile.map_or(0, |t| t.gid.value()) != 1);
+    assert_eq!(regions.len(), 3);
+    assert_eq!(regions[0].label, 0);
+    assert_eq!(regions[0].bounds, TileRect::new(1, 1, 3, 2));
+    assert_eq!(regions[0].cells.len(), 6);
+    assert_eq!(regions[1].bounds, TileRect::new(5, 4, 2, 2));
+    assert_eq!(regions[2].bounds, TileRect::new(8, 8, 2, 2));
+    assert_eq!(regions[2].cells, vec![(9, 8), (8, 9), (9, 9)]);
+
+    let regions = layer.connected_components(|tile| tile.map_or(0, |t| t.gid.value()) == 1);
+    assert_eq!(regions.len(), 1);
+    assert_eq!(regions[0].cells.len(), 100 - 13);
+}
+
+#[test]
+fn test_raycast() {
+    let mut r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let layer = r.layers[0].as_tile_layer().unwrap().clone();
+    let walls = |tile: LayerTile| tile.gid == Gid(2);
+
+    let hit = r
+        .raycast(&layer, (16.0, 48.0), (300.0, 48.0), walls)
+        .unwrap()
+        .unwrap();
+    assert_eq!((hit.tile_x, hit.tile_y), (1, 1));
+    assert_eq!(hit.tile.gid, Gid(2));
+    assert_eq!(hit.point, (32.0, 48.0));
+    assert_eq!(hit.distance, 16.0);
+
+    let hit = r
+        .raycast(&layer, (300.0, 80.0), (16.0, 80.0), walls)
+        .unwrap()
+        .unwrap();
+    assert_eq!((hit.tile_x, hit.tile_y), (3, 2));
+    assert_eq!(hit.point, (128.0, 80.0));
+
+    assert_eq!(
+        r.raycast(&layer, (16.0, 16.0), (300.0, 16.0), walls).unwrap(),
+        None
+    );
+    assert_eq!(
+        r.raycast(&layer, (16.0, 48.0), (20.0, 48.0), walls).unwrap(),
+        None
+    );
+
+    r.orientation = Orientation::Isometric;
+    let hit = r
+        .raycast(&layer, (160.0, 16.0), (160.0, 100.0), walls)
+        .unwrap()
+        .unwrap();
+    assert_eq!((hit.tile_x, hit.tile_y), (1, 1));
+    assert_eq!(hit.point, (160.0, 32.0));
+
+    r.orientation = Orientation::Hexagonal;
+    assert!(r.raycast(&layer, (0.0, 0.0), (1.0, 1.0), walls).is_err());
+}
+
+#[cfg(feature = "pathfinding")]
+#[test]
+fn test_find_path() {
+    use tiled::{find_path, CostGrid, Neighborhood};
+
+    let mut r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let layer = r.layers[0].as_tile_layer().unwrap();
+    // Walls are impassable and mud is expensive to cross.
+    let grid = CostGrid::from_layer(layer, |tile| match tile.map_or(0, |t| t.gid.value()) {
+        2 => None,
+        3 => Some(5.0),
+        _ => Some(1.0),
+    });
+
+    let path = find_path(&grid, (0, 1), (4, 1), Neighborhood::Four).unwrap();
+    assert_eq!(
+        path.tiles,
+        vec![(0, 1), (0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (4, 1)]
+    );
+    assert_eq!(path.cost, 6.0);
+    assert_eq!(
+        path.waypoints(&r, &grid, Neighborhood::Four),
+        vec![(16.0, 48.0), (16.0, 16.0), (144.0, 16.0), (144.0, 48.0)]
+    );
+
+    let path = find_path(&grid, (4, 4), (7, 4), Neighborhood::Four).unwrap();
+    assert_eq!(path.cost, 5.0);
+    assert!(path.tiles.iter().all(|&(x, y)| grid.cost(x, y) == Some(1.0)));
+
+    let path = find_path(&grid, (5, 0), (9, 4), Neighborhood::Eight).unwrap();
+    assert_eq!(path.tiles.len(), 5);
+    assert!((path.cost - 4.0 * std::f32::consts::SQRT_2).abs() < 1e-4);
+    assert_eq!(
+        path.waypoints(&r, &grid, Neighborhood::Eight),
+        vec![(176.0, 16.0), (304.0, 144.0)]
+    );
+    assert_eq!(find_path(&grid, (0, 0), (1, 1), Neighborhood::Eight), None);
+    assert_eq!(find_path(&grid, (0, 0), (20, 20), Neighborhood::Eight), None);
+
+    let hex = Neighborhood::Hexagonal {
+        stagger_axis: StaggerAxis::Y,
+        stagger_index: StaggerIndex::Odd,
+        hex_side_length: 16,
+    };
+    let open = CostGrid::from_layer(layer, |_| Some(1.0));
+    let path = find_path(&open, (0, 0), (2, 2), hex).unwrap();
+    assert_eq!(path.cost, 3.0);
+    assert_eq!(path.tiles.len(), 4);
+    for pair in path.tiles.windows(2) {
+        assert!((pair[0].1 - pair[1].1).abs() <= 1 && (pair[0].0 - pair[1].0).abs() <= 1);
+    }
+    r.orientation = Orientation::Hexagonal;
+    let waypoints = path.waypoints(&r, &open, hex);
+    assert_eq!(waypoints[0], (16.0, 16.0));
+    assert_eq!(*waypoints.last().unwrap(), (80.0, 64.0));
+
+    assert_eq!(Neighborhood::from_map(&r), None);
+
+    // Staggered maps place the tiles of square paths by their own stagger
+    // attributes.
+    r.orientation = Orientation::Staggered;
+    r.stagger_axis = Some(StaggerAxis::X);
+    r.stagger_index = Some(StaggerIndex::Even);
+    let path = find_path(&open, (0, 0), (1, 0), Neighborhood::Four).unwrap();
+    assert_eq!(
+        path.waypoints(&r, &open, Neighborhood::Four),
+        vec![(16.0, 32.0), (32.0, 16.0)]
+    );
+
+    let r = read_from_file(Path::new("assets/tiled_hexagonal.tmx")).unwrap();
+    assert_eq!(
+        Neighborhood::from_map(&r),
+        Some(Neighborhood::Hexagonal {
+            stagger_axis: StaggerAxis::Y,
+            stagger_index: StaggerIndex::Even,
+            hex_side_length: 14,
+        })
+    );
+}
+
+#[test]
+fn test_field_of_view() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let layer = r.layers[0].as_tile_layer().unwrap();
+    let walls = |tile: Option<LayerTile>| tile.map_or(0, |t| t.gid.value()) == 2;
+
+    let visible = layer.field_of_view((2, 4), None, walls);
+    assert!(visible.contains(&(2, 4)));
+    assert!(visible.contains(&(2, 2)) && visible.contains(&(1, 2)));
+    assert!(!visible.contains(&(2, 1)) && !visible.contains(&(2, 0)));
+    assert!(visible.contains(&(9, 9)));
+    assert!(!visible.contains(&(0, 0)));
+    assert!(visible.iter().all(|&(x, y)| (0..10).contains(&x) && (0..10).contains(&y)));
+
+    for &origin in [(2, 4), (0, 0), (5, 1), (4, 2)].iter() {
+        for &(x, y) in layer.field_of_view(origin, None, walls).iter() {
+            if !walls(layer.get_tile(x, y)) {
+                assert!(
+                    layer.field_of_view((x, y), None, walls).contains(&origin),
+                    "{:?} sees {:?} but not the other way around",
+                    origin,
+                    (x, y)
+                );
+            }
+        }
+    }
+
+    let visible = layer.field_of_view((5, 5), Some(2), walls);
+    assert!(visible.contains(&(5, 7)) && visible.contains(&(6, 6)));
+    assert!(!visible.contains(&(5, 8)) && !visible.contains(&(7, 7)));
+    assert!(layer.field_of_view((20, 20), None, walls).is_empty());
+}
+
+#[test]
+fn test_audio_zones() {
+    let r = read_from_file(Path::new("assets/tiled_audio.tmx")).unwrap();
+    let zones = r.audio_zones("audio_zone");
+    let names: Vec<&str> = zones.iter().map(|z| z.name.as_str()).collect();
+    assert_eq!(names, vec!["cave", "river", "tower"]);
+
+    let cave = &zones[0];
+    assert_eq!(cave.track, "cave.ogg");
+    assert_eq!((cave.volume, cave.fade_distance), (0.5, 20.0));
+    assert_eq!(cave.polygon[0], (10.0, 0.0));
+    assert!(cave.contains(60.0, 25.0));
+    assert!(!cave.contains(5.0, 25.0));
+    assert_eq!(cave.volume_at(60.0, 25.0), 0.5);
+    assert_eq!(cave.volume_at(60.0, 60.0), 0.25);
+    assert_eq!(cave.volume_at(60.0, 80.0), 0.0);
+
+    let river = &zones[1];
+    assert_eq!((river.volume, river.fade_distance), (1.0, 0.0));
+    assert!(river.contains(260.0, 20.0));
+    assert!(!river.contains(215.0, 90.0));
+
+    let tower = &zones[2];
+    assert_eq!(tower.volume, 2.0);
+    assert!(tower.contains(300.0, 230.0));
+    assert!(!tower.contains(320.0, 210.0));
+
+    let all = r.layers[0].audio_zones();
+    assert_eq!(all.len(), 4);
+    assert!(all[3].contains(460.0, 430.0));
+    assert!(!all[3].contains(412.0, 404.0));
+}
+
+#[test]
+fn test_write_encodings() {
+    let encodings = [
+        DataEncoding::Base64(None),
+        DataEncoding::Base64(Some(Compression::Zlib)),
+        DataEncoding::Base64(Some(Compression::Gzip)),
+        // Zstandard compression, when the feature is enabled.
+        ExportProfile::release().write_options.data_encoding,
+    ];
+    for path in ["assets/tiled_objects.tmx", "assets/tiled_base64_zlib_infinite.tmx"] {
+        let r = read_from_file_with_path(Path::new(path)).unwrap();
+        for &data_encoding in encodings.iter() {
+            let mut out = Vec::new();
+            let options = WriteOptions {
+                data_encoding,
+                ..WriteOptions::default()
+            };
+            r.write_with_options(&mut out, &options).unwrap();
+            assert_eq!(without_sources(r.clone()), parse(&out[..]).unwrap(), "{:?} in {}", data_encoding, path);
+        }
+    }
+}
+
+#[test]
+fn test_export_profiles() {
+    let mut r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    r.layers[1]
+        .properties
+        .insert("editor_only".to_string(), PropertyValue::BoolValue(true));
+    let mut hidden = r.layers[0].clone();
+    hidden.name = "Hidden".to_string();
+    hidden.visible = false;
+    r.layers.push(hidden);
+
+    let release = ExportProfile::named("release").unwrap();
+    let mut out = Vec::new();
+    release.export(&r, &mut out).unwrap();
+    let exported = parse(&out[..]).unwrap();
+    // Hidden layers are kept, since games may show them at runtime.
+    let names: Vec<&str> = exported.layers.iter().map(|l| l.name.as_str()).collect();
+    assert_eq!(names, vec!["Ground", "Hidden"]);
+    assert_eq!(exported, release.apply(&r));
+    assert!(String::from_utf8(out).unwrap().contains("encoding=\"base64\""));
+    let without_hidden = ExportProfile {
+        strip_hidden_layers: true,
+        ..release
+    };
+    let stripped = without_hidden.apply(&r);
+    assert_eq!(stripped.layers.len(), 1);
+    assert_eq!(stripped.layers[0].name, "Ground");
+
+    let debug = ExportProfile::named("debug").unwrap();
+    let mut out = Vec::new();
+    debug.export(&r, &mut out).unwrap();
+    assert_eq!(parse(&out[..]).unwrap(), r);
+    assert!(ExportProfile::named("profile").is_none());
+}
+
+#[test]
+fn test_write_incremental() {
+    let original = std::fs::read("assets/tiled_objects.tmx").unwrap();
+    let mut r = parse(&original[..]).unwrap();
+    let mut saved = SavedLayers::new(&r, &original).unwrap();
+    let mut out = Vec::new();
+    assert_eq!(
+        r.write_incremental(&original, &mut saved, &mut out)
+            .unwrap(),
+        0
+    );
+    assert_eq!(out, original);
+
+    r.layers[0]
+        .as_tile_layer_mut()
+        .unwrap()
+        .set_tile(4, 4, LayerTile::new(7))
+        .unwrap();
+    let mut out = Vec::new();
+    assert_eq!(
+        r.write_incremental(&original, &mut saved, &mut out)
+            .unwrap(),
+        1
+    );
+    assert_eq!(parse(&out[..]).unwrap(), r);
+    assert_eq!(saved, SavedLayers::new(&r, &out).unwrap());
+    let mut again = Vec::new();
+    assert_eq!(
+        r.write_incremental(&out, &mut saved, &mut again).unwrap(),
+        0
+    );
+    assert_eq!(again, out);
+    // A document other than the one last saved is replaced as a whole.
+    let mut whole = Vec::new();
+    assert_eq!(
+        r.write_incremental(&original, &mut saved, &mut whole)
+            .unwrap(),
+        2
+    );
+    assert_eq!(parse(&whole[..]).unwrap(), r);
+
+    let original = String::from_utf8(original).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let layer_start = original.find("<layer").unwrap();
+    assert_eq!(out[..layer_start], original[..layer_start]);
+    let objects = original.find(" <objectgroup").unwrap();
+    assert!(out.ends_with(&original[objects..]));
+
+    let original = std::fs::read("assets/tiled_base64_zlib.tmx").unwrap();
+    let mut r = parse_file(Path::new("assets/tiled_base64_zlib.tmx")).unwrap();
+    let mut saved = SavedLayers::new(&r, &original).unwrap();
+    r.layers[0]
+        .as_tile_layer_mut()
+        .unwrap()
+        .set_tile(0, 0, LayerTile::new(7))
+        .unwrap();
+    let mut out = Vec::new();
+    assert_eq!(
+        r.write_incremental(&original, &mut saved, &mut out)
+            .unwrap(),
+        1
+    );
+    assert_eq!(parse(&out[..]).unwrap(), without_sources(r));
+    assert!(String::from_utf8(out).unwrap().contains("compression=\"zlib\""));
+}
+
+#[test]
+fn test_save_incremental() {
+    let dir = std::env::temp_dir().join("tiled_test_save_incremental");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for file in ["tiled_base64_external.tmx", "tilesheet.tsx"] {
+        std::fs::copy(Path::new("assets").join(file), dir.join(file)).unwrap();
+    }
+    let path = dir.join("tiled_base64_external.tmx");
+    let original = std::fs::read(&path).unwrap();
+    let mut r = parse_file(&path).unwrap();
+    let mut saved = SavedLayers::new(&r, &original).unwrap();
+    // Saving doesn't need the external tilesets again.
+    std::fs::remove_file(dir.join("tilesheet.tsx")).unwrap();
+    r.layers[0]
+        .as_tile_layer_mut()
+        .unwrap()
+        .set_tile(0, 0, LayerTile::new(7))
+        .unwrap();
+    assert_eq!(r.save_incremental(&path, &mut saved).unwrap(), 1);
+    assert_eq!(r.save_incremental(&path, &mut saved).unwrap(), 0);
+    let saved_map = Loader::new()
+        .without_external_tilesets()
+        .parse_file(&path)
+        .unwrap();
+    assert_eq!(saved_map.layers, r.layers);
+    let files: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+    assert_eq!(files.len(), 1);
+
+    let missing = dir.join("missing.tmx");
+    assert!(r.save_incremental(&missing, &mut saved).is_err());
+    assert!(!missing.exists());
+}
+
+#[test]
+fn test_tile_render_size() {
+    let t = parse_tileset(File::open(Path::new("assets/tilesheet.tsx")).unwrap(), 1).unwrap();
+    assert_eq!(t.tile_render_size, TileRenderSize::Tile);
+    assert_eq!(t.fill_mode, FillMode::Stretch);
+
+    let t = parse_tileset(
+        File::open(Path::new("assets/tilesheet_scaled.tsx")).unwrap(),
+        1,
+    )
+    .unwrap();
+    assert_eq!(t.tile_render_size, TileRenderSize::Grid);
+    assert_eq!(t.fill_mode, FillMode::PreserveAspectFit);
+    let mut out = Vec::new();
+    t.write_to(&mut out).unwrap();
+    assert_eq!(t, parse_tileset(&out[..], 1).unwrap());
+}
+
+#[test]
+fn test_tileset_registry() {
+    let registry = TilesetRegistry::new();
+    let mut csv = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    let mut base64 = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    let first = csv.intern_tilesets(&registry).unwrap();
+    let second = base64.intern_tilesets(&registry).unwrap();
+    assert!(csv.tilesets.is_empty());
+    assert!(Arc::ptr_eq(&first[0], &second[0]));
+    assert_eq!(registry.len(), 1);
+
+    let mut moved = (*first[0]).clone();
+    moved.first_gid = Gid(100);
+    let moved = registry.intern(moved).unwrap();
+    assert!(!Arc::ptr_eq(&first[0], &moved));
+    assert_eq!(registry.len(), 2);
+
+    drop((first, second, moved));
+    assert!(registry.is_empty());
+    let global = TilesetRegistry::global().intern(csv_tileset()).unwrap();
+    assert!(Arc::ptr_eq(
+        &global,
+        &TilesetRegistry::global().intern(csv_tileset()).unwrap()
+    ));
+}
+
+fn csv_tileset() -> Tileset {
+    let tileset = read_from_file(Path::new("assets/tiled_csv.tmx"))
+        .unwrap()
+        .tilesets
+        .remove(0);
+    (*tileset).clone()
+}
+
+#[test]
+fn test_tile_source_rect() {
+    let t = parse_tileset(
+        File::open(Path::new("assets/tilesheet_collection.tsx")).unwrap(),
+        1,
+    )
+    .unwrap();
+    assert_eq!(
+        t.tiles[0].source_rect,
+        Some(ImageRect {
+            x: 0,
+            y: 0,
+            width: 64,
+            height: 32
+        })
+    );
+    // The size defaults to the size of the image.
+    assert_eq!(
+        t.tiles[1].source_rect,
+        Some(ImageRect {
+            x: 64,
+            y: 32,
+            width: 448,
+            height: 192
+        })
+    );
+    assert_eq!(t.tiles[2].source_rect, None);
+    let mut out = Vec::new();
+    t.write_to(&mut out).unwrap();
+    assert_eq!(t, parse_tileset(&out[..], 1).unwrap());
+}
+
+#[test]
+fn test_tile_collision_group_round_trip() {
+    let tsx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tileset name="walls" tilewidth="16" tileheight="16" tilecount="1" columns="1">
+ <image source="walls.png" width="16" height="16"/>
+ <tile id="0">
+  <objectgroup draworder="index" id="2" name="Hitbox" opacity="0.5" visible="0">
+   <properties>
+    <property name="solid" type="bool" value="true"/>
+   </properties>
+   <object id="1" x="0" y="0" width="16" height="8"/>
+   <custom note="kept"/>
+  </objectgroup>
+ </tile>
+</tileset>
+"#;
+    let t = parse_tileset(tsx.as_bytes(), 1).unwrap();
+    let group = t.tiles[0].objectgroup.as_ref().unwrap();
+    assert_eq!(group.name, "Hitbox");
+    assert_eq!(group.opacity, 0.5);
+    assert!(!group.visible);
+    assert_eq!(
+        group.properties.get("solid"),
+        Some(&PropertyValue::BoolValue(true))
+    );
+    assert!(!group.extra.is_empty());
+    assert_eq!(group.as_object_layer().unwrap().objects.len(), 1);
+    let mut out = Vec::new();
+    t.write_to(&mut out).unwrap();
+    assert_eq!(t, parse_tileset(&out[..], 1).unwrap());
+    let mut out = Vec::new();
+    t.write_json_to(&mut out).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    let group = &json["tiles"][0]["objectgroup"];
+    assert_eq!(group["name"], "Hitbox");
+    assert_eq!(group["visible"], false);
+    assert_eq!(group["properties"][0]["name"], "solid");
+}
+
+#[test]
+fn test_embedded_image_data() {
+    let r = read_from_file(Path::new("assets/tiled_embedded_image.tmx")).unwrap();
+    let image = &r.tilesets[0].images[0];
+    assert_eq!(image.source, Path::new(""));
+    let data = image.data.as_ref().unwrap();
+    assert_eq!(data.format, "png");
+    assert!(data.bytes.starts_with(b"\x89PNG"));
+    let layer_image = r.layers[1].as_image_layer().unwrap().image.as_ref();
+    assert_eq!(layer_image.unwrap().data.as_ref(), Some(data));
+
+    let mut out = Vec::new();
+    r.write_to(&mut out).unwrap();
+    assert_eq!(parse(&out[..]).unwrap(), r);
+}
+
+#[test]
+fn test_property_units() {
+    let mut properties = Properties::new();
+    properties.insert("range_tiles".to_string(), PropertyValue::IntValue(3));
+    properties.insert("reach_px".to_string(), PropertyValue::FloatValue(48.0));
+    properties.insert("cooldown_ms".to_string(), PropertyValue::IntValue(1500));
+    properties.insert("speed".to_string(), PropertyValue::FloatValue(2.0));
+
+    assert_eq!(
+        properties.get_with_unit("range").unwrap(),
+        Some((3.0, Unit::Tiles))
+    );
+    assert_eq!(
+        properties.get_distance_in_pixels("range", 32.0).unwrap(),
+        Some(96.0)
+    );
+    assert_eq!(
+        properties.get_distance_in_tiles("reach", 32.0).unwrap(),
+        Some(1.5)
+    );
+    assert_eq!(
+        properties.get_duration_in_seconds("cooldown").unwrap(),
+        Some(1.5)
+    );
+    assert_eq!(properties.get_duration_in_seconds("missing").unwrap(), None);
+    assert!(properties.get_distance_in_tiles("cooldown", 32.0).is_err());
+    assert!(properties.get_duration_in_seconds("range").is_err());
+    assert!(properties.get_distance_in_tiles("speed", 32.0).is_err());
+}
+
+#[test]
+fn test_localization_strings() {
+    let mut r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let table = r.collect_strings("text.");
+    let keys: Vec<&str> = table.keys().map(String::as_str).collect();
+    assert_eq!(
+        keys,
+        [
+            "map/text.name",
+            "objects/2/text.hint",
+            "objects/2/text.title"
+        ]
+    );
+    assert_eq!(table["objects/2/text.title"], "Great Hall");
+
+    let mut translated = StringTable::new();
+    translated.insert("objects/2/text.title".to_string(), "Grande salle".to_string());
+    translated.insert("objects/9/text.title".to_string(), "Nowhere".to_string());
+    assert_eq!(r.apply_strings(&translated), 1);
+    let room = &r.layers[1].as_object_layer().unwrap().objects[1];
+    assert_eq!(
+        room.properties["text.title"],
+        PropertyValue::StringValue("Grande salle".to_string())
+    );
+    assert_eq!(r.collect_strings("text.")["map/text.name"], "Keep");
+}
+
+#[test]
+fn test_map_seed() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let seed = r.seed("loot").unwrap();
+    let again = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    assert_eq!(again.seed("loot").unwrap(), seed);
+    assert_ne!(r.seed("scatter").unwrap(), seed);
+
+    let mut changed = r.clone();
+    changed.layers[0]
+        .as_tile_layer_mut()
+        .unwrap()
+        .set_tile(0, 0, LayerTile::new(2))
+        .unwrap();
+    assert_ne!(changed.seed("loot").unwrap(), seed);
+    assert_eq!(
+        Rng::new(seed).next_u64(),
+        Rng::new(again.seed("loot").unwrap()).next_u64()
+    );
+}
+
+#[test]
+fn test_map_frames() {
+    let server = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let frames = server.layer_frames(0, 4, 7).unwrap();
+    // A 10x10 layer splits into 3x3 chunks of at most 4x4 tiles.
+    assert_eq!(frames.len(), 9);
+    assert_eq!(frames[8].sequence, 15);
+    assert_eq!(frames[8].area, TileRect::new(8, 8, 2, 2));
+    let encoded: Vec<Vec<u8>> = frames.iter().map(|f| f.encode().unwrap()).collect();
+    assert_eq!(MapFrame::decode(&encoded[4]).unwrap(), frames[4]);
+    assert!(MapFrame::decode(&encoded[4][..10]).is_err());
+
+    let mut client = server.clone();
+    client.layers[0]
+        .as_tile_layer_mut()
+        .unwrap()
+        .fill_rect(TileRect::new(0, 0, 10, 10), None);
+    let mut receiver = FrameReceiver::new(7);
+    // Frames arriving early are held back until the gap is filled.
+    for bytes in encoded[1..].iter() {
+        let frame = MapFrame::decode(bytes).unwrap();
+        assert_eq!(receiver.receive(&mut client, frame).unwrap(), 0);
+    }
+    let first = MapFrame::decode(&encoded[0]).unwrap();
+    assert_eq!(receiver.receive(&mut client, first.clone()).unwrap(), 9);
+    assert_eq!(receiver.receive(&mut client, first).unwrap(), 0);
+    assert_eq!(receiver.next_sequence(), 16);
+    assert_eq!(client, server);
+
+    assert!(server.layer_frames(1, 4, 0).is_err());
+}
+
+#[test]
+fn test_malformed_map_frames() {
+    let server = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let frame = MapFrame::from_region(&server, 0, TileRect::new(0, 0, 2, 2), 0).unwrap();
+    let encoded = frame.encode().unwrap();
+    // A frame claiming a smaller area than its tiles isn't inflated past it.
+    let mut small = encoded.clone();
+    small[20..24].copy_from_slice(&1u32.to_le_bytes());
+    assert!(MapFrame::decode(&small).is_err());
+    // Areas reaching past the largest coordinates are rejected.
+    let mut overflowing = encoded.clone();
+    overflowing[12..16].copy_from_slice(&i32::MAX.to_le_bytes());
+    assert!(MapFrame::decode(&overflowing).is_err());
+    let mut client = server.clone();
+    let far = MapFrame {
+        area: TileRect::new(i32::MAX, 0, 2, 2),
+        ..frame.clone()
+    };
+    assert!(client.apply_frame(&far).is_err());
+    assert!(MapFrame::from_region(&server, 0, far.area, 0).is_err());
+
+    let mut receiver = FrameReceiver::new(0);
+    let ahead = MapFrame {
+        sequence: MAX_PENDING_FRAMES,
+        ..frame.clone()
+    };
+    assert!(receiver.receive(&mut client, ahead).is_err());
+    let held = MapFrame {
+        sequence: MAX_PENDING_FRAMES - 1,
+        ..frame
+    };
+    assert_eq!(receiver.receive(&mut client, held).unwrap(), 0);
+}
+
+#[test]
+fn test_failed_map_frame_is_skipped() {
+    let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.4" orientation="orthogonal" width="4" height="1" tilewidth="16" tileheight="16">
+ <layer name="row" width="4" height="1">
+  <data encoding="csv">1,1,1,1</data>
+ </layer>
+</map>"#;
+    let mut r = parse_str(text).unwrap();
+    let original = r.clone();
+    let bad = MapFrame {
+        sequence: 0,
+        layer_index: 0,
+        area: TileRect::new(2, 0, 3, 1),
+        tiles: vec![Some(LayerTile::new(5)); 3],
+    };
+    assert!(r.apply_frame(&bad).is_err());
+    assert_eq!(r, original);
+
+    let good = MapFrame {
+        sequence: 1,
+        area: TileRect::new(0, 0, 1, 1),
+        tiles: vec![Some(LayerTile::new(7))],
+        ..bad.clone()
+    };
+    let mut receiver = FrameReceiver::new(0);
+    assert_eq!(receiver.receive(&mut r, good.clone()).unwrap(), 0);
+    // The bad frame is skipped, and the frame held back behind it applied.
+    assert!(receiver.receive(&mut r, bad.clone()).is_err());
+    assert_eq!(receiver.next_sequence(), 2);
+    let layer = r.layers[0].as_tile_layer().unwrap();
+    assert_eq!(layer.get_tile(0, 0), Some(LayerTile::new(7)));
+    assert_eq!(layer.get_tile(2, 0), Some(LayerTile::new(1)));
+    assert_eq!(receiver.receive(&mut r, bad).unwrap(), 0);
+}
+
+#[test]
+fn test_tile_delta() {
+    let mut server = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let mut client = server.clone();
+    let delta = TileDelta::record(&mut server, 1, |layer| {
+        layer.set_tile(0, 0, LayerTile::new(5))?;
+        layer.flood_fill(1, 1, LayerTile::new(6));
+        Ok(())
+    })
+    .unwrap();
+    // The flood fill replaces the 2x3 block of 2s.
+    assert_eq!(delta.cells.len(), 7);
+    assert_eq!(delta.cells[0], (0, 0, Some(LayerTile::new(5))));
+
+    let decoded = TileDelta::decode(&delta.encode()).unwrap();
+    assert_eq!(decoded, delta);
+    assert!(TileDelta::decode(&delta.encode()[..20]).is_err());
+    // Deltas follow their layer when the layers are reordered.
+    client.layers.reverse();
+    client.apply_delta(&decoded).unwrap();
+    client.layers.reverse();
+    assert_eq!(client, server);
+
+    let unchanged = TileDelta::record(&mut server, 1, |_| Ok(())).unwrap();
+    assert!(unchanged.is_empty());
+    assert!(TileDelta::record(&mut server, 2, |_| Ok(())).is_err());
+    assert!(TileDelta::record(&mut server, 9, |_| Ok(())).is_err());
+
+    // A delta with a change outside of the layer isn't applied at all.
+    let mut partly_outside = TileDelta::new(1);
+    partly_outside.cells.push((2, 2, Some(LayerTile::new(7))));
+    partly_outside.cells.push((20, 2, Some(LayerTile::new(7))));
+    assert!(client.apply_delta(&partly_outside).is_err());
+    assert_eq!(client, server);
+
+    // Only the chunks which changed are compared, however far apart the
+    // chunks of the layer are.
+    let path = Path::new("assets/tiled_base64_zlib_infinite.tmx");
+    let mut server = read_from_file_with_path(path).unwrap();
+    let mut client = server.clone();
+    let delta = TileDelta::record(&mut server, 3, |layer| {
+        layer.set_tile(1 << 30, 1 << 30, LayerTile::new(4))?;
+        layer.set_tile(-32, 0, LayerTile::new(99))?;
+        layer.set_tile(5, 40, LayerTile::new(4))
+    })
+    .unwrap();
+    assert_eq!(
+        delta.cells,
+        vec![
+            (-32, 0, Some(LayerTile::new(99))),
+            (5, 40, Some(LayerTile::new(4))),
+            (1 << 30, 1 << 30, Some(LayerTile::new(4)))
+        ]
+    );
+    client.apply_delta(&delta).unwrap();
+    assert_eq!(client, server);
+}
+
+#[test]
+fn test_runtime_map() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let runtime = RuntimeMap::from(&r);
+    assert_eq!(runtime.layer_count(), 2);
+    assert_eq!(runtime.layers.kind[0], RuntimeLayerKind::Tiles);
+    assert_eq!(runtime.layers.name[0], None);
+    assert_eq!(runtime.tiles.len(), 100);
+    assert_eq!(runtime.tile_at(0, 1, 1), Some(LayerTile::new(2)));
+    assert_eq!(runtime.tile_at(0, 10, 0), None);
+    assert_eq!(runtime.tile_at(1, 0, 0), None);
+    assert!(runtime.properties.is_empty());
+
+    let objects = &runtime.objects;
+    assert_eq!(runtime.layers.contents[1], 0..objects.id.len());
+    // Object types are interned, so objects of the same type share them.
+    assert_eq!(objects.obj_type[1], objects.obj_type[2]);
+    assert_eq!(runtime.string(objects.obj_type[1]), "area");
+    assert_eq!(objects.name[1], None);
+
+    let options = RuntimeOptions {
+        keep_names: true,
+        properties: vec!["team".to_string()],
+    };
+    let runtime = RuntimeMap::new(&r, &options);
+    assert_eq!(runtime.string(runtime.layers.name[0].unwrap()), "Ground");
+    assert_eq!(runtime.string(runtime.objects.name[0].unwrap()), "spawn");
+    assert_eq!(runtime.properties.len(), 1);
+    assert_eq!(
+        runtime.property(PropertyOwner::Object(0), "team"),
+        Some(&PropertyValue::StringValue("red".to_string()))
+    );
+    assert_eq!(runtime.property(PropertyOwner::Object(1), "text.title"), None);
+}
+
+#[test]
+fn test_layer_size() {
+    let r = read_from_file(Path::new("assets/tiled_layer_size.tmx")).unwrap();
+    let full = r.layers[0].as_tile_layer().unwrap();
+    assert_eq!((full.width, full.height), (4, 3));
+    let small = r.layers[1].as_tile_layer().unwrap();
+    assert_eq!((small.width, small.height), (2, 2));
+    // The data is split into rows using the layer's own width.
+    assert_eq!(small.get_tile(1, 1), Some(LayerTile::new(4)));
+    assert_eq!(small.get_tile(2, 0), None);
+
+    let mut out = Vec::new();
+    r.write_to(&mut out).unwrap();
+    assert_eq!(parse(&out[..]).unwrap(), r);
+}
+
+#[test]
+fn test_vertical_strips() {
+    let mut r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    r.layers[0]
+        .as_tile_layer_mut()
+        .unwrap()
+        .fill_rect(TileRect::new(8, 0, 2, 10), None);
+    let partition = r.vertical_strips(4).unwrap();
+    assert_eq!(partition.strips.len(), 3);
+    assert_eq!(partition.strips[2].area, TileRect::new(8, 0, 2, 10));
+    assert_eq!(
+        partition.strips[0].bounds,
+        Some(TileRect::new(0, 0, 4, 10))
+    );
+    // The last strip was emptied above.
+    assert_eq!(partition.strips[2].bounds, None);
+    // The spawn point at x = 48 lies in the first strip.
+    assert!(partition.strips[0].objects.contains(&(1, 0)));
+
+    assert_eq!(partition.strip_at(130.0), Some(1));
+    assert_eq!(partition.strip_at(400.0), None);
+    assert_eq!(partition.strips_between(100.0, 300.0), 0..3);
+    assert_eq!(partition.strips_between(130.0, 200.0), 1..2);
+    assert!(r.vertical_strips(0).is_err());
+}
+
+#[test]
+fn test_locked() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    assert!(r.layers[0].locked);
+    assert!(!r.layers[1].locked);
+    let group = r.layers[1].as_object_layer().unwrap();
+    let locked: Vec<_> = group
+        .objects
+        .iter()
+        .filter(|o| o.locked)
+        .map(|o| o.name.as_str())
+        .collect();
+    assert_eq!(locked, ["wall"]);
+
+    let mut buffer = Vec::new();
+    r.write_to(&mut buffer).unwrap();
+    let written = parse(&buffer[..]).unwrap();
+    assert_eq!(r, written);
+}
+
+/// Stores tile data as the hex digits of its bytes.
+struct HexCodec;
+
+impl TileDataCodec for HexCodec {
+    fn encoding(&self) -> &str {
+        "hex"
+    }
+
+    fn compression(&self) -> Option<&str> {
+        None
+    }
+
+    fn decode(&self, text: &str) -> Result<Vec<u8>, TiledError> {
+        let text = text.trim();
+        (0..text.len())
+            .step_by(2)
+            .map(|i| {
+                u8::from_str_radix(&text[i..i + 2], 16)
+                    .map_err(|e| TiledError::Other(e.to_string()))
+            })
+            .collect()
+    }
+
+    fn encode(&self, data: &[u8]) -> Result<String, TiledError> {
+        Ok(data.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+}
+
+#[test]
+fn test_tile_data_codec() {
+    for path in ["assets/tiled_objects.tmx", "assets/tiled_base64_zlib_infinite.tmx"] {
+        let r = read_from_file_with_path(Path::new(path)).unwrap();
+        let options = WriteOptions {
+            codec: Some(Arc::new(HexCodec)),
+            ..WriteOptions::default()
+        };
+        let mut out = Vec::new();
+        r.write_with_options(&mut out, &options).unwrap();
+        assert!(String::from_utf8_lossy(&out).contains("encoding=\"hex\""));
+
+        assert!(parse(&out[..]).is_err());
+        let loader = Loader::new().with_codec(HexCodec);
+        assert_eq!(without_sources(r.clone()), loader.parse(&out[..]).unwrap(), "{}", path);
+    }
+}
+
+#[test]
+fn test_loader_transform() {
+    let xor = |bytes: &[u8]| -> Vec<u8> { bytes.iter().map(|b| b ^ 0x5a).collect() };
+    let dir = std::env::temp_dir().join("tiled_test_loader_transform");
+    std::fs::create_dir_all(&dir).unwrap();
+    for file in ["tiled_base64_external.tmx", "tilesheet.tsx"] {
+        let bytes = std::fs::read(Path::new("assets").join(file)).unwrap();
+        std::fs::write(dir.join(file), xor(&bytes)).unwrap();
+    }
+    let path = dir.join("tiled_base64_external.tmx");
+    assert!(parse_file(&path).is_err());
+
+    let loader = Loader::new().with_transform(move |bytes| Ok(xor(bytes)));
+    let r = loader.parse_file(&path).unwrap();
+    let e = read_from_file_with_path(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    assert_eq!(without_sources(r), without_sources(e));
+
+    let reject =
+        Loader::new().with_transform(|_| Err(TiledError::Other("Bad signature".to_string())));
+    assert!(reject.parse_file(&path).is_err());
+}
+
+#[test]
+fn test_hexagonal_map() {
+    let r = read_from_file(Path::new("assets/tiled_hexagonal.tmx")).unwrap();
+    assert_eq!(r.orientation, Orientation::Hexagonal);
+    assert_eq!(r.hex_side_length, Some(14));
+    assert_eq!(r.stagger_axis, Some(StaggerAxis::Y));
+    assert_eq!(r.stagger_index, Some(StaggerIndex::Even));
+
+    let mut buffer = Vec::new();
+    r.write_to(&mut buffer).unwrap();
+    assert_eq!(r, parse(&buffer[..]).unwrap());
+
+    let e = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    assert_eq!(e.hex_side_length, None);
+    assert_eq!(e.stagger_axis, None);
+    assert_eq!(e.stagger_index, None);
+}
+
+#[test]
+fn test_map_checksum() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let options = WriteOptions {
+        checksum: true,
+        ..WriteOptions::default()
+    };
+    let mut buffer = Vec::new();
+    r.write_with_options(&mut buffer, &options).unwrap();
+
+    let loader = Loader::new().with_checksum_verification();
+    assert_eq!(loader.parse(&buffer[..]).unwrap(), r);
+    // Without verification, the checksum is kept as a property.
+    let plain = parse(&buffer[..]).unwrap();
+    assert!(plain.properties.contains_key("checksum"));
+
+    // A single changed tile is caught.
+    let text = String::from_utf8(buffer).unwrap();
+    let corrupted = text.replacen("\n1,", "\n2,", 1);
+    assert_ne!(text, corrupted);
+    assert!(loader.parse(corrupted.as_bytes()).is_err());
+
+    let mut unsigned = Vec::new();
+    r.write_to(&mut unsigned).unwrap();
+    assert!(loader.parse(&unsigned[..]).is_err());
+}
+
+#[test]
+fn test_compression_level() {
+    let mut r = read_from_file(Path::new("assets/ldk_tiled_export.tmx")).unwrap();
+    assert_eq!(r.compression_level, Some(0));
+    let e = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    assert_eq!(e.compression_level, None);
+
+    let options = WriteOptions {
+        data_encoding: DataEncoding::Base64(Some(Compression::Zlib)),
+        ..WriteOptions::default()
+    };
+    let mut stored = Vec::new();
+    r.write_with_options(&mut stored, &options).unwrap();
+    assert_eq!(parse(&stored[..]).unwrap(), r);
+
+    r.compression_level = Some(-1);
+    let mut compressed = Vec::new();
+    r.write_with_options(&mut compressed, &options).unwrap();
+    assert_eq!(parse(&compressed[..]).unwrap(), r);
+    assert!(compressed.len() < stored.len());
+}
+
+#[test]
+fn test_object_density() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let group = r.layers[1].as_object_layer().unwrap();
+    let grid = group.density_grid(100.0);
+    assert_eq!(grid.origin, (0.0, 0.0));
+    assert_eq!(grid.counts, vec![vec![2, 0, 1], vec![0, 1, 0], vec![2, 0, 0]]);
+    assert_eq!(grid.max(), 2);
+    assert_eq!(grid.count_at(50.0, 250.0), 2);
+    assert_eq!(grid.count_at(-50.0, 0.0), 0);
+    assert_eq!(grid.normalized()[0], vec![1.0, 0.0, 0.5]);
+
+    let counts = group.type_counts();
+    assert_eq!(counts["area"], 2);
+    assert_eq!(counts["spawn"], 1);
+    assert_eq!(counts.len(), 5);
+}
+
+#[test]
+fn test_next_ids() {
+    let mut r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    assert_eq!(r.next_layer_id, 3);
+    assert_eq!(r.next_object_id, 7);
+
+    let mut object = r.layers[1].as_object_layer().unwrap().objects[0].clone();
+    object.name = "spawn 2".to_string();
+    assert_eq!(r.add_object(1, object.clone()).unwrap(), 7);
+    assert_eq!(r.next_object_id, 8);
+    assert!(r.add_object(0, object.clone()).is_err());
+
+    let mut layer = r.layers[1].clone();
+    layer.as_object_layer_mut().unwrap().objects[0].id = 20;
+    r.add_layer(layer);
+    assert_eq!(r.layers[2].id, 3);
+    assert_eq!(r.next_layer_id, 4);
+    assert_eq!(r.next_object_id, 21);
+
+    let mut buffer = Vec::new();
+    r.write_to(&mut buffer).unwrap();
+    assert_eq!(r, parse(&buffer[..]).unwrap());
+
+    // Maps without the attributes get ids past their layers and objects.
+    let text = std::fs::read_to_string("assets/tiled_objects.tmx").unwrap();
+    let text = text.replace(" nextlayerid=\"3\" nextobjectid=\"7\"", "");
+    let r = parse(text.as_bytes()).unwrap();
+    assert_eq!(r.next_layer_id, 3);
+    assert_eq!(r.next_object_id, 7);
+
+    // They go past the highest ids, including those within groups, and
+    // stop at the largest id.
+    let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.4" orientation="orthogonal" width="1" height="1" tilewidth="16" tileheight="16">
+ <objectgroup id="2" name="Objects">
+  <object id="4294967295" x="0" y="0"/>
+ </objectgroup>
+ <group id="3" name="Group">
+  <objectgroup id="9" name="Nested"/>
+ </group>
+</map>"#;
+    let r = parse_str(text).unwrap();
+    assert_eq!(r.next_layer_id, 10);
+    assert_eq!(r.next_object_id, u32::MAX);
+}
+
+#[test]
+fn test_gid_histogram() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let ground = r.layers[0].gid_histogram();
+    assert_eq!(
+        ground.into_iter().collect::<Vec<_>>(),
+        vec![(1, 87), (2, 6), (3, 4), (4, 3)]
+    );
+    // The chest is a tile object.
+    assert_eq!(
+        r.layers[1].gid_histogram().into_iter().collect::<Vec<_>>(),
+        vec![(5, 1)]
+    );
+    assert_eq!(r.gid_histogram()[&1], 87);
+
+    let rare = r.rarely_used_tiles(3);
+    assert_eq!(rare.len(), 79 + 2);
+    assert_eq!(rare[0], (6, 0));
+    assert_eq!(rare[rare.len() - 2..], [(5, 1), (4, 3)]);
+}
+
+#[test]
+fn test_user_types() {
+    let r = read_from_file(Path::new("assets/tiled_class.tmx")).unwrap();
+    assert_eq!(r.user_type.as_deref(), Some("dungeon"));
+    assert_eq!(r.tilesets[0].user_type.as_deref(), Some("terrain"));
+    let classes: Vec<_> = r.layers.iter().map(|l| l.user_type.as_deref()).collect();
+    assert_eq!(classes, [Some("walkable"), Some("spawns"), None]);
+
+    let mut buffer = Vec::new();
+    r.write_to(&mut buffer).unwrap();
+    assert_eq!(r, parse(&buffer[..]).unwrap());
+
+    let e = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    assert_eq!(e.user_type, None);
+}
+
+#[test]
+fn test_maps_equivalent() {
+    let finite = read_from_file(Path::new("assets/tiled_base64_zlib.tmx")).unwrap();
+    let csv = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    assert_maps_equivalent(&finite, &csv, 0.0);
+
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let mut moved = r.clone();
+    let object = &mut moved.layers[1].as_object_layer_mut().unwrap().objects[0];
+    object.x += 0.001;
+    assert_maps_equivalent(&r, &moved, 0.01);
+    assert_eq!(
+        r.differences(&moved, 0.0),
+        vec!["layers[1].objects[0].x: 48 != 48.001".to_string()]
+    );
+
+    let mut edited = r.clone();
+    let layer = edited.layers[0].as_tile_layer_mut().unwrap();
+    layer.set_tile(3, 4, LayerTile::new(2)).unwrap();
+    let differences = r.differences(&edited, 0.0);
+    assert_eq!(
+        differences,
+        vec!["layers[0]: 1 tiles differ, the first at (3, 4)"]
+    );
+    let panic = std::panic::catch_unwind(|| assert_maps_equivalent(&r, &edited, 0.0));
+    assert!(panic.is_err());
+}
+
+#[test]
+fn test_compatibility_warnings() {
+    let r = read_from_file(Path::new("assets/tiled_class.tmx")).unwrap();
+    assert_eq!(r.tiled_version.as_deref(), Some("1.9.0"));
+    assert!(r.compatibility_warnings().is_empty());
+    let file = File::open(Path::new("assets/tiled_class.tmx")).unwrap();
+    assert!(check_compatibility(file).unwrap().is_empty());
+
+    let file = File::open(Path::new("assets/tiled_newer.tmx")).unwrap();
+    let warnings = check_compatibility(file).unwrap();
+    let newer = CompatibilityWarning::NewerTiledVersion {
+        version: "1.10.2".to_string(),
+    };
+    let element = |name: &str, count| CompatibilityWarning::UnsupportedElement {
+        name: name.to_string(),
+        count,
+    };
+    assert_eq!(warnings, vec![newer.clone()]);
+    let future = "<map><future/><layer/><future/></map>";
+    assert_eq!(
+        check_compatibility(future.as_bytes()).unwrap(),
+        vec![element("future", 2)]
+    );
+    let mut r = r;
+    r.tiled_version = Some("1.10.2".to_string());
+    assert_eq!(r.compatibility_warnings(), vec![newer]);
+}
+
+#[test]
+fn test_rasterize_objects() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let group = r.layers[1].as_object_layer().unwrap();
+    let layer = group.rasterize(10, 10, (32, 32), |object| Some(object.id));
+    let gid = |x, y| layer.get_tile(x, y).map_or(0, |t| t.gid.value());
+    let filled = |layer: &TileLayer| match layer.tiles {
+        LayerData::Finite(ref rows) => rows.iter().flatten().flatten().count(),
+        LayerData::Infinite(_) => 0,
+    };
+    // The spawn point.
+    assert_eq!(gid(1, 1), 1);
+    // The room's rectangle.
+    assert_eq!(gid(2, 2), 2);
+    assert_eq!(gid(4, 3), 2);
+    assert_eq!(gid(5, 3), 0);
+    // The pond's ellipse.
+    assert_eq!(gid(5, 5), 3);
+    assert_eq!(gid(6, 5), 3);
+    // The wall's polygon.
+    assert_eq!(gid(1, 7), 4);
+    assert_eq!(gid(2, 7), 4);
+    // The path's polyline.
+    assert_eq!(gid(0, 9), 5);
+    assert_eq!(gid(3, 9), 5);
+    assert_eq!(gid(3, 7), 5);
+    // The rotated chest tile.
+    assert_eq!(gid(8, 3), 6);
+    assert_eq!(filled(&layer), 18);
+
+    let walls = group.rasterize(10, 10, (32, 32), |object| {
+        Some(1).filter(|_| object.obj_type == "collision")
+    });
+    assert_eq!(walls.width, 10);
+    assert_eq!(filled(&walls), 2);
+}
+
+#[test]
+fn test_editor_settings() {
+    let r = read_from_file(Path::new("assets/tiled_class.tmx")).unwrap();
+    let settings = r.editor_settings.as_ref().unwrap();
+    assert_eq!(settings.chunk_size, Some((32, 16)));
+    assert_eq!(settings.export_target.as_deref(), Some("class.tmj"));
+    assert_eq!(settings.export_format.as_deref(), Some("json"));
+
+    let mut buffer = Vec::new();
+    r.write_to(&mut buffer).unwrap();
+    let written = parse(&buffer[..]).unwrap();
+    assert_eq!(written.editor_settings, r.editor_settings);
+
+    let mut out = Vec::new();
+    r.write_json_to(&mut out).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(json["editorsettings"]["chunksize"]["height"], 16);
+    assert_eq!(json["editorsettings"]["export"]["format"], "json");
+
+    let r = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    assert_eq!(r.editor_settings, None);
+}
+
+#[test]
+fn test_trace_outlines() {
+    let rows = [
+        [1, 1, 0, 0, 0],
+        [1, 0, 0, 1, 0],
+        [1, 1, 1, 0, 0],
+        [0, 0, 0, 0, 0],
+    ];
+    let layer = TileLayer {
+        width: 5,
+        height: 4,
+        tiles: LayerData::Finite(
+            rows.iter()
+                .map(|row| row.iter().map(|&gid| LayerTile::from_bits(gid)).collect())
+                .collect(),
+        ),
+    };
+    let outlines = layer.trace_outlines((16, 16), |tile| tile.map_or(0, |t| t.gid.value()) == 1);
+    let corners = |points: &[(i32, i32)]| -> Vec<(f32, f32)> {
+        points
+            .iter()
+            .map(|&(x, y)| (x as f32 * 16.0, y as f32 * 16.0))
+            .collect()
+    };
+    assert_eq!(
+        outlines,
+        vec![
+            corners(&[
+                (0, 0),
+                (2, 0),
+                (2, 1),
+                (1, 1),
+                (1, 2),
+                (3, 2),
+                (3, 3),
+                (0, 3)
+            ]),
+            corners(&[(3, 1), (4, 1), (4, 2), (3, 2)]),
+        ]
+    );
+
+    let objects = layer.to_polygon_objects((16, 16), |tile| tile.map_or(0, |t| t.gid.value()) == 1);
+    assert_eq!(objects.len(), 2);
+    assert_eq!((objects[1].x, objects[1].y), (48.0, 16.0));
+    assert_eq!(
+        objects[1].shape,
+        ObjectShape::Polygon {
+            points: vec![(0.0, 0.0), (16.0, 0.0), (16.0, 16.0), (0.0, 16.0)]
+        }
+    );
+}
+
+#[test]
+fn test_draw_order() {
+    let r = read_from_file(Path::new("assets/tiled_class.tmx")).unwrap();
+    let spawns = r.layers[1].as_object_layer().unwrap();
+    assert_eq!(spawns.draw_order, DrawOrder::Index);
+    let mut buffer = Vec::new();
+    r.write_to(&mut buffer).unwrap();
+    let written = parse(&buffer[..]).unwrap();
+    assert_eq!(
+        written.layers[1].as_object_layer().unwrap().draw_order,
+        DrawOrder::Index
+    );
+
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let mut group = r.layers[1].as_object_layer().unwrap().clone();
+    assert_eq!(group.draw_order, DrawOrder::TopDown);
+    let ids = |group: &ObjectGroup| -> Vec<u32> {
+        group.objects_in_draw_order().iter().map(|o| o.id).collect()
+    };
+    assert_eq!(ids(&group), vec![1, 2, 6, 3, 4, 5]);
+    group.draw_order = DrawOrder::Index;
+    assert_eq!(ids(&group), vec![1, 2, 3, 4, 5, 6]);
+}
+
+#[test]
+fn test_colours() {
+    let opaque: Colour = "#ff8000".parse().unwrap();
+    assert_eq!(opaque, Colour::new(255, 128, 0));
+    assert!(opaque.is_opaque());
+    assert_eq!("ff8000".parse::<Colour>().unwrap(), opaque);
+
+    let translucent: Colour = "#40ff8000".parse().unwrap();
+    assert_eq!(translucent, opaque.with_alpha(0x40));
+    assert!(!translucent.is_opaque());
+    assert_eq!(translucent.to_argb(), 0x40ff8000);
+    assert_eq!(Colour::from_argb(0x40ff8000), translucent);
+    assert_eq!(translucent.to_string(), "#40ff8000");
+    assert_eq!(
+        translucent.to_rgba_f32(),
+        [1.0, 128.0 / 255.0, 0.0, 64.0 / 255.0]
+    );
+
+    for malformed in ["#ff80", "#ff80001", "#gg8000", "#ééé", "#aé000"] {
+        assert!(malformed.parse::<Colour>().is_err(), "{}", malformed);
+    }
+}
+
+#[test]
+fn test_trace_shapes() {
+    // A ring of tiles around a hole, and a staircase.
+    let rows = [
+        [1, 1, 1, 0, 0, 0, 2],
+        [1, 0, 1, 0, 0, 2, 2],
+        [1, 1, 1, 0, 2, 2, 2],
+    ];
+    let layer = TileLayer {
+        width: 7,
+        height: 3,
+        tiles: LayerData::Finite(
+            rows.iter()
+                .map(|row| row.iter().map(|&gid| LayerTile::from_bits(gid)).collect())
+                .collect(),
+        ),
+    };
+    let ring = layer.trace_shapes((1, 1), 0.0, |tile| tile.map_or(0, |t| t.gid.value()) == 1);
+    assert_eq!(
+        ring,
+        vec![TracedShape {
+            outline: vec![(0.0, 0.0), (3.0, 0.0), (3.0, 3.0), (0.0, 3.0)],
+            holes: vec![vec![(1.0, 1.0), (1.0, 2.0), (2.0, 2.0), (2.0, 1.0)]],
+        }]
+    );
+    // The outlines alone cover the hole.
+    assert_eq!(
+        layer.trace_outlines((1, 1), |tile| tile.map_or(0, |t| t.gid.value()) == 1),
+        vec![ring[0].outline.clone()]
+    );
+
+    let stairs = layer.trace_shapes((1, 1), 0.0, |tile| tile.map_or(0, |t| t.gid.value()) == 2);
+    assert_eq!(stairs[0].outline.len(), 8);
+    assert!(stairs[0].holes.is_empty());
+    // Simplifying smooths the steps into a slope.
+    let slope = layer.trace_shapes((1, 1), 0.9, |tile| tile.map_or(0, |t| t.gid.value()) == 2);
+    assert_eq!(
+        slope[0].outline,
+        vec![(6.0, 0.0), (7.0, 0.0), (7.0, 3.0), (4.0, 3.0)]
+    );
+
+    let square = [(0.0, 0.0), (1.0, 0.0), (2.0, 0.1), (2.0, 2.0), (0.0, 2.0)];
+    assert_eq!(simplify_polygon(&square, 0.0), square.to_vec());
+    assert_eq!(
+        simplify_polygon(&square, 0.5),
+        vec![(0.0, 0.0), (2.0, 0.1), (2.0, 2.0), (0.0, 2.0)]
+    );
+    // Polygons aren't simplified below a triangle.
+    let sliver = [(0.0, 0.0), (1.0, 0.0), (2.0, 0.1), (1.0, 0.1)];
+    assert_eq!(simplify_polygon(&sliver, 1.0), sliver.to_vec());
+}
+
+#[test]
+fn test_tile_collision() {
+    let file = File::open(Path::new("assets/tilesheet_slopes.tsx")).unwrap();
+    let slopes = parse_tileset(file, 1000).unwrap();
+    let expected = [
+        TileCollision::Full,
+        TileCollision::Slope {
+            left: 0.0,
+            right: 1.0,
+        },
+        TileCollision::Slope {
+            left: 0.5,
+            right: 1.0,
+        },
+        TileCollision::Slope {
+            left: 0.5,
+            right: 0.5,
+        },
+        TileCollision::Platform,
+        TileCollision::Platform,
+        TileCollision::Ceiling {
+            left: 1.0,
+            right: 0.0,
+        },
+        TileCollision::Custom,
+        TileCollision::Empty,
+    ];
+    for (id, collision) in (0..).zip(expected.iter()) {
+        assert_eq!(slopes.tile_collision(id), *collision, "tile {}", id);
+    }
+    let ramp = slopes.tile_collision(1);
+    assert_eq!(ramp.surface_height(0.25), Some(0.25));
+    assert_eq!(slopes.tile_collision(8).surface_height(0.5), None);
+    assert_eq!(
+        ramp.flipped(true, false, false),
+        TileCollision::Slope {
+            left: 1.0,
+            right: 0.0
+        }
+    );
+    assert_eq!(
+        ramp.flipped(false, true, false),
+        TileCollision::Ceiling {
+            left: 0.0,
+            right: 1.0
+        }
+    );
+    assert_eq!(ramp.flipped(false, false, true), TileCollision::Custom);
+
+    let mut r = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    r.tilesets.push(Arc::new(slopes));
+    let mut tile = LayerTile::new(1001);
+    tile.flip_h = true;
+    assert_eq!(
+        r.tile_collision(tile),
+        TileCollision::Slope {
+            left: 1.0,
+            right: 0.0
+        }
+    );
+    assert_eq!(r.tile_collision(LayerTile::new(0)), TileCollision::Empty);
+}
+
+#[test]
+fn test_colour_properties() {
+    let r = read_from_file(Path::new("assets/tiled_class.tmx")).unwrap();
+    assert_eq!(
+        r.properties["fog"],
+        PropertyValue::ColorValue(Colour::new(255, 0, 0).with_alpha(128))
+    );
+    assert_eq!(
+        r.properties["sky"],
+        PropertyValue::ColorValue(Colour::new(0x33, 0x66, 0xcc))
+    );
+
+    let mut buffer = Vec::new();
+    r.write_to(&mut buffer).unwrap();
+    let written = parse(&buffer[..]).unwrap();
+    assert_eq!(written.properties, r.properties);
+
+    let mut out = Vec::new();
+    r.write_json_to(&mut out).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    let fog = json["properties"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|p| p["name"] == "fog")
+        .unwrap();
+    assert_eq!(fog["type"], "color");
+    assert_eq!(fog["value"], "#80ff0000");
+}
+
+#[test]
+fn test_object_properties() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let room = r.resolve_object_ref(2).unwrap();
+    assert_eq!(room.name, "room");
+    assert_eq!(room.properties["key"], PropertyValue::ObjectValue(6));
+    let key = match room.properties["key"] {
+        PropertyValue::ObjectValue(id) => r.resolve_object_ref(id).unwrap(),
+        _ => unreachable!(),
+    };
+    assert_eq!(key.name, "chest");
+    assert!(r.resolve_object_ref(0).is_none());
+    assert!(r.resolve_object_ref(100).is_none());
+
+    let mut buffer = Vec::new();
+    r.write_to(&mut buffer).unwrap();
+    let written = parse(&buffer[..]).unwrap();
+    assert_eq!(
+        written.resolve_object_ref(2).unwrap().properties["key"],
+        PropertyValue::ObjectValue(6)
+    );
+}
+
+#[test]
+fn test_collision_edges() {
+    let file = File::open(Path::new("assets/tilesheet_slopes.tsx")).unwrap();
+    let mut r = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    r.tile_width = 16;
+    r.tile_height = 16;
+    r.tilesets.push(Arc::new(parse_tileset(file, 1000).unwrap()));
+    // A ramp up to a solid tile, followed by a flipped ramp down.
+    let mut flipped = LayerTile::new(1001);
+    flipped.flip_h = true;
+    let layer = TileLayer {
+        width: 3,
+        height: 1,
+        tiles: LayerData::Finite(vec![vec![
+            Some(LayerTile::new(1001)),
+            Some(LayerTile::new(1000)),
+            Some(flipped),
+        ]]),
+    };
+    let edges = r.collision_edges(&layer);
+    let normals = |x| -> Vec<(f32, f32)> {
+        edges[&(x, 0)]
+            .iter()
+            .map(|e| ((e.normal.0 * 100.0).round(), (e.normal.1 * 100.0).round()))
+            .collect()
+    };
+    // The sides shared with the solid tile are left out.
+    assert_eq!(normals(0), vec![(-71.0, -71.0), (0.0, 100.0)]);
+    assert_eq!(normals(1), vec![(0.0, -100.0), (0.0, 100.0)]);
+    assert_eq!(normals(2), vec![(71.0, -71.0), (0.0, 100.0)]);
+    let ramp = edges[&(0, 0)][0];
+    assert_eq!((ramp.start, ramp.end), ((0.0, 16.0), (16.0, 0.0)));
+    let top = edges[&(1, 0)][0];
+    assert_eq!((top.start, top.end), ((16.0, 0.0), (32.0, 0.0)));
+}
+
+#[test]
+fn test_class_properties() {
+    let r = read_from_file(Path::new("assets/tiled_class.tmx")).unwrap();
+    let mut loot = HashMap::new();
+    loot.insert(
+        "item".to_string(),
+        PropertyValue::StringValue("sword".to_string()),
+    );
+    let mut spawner = HashMap::new();
+    spawner.insert("count".to_string(), PropertyValue::IntValue(3));
+    spawner.insert(
+        "loot".to_string(),
+        PropertyValue::ClassValue {
+            type_name: "Loot".to_string(),
+            properties: loot,
+        },
+    );
+    assert_eq!(
+        r.properties["spawner"],
+        PropertyValue::ClassValue {
+            type_name: "Spawner".to_string(),
+            properties: spawner,
+        }
+    );
+    assert_eq!(
+        r.properties["unset"],
+        PropertyValue::ClassValue {
+            type_name: "Spawner".to_string(),
+            properties: HashMap::new(),
+        }
+    );
+    // The members don't leak into the map's own properties.
+    assert!(!r.properties.contains_key("count"));
+
+    let mut buffer = Vec::new();
+    r.write_to(&mut buffer).unwrap();
+    let written = parse(&buffer[..]).unwrap();
+    assert_eq!(written.properties, r.properties);
+
+    let mut out = Vec::new();
+    r.write_json_to(&mut out).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    let spawner = json["properties"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|p| p["name"] == "spawner")
+        .unwrap();
+    assert_eq!(spawner["type"], "class");
+    assert_eq!(spawner["propertytype"], "Spawner");
+    assert_eq!(spawner["value"]["count"], 3);
+    assert_eq!(spawner["value"]["loot"]["item"], "sword");
+}
+
+#[test]
+fn test_tileset_versions() {
+    let path = Path::new("assets/tiled_mixed_versions.tmx");
+    let r = read_from_file_with_path(path).unwrap();
+    assert_eq!(r.tilesets[0].version.as_deref(), Some("1.8"));
+    assert_eq!(r.tilesets[0].tiled_version.as_deref(), Some("1.8.2"));
+    assert_eq!(
+        r.compatibility_warnings(),
+        vec![CompatibilityWarning::MixedTilesetVersion {
+            tileset: "autotile".to_string(),
+            version: "1.8.2".to_string(),
+            map_version: "1.4.0".to_string(),
+        }]
+    );
+    let strict = Loader::new().with_strict_versions();
+    assert!(strict.parse_file(path).is_err());
+    assert!(strict.parse_file(Path::new("assets/tiled_csv.tmx")).is_ok());
+
+    // Embedded tilesets share the map's versions.
+    let r = read_from_file(Path::new("assets/tiled_embedded_image.tmx")).unwrap();
+    assert_eq!(r.tilesets[0].tiled_version, r.tiled_version);
+    assert!(r.compatibility_warnings().is_empty());
+
+    let tsx = r#"<tileset version="1.10" tiledversion="1.10.1" name="new" tilewidth="32" tileheight="32" tilecount="0" columns="0"/>"#;
+    let tileset = Loader::new().parse_tileset(tsx.as_bytes(), 1).unwrap();
+    assert_eq!(
+        tileset.compatibility_warnings(),
+        vec![CompatibilityWarning::NewerTilesetVersion {
+            tileset: "new".to_string(),
+            version: "1.10.1".to_string(),
+        }]
+    );
+    assert!(strict.parse_tileset(tsx.as_bytes(), 1).is_err());
+}
+
+#[test]
+fn test_tileset_overlaps() {
+    let r = read_from_file(Path::new("assets/tiled_overlapping_tilesets.tmx")).unwrap();
+    assert_eq!(
+        r.tileset_overlaps(),
+        vec![
+            TilesetOverlap {
+                first: 0,
+                second: 1,
+                gids: 3..5
+            },
+            TilesetOverlap {
+                first: 0,
+                second: 2,
+                gids: 3..4
+            },
+            TilesetOverlap {
+                first: 1,
+                second: 2,
+                gids: 3..4
+            },
+        ]
+    );
+    let name = |gid| {
+        r.get_tileset_by_gid(gid)
+            .map(|tileset| tileset.name.as_str())
+    };
+    assert_eq!(name(2), Some("first"));
+    assert_eq!(name(3), Some("third"));
+    assert_eq!(name(4), Some("second"));
+    assert_eq!(name(6), Some("second"));
+
+    let file = File::open(Path::new("assets/tiled_overlapping_tilesets.tmx")).unwrap();
+    assert!(Loader::new()
+        .with_strict_tileset_ranges()
+        .parse(file)
+        .is_err());
+    let r = read_from_file_with_path(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    assert!(r.tileset_overlaps().is_empty());
+}
+
+#[test]
+fn test_project_property_types() {
+    let project = parse_project_file(Path::new("assets/tiled_class.tiled-project")).unwrap();
+    assert_eq!(project.property_types.len(), 4);
+    assert_eq!(
+        project.property_type("Flags"),
+        Some(&PropertyType::Enum {
+            id: 2,
+            name: "Flags".to_string(),
+            storage: EnumStorage::Int,
+            values: vec![
+                "Solid".to_string(),
+                "Water".to_string(),
+                "Hidden".to_string()
+            ],
+            values_as_flags: true,
+        })
+    );
+
+    let mut r = read_from_file(Path::new("assets/tiled_class.tmx")).unwrap();
+    let enum_value = |type_name: &str, value| PropertyValue::EnumValue {
+        type_name: type_name.to_string(),
+        value: Box::new(value),
+    };
+    let facing = enum_value("Direction", PropertyValue::StringValue("East".to_string()));
+    assert_eq!(r.properties["facing"], facing);
+    assert_eq!(
+        r.properties["flags"],
+        enum_value("Flags", PropertyValue::IntValue(5))
+    );
+
+    let mut buffer = Vec::new();
+    r.write_to(&mut buffer).unwrap();
+    assert_eq!(parse(&buffer[..]).unwrap().properties, r.properties);
+
+    r.apply_project(&project);
+    assert_eq!(r.properties["facing"], facing);
+    assert_eq!(
+        r.properties["flags"],
+        enum_value(
+            "Flags",
+            PropertyValue::StringValue("Solid,Hidden".to_string())
+        )
+    );
+    // Members left at their defaults are filled in, down to nested classes.
+    let mut loot = HashMap::new();
+    loot.insert(
+        "item".to_string(),
+        PropertyValue::StringValue("coin".to_string()),
+    );
+    let mut spawner = HashMap::new();
+    spawner.insert("count".to_string(), PropertyValue::IntValue(1));
+    spawner.insert(
+        "facing".to_string(),
+        enum_value("Direction", PropertyValue::StringValue("South".to_string())),
+    );
+    spawner.insert(
+        "loot".to_string(),
+        PropertyValue::ClassValue {
+            type_name: "Loot".to_string(),
+            properties: loot,
+        },
+    );
+    assert_eq!(
+        r.properties["unset"],
+        PropertyValue::ClassValue {
+            type_name: "Spawner".to_string(),
+            properties: spawner,
+        }
+    );
+    match r.properties["spawner"] {
+        PropertyValue::ClassValue { ref properties, .. } => {
+            assert_eq!(properties["count"], PropertyValue::IntValue(3));
+            assert_eq!(
+                properties["loot"],
+                PropertyValue::ClassValue {
+                    type_name: "Loot".to_string(),
+                    properties: [(
+                        "item".to_string(),
+                        PropertyValue::StringValue("sword".to_string())
+                    )]
+                    .iter()
+                    .cloned()
+                    .collect(),
+                }
+            );
+        }
+        ref other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn test_geometry() {
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    assert_eq!(r.tile_size(), TileSize::new(32, 32));
+    let view = MapRect::new(-40.0, 20.0, 100.0, 50.0);
+    assert_eq!(view.to_tile_rect(r.tile_size()), TileRect::new(-2, 0, 4, 3));
+    assert_eq!(r.tiles_in_view(view), TileRect::new(0, 0, 2, 3));
+    assert!(r
+        .tiles_in_view(MapRect::new(-100.0, 0.0, 50.0, 50.0))
+        .is_empty());
+    assert_eq!(
+        TileRect::new(1, 2, 3, 4).to_map_rect(r.tile_size()),
+        MapRect::new(32.0, 64.0, 96.0, 128.0)
+    );
+    assert!(view.contains(MapPoint::new(-40.0, 69.0)));
+    assert!(!view.contains(MapPoint::new(60.0, 20.0)));
+    assert_eq!(
+        view.intersection(&MapRect::new(0.0, 0.0, 10.0, 30.0)),
+        Some(MapRect::new(0.0, 20.0, 10.0, 10.0))
+    );
+
+    let rect = TileRect::from_origin_size(TilePoint::new(1, 1), TileSize::new(2, 2));
+    let positions: Vec<TilePoint> = rect.positions().collect();
+    assert_eq!(
+        positions,
+        vec![(1, 1).into(), (2, 1).into(), (1, 2).into(), (2, 2).into()]
+    );
+
+    let layer = match r.layers[0].layer_type {
+        LayerType::TileLayer(ref layer) => layer,
+        _ => panic!(),
+    };
+    let cropped = layer.crop(rect);
+    assert_eq!(cropped.bounds(), Some(TileRect::new(0, 0, 2, 2)));
+    assert_eq!(cropped.get_tile(0, 0), layer.get_tile(1, 1));
+    assert_eq!(cropped.get_tile(1, 1), layer.get_tile(2, 2));
+
+    let r = read_from_file_with_path(Path::new("assets/tiled_base64_zlib_infinite.tmx")).unwrap();
+    let layer = match r.layers[0].layer_type {
+        LayerType::TileLayer(ref layer) => layer,
+        _ => panic!(),
+    };
+    let chunks = match layer.tiles {
+        LayerData::Infinite(ref chunks) => chunks,
+        _ => panic!(),
+    };
+    let bounds = chunks
+        .values()
+        .map(|chunk| chunk.bounds())
+        .fold(None, |bounds: Option<TileRect>, chunk| {
+            Some(bounds.map_or(chunk, |bounds| bounds.union(&chunk)))
+        });
+    assert_eq!(bounds, layer.bounds());
+}
+
+#[test]
+fn test_object_types() {
+    let object_types = parse_object_types_file(Path::new("assets/objecttypes.xml")).unwrap();
+    assert_eq!(object_types.types.len(), 2);
+    let area = &object_types.types["area"];
+    assert_eq!(area.colour, Some(Colour::from_argb(0xffa0a0a4)));
+    assert_eq!(area.properties["key"], PropertyValue::ObjectValue(0));
+    assert_eq!(area.properties["lit"], PropertyValue::BoolValue(true));
+
+    let path = Path::new("assets/tiled_objects.tmx");
+    let plain = read_from_file_with_path(path).unwrap();
+    let r = Loader::new()
+        .with_object_types(object_types.clone())
+        .parse_file(path)
+        .unwrap();
+    let mut merged = plain.clone();
+    merged.apply_object_types(&object_types);
+    assert_eq!(merged, r);
+
+    let object = |id| r.resolve_object_ref(id).unwrap();
+    // Properties set on the objects win over the defaults.
+    assert_eq!(
+        object(1).properties["team"],
+        PropertyValue::StringValue("red".to_string())
+    );
+    assert_eq!(object(1).properties["lives"], PropertyValue::IntValue(3));
+    assert_eq!(object(2).properties["key"], PropertyValue::ObjectValue(6));
+    assert_eq!(
+        object(2).properties["depth"],
+        PropertyValue::FloatValue(1.5)
+    );
+    assert_eq!(object(3).properties["key"], PropertyValue::ObjectValue(0));
+    assert_eq!(object(3).properties.len(), 4);
+    assert_eq!(
+        object(4).properties,
+        plain.resolve_object_ref(4).unwrap().properties
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_deserialize_properties() {
+    use serde::Deserialize;
+    use tiled::DeserializeProperties;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum Direction {
+        North,
+        East,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Loot {
+        item: String,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Spawner {
+        count: u32,
+        loot: Loot,
+        #[serde(default)]
+        rate: f32,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Dungeon {
+        facing: Direction,
+        flags: u8,
+        fog: String,
+        spawner: Spawner,
+        music: Option<String>,
+    }
+
+    let r = read_from_file(Path::new("assets/tiled_class.tmx")).unwrap();
+    let dungeon: Dungeon = r.properties.deserialize_into().unwrap();
+    assert_eq!(
+        dungeon,
+        Dungeon {
+            facing: Direction::East,
+            flags: 5,
+            fog: "#80ff0000".to_string(),
+            spawner: Spawner {
+                count: 3,
+                loot: Loot {
+                    item: "sword".to_string()
+                },
+                rate: 0.0,
+            },
+            music: None,
+        }
+    );
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct WrongLoot {
+        item: i32,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct WrongSpawner {
+        loot: WrongLoot,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Wrong {
+        spawner: WrongSpawner,
+    }
+
+    let error = r.properties.deserialize_into::<Wrong>().unwrap_err();
+    let message = error.to_string();
+    assert!(
+        message.contains("property \"spawner.loot.item\""),
+        "{}",
+        message
+    );
+    assert!(message.contains("expected i32"), "{}", message);
+}
+
+#[test]
+fn test_object_points() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let path = r.resolve_object_ref(5).unwrap();
+    assert_eq!(path.position(), MapPoint::new(0.0, 288.0));
+    assert_eq!(
+        path.points(),
+        vec![
+            MapPoint::new(0.0, 288.0),
+            MapPoint::new(96.0, 288.0),
+            MapPoint::new(96.0, 224.0)
+        ]
+    );
+    assert!(r.resolve_object_ref(1).unwrap().points().is_empty());
+    let (x, y): (f32, f32) = path.position().into();
+    assert_eq!((x, y), (0.0, 288.0));
+}
+
+#[cfg(all(feature = "glam", feature = "mint"))]
+#[test]
+fn test_math_library_conversions() {
+    let point = MapPoint::new(1.5, -2.0);
+    let vec: glam::Vec2 = point.into();
+    assert_eq!(vec, glam::Vec2::new(1.5, -2.0));
+    assert_eq!(MapPoint::from(vec), point);
+    let mint_point: mint::Point2<f32> = point.into();
+    assert_eq!(MapPoint::from(mint_point), point);
+    let tile: glam::IVec2 = TilePoint::new(3, 4).into();
+    assert_eq!(TilePoint::from(tile), TilePoint::new(3, 4));
+    let size: mint::Vector2<u32> = TileSize::new(32, 16).into();
+    assert_eq!((size.x, size.y), (32, 16));
+
+    let r = read_from_file_with_path(Path::new("assets/tiled_base64.tmx")).unwrap();
+    let layer = match r.layers[0].layer_type {
+        LayerType::TileLayer(ref layer) => layer,
+        _ => panic!(),
+    };
+    let from_tuples = r.raycast(layer, (16.0, 16.0), (300.0, 16.0), |t| !t.gid.is_empty());
+    let from_glam = r.raycast(
+        layer,
+        glam::Vec2::new(16.0, 16.0),
+        glam::Vec2::new(300.0, 16.0),
+        |t| !t.gid.is_empty(),
+    );
+    assert_eq!(from_tuples.unwrap(), from_glam.unwrap());
+}
+
+#[test]
+fn test_material_table() {
+    #[derive(Debug, PartialEq)]
+    enum Material {
+        Stone,
+        Grass,
+        Water,
+        Metal,
+    }
+
+    impl std::str::FromStr for Material {
+        type Err = ();
+
+        fn from_str(s: &str) -> Result<Material, ()> {
+            match s {
+                "stone" => Ok(Material::Stone),
+                "grass" => Ok(Material::Grass),
+                "water" => Ok(Material::Water),
+                _ => Err(()),
+            }
+        }
+    }
+
+    let r = read_from_file(Path::new("assets/tiled_materials.tmx")).unwrap();
+    let table: MaterialTable<Material> = MaterialTable::new(&r, "material");
+    assert_eq!(table.get(0), None);
+    // Tiles without a material of their own fall back to their tileset's.
+    assert_eq!(table.get(1), Some(&Material::Stone));
+    assert_eq!(table.get(2), Some(&Material::Grass));
+    assert_eq!(table.get(3), Some(&Material::Water));
+    // Lava isn't a known material.
+    assert_eq!(table.get(4), Some(&Material::Stone));
+    assert_eq!(table.get(6), None);
+
+    let layer = match r.layers[0].layer_type {
+        LayerType::TileLayer(ref layer) => layer,
+        _ => panic!(),
+    };
+    let table = table.with_default(Material::Metal);
+    assert_eq!(table.material_at(layer, 1, 0), Some(&Material::Grass));
+    assert_eq!(table.material_at(layer, 3, 0), Some(&Material::Metal));
+    assert_eq!(table.material_at(layer, 4, 0), None);
+
+    let names = MaterialTable::with_parser(&r, "material", |value| match value {
+        PropertyValue::StringValue(name) => Some(name.clone()),
+        _ => None,
+    });
+    assert_eq!(names.get(4), Some(&"lava".to_string()));
+    assert_eq!(names.get(3), Some(&"stone".to_string()));
+}
+
+#[test]
+fn test_topmost_tile_at() {
+    let r = read_from_file(Path::new("assets/tiled_group.tmx")).unwrap();
+    let names: Vec<&str> = r.layers.iter().map(|l| l.name.as_str()).collect();
+    assert_eq!(names, vec!["Ground", "Decor", "Roof"]);
+    // Groups keep their own settings and properties.
+    let decor = &r.layers[1];
+    assert_eq!(decor.opacity, 0.5);
+    assert_eq!(decor.properties["level"], PropertyValue::IntValue(2));
+    assert_eq!(decor.as_group_layer().unwrap().len(), 2);
+    assert_eq!(r.properties["level"], PropertyValue::IntValue(1));
+    // They pass them on to the layers within them.
+    let flat = r.flat_layers();
+    let names: Vec<&str> = flat.iter().map(|f| f.layer.name.as_str()).collect();
+    assert_eq!(names, vec!["Ground", "Rugs", "Secret", "Roof"]);
+    assert_eq!(flat[1].opacity, 0.25);
+    assert_eq!((flat[1].offset_x, flat[1].offset_y), (4.0, 8.0));
+    assert!(!flat[2].visible);
+    assert_eq!(r.get_layer_by_id(5).unwrap().name, "Secret");
+
+    let top = |x, filter: &dyn Fn(&Layer) -> bool| {
+        r.topmost_tile_at(x, 0, filter)
+            .map(|(layer, tile)| (layer.name.as_str(), tile.gid.value()))
+    };
+    assert_eq!(top(0, &|_| true), Some(("Rugs", 2)));
+    assert_eq!(top(1, &|_| true), Some(("Ground", 1)));
+    assert_eq!(top(2, &|_| true), Some(("Roof", 4)));
+    assert_eq!(top(2, &|layer| layer.name != "Roof"), Some(("Ground", 1)));
+    assert_eq!(top(3, &|_| true), None);
+
+    let mut faded = r.clone();
+    faded.layers[1].opacity = 0.0;
+    let (layer, _) = faded.topmost_tile_at(0, 0, |_| true).unwrap();
+    assert_eq!(layer.name, "Ground");
+}
+
+#[test]
+fn test_xml_tile_data() {
+    let x = read_from_file(Path::new("assets/tiled_xml.tmx")).unwrap();
+    if let LayerData::Finite(tiles) = &x.layers[0].as_tile_layer().unwrap().tiles {
+        assert_eq!(tiles.len(), 100);
+        assert!(tiles.iter().all(|row| row.len() == 100));
+        assert_eq!(tiles[0][0].unwrap().gid, Gid(30));
+        assert_eq!(tiles[0][10], None);
+        let count = tiles.iter().flatten().flatten().count();
+        assert_eq!(count, 70);
+    } else {
+        panic!("It is wrongly recognised as an infinite map");
+    }
+
+    let r = read_from_file(Path::new("assets/tiled_xml_infinite.tmx")).unwrap();
+    if let LayerData::Infinite(chunks) = &r.layers[0].as_tile_layer().unwrap().tiles {
+        assert_eq!(chunks.len(), 2);
+        let left = &chunks[&(-2, 0)];
+        assert_eq!(left.tiles.len(), 2);
+        assert_eq!(left.tiles[0][0].unwrap().gid, Gid(30));
+        assert_eq!(left.tiles[0][1], None);
+        assert_eq!(left.tiles[1][1].unwrap().gid, Gid(17));
+        assert!(left.tiles[1][1].unwrap().flip_h);
+        let gids: Vec<Vec<u32>> = chunks[&(0, 0)]
+            .tiles
+            .iter()
+            .map(|row| row.iter().map(|t| t.map_or(0, |t| t.gid.value())).collect())
+            .collect();
+        assert_eq!(gids, vec![vec![1, 2], vec![3, 4]]);
+    } else {
+        panic!("It is wrongly recognised as a finite map");
+    }
+}
+
+fn gid_mapping(pairs: &[(u32, u32)]) -> HashMap<Gid, Gid> {
+    pairs
+        .iter()
+        .map(|&(from, to)| (Gid(from), Gid(to)))
+        .collect()
+}
+
+#[test]
+fn test_remap_gids() {
+    let mut r = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    let original = r.clone();
+    let invalid = gid_mapping(&[(35, 36), (17, 85)]);
+    assert!(r.remap_gids(&invalid).is_err());
+    assert_eq!(r, original);
+
+    let mapping = gid_mapping(&[(35, 36), (17, 0)]);
+    r.remap_gids(&mapping).unwrap();
+    let layer = r.layers[0].as_tile_layer().unwrap();
+    assert_eq!(layer.get_tile(0, 0).unwrap().gid, Gid(36));
+    assert_eq!(layer.get_tile(0, 1), None);
+    assert_eq!(r.gid_histogram().get(&35), None);
+
+    let mut r = read_from_file(Path::new("assets/tiled_xml_infinite.tmx")).unwrap();
+    let mapping = gid_mapping(&[(17, 18)]);
+    r.remap_gids(&mapping).unwrap();
+    let tile = r.layers[0]
+        .as_tile_layer()
+        .unwrap()
+        .get_tile(-1, 1)
+        .unwrap();
+    assert_eq!(tile.gid, Gid(18));
+    assert!(tile.flip_h);
+
+    let mut r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let mapping = gid_mapping(&[(5, 6)]);
+    r.remap_gids(&mapping).unwrap();
+    let chest = r.layers[1]
+        .as_object_layer()
+        .unwrap()
+        .objects
+        .iter()
+        .find(|o| o.name == "chest")
+        .unwrap();
+    assert_eq!(chest.gid, Gid(6));
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn test_write_zstd() {
+    let r = read_from_file(Path::new("assets/tiled_base64_zstandard.tmx")).unwrap();
+    let c = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    assert_eq!(r.layers, c.layers);
+
+    let mut options = WriteOptions {
+        data_encoding: DataEncoding::Base64(Some(Compression::Zstd)),
+        ..WriteOptions::default()
+    };
+    let mut default_level = Vec::new();
+    c.write_with_options(&mut default_level, &options).unwrap();
+    assert_eq!(parse(&default_level[..]).unwrap(), c);
+
+    options.compression_level = Some(19);
+    let mut high_level = Vec::new();
+    c.write_with_options(&mut high_level, &options).unwrap();
+    let w = parse(&high_level[..]).unwrap();
+    assert_eq!(w.compression_level, Some(19));
+    assert_eq!(w.layers, c.layers);
+    assert!(high_level.len() <= default_level.len());
+}
+
+#[test]
+fn test_map_patch() {
+    let base = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let mut modded = base.clone();
+    modded.properties.insert(
+        "difficulty".to_string(),
+        PropertyValue::StringValue("hard".to_string()),
+    );
+    let ground = modded.layers[0].as_tile_layer_mut().unwrap();
+    ground.set_tile(1, 1, LayerTile::new(7)).unwrap();
+    ground.set_tile(2, 1, LayerTile::new(7)).unwrap();
+    modded.layers[0]
+        .properties
+        .insert("muddy".to_string(), PropertyValue::BoolValue(true));
+    let objects = modded.layers[1].as_object_layer_mut().unwrap();
+    objects.objects.retain(|o| o.name != "pond");
+    objects.objects[0].name = "start".to_string();
+    let mut chest = objects.objects.last().unwrap().clone();
+    chest.x = 320.0;
+    modded.add_object(1, chest).unwrap();
+    let mut extra = modded.layers[1].clone();
+    extra.name = "Extra".to_string();
+    extra.as_object_layer_mut().unwrap().objects.clear();
+    modded.add_layer(extra);
+
+    let patch = modded.create_patch(&base);
+    assert_eq!(patch.properties.len(), 1);
+    assert_eq!(patch.changes.len(), 4);
+    match patch.changes[0] {
+        LayerChange::Tiles {
+            ref layer,
+            ref tiles,
+        } => {
+            assert_eq!(layer, "Ground");
+            assert_eq!(tiles.len(), 2);
+            assert_eq!(tiles[0].to, Some(LayerTile::new(7)));
+        }
+        ref change => panic!("Unexpected change {:?}", change),
+    }
+    match patch.changes[2] {
+        LayerChange::Objects { ref edits, .. } => {
+            assert_eq!(edits.len(), 3);
+            assert_eq!(edits[0], ObjectEdit::Remove(3));
+        }
+        ref change => panic!("Unexpected change {:?}", change),
+    }
+
+    let mut written = Vec::new();
+    patch.write_to(&mut written).unwrap();
+    let parsed = MapPatch::parse(&written[..]).unwrap();
+    assert_eq!(parsed, patch);
+
+    let mut patched = base.clone();
+    assert!(patched.apply_patch(&parsed).is_empty());
+    assert_eq!(patched, modded);
+
+    // Another mod changed a tile and removed an object the patch edits.
+    let mut other = base.clone();
+    let ground = other.layers[0].as_tile_layer_mut().unwrap();
+    ground.set_tile(1, 1, LayerTile::new(9)).unwrap();
+    other.layers[1]
+        .as_object_layer_mut()
+        .unwrap()
+        .objects
+        .retain(|o| o.id != 1);
+    let conflicts = other.apply_patch(&patch);
+    assert_eq!(conflicts.len(), 2);
+    assert_eq!(conflicts[0].layer, "Ground");
+    assert_eq!(
+        conflicts[1].to_string(),
+        "layer \"Objects\": the object with id 1 is missing"
+    );
+    let ground = other.layers[0].as_tile_layer().unwrap();
+    assert_eq!(ground.get_tile(1, 1).unwrap().gid, Gid(9));
+    assert_eq!(ground.get_tile(2, 1).unwrap().gid, Gid(7));
+}
+
+#[cfg(feature = "flate2")]
+#[test]
+fn test_flate2_compression_levels() {
+    let c = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    for compression in [Compression::Zlib, Compression::Gzip].iter() {
+        let mut sizes = Vec::new();
+        for level in [0, 1, 9].iter() {
+            let options = WriteOptions {
+                data_encoding: DataEncoding::Base64(Some(*compression)),
+                compression_level: Some(*level),
+                ..WriteOptions::default()
+            };
+            let mut out = Vec::new();
+            c.write_with_options(&mut out, &options).unwrap();
+            assert_eq!(parse(&out[..]).unwrap().layers, c.layers);
+            sizes.push(out.len());
+        }
+        assert!(sizes[0] > sizes[1]);
+        assert!(sizes[1] >= sizes[2]);
+    }
+}
+
+#[test]
+fn test_patch_stack() {
+    let base = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let patch_setting = |gid: u32| {
+        let mut modded = base.clone();
+        let ground = modded.layers[0].as_tile_layer_mut().unwrap();
+        ground.set_tile(1, 1, LayerTile::new(gid)).unwrap();
+        ground.set_tile(2, 2, LayerTile::new(gid)).unwrap();
+        modded.create_patch(&base)
+    };
+    let gid_at = |map: &Map, x, y| {
+        let ground = map.layers[0].as_tile_layer().unwrap();
+        ground.get_tile(x, y).unwrap().gid.value()
+    };
+    let stack = |policy| {
+        PatchStack::new(policy)
+            .with_patch("first", patch_setting(7))
+            .with_patch("second", patch_setting(8))
+    };
+
+    let mut map = base.clone();
+    let conflicts = stack(ConflictPolicy::LastWins).apply(&mut map).unwrap();
+    assert_eq!(conflicts.len(), 2);
+    assert_eq!(conflicts[0].patch, "second");
+    assert!(conflicts[0].applied);
+    assert_eq!(gid_at(&map, 1, 1), 8);
+
+    let mut map = base.clone();
+    assert!(stack(ConflictPolicy::Error).apply(&mut map).is_err());
+    assert_eq!(map, base);
+    let conflicts = stack(ConflictPolicy::Error).dry_run(&map);
+    assert_eq!(conflicts.len(), 2);
+    assert!(conflicts.iter().all(|c| !c.applied));
+    assert_eq!(map, base);
+
+    let priorities: HashMap<String, Vec<String>> =
+        [("Ground".to_string(), vec!["first".to_string()])]
+            .iter()
+            .cloned()
+            .collect();
+    let mut map = base.clone();
+    let conflicts = stack(ConflictPolicy::LayerPriority(priorities))
+        .apply(&mut map)
+        .unwrap();
+    assert_eq!(conflicts.len(), 2);
+    assert!(!conflicts[0].applied);
+    assert_eq!(gid_at(&map, 1, 1), 7);
+    assert_eq!(gid_at(&map, 2, 2), 7);
+}
+
+#[test]
+fn test_patch_stack_layer_changes() {
+    let base = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let mut modded = base.clone();
+    let ground = modded.layers[0].as_tile_layer_mut().unwrap();
+    ground.set_tile(1, 1, LayerTile::new(7)).unwrap();
+    let tiles = modded.create_patch(&base);
+    let mut modded = base.clone();
+    modded.layers[0].opacity = 0.5;
+    let set = modded.create_patch(&base);
+    assert!(matches!(set.changes[..], [LayerChange::Set(_)]));
+    let stack = |policy| {
+        PatchStack::new(policy)
+            .with_patch("tiles", tiles.clone())
+            .with_patch("set", set.clone())
+    };
+
+    let mut map = base.clone();
+    let conflicts = stack(ConflictPolicy::LastWins).apply(&mut map).unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].patch, "set");
+    assert!(conflicts[0].applied);
+    assert_eq!(map.layers[0].opacity, 0.5);
+
+    let mut map = base.clone();
+    assert!(stack(ConflictPolicy::Error).apply(&mut map).is_err());
+    assert_eq!(map, base);
+    let conflicts = stack(ConflictPolicy::Error).dry_run(&map);
+    assert_eq!(conflicts.len(), 1);
+    assert!(!conflicts[0].applied);
+
+    let priorities: HashMap<String, Vec<String>> =
+        [("Ground".to_string(), vec!["tiles".to_string()])]
+            .iter()
+            .cloned()
+            .collect();
+    let mut map = base.clone();
+    let conflicts = stack(ConflictPolicy::LayerPriority(priorities))
+        .apply(&mut map)
+        .unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert!(!conflicts[0].applied);
+    assert_eq!(map.layers[0].opacity, 1.0);
+    let ground = map.layers[0].as_tile_layer().unwrap();
+    assert_eq!(ground.get_tile(1, 1).unwrap().gid, Gid(7));
+}
+
+#[test]
+fn test_compression_codec() {
+    // A run-length compression of the tile bytes.
+    let decompress = |data: &[u8]| -> Result<Vec<u8>, TiledError> {
+        Ok(data
+            .chunks(2)
+            .flat_map(|run| std::iter::repeat(run[1]).take(run[0] as usize))
+            .collect())
+    };
+    let compress = |data: &[u8]| -> Result<Vec<u8>, TiledError> {
+        let mut out: Vec<u8> = Vec::new();
+        for &byte in data {
+            match out.len() {
+                n if n >= 2 && out[n - 1] == byte && out[n - 2] < 255 => out[n - 2] += 1,
+                _ => out.extend_from_slice(&[1, byte]),
+            }
+        }
+        Ok(out)
+    };
+    let r = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    let options = WriteOptions {
+        codec: Some(Arc::new(CompressionCodec::new("rle", decompress, compress))),
+        ..WriteOptions::default()
+    };
+    let mut out = Vec::new();
+    r.write_with_options(&mut out, &options).unwrap();
+    assert!(String::from_utf8_lossy(&out).contains("compression=\"rle\""));
+
+    assert!(parse(&out[..]).is_err());
+    let loader = Loader::new().with_codec(CompressionCodec::new("rle", decompress, compress));
+    assert_eq!(loader.parse(&out[..]).unwrap(), r);
+}
+
+#[test]
+fn test_loader_options() {
+    let e = read_from_file_with_path(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    let file = File::open(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    assert!(parse(file).is_err());
+    let file = File::open(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    let r = Loader::new()
+        .without_external_tilesets()
+        .parse(file)
+        .unwrap();
+    assert!(r.tilesets.is_empty());
+    assert_eq!(r.layers, e.layers);
+
+    let files: HashMap<PathBuf, Vec<u8>> = ["tiled_base64_external.tmx", "tilesheet.tsx"]
+        .iter()
+        .map(|name| {
+            let bytes = std::fs::read(Path::new("assets").join(name)).unwrap();
+            (Path::new("archive").join(name), bytes)
+        })
+        .collect();
+    let loader = Loader::new().with_file_reader(move |path| match files.get(path) {
+        Some(bytes) => Ok(Box::new(std::io::Cursor::new(bytes.clone())) as Box<dyn Read>),
+        None => Err(std::io::ErrorKind::NotFound.into()),
+    });
+    let r = loader
+        .parse_file(Path::new("archive/tiled_base64_external.tmx"))
+        .unwrap();
+    assert_eq!(without_sources(r), without_sources(e.clone()));
+    assert!(loader
+        .parse_file(Path::new("assets/tiled_base64.tmx"))
+        .is_err());
+
+    // The layer holds 100x100 tiles of 4 bytes.
+    let path = Path::new("assets/tiled_base64_zlib.tmx");
+    let limited = Loader::new().with_max_decompressed_size(40_000);
+    assert!(limited.parse_file(path).is_ok());
+    let limited = Loader::new().with_max_decompressed_size(39_999);
+    assert!(limited.parse_file(path).is_err());
+
+    let path = Path::new("assets/tiled_overlapping_tilesets.tmx");
+    assert!(Loader::new().parse_file(path).is_ok());
+    assert!(Loader::new().with_strict().parse_file(path).is_err());
+}
+
+#[cfg(feature = "async")]
+struct AssetsReader;
+
+#[cfg(feature = "async")]
+impl ResourceReader for AssetsReader {
+    fn read<'a>(&'a self, path: &'a Path) -> ResourceFuture<'a> {
+        Box::pin(async move { std::fs::read(Path::new("assets").join(path)) })
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_parse_file_async() {
+    let e = read_from_file_with_path(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    let loader = Loader::new();
+    let r = pollster::block_on(
+        loader.parse_file_async(Path::new("tiled_base64_external.tmx"), &AssetsReader),
+    )
+    .unwrap();
+    // Sources are the paths given to the reader, which reads from `assets`.
+    assert_eq!(
+        r.source.as_deref(),
+        Some(Path::new("tiled_base64_external.tmx"))
+    );
+    assert_eq!(
+        r.tilesets[0].source.as_deref(),
+        Some(Path::new("tilesheet.tsx"))
+    );
+    assert_eq!(without_sources(r), without_sources(e));
+    let missing = loader.parse_file_async(Path::new("missing.tmx"), &AssetsReader);
+    assert!(pollster::block_on(missing).is_err());
+}
+
+#[test]
+fn test_loader_resources() {
+    let e = read_from_file_with_path(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    let resources: HashMap<PathBuf, Vec<u8>> = ["tiled_base64_external.tmx", "tilesheet.tsx"]
+        .iter()
+        .map(|name| {
+            let bytes = std::fs::read(Path::new("assets").join(name)).unwrap();
+            (Path::new("web").join(name), bytes)
+        })
+        .collect();
+    let loader = Loader::new().with_resources(resources);
+    let r = loader
+        .parse_file(Path::new("web/tiled_base64_external.tmx"))
+        .unwrap();
+    assert_eq!(without_sources(r), without_sources(e));
+    assert!(loader
+        .parse_file(Path::new("assets/tiled_base64.tmx"))
+        .is_err());
+}
+
+#[test]
+fn test_tileset_cache() {
+    let e = read_from_file_with_path(Path::new("assets/tiled_flipped.tmx")).unwrap();
+    let opened = Arc::new(AtomicUsize::new(0));
+    let counter = opened.clone();
+    let loader = Loader::new().with_file_reader(move |path| {
+        if path.extension().is_some_and(|ext| ext == "tsx") {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }
+        Ok(Box::new(File::open(path)?) as Box<dyn Read>)
+    });
+    let cached = loader.clone().with_tileset_cache();
+    let maps = [
+        "tiled_base64_external.tmx",
+        "tiled_flipped.tmx",
+        "tiled_base64_zlib_infinite.tmx",
+    ];
+    for name in maps.iter() {
+        loader.parse_file(Path::new("assets").join(name)).unwrap();
+    }
+    assert_eq!(opened.load(Ordering::SeqCst), 3);
+
+    opened.store(0, Ordering::SeqCst);
+    for name in maps.iter() {
+        cached.parse_file(Path::new("assets").join(name)).unwrap();
+    }
+    let r = cached
+        .clone()
+        .parse_file(Path::new("assets/tiled_flipped.tmx"))
+        .unwrap();
+    assert_eq!(opened.load(Ordering::SeqCst), 1);
+    assert_eq!(r, e);
+}
+
+#[test]
+fn test_shared_tilesets() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    let mut copy = r.clone();
+    assert!(Arc::ptr_eq(&r.tilesets[0], &copy.tilesets[0]));
+    Arc::make_mut(&mut copy.tilesets[0]).name = "renamed".to_string();
+    assert!(!Arc::ptr_eq(&r.tilesets[0], &copy.tilesets[0]));
+    assert_eq!(r.tilesets[0].name, "tilesheet");
+}
+
+#[test]
+fn test_layer_filter() {
+    let e = read_from_file(Path::new("assets/tiled_group.tmx")).unwrap();
+    let file = File::open(Path::new("assets/tiled_group.tmx")).unwrap();
+    let r = Loader::new()
+        .with_layer_filter(|layer| layer.name == "Secret" || layer.id == Some(6))
+        .parse(file)
+        .unwrap();
+    assert_eq!(r.layers.len(), 2);
+    // Groups are kept for the layers within them, without the others.
+    let decor = r.layers[0].as_group_layer().unwrap();
+    assert_eq!(decor.len(), 1);
+    assert_eq!(decor[0], e.layers[1].as_group_layer().unwrap()[1]);
+    assert_eq!(r.layers[1], e.layers[2]);
+
+    let file = File::open(Path::new("assets/tiled_group.tmx")).unwrap();
+    let r = Loader::new()
+        .with_layer_filter(|layer| layer.id == Some(6))
+        .parse(file)
+        .unwrap();
+    // Groups left empty by the filter are dropped.
+    assert_eq!(r.layers, vec![e.layers[2].clone()]);
+
+    let file = File::open(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let r = Loader::new()
+        .with_layer_filter(|layer| layer.kind == LayerKind::Objects)
+        .parse(file)
+        .unwrap();
+    assert!(r
+        .layers
+        .iter()
+        .all(|layer| layer.as_object_layer().is_some()));
+    assert!(!r.layers.is_empty());
+}
+
+#[test]
+fn test_deferred_tilesets() {
+    let e = read_from_file_with_path(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    let file = File::open(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    let mut r = Loader::new().with_deferred_tilesets().parse(file).unwrap();
+    assert!(r.tilesets.is_empty());
+    assert_eq!(
+        r.unresolved_tilesets,
+        vec![TilesetSource {
+            first_gid: Gid(1),
+            source: PathBuf::from("tilesheet.tsx"),
+        }]
+    );
+    assert_eq!(r.layers, e.layers);
+
+    let mut written = Vec::new();
+    r.write_to(&mut written).unwrap();
+    let reread = Loader::new()
+        .with_deferred_tilesets()
+        .parse(&written[..])
+        .unwrap();
+    assert_eq!(reread.unresolved_tilesets, r.unresolved_tilesets);
+
+    assert!(r
+        .resolve_tilesets(|_| File::open("assets/missing.tsx"))
+        .is_err());
+    assert_eq!(r.unresolved_tilesets.len(), 1);
+    r.resolve_tilesets(|path| File::open(Path::new("assets").join(path)))
+        .unwrap();
+    assert_eq!(without_sources(r), without_sources(e));
+}
+
+#[test]
+fn test_resolve_paths() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    assert_eq!(
+        r.source.as_deref(),
+        Some(Path::new("assets/tiled_base64_external.tmx"))
+    );
+    let tileset = &r.tilesets[0];
+    assert_eq!(
+        tileset.source.as_deref(),
+        Some(Path::new("assets/tilesheet.tsx"))
+    );
+    assert_eq!(
+        r.resolve_tileset_path(tileset, &tileset.images[0].source),
+        Path::new("assets/tilesheet.png")
+    );
+    assert_eq!(
+        r.resolve_path("music/theme.ogg"),
+        Path::new("assets/music/theme.ogg")
+    );
+
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    assert!(r.source.is_none());
+    assert_eq!(
+        r.resolve_tileset_path(&r.tilesets[0], "tilesheet.png"),
+        Path::new("tilesheet.png")
+    );
+}
+
+#[test]
+fn test_parse_str_and_bytes() {
+    let e = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    let text = std::fs::read_to_string("assets/tiled_base64.tmx").unwrap();
+    assert_eq!(parse_str(&text).unwrap(), e);
+    assert_eq!(parse_bytes(text.as_bytes()).unwrap(), e);
+    assert_eq!(
+        without_sources(parse_file("assets/tiled_base64.tmx").unwrap()),
+        e
+    );
+    let r = Loader::new()
+        .parse_with_path(text.as_bytes(), PathBuf::from("assets/tiled_base64.tmx"))
+        .unwrap();
+    assert_eq!(without_sources(r), e);
+}
+
+#[test]
+fn test_error_position() {
+    let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.4" orientation="orthogonal" width="1" height="1" tilewidth="8" tileheight="8">
+  <tileset firstgid="1"/>
+</map>"#;
+    let e = parse_str(text).unwrap_err();
+    let position = e.position().unwrap();
+    assert_eq!((position.row, position.column), (2, 2));
+    assert!(e.to_string().starts_with("line 3, column 3: "));
+
+    let e = parse_str(&text.replace(" version=\"1.4\"", "")).unwrap_err();
+    assert_eq!(e.position().unwrap().row, 1);
+}
+
+#[test]
+fn test_error_element_path() {
+    let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.4" orientation="orthogonal" width="1" height="1" tilewidth="8" tileheight="8">
+  <layer id="1" name="Ground" width="1" height="1">
+    <data encoding="base64">!!!</data>
+  </layer>
+</map>"#;
+    let e = parse_str(text).unwrap_err();
+    let path: Vec<String> = e.element_path().iter().map(|c| c.to_string()).collect();
+    assert_eq!(path, vec!["map", "layer(\"Ground\")", "data"]);
+    assert_eq!(e.element_path()[1].id.as_deref(), Some("1"));
+    assert!(e.to_string().contains(": map > layer(\"Ground\") > data: "));
+}
+
+#[test]
+fn test_error_variants() {
+    let text =
+        r#"<map orientation="orthogonal" width="1" height="1" tilewidth="8" tileheight="8"/>"#;
+    match parse_str(text).unwrap_err().inner() {
+        TiledError::MissingAttribute { element, attribute } => {
+            assert_eq!((element.as_str(), attribute.as_str()), ("map", "version"));
+        }
+        e => panic!("unexpected error {:?}", e),
+    }
+
+    let e = parse_str(&text.replace("width=\"1\"", "version=\"1.4\" width=\"one\"")).unwrap_err();
+    assert!(e
+        .to_string()
+        .ends_with("invalid value \"one\" for the \"width\" attribute of map"));
+
+    let e = "#12345".parse::<Colour>().unwrap_err();
+    assert!(
+        matches!(e, TiledError::InvalidValue { ref kind, ref value } if kind == "colour" && value == "#12345")
+    );
+
+    match parse_file("assets/missing.tmx").unwrap_err() {
+        TiledError::Io { path, error } => {
+            assert_eq!(path, Path::new("assets/missing.tmx"));
+            assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+        }
+        e => panic!("unexpected error {:?}", e),
+    }
+}
+
+#[test]
+fn test_invalid_csv_tile() {
+    let text = r#"<map version="1.4" orientation="orthogonal" width="2" height="2" tilewidth="8" tileheight="8">
+ <layer id="1" name="Ground" width="2" height="2">
+  <data encoding="csv">
+1,2,
+3,x4
+</data>
+ </layer>
+</map>"#;
+    let e = parse_str(text).unwrap_err();
+    match e.inner() {
+        TiledError::InvalidTileData { token, index } => {
+            assert_eq!((token.as_str(), *index), ("x4", 3));
+        }
+        e => panic!("unexpected error {:?}", e),
+    }
+    assert_eq!(e.position().unwrap().row, 2);
+}
+
+#[test]
+fn test_malformed_data_is_an_error() {
+    let layer = |data: &str| {
+        format!(
+            r#"<map version="1.4" orientation="orthogonal" width="2" height="2" tilewidth="8" tileheight="8">
+ <layer id="1" name="Ground" width="2" height="2">
+  {}
+ </layer>
+</map>"#,
+            data
+        )
+    };
+    // Three bytes short of two rows of two tiles.
+    let short = format!(
+        r#"<data encoding="base64">{}</data>"#,
+        base64::encode(&[1u8; 13])
+    );
+    assert!(parse_str(&layer(&short)).is_err());
+    let zero_width =
+        layer(r#"<data encoding="csv">1,2,3,4</data>"#).replace(r#"width="2""#, r#"width="0""#);
+    assert!(parse_str(&zero_width).is_err());
+    // Every encoding must hold exactly a tile for each cell of the layer.
+    assert!(parse_str(&layer(r#"<data encoding="csv">1,2,3,4</data>"#)).is_ok());
+    assert!(parse_str(&layer(r#"<data encoding="csv">1,2,3</data>"#)).is_err());
+    assert!(parse_str(&layer(r#"<data encoding="csv">1,2,3,4,5,6</data>"#)).is_err());
+    let rows = |count: usize| {
+        let data = base64::encode(&vec![0u8; 8 * count]);
+        layer(&format!(r#"<data encoding="base64">{}</data>"#, data))
+    };
+    assert!(parse_str(&rows(2)).is_ok());
+    assert!(parse_str(&rows(1)).is_err());
+    assert!(parse_str(&rows(3)).is_err());
+    assert!(parse_str(&layer(r#"<data><tile gid="1"/><tile/><tile/></data>"#)).is_err());
+    let short_chunk =
+        r#"<data encoding="csv"><chunk x="0" y="0" width="2" height="2">1,2</chunk></data>"#;
+    let infinite =
+        layer(short_chunk).replace(r#"tileheight="8""#, r#"tileheight="8" infinite="1""#);
+    assert!(parse_str(&infinite).is_err());
+    let chunk =
+        r#"<data encoding="csv"><chunk x="0" y="0" width="0" height="2">1,2</chunk></data>"#;
+    let infinite = layer(chunk).replace(r#"tileheight="8""#, r#"tileheight="8" infinite="1""#);
+    assert!(parse_str(&infinite).is_err());
+
+    // Cutting off or scrambling the example maps gives errors, never panics.
+    let mut seed = 1u32;
+    for entry in std::fs::read_dir("assets").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().map_or(true, |e| e != "tmx") {
+            continue;
+        }
+        let bytes = std::fs::read(&path).unwrap();
+        for end in (0..bytes.len()).step_by(bytes.len() / 100 + 1) {
+            let _ = parse_bytes(&bytes[..end]);
+        }
+        for _ in 0..50 {
+            let mut bytes = bytes.clone();
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let i = (seed >> 8) as usize % bytes.len();
+            bytes[i] = b"0123456789,=\"<>/ x-"[(seed >> 4) as usize % 19];
+            let _ = parse_bytes(&bytes);
+        }
+    }
+}
+
+#[test]
+fn test_unknown_xml_is_kept() {
+    let text = r##"<map version="1.10" orientation="orthogonal" renderorder="right-down" width="1" height="1" tilewidth="8" tileheight="8">
+ <tileset firstgid="1" name="tiles" tilewidth="8" tileheight="8" tilecount="1" columns="1">
+  <grid orientation="orthogonal" width="1" height="1"/>
+  <tile id="0" future="yes"/>
+ </tileset>
+ <layer id="1" name="Ground" width="1" height="1" parallaxx="0.5">
+  <data encoding="csv">1</data>
+ </layer>
+ <objectgroup id="2" name="Things" color="#ff0000">
+  <object id="1" x="0" y="0" template="crate.tx"><note lang="en">A <b>crate</b></note></object>
+ </objectgroup>
+ <future kind="lights"><light x="4" y="4"/></future>
+</map>"##;
+    let map = parse_str(text).unwrap();
+    assert_eq!(map.extra.attribute("renderorder"), Some("right-down"));
+    assert_eq!(map.extra.elements.len(), 1);
+    assert_eq!(map.extra.elements[0].name, "future");
+    assert_eq!(map.tilesets[0].extra.elements[0].name, "grid");
+    assert_eq!(
+        map.tilesets[0].tiles[0].extra.attribute("future"),
+        Some("yes")
+    );
+    let layer = &map.layers[0];
+    assert_eq!(layer.id, 1);
+    assert_eq!(
+        layer.extra.attributes,
+        vec![("parallaxx".to_string(), "0.5".to_string())]
+    );
+    let group = &map.layers[1];
+    assert_eq!(group.extra.attribute("color"), None);
+    let object = &group.as_object_layer().unwrap().objects[0];
+    assert_eq!(object.extra.attribute("template"), Some("crate.tx"));
+    assert_eq!(
+        object.extra.elements[0].children,
+        vec![
+            XmlNode::Text("A ".to_string()),
+            XmlNode::Element(XmlElement {
+                name: "b".to_string(),
+                attributes: Vec::new(),
+                children: vec![XmlNode::Text("crate".to_string())],
+            }),
+        ]
+    );
+
+    let mut written = Vec::new();
+    map.write_to(&mut written).unwrap();
+    assert_eq!(parse_bytes(&written).unwrap(), map);
+}
+
+#[test]
+fn test_preserve_layout() {
+    let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" tiledversion="1.10.2" orientation="orthogonal" renderorder="right-down" width="2" height="1" tilewidth="8" tileheight="8" infinite="0" nextlayerid="3" nextobjectid="2">
+ <!--Generated by a level tool-->
+ <tileset firstgid="1" name="tiles" tilewidth="8" tileheight="8" tilecount="2" columns="2">
+  <grid orientation="orthogonal" width="1" height="1" />
+  <image source="tiles.png" width="16" height="8" />
+ </tileset>
+ <layer id="1" name="Ground" width="2" height="1">
+  <data encoding="csv">
+1,2
+</data>
+ </layer>
+ <objectgroup id="2" name="Things">
+  <object id="1" x="4" y="4" name="crate" />
+ </objectgroup>
+</map>"#;
+    let map = parse_str(text).unwrap();
+    let options = WriteOptions {
+        preserve_layout: true,
+        ..WriteOptions::default()
+    };
+    let mut written = Vec::new();
+    map.write_with_options(&mut written, &options).unwrap();
+    assert_eq!(String::from_utf8(written).unwrap(), text);
+
+    let mut written = Vec::new();
+    map.write_to(&mut written).unwrap();
+    let written = String::from_utf8(written).unwrap();
+    assert!(!written.contains("level tool"));
+    assert!(written.contains(r#"<object id="1" name="crate" x="4" y="4" />"#));
+}
+
+#[test]
+fn test_empty_tiles_are_none() {
+    let mut r = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    let layer = r.layers[0].as_tile_layer_mut().unwrap();
+    assert_eq!(layer.get_tile(0, 2), None);
+    assert_eq!(layer.get_tile(0, 0), Some(LayerTile::new(35)));
+    assert_eq!(LayerTile::from_bits(0x8000_0000), None);
+
+    layer.set_tile(0, 0, None).unwrap();
+    assert_eq!(layer.get_tile(0, 0), None);
+    if let LayerData::Finite(ref rows) = layer.tiles {
+        assert_eq!(rows[0][0], None);
+    }
+    let mut out = Vec::new();
+    r.write_to(&mut out).unwrap();
+    assert_eq!(parse(&out[..]).unwrap(), r);
+}
+
+#[test]
+fn test_gid() {
+    let gid = Gid(0xA000_0005);
+    assert_eq!(gid.value(), 5);
+    assert!(gid.flip_h() && !gid.flip_v() && gid.flip_d());
+    assert!(!gid.is_empty());
+    assert!(Gid::EMPTY.is_empty());
+    assert!(Gid(0x4000_0000).is_empty());
+
+    let tile = LayerTile::new(gid);
+    assert_eq!(tile, LayerTile::new(0xA000_0005));
+    assert_eq!(Gid::from(tile), gid);
+
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let chest = r.layers[1]
+        .as_object_layer()
+        .unwrap()
+        .objects
+        .iter()
+        .find(|o| o.name == "chest")
+        .unwrap();
+    assert_eq!(chest.gid, Gid(5));
+    let flipped = Gid(chest.gid.0 | 0x8000_0000);
+    assert_eq!(
+        r.get_tileset_by_gid(flipped).map(|t| &t.name),
+        r.get_tileset_by_gid(5).map(|t| &t.name)
+    );
+    assert!(r.get_tileset_by_gid(flipped).is_some());
+}
+
+#[test]
+fn test_tileset_get_tile() {
+    let text = r#"<tileset name="t" tilewidth="8" tileheight="8" tilecount="10" columns="10">
+ <tile id="7" probability="0.5"/>
+ <tile id="2" probability="0.25"/>
+</tileset>"#;
+    let mut t = parse_tileset(text.as_bytes(), 1).unwrap();
+    assert_eq!(t.get_tile(7).unwrap().probability, 0.5);
+    assert_eq!(t.get_tile(2).unwrap().probability, 0.25);
+    assert!(t.get_tile(3).is_none());
+    let index = t.tile_index();
+    assert_eq!(index.get(7).unwrap().probability, 0.5);
+    assert_eq!(index.get(2).unwrap().probability, 0.25);
+    assert!(index.get(3).is_none());
+
+    // Tiles changed after parsing are found by new indexes.
+    let mut extra = t.tiles[0].clone();
+    extra.id = 3;
+    t.tiles.insert(0, extra);
+    t.tiles.retain(|tile| tile.id != 2);
+    t.tiles[1].id = 4;
+    let index = t.tile_index();
+    assert!(index.get(2).is_none());
+    assert!(index.get(7).is_none());
+    assert_eq!(index.get(3).unwrap().id, 3);
+    assert_eq!(index.get(4).unwrap().probability, 0.5);
+    assert_eq!(t.get_tile(4).unwrap().probability, 0.5);
+}
+
+#[test]
+fn test_tileset_for_gid() {
+    let text = r#"<map version="1.10" orientation="orthogonal" width="1" height="1" tilewidth="8" tileheight="8">
+ <tileset firstgid="1" name="a" tilewidth="8" tileheight="8" tilecount="10" columns="10"/>
+ <tileset firstgid="11" name="b" tilewidth="8" tileheight="8" tilecount="5" columns="5"/>
+ <tileset firstgid="21" name="c" tilewidth="8" tileheight="8" columns="5"/>
+ <layer id="1" name="Ground" width="1" height="1">
+  <data encoding="csv">0</data>
+ </layer>
+</map>"#;
+    let mut r = parse_str(text).unwrap();
+    let find = |r: &Map, gid: u32| r.tileset_for_gid(gid).map(|(t, id)| (t.name.clone(), id));
+    assert_eq!(find(&r, 0), None);
+    assert_eq!(find(&r, 1), Some(("a".to_string(), 0)));
+    assert_eq!(find(&r, 10), Some(("a".to_string(), 9)));
+    assert_eq!(find(&r, 11), Some(("b".to_string(), 0)));
+    assert_eq!(find(&r, 0x8000_000C), Some(("b".to_string(), 1)));
+    // Past the end of `b` but before `c`, like Tiled the closest tileset
+    // below is used.
+    assert_eq!(find(&r, 17), Some(("b".to_string(), 6)));
+    assert_eq!(find(&r, 500), Some(("c".to_string(), 479)));
+
+    let index = r.tileset_index();
+    for gid in [0, 1, 10, 11, 0x8000_000C, 17, 500] {
+        let indexed = index
+            .tileset_for_gid(gid)
+            .map(|(t, id)| (t.name.clone(), id));
+        assert_eq!(indexed, find(&r, gid));
+    }
+
+    // Tilesets added after parsing are found, and win where they overlap.
+    let mut d = (*r.tilesets[0]).clone();
+    d.name = "d".to_string();
+    d.first_gid = Gid(5);
+    d.tilecount = Some(2);
+    r.tilesets.push(Arc::new(d));
+    assert_eq!(find(&r, 5), Some(("d".to_string(), 0)));
+    assert_eq!(find(&r, 6), Some(("d".to_string(), 1)));
+    assert_eq!(find(&r, 7), Some(("a".to_string(), 6)));
+    // Tilesets edited in place are found at their new range.
+    Arc::make_mut(&mut r.tilesets[1]).first_gid = Gid(31);
+    assert_ne!(find(&r, 11).unwrap().0, "b");
+    assert_eq!(find(&r, 31), Some(("b".to_string(), 0)));
+    let index = r.tileset_index();
+    assert_eq!(index.tileset_for_gid(31).unwrap().0.name, "b");
+    assert_eq!(index.tileset_for_gid(6).unwrap().0.name, "d");
+    assert_eq!(r.get_tileset_by_gid(21).unwrap().name, "c");
+}
+
+#[test]
+fn test_get_tile_by_gid() {
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    // Tile 1 of the tileset has a property, and the tileset starts at gid 1.
+    let tile = r.get_tile_by_gid(2).unwrap();
+    assert_eq!(tile.id, 1);
+    assert_eq!(
+        tile.properties.get("a tile property"),
+        Some(&PropertyValue::StringValue("123".to_string()))
+    );
+    let mut flipped = LayerTile::new(2);
+    flipped.flip_v = true;
+    assert_eq!(r.get_tile_by_gid(flipped), Some(tile));
+    assert_eq!(r.get_tile_by_gid(Gid(0x8000_0002)), Some(tile));
+    assert!(r.get_tile_by_gid(1).is_none());
+    assert!(r.get_tile_by_gid(0).is_none());
+}
+
+#[test]
+fn test_tile_rect() {
+    let text = r#"<tileset name="t" tilewidth="16" tileheight="8" spacing="2" margin="1" tilecount="8" columns="4">
+ <image source="tiles.png" width="73" height="21"/>
+</tileset>"#;
+    let t = parse_tileset(text.as_bytes(), 1).unwrap();
+    let rect = |x, y, width, height| ImageRect {
+        x,
+        y,
+        width,
+        height,
+    };
+    assert_eq!(t.tile_rect(0), Some(rect(1, 1, 16, 8)));
+    assert_eq!(t.tile_rect(3), Some(rect(55, 1, 16, 8)));
+    assert_eq!(t.tile_rect(5), Some(rect(19, 11, 16, 8)));
+    assert_eq!(t.tile_rect(8), None);
+
+    let t = parse_tileset(
+        File::open(Path::new("assets/tilesheet_collection.tsx")).unwrap(),
+        1,
+    )
+    .unwrap();
+    assert_eq!(t.tile_rect(0), Some(rect(0, 0, 64, 32)));
+    assert_eq!(t.tile_rect(1), Some(rect(64, 32, 448, 192)));
+    assert_eq!(t.tile_rect(2), Some(rect(0, 0, 448, 192)));
+    assert_eq!(t.tile_rect(3), None);
+}
+
+#[test]
+fn test_tile_image() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    let image = r.tile_image(Gid(0x8000_0010)).unwrap();
+    assert_eq!(image.path, Some(PathBuf::from("assets/tilesheet.png")));
+    assert_eq!(image.image.width, 448);
+    assert_eq!(
+        image.rect,
+        ImageRect {
+            x: 32,
+            y: 32,
+            width: 32,
+            height: 32
+        }
+    );
+    assert!(r.tile_image(0).is_none());
+    assert!(r.tile_image(85).is_none());
+
+    let text = r#"<map version="1.10" orientation="orthogonal" width="1" height="1" tilewidth="64" tileheight="64">
+ <tileset firstgid="1" name="collection" tilewidth="64" tileheight="64" tilecount="2" columns="0">
+  <tile id="0"><image width="64" height="32" source="a.png"/></tile>
+  <tile id="1" x="8" y="0" width="16" height="16"><image width="64" height="64" source="b.png"/></tile>
+ </tileset>
+</map>"#;
+    let r = parse_str(text).unwrap();
+    let image = r.tile_image(1).unwrap();
+    assert_eq!(image.path, Some(PathBuf::from("a.png")));
+    assert_eq!((image.rect.width, image.rect.height), (64, 32));
+    let image = r.tile_image(2).unwrap();
+    assert_eq!(image.path, Some(PathBuf::from("b.png")));
+    assert_eq!((image.rect.x, image.rect.width), (8, 16));
+}
+
+#[test]
+fn test_animation_frame_at() {
+    let text = r#"<tileset name="t" tilewidth="8" tileheight="8" tilecount="10" columns="10">
+ <tile id="0">
+  <animation>
+   <frame tileid="1" duration="100"/>
+   <frame tileid="0" duration="0"/>
+   <frame tileid="2" duration="50"/>
+  </animation>
+ </tile>
+ <tile id="3" probability="0.5"/>
+</tileset>"#;
+    let t = parse_tileset(text.as_bytes(), 1).unwrap();
+    let animated = t.get_tile(0).unwrap();
+    assert_eq!(animated.animation_duration(), 150);
+    assert_eq!(animated.frame_at(0), 1);
+    assert_eq!(animated.frame_at(99), 1);
+    assert_eq!(animated.frame_at(100), 2);
+    assert_eq!(animated.frame_at(149), 2);
+    assert_eq!(animated.frame_at(150), 1);
+    assert_eq!(animated.frame_at(u64::MAX), 1);
+
+    let still = t.get_tile(3).unwrap();
+    assert_eq!(still.animation_duration(), 0);
+    assert_eq!(still.frame_at(1234), 3);
+}
+
+#[test]
+fn test_choose_tile() {
+    let text = r#"<tileset name="t" tilewidth="8" tileheight="8" tilecount="10" columns="10">
+ <tile id="1" probability="3"/>
+ <tile id="2" probability="0"/>
+</tileset>"#;
+    let t = parse_tileset(text.as_bytes(), 1).unwrap();
+    assert_eq!(t.tile_probability(1), 3.0);
+    assert_eq!(t.tile_probability(5), 1.0);
+
+    let mut rng = Rng::new(7);
+    let mut counts = [0; 3];
+    for _ in 0..4000 {
+        counts[t.choose_tile(&[0, 1, 2], &mut rng).unwrap() as usize] += 1;
+    }
+    assert_eq!(counts[2], 0);
+    // Tile 1 is three times as likely as tile 0.
+    assert!((2700..3300).contains(&counts[1]), "{:?}", counts);
+    assert_eq!(t.choose_tile(&[2], &mut rng), None);
+    assert_eq!(t.choose_tile(&[], &mut rng), None);
+}
+
+#[test]
+fn test_tile_colliders() {
+    let file = File::open(Path::new("assets/tilesheet_slopes.tsx")).unwrap();
+    let mut r = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    r.tile_width = 16;
+    r.tile_height = 16;
+    r.tilesets
+        .push(Arc::new(parse_tileset(file, 1000).unwrap()));
+    r.layers[0].offset_x = 100.0;
+    let layer = r.layers[0].as_tile_layer_mut().unwrap();
+    layer.fill_rect(TileRect::new(0, 0, 100, 100), None);
+    let mut flipped = LayerTile::new(1001);
+    flipped.flip_h = true;
+    layer.set_tile(0, 0, LayerTile::new(1000)).unwrap();
+    layer.set_tile(1, 0, flipped).unwrap();
+    layer.set_tile(2, 0, LayerTile::new(1005)).unwrap();
+    layer.set_tile(3, 1, LayerTile::new(1007)).unwrap();
+
+    let colliders = r.tile_colliders(&r.layers[0]);
+    assert_eq!(colliders.len(), 4);
+    assert_eq!(colliders[0].tile, (0, 0));
+    assert_eq!(
+        colliders[0].points,
+        vec![(100.0, 0.0), (116.0, 0.0), (116.0, 16.0), (100.0, 16.0)]
+    );
+    assert!(colliders[0].closed);
+    // The ramp up is flipped into a ramp down.
+    assert_eq!(
+        colliders[1].points,
+        vec![(132.0, 16.0), (116.0, 0.0), (116.0, 16.0)]
+    );
+    assert_eq!(colliders[2].points, vec![(132.0, 0.0), (148.0, 0.0)]);
+    assert!(!colliders[2].closed);
+    assert_eq!(colliders[3].tile, (3, 1));
+    assert_eq!(colliders[3].points.len(), 32);
+    assert_eq!(colliders[3].points[0], (160.0, 24.0));
+    assert_eq!(colliders[3].object.id, 1);
+
+    assert!(r.tile_colliders(&r.layers[1]).is_empty());
+}
+
+#[test]
+fn test_solid_grid() {
+    let r = read_from_file(Path::new("assets/tiled_solid.tmx")).unwrap();
+    let layer = r.layers[0].as_tile_layer().unwrap();
+    let grid = r.solid_grid(layer, "solid");
+    assert_eq!(grid.area, TileRect::new(0, 0, 4, 3));
+    assert_eq!(
+        grid.cells,
+        vec![false, true, true, false, false, false, true, false, false, true, true, true]
+    );
+    assert_eq!(grid.get(1, 0), Some(&true));
+    assert_eq!(grid.get(1, 1), Some(&false));
+    assert_eq!(grid.get(4, 0), None);
+
+    // Tiles without an entry in their tileset still get a value.
+    let gids = r.build_grid(layer, |tile, _| tile.gid.value());
+    assert_eq!(gids.get(0, 0), Some(&1));
+    assert_eq!(gids.get(1, 1), Some(&0));
+    let listed = r.build_grid(layer, |_, tile| tile.map(|tile| tile.id));
+    assert_eq!(listed.get(0, 0), Some(&None));
+    assert_eq!(listed.get(3, 0), Some(&Some(2)));
+}
+
+#[cfg(feature = "pathfinding")]
+#[test]
+fn test_cost_grid_from_solid() {
+    use tiled::{find_path, CostGrid, Neighborhood};
+
+    let r = read_from_file(Path::new("assets/tiled_solid.tmx")).unwrap();
+    let layer = r.layers[0].as_tile_layer().unwrap();
+    let grid = CostGrid::from_solid(&r.solid_grid(layer, "solid"));
+    assert_eq!(grid.cost(0, 0), Some(1.0));
+    assert_eq!(grid.cost(1, 0), None);
+    let path = find_path(&grid, (0, 0), (1, 1), Neighborhood::Four).unwrap();
+    assert_eq!(path.cost, 2.0);
+    assert_eq!(find_path(&grid, (0, 0), (3, 0), Neighborhood::Four), None);
+}
+
+#[test]
+fn test_object_queries() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let ids = |objects: Vec<&Object>| objects.iter().map(|o| o.id).collect::<Vec<_>>();
+    assert_eq!(ids(r.objects().collect()), vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(ids(r.objects_by_type("area").collect()), vec![2, 3]);
+    assert_eq!(ids(r.objects_by_type("enemy").collect()), Vec::<u32>::new());
+    assert_eq!(ids(r.objects_by_name("chest").collect()), vec![6]);
+    let red = PropertyValue::StringValue("red".to_string());
+    assert_eq!(
+        ids(r.objects_with_property("team", &red).collect()),
+        vec![1]
+    );
+    let blue = PropertyValue::StringValue("blue".to_string());
+    assert_eq!(r.objects_with_property("team", &blue).count(), 0);
+    assert_eq!(r.resolve_object_ref(6).map(|o| o.id), Some(6));
+}
+
+#[test]
+fn test_object_contains_point() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let object = |id| r.resolve_object_ref(id).unwrap();
+    // Points and polylines are hit within the tolerance.
+    assert!(object(1).contains_point(50.0, 49.0, 4.0));
+    assert!(!object(1).contains_point(50.0, 49.0, 1.0));
+    assert!(object(2).contains_point(100.0, 100.0, 0.0));
+    assert!(!object(2).contains_point(170.0, 100.0, 0.0));
+    // The pond's bounding box holds points outside of the ellipse.
+    assert!(object(3).contains_point(192.0, 176.0, 0.0));
+    assert!(!object(3).contains_point(162.0, 162.0, 0.0));
+    assert!(object(4).contains_point(64.0, 240.0, 0.0));
+    assert!(!object(4).contains_point(100.0, 240.0, 0.0));
+    assert!(object(5).contains_point(50.0, 290.0, 3.0));
+    assert!(object(5).contains_point(95.0, 250.0, 3.0));
+    assert!(!object(5).contains_point(50.0, 250.0, 3.0));
+    // The chest is a tile object, standing on its position before being
+    // turned a quarter clockwise around it.
+    assert!(object(6).contains_point(270.0, 110.0, 0.0));
+    assert!(!object(6).contains_point(270.0, 80.0, 0.0));
+}
+
+#[test]
+fn test_object_bounding_box() {
+    let r = read_from_file(Path::new("assets/tiled_objects.tmx")).unwrap();
+    let bounds = |object: &Object| {
+        let b = object.bounding_box();
+        [b.x, b.y, b.width, b.height].map(|v| (v * 1000.0).round() / 1000.0)
+    };
+    let object = |id| r.resolve_object_ref(id).unwrap();
+    assert_eq!(bounds(object(1)), [48.0, 48.0, 0.0, 0.0]);
+    assert_eq!(bounds(object(2)), [64.0, 64.0, 96.0, 64.0]);
+    assert_eq!(bounds(object(3)), [160.0, 160.0, 64.0, 32.0]);
+    assert_eq!(bounds(object(4)), [32.0, 224.0, 64.0, 32.0]);
+    assert_eq!(bounds(object(5)), [0.0, 224.0, 96.0, 64.0]);
+    // Tile objects stand on their position, and rotate around it.
+    assert_eq!(bounds(object(6)), [256.0, 96.0, 32.0, 32.0]);
+
+    let mut pond = object(3).clone();
+    pond.rotation = 90.0;
+    assert_eq!(bounds(&pond), [128.0, 160.0, 32.0, 64.0]);
+    pond.rotation = 45.0;
+    let b = pond.bounding_box();
+    assert!(b.width > 32.0 && b.width < 64.0);
+    assert!((b.width - b.height).abs() < 1e-3);
+}
+
+#[test]
+fn test_set_tile_on_uneven_chunks() {
+    let chunk = |x: i32, width: u32, height: u32, rows: usize| Chunk {
+        x,
+        y: 0,
+        width,
+        height,
+        tiles: vec![vec![None; width as usize]; rows],
+    };
+    let mut chunks = HashMap::new();
+    // A chunk missing most of its rows, next to a smaller one.
+    chunks.insert((0, 0), chunk(0, 16, 16, 2));
+    chunks.insert((16, 0), chunk(16, 8, 8, 8));
+    let mut layer = TileLayer {
+        width: 0,
+        height: 0,
+        tiles: LayerData::Infinite(chunks),
+    };
+
+    assert!(layer.set_tile(1, 5, LayerTile::new(1)).is_err());
+    layer.set_tile(1, 1, LayerTile::new(1)).unwrap();
+    assert_eq!(layer.get_tile(1, 1), Some(LayerTile::new(1)));
+    layer.set_tile(20, 3, LayerTile::new(2)).unwrap();
+    assert_eq!(layer.get_tile(20, 3), Some(LayerTile::new(2)));
+    // Positions outside of every chunk get a chunk of the first one's size.
+    layer.set_tile(-1, -1, LayerTile::new(3)).unwrap();
+    assert_eq!(layer.get_tile(-1, -1), Some(LayerTile::new(3)));
+    assert_eq!(layer.bounds(), Some(TileRect::new(-16, -16, 40, 32)));
+}
+
+#[test]
+fn test_chunks_at_extreme_coordinates() {
+    let map = |second_x: i32| {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.4" orientation="orthogonal" width="16" height="16" tilewidth="16" tileheight="16" infinite="1">
+ <layer name="far" width="16" height="16">
+  <data encoding="csv">
+   <chunk x="-2147483648" y="0" width="16" height="1">1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0</chunk>
+   <chunk x="{}" y="0" width="16" height="1">0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,2</chunk>
+  </data>
+ </layer>
+</map>"#,
+            second_x
+        )
+    };
+    let r = parse_str(&map(2147483600)).unwrap();
+    let layer = r.layers[0].as_tile_layer().unwrap();
+    assert_eq!(
+        layer.bounds(),
+        Some(TileRect::new(i32::MIN, 0, 4294967264, 1))
+    );
+    assert_eq!(layer.get_tile(2147483615, 0), Some(LayerTile::new(2)));
+    // Chunks reaching past the largest coordinates are rejected.
+    assert!(parse_str(&map(2147483640)).is_err());
+
+    let far = TileRect::new(i32::MAX - 1, 0, 16, 1);
+    assert_eq!(far.right(), i32::MAX);
+    assert_eq!(
+        far.union(&TileRect::new(i32::MIN, 0, 1, 1)),
+        TileRect::new(i32::MIN, 0, u32::MAX, 1)
+    );
+}